@@ -0,0 +1 @@
+CREATE INDEX feed_entries_unread ON feed_entries USING btree (feed_id, created_at) WHERE read_at IS NULL;
@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN feed_order text NOT NULL DEFAULT 'added_at:desc';
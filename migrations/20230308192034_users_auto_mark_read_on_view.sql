@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN auto_mark_read_on_view boolean NOT NULL DEFAULT false;
@@ -0,0 +1 @@
+ALTER TABLE feeds ADD COLUMN site_favicon_mime text;
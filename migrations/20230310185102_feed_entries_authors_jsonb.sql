@@ -0,0 +1,2 @@
+-- Store the authors as a JSON array of strings, converting the existing arrays.
+ALTER TABLE feed_entries ALTER COLUMN authors TYPE jsonb USING to_jsonb(authors);
@@ -0,0 +1,2 @@
+-- An entry external id is only unique within its feed.
+CREATE UNIQUE INDEX feed_entries_feed_id_external_id ON feed_entries(feed_id, external_id);
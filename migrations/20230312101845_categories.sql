@@ -0,0 +1,10 @@
+CREATE TABLE categories (
+    id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    user_id uuid NOT NULL,
+    name text NOT NULL
+);
+ALTER TABLE categories ADD CONSTRAINT categories_user_id_fkey FOREIGN KEY (user_id) REFERENCES users(id);
+ALTER TABLE ONLY categories ADD CONSTRAINT categories_user_id_name_key UNIQUE (user_id, name);
+
+ALTER TABLE feeds ADD COLUMN category_id bigint;
+ALTER TABLE feeds ADD CONSTRAINT feeds_category_id_fkey FOREIGN KEY (category_id) REFERENCES categories(id) ON DELETE SET NULL;
@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN unread_since_days integer;
@@ -0,0 +1,11 @@
+-- Replace the site links which are not valid URLs with the feed URL
+DO $$
+DECLARE
+    fixed integer;
+BEGIN
+    UPDATE feeds SET site_link = url WHERE regexp_match(site_link, '^https?://') IS NULL;
+
+    GET DIAGNOSTICS fixed = ROW_COUNT;
+    RAISE NOTICE 'fixed % feed(s) with an invalid site link', fixed;
+END
+$$;
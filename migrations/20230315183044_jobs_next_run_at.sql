@@ -0,0 +1 @@
+ALTER TABLE jobs ADD COLUMN next_run_at timestamp with time zone NOT NULL DEFAULT now();
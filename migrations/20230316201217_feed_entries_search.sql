@@ -0,0 +1,4 @@
+ALTER TABLE feed_entries ADD COLUMN search_tsv tsvector
+    GENERATED ALWAYS AS (to_tsvector('english', title || ' ' || summary)) STORED;
+
+CREATE INDEX feed_entries_search_tsv ON feed_entries USING gin (search_tsv);
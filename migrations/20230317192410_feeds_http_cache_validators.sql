@@ -0,0 +1,2 @@
+ALTER TABLE feeds ADD COLUMN last_etag text;
+ALTER TABLE feeds ADD COLUMN last_modified text;
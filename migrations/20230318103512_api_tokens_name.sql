@@ -0,0 +1 @@
+ALTER TABLE api_tokens ADD COLUMN name text NOT NULL DEFAULT '';
@@ -0,0 +1,15 @@
+CREATE TABLE tags (
+    id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    user_id uuid NOT NULL,
+    name text NOT NULL
+);
+ALTER TABLE tags ADD CONSTRAINT tags_user_id_fkey FOREIGN KEY (user_id) REFERENCES users(id);
+ALTER TABLE ONLY tags ADD CONSTRAINT tags_user_id_name_key UNIQUE (user_id, name);
+
+CREATE TABLE feed_tags (
+    feed_id bigint NOT NULL,
+    tag_id bigint NOT NULL
+);
+ALTER TABLE ONLY feed_tags ADD CONSTRAINT feed_tags_pkey PRIMARY KEY (feed_id, tag_id);
+ALTER TABLE feed_tags ADD CONSTRAINT feed_tags_feed_id_fkey FOREIGN KEY (feed_id) REFERENCES feeds(id) ON DELETE CASCADE;
+ALTER TABLE feed_tags ADD CONSTRAINT feed_tags_tag_id_fkey FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE;
@@ -0,0 +1,12 @@
+CREATE TABLE dead_letter_jobs (
+    id uuid NOT NULL,
+    data jsonb NOT NULL,
+    created_at timestamp with time zone NOT NULL,
+    attempts integer NOT NULL,
+    error text,
+    key bytea NOT NULL,
+    failed_at timestamp with time zone DEFAULT now() NOT NULL
+);
+ALTER TABLE ONLY dead_letter_jobs ADD CONSTRAINT dead_letter_jobs_pkey PRIMARY KEY (id);
+
+ALTER TABLE users ADD COLUMN is_admin boolean NOT NULL DEFAULT false;
@@ -0,0 +1,8 @@
+CREATE TABLE pending_email_changes (
+    user_id uuid NOT NULL,
+    new_email text NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    expires_at timestamp with time zone NOT NULL
+);
+ALTER TABLE pending_email_changes ADD CONSTRAINT pending_email_changes_pkey PRIMARY KEY (user_id);
+ALTER TABLE pending_email_changes ADD CONSTRAINT pending_email_changes_user_id_fkey FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE;
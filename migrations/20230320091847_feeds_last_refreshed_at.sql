@@ -0,0 +1 @@
+ALTER TABLE feeds ADD COLUMN last_refreshed_at timestamp with time zone;
@@ -0,0 +1 @@
+ALTER TABLE feed_entries ADD COLUMN starred_at timestamp with time zone;
@@ -0,0 +1 @@
+ALTER TABLE feed_entries ADD COLUMN published_at timestamp with time zone;
@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN digest_last_sent_at timestamp with time zone;
@@ -0,0 +1,8 @@
+CREATE TABLE user_preferences (
+    user_id uuid NOT NULL,
+    entries_per_page integer DEFAULT 25 NOT NULL,
+    timezone text DEFAULT 'UTC' NOT NULL,
+    theme text DEFAULT 'light' NOT NULL
+);
+ALTER TABLE user_preferences ADD CONSTRAINT user_preferences_pkey PRIMARY KEY (user_id);
+ALTER TABLE user_preferences ADD CONSTRAINT user_preferences_user_id_fkey FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE;
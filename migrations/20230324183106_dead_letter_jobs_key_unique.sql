@@ -0,0 +1,6 @@
+-- Only keep the most recent failure of each job
+DELETE FROM dead_letter_jobs d
+USING dead_letter_jobs newer
+WHERE d.key = newer.key AND (d.failed_at, d.id) < (newer.failed_at, newer.id);
+
+ALTER TABLE ONLY dead_letter_jobs ADD CONSTRAINT dead_letter_jobs_key_key UNIQUE (key);
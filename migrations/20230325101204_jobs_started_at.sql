@@ -0,0 +1,4 @@
+ALTER TABLE jobs ADD COLUMN started_at timestamp with time zone;
+
+-- The jobs already running are considered started now
+UPDATE jobs SET started_at = now() WHERE status = 'running';
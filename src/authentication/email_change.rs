@@ -0,0 +1,243 @@
+use crate::domain::{UserEmail, UserId};
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How long a user has to confirm an email change.
+pub const EMAIL_CHANGE_TTL: time::Duration = time::Duration::hours(24);
+
+/// A signed token confirming that the user `user_id` owns the email address it wants to use.
+///
+/// The token is sent in the confirmation link as `<user id>.<signature>`, the signature being a
+/// HMAC-SHA256 of the new email address and the user id.
+pub struct EmailChangeToken {
+    pub user_id: UserId,
+    signature: Vec<u8>,
+}
+
+impl EmailChangeToken {
+    /// Creates the token confirming the change of the email of `user_id` to `new_email`.
+    pub fn new(secret: &Secret<String>, user_id: UserId, new_email: &UserEmail) -> Self {
+        let signature = email_change_mac(secret, user_id, new_email)
+            .finalize()
+            .into_bytes()
+            .to_vec();
+
+        Self { user_id, signature }
+    }
+
+    /// Parses a token created by [`EmailChangeToken::new`] and formatted with [`ToString`].
+    ///
+    /// Returns None if `s` is not a valid token. The signature is not verified here, see
+    /// [`EmailChangeToken::verify`].
+    pub fn parse(s: &str) -> Option<Self> {
+        let (user_id, signature) = s.split_once('.')?;
+
+        Some(Self {
+            user_id: UserId(Uuid::try_parse(user_id).ok()?),
+            signature: hex::decode(signature).ok()?,
+        })
+    }
+
+    /// Returns true if the token confirms the change of the email to `new_email`.
+    pub fn verify(&self, secret: &Secret<String>, new_email: &UserEmail) -> bool {
+        email_change_mac(secret, self.user_id, new_email)
+            .verify_slice(&self.signature)
+            .is_ok()
+    }
+}
+
+impl std::fmt::Display for EmailChangeToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.user_id, hex::encode(&self.signature))
+    }
+}
+
+fn email_change_mac(secret: &Secret<String>, user_id: UserId, new_email: &UserEmail) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(new_email.as_ref().as_bytes());
+    mac.update(user_id.as_ref());
+    mac
+}
+
+/// Stores the pending change of the email of `user_id` to `new_email`, replacing any previous one.
+///
+/// The change must be confirmed with [`confirm_email_change`] before it expires.
+#[tracing::instrument(name = "Set pending email change", skip(executor))]
+pub async fn set_pending_email_change<'e, E>(
+    executor: E,
+    user_id: UserId,
+    new_email: &UserEmail,
+) -> Result<(), anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let expires_at = time::OffsetDateTime::now_utc() + EMAIL_CHANGE_TTL;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO pending_email_changes(user_id, new_email, expires_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (user_id) DO UPDATE
+        SET new_email = excluded.new_email, created_at = now(), expires_at = excluded.expires_at
+        "#,
+        &user_id.0,
+        new_email.as_ref(),
+        expires_at,
+    )
+    .execute(executor)
+    .await
+    .context("Failed to store the pending email change")?;
+
+    Ok(())
+}
+
+/// Get the new email of the pending email change of `user_id`.
+///
+/// Returns None if there's no pending change or if it expired.
+#[tracing::instrument(name = "Get pending email change", skip(executor))]
+pub async fn get_pending_email_change<'e, E>(
+    executor: E,
+    user_id: UserId,
+) -> Result<Option<UserEmail>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let record = sqlx::query!(
+        r#"
+        SELECT new_email FROM pending_email_changes
+        WHERE user_id = $1 AND expires_at > now()
+        "#,
+        &user_id.0,
+    )
+    .fetch_optional(executor)
+    .await
+    .context("Failed to fetch the pending email change")?;
+
+    Ok(record.map(|record| UserEmail(record.new_email)))
+}
+
+/// Applies the pending change of the email of `user_id` to `new_email`.
+///
+/// Returns false if there's no such pending change, if it expired or if another user already
+/// uses `new_email`.
+#[tracing::instrument(name = "Confirm email change", skip(executor))]
+pub async fn confirm_email_change<'e, E>(
+    executor: E,
+    user_id: UserId,
+    new_email: &UserEmail,
+) -> Result<bool, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let result = sqlx::query!(
+        r#"
+        WITH deleted AS (
+            DELETE FROM pending_email_changes
+            WHERE user_id = $1 AND new_email = $2 AND expires_at > now()
+            RETURNING new_email
+        )
+        UPDATE users SET email = deleted.new_email
+        FROM deleted
+        WHERE users.id = $1
+        AND NOT EXISTS (SELECT 1 FROM users WHERE email = deleted.new_email)
+        "#,
+        &user_id.0,
+        new_email.as_ref(),
+    )
+    .execute(executor)
+    .await
+    .context("Failed to change the email")?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::get_user;
+    use crate::tests::{create_user, get_pool};
+
+    fn secret() -> Secret<String> {
+        Secret::new("foobar".to_string())
+    }
+
+    #[test]
+    fn email_change_token_should_round_trip() {
+        let user_id = UserId::default();
+        let new_email = UserEmail("foo@example.com".to_string());
+
+        let token = EmailChangeToken::new(&secret(), user_id, &new_email).to_string();
+
+        let parsed_token = EmailChangeToken::parse(&token).unwrap();
+        assert_eq!(user_id, parsed_token.user_id);
+        assert!(parsed_token.verify(&secret(), &new_email));
+
+        // The signature depends on the email and the secret
+
+        let other_email = UserEmail("bar@example.com".to_string());
+        assert!(!parsed_token.verify(&secret(), &other_email));
+
+        let other_secret = Secret::new("barbaz".to_string());
+        assert!(!parsed_token.verify(&other_secret, &new_email));
+    }
+
+    #[test]
+    fn email_change_token_should_depend_on_the_user() {
+        let new_email = UserEmail("foo@example.com".to_string());
+
+        let token = EmailChangeToken::new(&secret(), UserId::default(), &new_email).to_string();
+        let (_, signature) = token.split_once('.').unwrap();
+
+        let forged_token = format!("{}.{}", UserId::default(), signature);
+        let parsed_token = EmailChangeToken::parse(&forged_token).unwrap();
+        assert!(!parsed_token.verify(&secret(), &new_email));
+
+        assert!(EmailChangeToken::parse("foobar").is_none());
+        assert!(EmailChangeToken::parse(&format!("{}.zz", UserId::default())).is_none());
+    }
+
+    #[tokio::test]
+    async fn confirm_email_change_should_change_the_email_once() {
+        let pool = get_pool().await;
+        let user_id = create_user(&pool).await;
+        let new_email = UserEmail(format!("{}@example.com", Uuid::new_v4()));
+
+        // Nothing to confirm yet
+
+        assert!(!confirm_email_change(&pool, user_id, &new_email)
+            .await
+            .unwrap());
+
+        set_pending_email_change(&pool, user_id, &new_email)
+            .await
+            .unwrap();
+        let pending_email = get_pending_email_change(&pool, user_id).await.unwrap();
+        assert_eq!(
+            Some(new_email.as_ref()),
+            pending_email.as_ref().map(AsRef::as_ref)
+        );
+
+        assert!(confirm_email_change(&pool, user_id, &new_email)
+            .await
+            .unwrap());
+
+        let user = get_user(&pool, user_id).await.unwrap().unwrap();
+        assert_eq!(new_email.as_ref(), user.email.as_ref());
+
+        // The pending change is consumed
+
+        assert!(get_pending_email_change(&pool, user_id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(!confirm_email_change(&pool, user_id, &new_email)
+            .await
+            .unwrap());
+    }
+}
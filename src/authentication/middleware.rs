@@ -1,16 +1,31 @@
+use crate::authentication::get_user_id_for_api_token;
 use crate::routes::{e500, see_other};
 use crate::sessions::TypedSession;
 use actix_web::body::MessageBody;
-use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
 use actix_web::error::InternalError;
-use actix_web::{FromRequest, HttpMessage};
+use actix_web::http::header;
+use actix_web::{web, FromRequest, HttpMessage, HttpResponse};
 use actix_web_lab::middleware::Next;
 use anyhow::anyhow;
+use secrecy::Secret;
+use sqlx::PgPool;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
 
+/// Rejects the requests of anonymous users by redirecting them to the login page.
+///
+/// A request already authenticated by [`ApiTokenMiddleware`] goes through, otherwise the
+/// [`crate::domain::UserId`] is taken from the session and stored in the request extensions.
 pub async fn reject_anonymous_users(
     mut req: ServiceRequest,
     next: Next<impl MessageBody>,
 ) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if req.extensions().get::<ApiTokenAuthenticated>().is_some() {
+        return next.call(req).await;
+    }
+
     let session_result = {
         let (http_request, payload) = req.parts_mut();
         TypedSession::from_request(http_request, payload).await
@@ -30,3 +45,116 @@ pub async fn reject_anonymous_users(
         }
     }
 }
+
+/// Rejects the requests authenticated with an API token with a 403 Forbidden.
+///
+/// An API token only gives access to the feeds: managing the credentials and the account
+/// (tokens, sessions, email, account deletion) requires a session obtained by logging in.
+pub async fn reject_api_token_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if req.extensions().get::<ApiTokenAuthenticated>().is_some() {
+        let response = HttpResponse::Forbidden().finish();
+        let err = anyhow!("API tokens can't be used on this route");
+        return Err(InternalError::from_response(err, response).into());
+    }
+
+    next.call(req).await
+}
+
+/// Stored in the request extensions by [`ApiTokenMiddleware`] when the request is authenticated
+/// with an API token.
+#[derive(Copy, Clone, Debug)]
+pub struct ApiTokenAuthenticated;
+
+/// The [`ApiTokenMiddleware`] authenticates a request using the `Authorization: Bearer` header.
+///
+/// If the header contains a valid API token, the [`crate::domain::UserId`] owning it is stored
+/// in the request extensions along with [`ApiTokenAuthenticated`]; the session is never touched.
+/// If there's no such header the request goes through unchanged, and the usual session
+/// authentication applies.
+///
+/// A request with an invalid token is rejected with a 401 Unauthorized.
+pub struct ApiTokenMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for ApiTokenMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = ApiTokenMiddlewareService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ApiTokenMiddlewareService {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct ApiTokenMiddlewareService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for ApiTokenMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let token = match get_bearer_token(&req) {
+                Some(token) => token,
+                None => return service.call(req).await,
+            };
+
+            let pool = req
+                .app_data::<web::Data<PgPool>>()
+                .cloned()
+                .ok_or_else(|| anyhow!("no database pool configured"))
+                .map_err(e500)?;
+
+            let user_id = get_user_id_for_api_token(pool.as_ref(), &token)
+                .await
+                .map_err(e500)?;
+
+            match user_id {
+                Some(user_id) => {
+                    req.extensions_mut().insert(user_id);
+                    req.extensions_mut().insert(ApiTokenAuthenticated);
+                    service.call(req).await
+                }
+                None => {
+                    let response = HttpResponse::Unauthorized().finish();
+                    let err = anyhow!("Invalid API token");
+                    Err(InternalError::from_response(err, response).into())
+                }
+            }
+        })
+    }
+}
+
+/// Extracts the token from the `Authorization: Bearer <token>` header, if any.
+fn get_bearer_token(req: &ServiceRequest) -> Option<Secret<String>> {
+    let value = req.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+    let token = value.strip_prefix("Bearer ")?.trim();
+
+    if token.is_empty() {
+        None
+    } else {
+        Some(Secret::new(token.to_string()))
+    }
+}
@@ -1,5 +1,9 @@
+mod email_change;
 mod middleware;
 mod password;
+mod token;
 
+pub use email_change::*;
 pub use middleware::*;
 pub use password::*;
+pub use token::*;
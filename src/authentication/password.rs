@@ -1,3 +1,4 @@
+use crate::configuration::Argon2Config;
 use crate::domain::{UserEmail, UserId};
 use crate::telemetry::spawn_blocking_with_tracing;
 use anyhow::anyhow;
@@ -13,10 +14,157 @@ use sqlx::PgPool;
 pub enum AuthError {
     #[error("Invalid credentials")]
     InvalidCredentials(#[source] anyhow::Error),
+    #[error("Weak password")]
+    WeakPassword(#[source] PasswordStrengthError),
     #[error(transparent)]
     Unexpected(#[from] anyhow::Error),
 }
 
+/// The minimum number of characters of a password.
+pub const PASSWORD_MIN_LENGTH: usize = 12;
+
+/// Passwords long enough and with a digit but still among the most commonly used ones.
+///
+/// Compared case insensitively.
+const COMMON_PASSWORDS: [&str; 50] = [
+    "123456789012",
+    "1234567890123",
+    "12345678901234",
+    "123123123123",
+    "111111111111",
+    "000000000000",
+    "123456123456",
+    "1q2w3e4r5t6y",
+    "1qaz2wsx3edc",
+    "q1w2e3r4t5y6",
+    "qwerty123456",
+    "qwertyuiop123",
+    "qwertyuiop1234",
+    "asdfghjkl123",
+    "zxcvbnm12345",
+    "abc123456789",
+    "abcdef123456",
+    "abcd12345678",
+    "password1234",
+    "password12345",
+    "password123456",
+    "password2023",
+    "password2024",
+    "passw0rd1234",
+    "p@ssw0rd1234",
+    "p@ssword1234",
+    "iloveyou1234",
+    "iloveyou123456",
+    "letmein12345",
+    "welcome12345",
+    "welcome123456",
+    "admin1234567",
+    "admin123456789",
+    "administrator1",
+    "changeme1234",
+    "football1234",
+    "baseball1234",
+    "basketball12",
+    "superman1234",
+    "sunshine1234",
+    "princess1234",
+    "starwars1234",
+    "trustno11234",
+    "dragon123456",
+    "monkey123456",
+    "shadow123456",
+    "master123456",
+    "michael12345",
+    "jennifer1234",
+    "computer1234",
+];
+
+/// This error is returned when a password is too weak to be used.
+#[derive(Debug, thiserror::Error)]
+pub enum PasswordStrengthError {
+    #[error("The password must be at least {PASSWORD_MIN_LENGTH} characters long")]
+    TooShort,
+    #[error("The password must contain at least one digit")]
+    NoDigit,
+    #[error("The password is too common")]
+    CommonPassword,
+}
+
+/// Checks that `password` is strong enough to be used.
+///
+/// A strong password is at least [`PASSWORD_MIN_LENGTH`] characters long, contains a digit and
+/// isn't one of the most common passwords.
+///
+/// # Errors
+///
+/// This function will return a [`PasswordStrengthError`] describing the first failed check.
+pub fn validate_password_strength(password: &Secret<String>) -> Result<(), PasswordStrengthError> {
+    let password = password.expose_secret();
+
+    if password.chars().count() < PASSWORD_MIN_LENGTH {
+        return Err(PasswordStrengthError::TooShort);
+    }
+    if !password.chars().any(|c| c.is_ascii_digit()) {
+        return Err(PasswordStrengthError::NoDigit);
+    }
+    if COMMON_PASSWORDS
+        .iter()
+        .any(|common| common.eq_ignore_ascii_case(password))
+    {
+        return Err(PasswordStrengthError::CommonPassword);
+    }
+
+    Ok(())
+}
+
+/// The Argon2id parameters used to hash passwords.
+///
+/// Verifying a password always uses the parameters stored in its hash, so changing them doesn't
+/// invalidate the existing passwords.
+#[derive(Clone, Debug)]
+pub struct PasswordHasherConfig {
+    params: argon2::Params,
+}
+
+impl PasswordHasherConfig {
+    /// Creates a new config with `memory_kib` KiB of memory, `iterations` passes and
+    /// `parallelism` lanes.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the parameters are out of the range allowed by
+    /// Argon2.
+    pub fn new(memory_kib: u32, iterations: u32, parallelism: u32) -> Result<Self, argon2::Error> {
+        let params = argon2::Params::new(memory_kib, iterations, parallelism, None)?;
+
+        Ok(Self { params })
+    }
+
+    fn hasher(&self) -> Argon2<'static> {
+        Argon2::new(
+            argon2::Algorithm::Argon2id,
+            argon2::Version::V0x13,
+            self.params.clone(),
+        )
+    }
+}
+
+impl Default for PasswordHasherConfig {
+    fn default() -> Self {
+        let config = Argon2Config::default();
+
+        Self::try_from(&config).expect("default Argon2 parameters should be valid")
+    }
+}
+
+impl TryFrom<&Argon2Config> for PasswordHasherConfig {
+    type Error = argon2::Error;
+
+    fn try_from(config: &Argon2Config) -> Result<Self, Self::Error> {
+        Self::new(config.memory_kib, config.iterations, config.parallelism)
+    }
+}
+
 /// Represents the credentials used for authentication.
 pub struct Credentials {
     pub email: UserEmail,
@@ -27,8 +175,12 @@ pub struct Credentials {
 ///
 /// If the credentials are validated then this function returns the [`crate::domain::UserId`].
 /// Otherwise it returns an [`AuthError`].
-#[tracing::instrument(name = "Authenticate", skip(pool, credentials))]
-pub async fn authenticate(pool: &PgPool, credentials: Credentials) -> Result<UserId, AuthError> {
+#[tracing::instrument(name = "Authenticate", skip(pool, hasher_config, credentials))]
+pub async fn authenticate(
+    pool: &PgPool,
+    hasher_config: &PasswordHasherConfig,
+    credentials: Credentials,
+) -> Result<UserId, AuthError> {
     let mut user_id = None;
     let mut expected_password_hash = Secret::new(
         "$argon2id$v=19$m=15000,t=2,p=1\
@@ -48,8 +200,10 @@ pub async fn authenticate(pool: &PgPool, credentials: Credentials) -> Result<Use
 
     //
 
+    let hasher_config = hasher_config.clone();
+
     let verify_result = spawn_blocking_with_tracing(move || {
-        verify_password_hash(expected_password_hash, credentials.password)
+        verify_password_hash(&hasher_config, expected_password_hash, credentials.password)
     })
     .await
     .context("Failed to spawn blocking task")
@@ -64,17 +218,22 @@ pub async fn authenticate(pool: &PgPool, credentials: Credentials) -> Result<Use
         .map_err(AuthError::InvalidCredentials)
 }
 
-#[tracing::instrument(name = "Change password", skip(pool, password))]
+#[tracing::instrument(name = "Change password", skip(pool, hasher_config, password))]
 pub async fn change_password(
     pool: &PgPool,
+    hasher_config: &PasswordHasherConfig,
     user_id: UserId,
     password: Secret<String>,
-) -> Result<(), anyhow::Error> {
+) -> Result<(), AuthError> {
+    validate_password_strength(&password).map_err(AuthError::WeakPassword)?;
+
     // Compute the new hash
-    let password_hash_result = spawn_blocking_with_tracing(move || compute_password_hash(password))
-        .await
-        .context("Failed to spawn blocking task")
-        .map_err(Into::<anyhow::Error>::into)?;
+    let hasher_config = hasher_config.clone();
+    let password_hash_result =
+        spawn_blocking_with_tracing(move || compute_password_hash(&hasher_config, password))
+            .await
+            .context("Failed to spawn blocking task")
+            .map_err(Into::<anyhow::Error>::into)?;
     let password_hash = password_hash_result?;
 
     // Store it
@@ -89,27 +248,33 @@ pub async fn change_password(
     )
     .execute(pool)
     .await
-    .context("Failed to update the users password")?;
+    .context("Failed to update the users password")
+    .map_err(AuthError::Unexpected)?;
 
     Ok(())
 }
 
 #[tracing::instrument(
     name = "Create user",
-    skip(pool, password),
+    skip(pool, hasher_config, password),
     fields(
         user_id = tracing::field::Empty,
     )
 )]
 pub async fn create_user(
     pool: &PgPool,
+    hasher_config: &PasswordHasherConfig,
     email: &UserEmail,
     password: Secret<String>,
 ) -> Result<UserId, AuthError> {
-    let password_hash_result = spawn_blocking_with_tracing(move || compute_password_hash(password))
-        .await
-        .context("Failed to spawn blocking task")
-        .map_err(Into::<anyhow::Error>::into)?;
+    validate_password_strength(&password).map_err(AuthError::WeakPassword)?;
+
+    let hasher_config = hasher_config.clone();
+    let password_hash_result =
+        spawn_blocking_with_tracing(move || compute_password_hash(&hasher_config, password))
+            .await
+            .context("Failed to spawn blocking task")
+            .map_err(Into::<anyhow::Error>::into)?;
     let password_hash = password_hash_result?;
 
     let user_id = UserId::default();
@@ -131,13 +296,12 @@ pub async fn create_user(
     Ok(user_id)
 }
 
-pub fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
+pub fn compute_password_hash(
+    hasher_config: &PasswordHasherConfig,
+    password: Secret<String>,
+) -> Result<Secret<String>, anyhow::Error> {
     let salt = SaltString::generate(&mut rand::thread_rng());
-    let hasher = Argon2::new(
-        argon2::Algorithm::Argon2id,
-        argon2::Version::V0x13,
-        argon2::Params::new(15000, 2, 1, None).unwrap(),
-    );
+    let hasher = hasher_config.hasher();
 
     let password_hash = hasher.hash_password(password.expose_secret().as_bytes(), &salt)?;
     let password_hash_string = password_hash.to_string();
@@ -147,9 +311,10 @@ pub fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>,
 
 #[tracing::instrument(
     name = "Verify password hash",
-    skip(expected_password_hash, password_candidate)
+    skip(hasher_config, expected_password_hash, password_candidate)
 )]
 fn verify_password_hash(
+    hasher_config: &PasswordHasherConfig,
     expected_password_hash: Secret<String>,
     password_candidate: Secret<String>,
 ) -> Result<(), AuthError> {
@@ -157,7 +322,8 @@ fn verify_password_hash(
         .context("Failed to parse hash in PHC string format")
         .map_err(AuthError::Unexpected)?;
 
-    Argon2::default()
+    hasher_config
+        .hasher()
         .verify_password(
             password_candidate.expose_secret().as_bytes(),
             &expected_password_hash,
@@ -205,13 +371,15 @@ mod tests {
     use super::*;
     use crate::configuration::get_configuration;
     use crate::domain::UserEmail;
-    use crate::startup::get_connection_pool;
+    use crate::startup::{get_connection_pool, run_migrations};
     use fake::faker::internet::en::{Password as FakerPassword, SafeEmail as FakerSafeEmail};
     use fake::Fake;
 
     async fn get_pool() -> PgPool {
         let config = get_configuration().unwrap();
-        get_connection_pool(&config.database).await.unwrap()
+        let pool = get_connection_pool(&config.database).await.unwrap();
+        run_migrations(&pool).await.unwrap();
+        pool
     }
 
     #[tokio::test]
@@ -223,10 +391,16 @@ mod tests {
             password: Secret::from(FakerPassword(10..20).fake::<String>()),
         };
 
-        let result = authenticate(&pool, credentials).await;
+        let result = authenticate(&pool, &PasswordHasherConfig::default(), credentials).await;
         assert!(result.is_err());
         match result.unwrap_err() {
             AuthError::InvalidCredentials(_) => {}
+            AuthError::WeakPassword(err) => {
+                panic!(
+                    "expected a InvalidCredentials error, got WeakPassword: {}",
+                    err
+                )
+            }
             AuthError::Unexpected(err) => {
                 panic!(
                     "expected a InvalidCredentials error, got Unexpected: {}",
@@ -236,17 +410,111 @@ mod tests {
         }
     }
 
+    #[test]
+    fn validate_password_strength_should_reject_weak_passwords() {
+        let validate =
+            |password: &str| validate_password_strength(&Secret::from(password.to_string()));
+
+        assert!(matches!(
+            validate("short1"),
+            Err(PasswordStrengthError::TooShort)
+        ));
+        // Characters are counted, not bytes
+        assert!(matches!(
+            validate("éééééééééé1"),
+            Err(PasswordStrengthError::TooShort)
+        ));
+        assert!(matches!(
+            validate("no-digit-in-here"),
+            Err(PasswordStrengthError::NoDigit)
+        ));
+        assert!(matches!(
+            validate("Password1234"),
+            Err(PasswordStrengthError::CommonPassword)
+        ));
+        assert!(matches!(
+            validate("qwerty123456"),
+            Err(PasswordStrengthError::CommonPassword)
+        ));
+
+        assert!(validate("correct-horse-battery-9").is_ok());
+        assert!(validate("ééééééééééé1").is_ok());
+    }
+
+    #[tokio::test]
+    async fn create_user_should_reject_a_weak_password() {
+        let pool = get_pool().await;
+
+        let email = UserEmail::parse(FakerSafeEmail().fake()).unwrap();
+
+        let result = create_user(
+            &pool,
+            &PasswordHasherConfig::default(),
+            &email,
+            Secret::from("foobar".to_string()),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(AuthError::WeakPassword(PasswordStrengthError::TooShort))
+        ));
+
+        let credentials = get_stored_credentials(&pool, &email).await.unwrap();
+        assert!(credentials.is_none());
+    }
+
     #[tokio::test]
     async fn compute_password_hash_should_work() {
         let password = Secret::from("foobar".to_string());
 
-        let result = compute_password_hash(password);
+        let result = compute_password_hash(&PasswordHasherConfig::default(), password);
         assert!(result.is_ok());
 
         let password_hash = result.unwrap();
         assert!(!password_hash.expose_secret().is_empty());
     }
 
+    #[test]
+    fn password_hash_should_use_the_configured_parameters() {
+        let hasher_config = PasswordHasherConfig::try_from(&Argon2Config {
+            memory_kib: 8192,
+            iterations: 3,
+            parallelism: 2,
+        })
+        .unwrap();
+
+        let password_hash =
+            compute_password_hash(&hasher_config, Secret::from("foobar".to_string())).unwrap();
+        assert!(password_hash
+            .expose_secret()
+            .starts_with("$argon2id$v=19$m=8192,t=3,p=2$"));
+
+        // The hash is verified with its own parameters, whatever the current config is
+
+        for config in [&hasher_config, &PasswordHasherConfig::default()] {
+            assert!(verify_password_hash(
+                config,
+                password_hash.clone(),
+                Secret::from("foobar".to_string())
+            )
+            .is_ok());
+            assert!(matches!(
+                verify_password_hash(
+                    config,
+                    password_hash.clone(),
+                    Secret::from("barbaz".to_string())
+                ),
+                Err(AuthError::InvalidCredentials(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn password_hasher_config_should_reject_invalid_parameters() {
+        assert!(PasswordHasherConfig::new(15000, 0, 1).is_err());
+        assert!(PasswordHasherConfig::new(15000, 2, 0).is_err());
+    }
+
     #[tokio::test]
     async fn get_stored_credentials_for_non_existing_user_should_return_none() {
         let pool = get_pool().await;
@@ -0,0 +1,201 @@
+use crate::domain::UserId;
+use crate::impl_typed_uuid;
+use anyhow::Context;
+use blake2::{Blake2b512, Digest};
+use rand::RngCore;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct ApiTokenId(pub Uuid);
+impl_typed_uuid!(ApiTokenId);
+
+/// An API token of a user, as listed in the settings.
+///
+/// The token itself is never stored so it's not available here.
+#[derive(Debug)]
+pub struct ApiToken {
+    pub id: ApiTokenId,
+    pub name: String,
+    pub created_at: time::OffsetDateTime,
+}
+
+/// Creates a new API token named `name` for the user `user_id`.
+///
+/// Only a hash of the token is stored, the token itself is returned and can't be retrieved later.
+#[tracing::instrument(name = "Create API token", skip(executor))]
+pub async fn create_api_token<'e, E>(
+    executor: E,
+    user_id: UserId,
+    name: &str,
+) -> Result<Secret<String>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let token = {
+        let mut buf = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut buf);
+        Secret::new(hex::encode(buf))
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO api_tokens(id, user_id, token_hash, name)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        Uuid::new_v4(),
+        &user_id.0,
+        &compute_token_hash(&token)[..],
+        name,
+    )
+    .execute(executor)
+    .await
+    .context("Failed to create API token")?;
+
+    Ok(token)
+}
+
+/// Get the [`UserId`] owning the API token `token`.
+///
+/// Returns None if the token doesn't exist.
+#[tracing::instrument(name = "Get user id for API token", skip(executor, token))]
+pub async fn get_user_id_for_api_token<'e, E>(
+    executor: E,
+    token: &Secret<String>,
+) -> Result<Option<UserId>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let record = sqlx::query!(
+        r#"
+        SELECT user_id FROM api_tokens
+        WHERE token_hash = $1
+        "#,
+        &compute_token_hash(token)[..],
+    )
+    .fetch_optional(executor)
+    .await
+    .context("Failed to perform a query to retrieve the API token")?;
+
+    Ok(record.map(|record| UserId(record.user_id)))
+}
+
+/// Get all the API tokens of the user `user_id`, from the most recent to the oldest.
+#[tracing::instrument(name = "Get API tokens", skip(executor))]
+pub async fn get_api_tokens<'e, E>(
+    executor: E,
+    user_id: UserId,
+) -> Result<Vec<ApiToken>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let records = sqlx::query!(
+        r#"
+        SELECT id, name, created_at FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        &user_id.0,
+    )
+    .fetch_all(executor)
+    .await
+    .context("Failed to get the API tokens")?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| ApiToken {
+            id: ApiTokenId(record.id),
+            name: record.name,
+            created_at: record.created_at,
+        })
+        .collect())
+}
+
+/// Deletes the API token `token_id` of the user `user_id`; it can't be used anymore.
+///
+/// Returns false if the user has no such token.
+#[tracing::instrument(name = "Delete API token", skip(executor))]
+pub async fn delete_api_token<'e, E>(
+    executor: E,
+    user_id: UserId,
+    token_id: ApiTokenId,
+) -> Result<bool, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM api_tokens
+        WHERE user_id = $1 AND id = $2
+        "#,
+        &user_id.0,
+        &token_id.0,
+    )
+    .execute(executor)
+    .await
+    .context("Failed to delete the API token")?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+fn compute_token_hash(token: &Secret<String>) -> [u8; 64] {
+    let mut hasher = Blake2b512::new();
+    hasher.update(token.expose_secret().as_bytes());
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{create_user, get_pool};
+
+    #[tokio::test]
+    async fn api_token_should_resolve_to_its_user() {
+        let pool = get_pool().await;
+
+        let user_id = create_user(&pool).await;
+        let token = create_api_token(&pool, user_id, "test").await.unwrap();
+
+        let result = get_user_id_for_api_token(&pool, &token).await.unwrap();
+        assert_eq!(Some(user_id), result);
+    }
+
+    #[tokio::test]
+    async fn deleted_api_token_should_resolve_to_nothing() {
+        let pool = get_pool().await;
+
+        let user_id = create_user(&pool).await;
+        let token = create_api_token(&pool, user_id, "scripts").await.unwrap();
+
+        let tokens = get_api_tokens(&pool, user_id).await.unwrap();
+        assert_eq!(1, tokens.len());
+        assert_eq!("scripts", tokens[0].name);
+
+        // Another user can't delete the token
+        let other_user_id = create_user(&pool).await;
+        let deleted = delete_api_token(&pool, other_user_id, tokens[0].id)
+            .await
+            .unwrap();
+        assert!(!deleted);
+
+        let deleted = delete_api_token(&pool, user_id, tokens[0].id)
+            .await
+            .unwrap();
+        assert!(deleted);
+
+        let result = get_user_id_for_api_token(&pool, &token).await.unwrap();
+        assert!(result.is_none());
+        assert!(get_api_tokens(&pool, user_id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unknown_api_token_should_resolve_to_nothing() {
+        let pool = get_pool().await;
+
+        let token = Secret::new("foobar".to_string());
+
+        let result = get_user_id_for_api_token(&pool, &token).await.unwrap();
+        assert!(result.is_none());
+    }
+}
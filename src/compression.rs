@@ -0,0 +1,58 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, ContentEncoding, HeaderValue};
+use actix_web_lab::middleware::Next;
+
+/// Returns true if a response with the content type `content_type` is already compressed.
+fn is_compressed_content_type(content_type: &str) -> bool {
+    let content_type = content_type.trim_start().to_ascii_lowercase();
+
+    (content_type.starts_with("image/") && !content_type.starts_with("image/svg+xml"))
+        || content_type.starts_with("video/")
+        || content_type.starts_with("audio/")
+        || content_type.starts_with("application/zip")
+        || content_type.starts_with("application/gzip")
+}
+
+/// Prevents the compression of responses whose content is already compressed, like favicons.
+///
+/// This middleware must be registered _inside_ the [`actix_web::middleware::Compress`] middleware:
+/// it sets the `Content-Encoding` header to `identity` which makes the compress middleware skip the response.
+pub async fn skip_compressed_content(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let mut res = next.call(req).await?;
+
+    let already_compressed = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(is_compressed_content_type)
+        .unwrap_or(false);
+
+    if already_compressed && !res.headers().contains_key(header::CONTENT_ENCODING) {
+        res.headers_mut().insert(
+            header::CONTENT_ENCODING,
+            HeaderValue::from_static(ContentEncoding::Identity.as_str()),
+        );
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compressed_content_types_should_be_detected() {
+        assert!(is_compressed_content_type("image/x-icon"));
+        assert!(is_compressed_content_type("image/png"));
+        assert!(is_compressed_content_type("application/gzip"));
+
+        assert!(!is_compressed_content_type("image/svg+xml"));
+        assert!(!is_compressed_content_type("text/html; charset=utf-8"));
+        assert!(!is_compressed_content_type("application/json"));
+    }
+}
@@ -1,41 +1,226 @@
+use crate::authentication::PasswordHasherConfig;
 use crate::domain::UserEmail;
 use crate::tem;
-use secrecy::Secret;
+use secrecy::{ExposeSecret, Secret};
+use std::net::{Ipv6Addr, SocketAddr};
 use std::time::Duration as StdDuration;
 use tracing_subscriber::filter;
 
 #[derive(Clone, Debug, serde::Deserialize)]
 pub struct ApplicationConfig {
+    #[serde(default = "default_worker_threads")]
     pub worker_threads: usize,
     pub host: String,
+    /// Hosts to listen on, takes priority over `host` when set.
+    ///
+    /// A host can be a `host:port` pair, otherwise `port` is used.
+    pub hosts: Option<Vec<String>>,
     pub port: usize,
     pub base_url: String,
     pub cookie_signing_key: Secret<String>,
+    #[serde(default = "default_favicon_cache_size")]
+    pub favicon_cache_size: usize,
+    /// Compresses the HTTP responses if the client accepts it. Enabled by default.
+    #[serde(default = "default_compression")]
+    pub compression: bool,
+    /// Enables routes only useful for testing, like `POST /test-email`.
+    ///
+    /// Never enable this in production.
+    #[serde(default)]
+    pub test_mode: bool,
+    /// Allows adding feeds whose URL resolves to a loopback or private address.
+    ///
+    /// Only useful when the feeds are served locally, never enable this in production.
+    #[serde(default)]
+    pub allow_private_feed_urls: bool,
+    /// How long in-flight requests have to complete when shutting down.
+    #[serde(default = "default_shutdown_timeout_seconds")]
+    pub shutdown_timeout_seconds: u64,
+}
+
+fn default_worker_threads() -> usize {
+    num_cpus::get()
+}
+
+fn default_shutdown_timeout_seconds() -> u64 {
+    30
+}
+
+fn default_compression() -> bool {
+    true
+}
+
+fn default_favicon_cache_size() -> usize {
+    1000
+}
+
+impl ApplicationConfig {
+    /// Returns true if the routes only useful for testing should be enabled.
+    pub fn is_test_mode(&self) -> bool {
+        self.test_mode
+    }
+
+    pub fn shutdown_timeout(&self) -> StdDuration {
+        StdDuration::from_secs(self.shutdown_timeout_seconds)
+    }
+
+    /// Returns true if the HTTP responses should be compressed.
+    pub fn compression_enabled(&self) -> bool {
+        self.compression
+    }
+
+    /// Returns the `host:port` addresses to listen on.
+    ///
+    /// These are built from `hosts` if it's not empty, from `host` otherwise.
+    pub fn listen_addresses(&self) -> Vec<String> {
+        match &self.hosts {
+            Some(hosts) if !hosts.is_empty() => hosts
+                .iter()
+                .map(|host| with_port(host, self.port))
+                .collect(),
+            _ => vec![with_port(&self.host, self.port)],
+        }
+    }
+}
+
+/// Appends `port` to `host` unless it already has one.
+///
+/// IPv6 addresses are bracketed if necessary.
+fn with_port(host: &str, port: usize) -> String {
+    if host.parse::<SocketAddr>().is_ok() {
+        return host.to_string();
+    }
+
+    let unbracketed = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = unbracketed.parse::<Ipv6Addr>() {
+        return format!("[{}]:{}", ip, port);
+    }
+
+    if host.contains(':') {
+        // A hostname with an explicit port
+        host.to_string()
+    } else {
+        format!("{}:{}", host, port)
+    }
 }
 
 #[derive(Clone, Debug, serde::Deserialize)]
 pub struct JobConfig {
     pub run_interval_seconds: u64,
+    /// Jobs in the dead letter queue for longer than this are deleted.
+    #[serde(default = "default_job_retention_days")]
+    pub job_retention_days: u32,
+    /// A job is marked as failed after this many attempts.
+    #[serde(default = "default_job_max_attempts")]
+    pub max_attempts: u32,
+    /// A feed is refreshed in the background when its last refresh is older than this.
+    #[serde(default = "default_job_refresh_interval_seconds")]
+    pub refresh_interval_seconds: u64,
+    /// The maximum number of jobs added by the job runner in one tick.
+    #[serde(default = "default_job_limit")]
+    pub manage_jobs_limit: usize,
+    /// The maximum number of jobs run concurrently by the job runner in one tick.
+    #[serde(default = "default_job_limit")]
+    pub run_jobs_limit: usize,
+    /// A job running for longer than this is aborted and counted as a failed attempt.
+    #[serde(default = "default_job_timeout_seconds")]
+    pub job_timeout_seconds: u64,
+    #[serde(default)]
+    pub digest: DigestConfig,
+}
+
+fn default_job_retention_days() -> u32 {
+    30
+}
+
+fn default_job_max_attempts() -> u32 {
+    5
+}
+
+fn default_job_refresh_interval_seconds() -> u64 {
+    3600
+}
+
+fn default_job_limit() -> usize {
+    1
+}
+
+fn default_job_timeout_seconds() -> u64 {
+    300
 }
 
 impl JobConfig {
     pub fn run_interval(&self) -> StdDuration {
         StdDuration::from_secs(self.run_interval_seconds)
     }
+
+    pub fn refresh_interval(&self) -> StdDuration {
+        StdDuration::from_secs(self.refresh_interval_seconds)
+    }
+
+    pub fn job_timeout(&self) -> StdDuration {
+        StdDuration::from_secs(self.job_timeout_seconds)
+    }
+}
+
+/// Configures the daily digest email listing the unread entries of each user.
+///
+/// Sending the digest requires a TEM configuration.
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct DigestConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// The hour of the day, in UTC, from which the digest of the day is sent.
+    #[serde(default = "default_digest_send_hour")]
+    pub send_hour: u8,
+}
+
+fn default_digest_send_hour() -> u8 {
+    7
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            send_hour: default_digest_send_hour(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, serde::Deserialize)]
 pub struct SessionConfig {
     pub ttl_seconds: u64,
+    #[serde(default = "default_remember_me_ttl_seconds")]
+    pub remember_me_ttl_seconds: u64,
+    /// A session is logged out this long after the login, even if it is still in use.
+    #[serde(default = "default_absolute_ttl_seconds")]
+    pub absolute_ttl_seconds: u64,
     pub cleanup_enabled: bool,
     pub cleanup_interval_seconds: i64,
 }
 
+fn default_remember_me_ttl_seconds() -> u64 {
+    30 * 24 * 3600
+}
+
+fn default_absolute_ttl_seconds() -> u64 {
+    90 * 24 * 3600
+}
+
 impl SessionConfig {
     pub fn ttl(&self) -> StdDuration {
         StdDuration::from_secs(self.ttl_seconds)
     }
 
+    pub fn remember_me_ttl(&self) -> StdDuration {
+        StdDuration::from_secs(self.remember_me_ttl_seconds)
+    }
+
+    pub fn absolute_ttl(&self) -> StdDuration {
+        StdDuration::from_secs(self.absolute_ttl_seconds)
+    }
+
     pub fn cleanup_interval(&self) -> time::Duration {
         time::Duration::seconds(self.cleanup_interval_seconds)
     }
@@ -48,9 +233,37 @@ pub struct DatabaseConfig {
     pub port: u16,
     pub host: String,
     pub name: String,
+    /// The maximum number of connections in the pool.
+    #[serde(default = "default_database_max_connections")]
+    pub max_connections: u32,
+    /// The number of connections the pool tries to keep open, even when idle.
+    #[serde(default)]
+    pub min_connections: u32,
+    /// How long to wait for a connection of the pool before failing.
+    #[serde(default = "default_database_acquire_timeout_ms")]
+    pub acquire_timeout_ms: u64,
+    /// Idle connections are closed after this, if set.
+    #[serde(default)]
+    pub idle_timeout_seconds: Option<u64>,
+}
+
+fn default_database_max_connections() -> u32 {
+    10
+}
+
+fn default_database_acquire_timeout_ms() -> u64 {
+    3000
 }
 
-impl DatabaseConfig {}
+impl DatabaseConfig {
+    pub fn acquire_timeout(&self) -> StdDuration {
+        StdDuration::from_millis(self.acquire_timeout_ms)
+    }
+
+    pub fn idle_timeout(&self) -> Option<StdDuration> {
+        self.idle_timeout_seconds.map(StdDuration::from_secs)
+    }
+}
 
 #[derive(Clone, Debug, serde::Deserialize)]
 pub struct TEMConfig {
@@ -59,6 +272,8 @@ pub struct TEMConfig {
     pub auth_key: Secret<String>,
     pub sender_email: String,
     pub timeout_milliseconds: u64,
+    #[serde(default)]
+    pub retry: tem::RetryConfig,
 }
 
 impl TEMConfig {
@@ -71,6 +286,42 @@ impl TEMConfig {
     }
 }
 
+/// Configures the Argon2id parameters used to hash passwords.
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct Argon2Config {
+    /// The memory cost, in KiB.
+    #[serde(default = "default_argon2_memory_kib")]
+    pub memory_kib: u32,
+    /// The time cost, in number of passes.
+    #[serde(default = "default_argon2_iterations")]
+    pub iterations: u32,
+    /// The number of lanes.
+    #[serde(default = "default_argon2_parallelism")]
+    pub parallelism: u32,
+}
+
+fn default_argon2_memory_kib() -> u32 {
+    15000
+}
+
+fn default_argon2_iterations() -> u32 {
+    2
+}
+
+fn default_argon2_parallelism() -> u32 {
+    1
+}
+
+impl Default for Argon2Config {
+    fn default() -> Self {
+        Self {
+            memory_kib: default_argon2_memory_kib(),
+            iterations: default_argon2_iterations(),
+            parallelism: default_argon2_parallelism(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, serde::Deserialize)]
 pub struct JaegerConfig {
     pub host: String,
@@ -83,9 +334,36 @@ impl JaegerConfig {
     }
 }
 
+/// A list of tracing targets.
+///
+/// It can be either a list or a comma-separated string, which makes it possible to set it with an
+/// environment variable like `TRACING__TARGETS__LOGGING=sqlx=error,info`.
 #[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(from = "TracingTargetsRepr")]
 pub struct TracingTargets(Vec<String>);
 
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum TracingTargetsRepr {
+    List(Vec<String>),
+    CommaSeparated(String),
+}
+
+impl From<TracingTargetsRepr> for TracingTargets {
+    fn from(repr: TracingTargetsRepr) -> Self {
+        match repr {
+            TracingTargetsRepr::List(targets) => Self(targets),
+            TracingTargetsRepr::CommaSeparated(s) => Self(
+                s.split(',')
+                    .map(str::trim)
+                    .filter(|target| !target.is_empty())
+                    .map(ToString::to_string)
+                    .collect(),
+            ),
+        }
+    }
+}
+
 impl From<TracingTargets> for filter::Targets {
     fn from(targets: TracingTargets) -> Self {
         let v = targets.0.join(",");
@@ -104,15 +382,118 @@ pub struct TracingConfig {
     pub targets: AllTracingTargets,
 }
 
+impl Default for TracingConfig {
+    /// Logs everything at the info level except sqlx which is too noisy.
+    fn default() -> Self {
+        Self {
+            targets: AllTracingTargets {
+                logging: TracingTargets(vec!["sqlx=error".to_string(), "info".to_string()]),
+                jaeger: None,
+            },
+        }
+    }
+}
+
 #[derive(Clone, Debug, serde::Deserialize)]
 pub struct Config {
     pub application: ApplicationConfig,
     pub job: JobConfig,
     pub session: SessionConfig,
     pub database: DatabaseConfig,
-    pub tem: TEMConfig,
+    /// Without a TEM configuration no email is sent.
+    pub tem: Option<TEMConfig>,
     pub jaeger: Option<JaegerConfig>,
+    #[serde(default)]
     pub tracing: TracingConfig,
+    #[serde(default)]
+    pub argon2: Argon2Config,
+}
+
+/// The minimum length of the cookie signing key, actix-web panics with a shorter key.
+const MIN_COOKIE_SIGNING_KEY_LEN: usize = 64;
+
+impl Config {
+    /// Checks what deserialization can't: the values must also make sense for the application
+    /// to start.
+    ///
+    /// # Errors
+    ///
+    /// This function returns every problem found, not only the first one.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        let cookie_signing_key_len = self.application.cookie_signing_key.expose_secret().len();
+        if cookie_signing_key_len < MIN_COOKIE_SIGNING_KEY_LEN {
+            errors.push(format!(
+                "application.cookie_signing_key must be at least {} bytes long, it is {} bytes long",
+                MIN_COOKIE_SIGNING_KEY_LEN, cookie_signing_key_len
+            ));
+        }
+
+        if let Err(err) = url::Url::parse(&self.application.base_url) {
+            errors.push(format!(
+                "application.base_url \"{}\" is not a valid URL: {}",
+                self.application.base_url, err
+            ));
+        }
+
+        if self.application.worker_threads < 1 {
+            errors.push("application.worker_threads must be at least 1".to_string());
+        }
+
+        if self.session.absolute_ttl_seconds < 1 {
+            errors.push("session.absolute_ttl_seconds must be at least 1".to_string());
+        }
+
+        if self.database.port == 0 {
+            errors.push("database.port must be between 1 and 65535".to_string());
+        }
+
+        if self.database.max_connections < 1 {
+            errors.push("database.max_connections must be at least 1".to_string());
+        }
+
+        if self.database.min_connections > self.database.max_connections {
+            errors.push(format!(
+                "database.min_connections must be at most database.max_connections ({}), it is {}",
+                self.database.max_connections, self.database.min_connections
+            ));
+        }
+
+        if let Err(err) = PasswordHasherConfig::try_from(&self.argon2) {
+            errors.push(format!("argon2 parameters are invalid: {}", err));
+        }
+
+        if self.job.job_timeout_seconds < 1 {
+            errors.push("job.job_timeout_seconds must be at least 1".to_string());
+        }
+
+        if self.job.digest.send_hour > 23 {
+            errors.push(format!(
+                "job.digest.send_hour must be between 0 and 23, it is {}",
+                self.job.digest.send_hour
+            ));
+        }
+
+        if self.job.digest.enabled && self.tem.is_none() {
+            errors.push("job.digest.enabled requires a tem configuration".to_string());
+        }
+
+        if let Some(tem) = &self.tem {
+            if let Err(err) = tem.sender() {
+                errors.push(format!(
+                    "tem.sender_email \"{}\" is not a valid email: {}",
+                    tem.sender_email, err
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
 }
 
 pub fn get_configuration() -> Result<Config, config::ConfigError> {
@@ -123,12 +504,469 @@ pub fn get_configuration() -> Result<Config, config::ConfigError> {
         .add_source(
             config::File::new("/etc/servare.toml", config::FileFormat::Toml).required(false),
         )
-        .add_source(
-            config::Environment::default()
-                .try_parsing(true)
-                .separator("_"),
-        )
-        .build()?;
+        .add_source(environment_source());
+    let config_reader = override_port(config_reader, std::env::var("PORT").ok())?.build()?;
 
     config_reader.try_deserialize::<Config>()
 }
+
+/// Returns the source reading the configuration from the environment variables.
+///
+/// The sections and keys are separated with `__` since keys can contain a `_`, for example
+/// `DATABASE__MAX_CONNECTIONS=20` sets `max_connections` in the `database` section.
+fn environment_source() -> config::Environment {
+    config::Environment::default()
+        .try_parsing(true)
+        .separator("__")
+}
+
+/// Overrides the application port with `port` if it is set.
+///
+/// Platforms like Heroku or Railway provide the port to listen on in the `PORT` environment variable,
+/// when set it takes precedence over any other source.
+fn override_port<St>(
+    builder: config::builder::ConfigBuilder<St>,
+    port: Option<String>,
+) -> Result<config::builder::ConfigBuilder<St>, config::ConfigError>
+where
+    St: config::builder::BuilderState,
+{
+    let port = port
+        .map(|port| {
+            port.parse::<u16>().map_err(|_| {
+                config::ConfigError::Message(format!(
+                    "invalid PORT environment variable \"{}\"",
+                    port
+                ))
+            })
+        })
+        .transpose()?;
+
+    builder.set_override_option("application.port", port.map(i64::from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_application_config(data: &str) -> ApplicationConfig {
+        config::Config::builder()
+            .add_source(config::File::from_str(data, config::FileFormat::Toml))
+            .build()
+            .unwrap()
+            .try_deserialize::<ApplicationConfig>()
+            .unwrap()
+    }
+
+    #[test]
+    fn worker_threads_should_default_to_the_number_of_cpus() {
+        let config = parse_application_config(
+            r#"
+            host = "127.0.0.1"
+            port = 4052
+            base_url = "http://127.0.0.1"
+            cookie_signing_key = "foobar"
+            "#,
+        );
+
+        assert_eq!(num_cpus::get(), config.worker_threads);
+    }
+
+    #[test]
+    fn listen_addresses_should_fall_back_to_the_host() {
+        let config = parse_application_config(
+            r#"
+            host = "127.0.0.1"
+            port = 4052
+            base_url = "http://127.0.0.1"
+            cookie_signing_key = "foobar"
+            "#,
+        );
+
+        assert_eq!(vec!["127.0.0.1:4052"], config.listen_addresses());
+    }
+
+    #[test]
+    fn listen_addresses_should_use_the_hosts() {
+        let config = parse_application_config(
+            r#"
+            host = "127.0.0.1"
+            hosts = ["0.0.0.0", "::", "[::1]", "localhost", "127.0.0.1:8080", "[::1]:8080", "localhost:8080"]
+            port = 4052
+            base_url = "http://127.0.0.1"
+            cookie_signing_key = "foobar"
+            "#,
+        );
+
+        assert_eq!(
+            vec![
+                "0.0.0.0:4052",
+                "[::]:4052",
+                "[::1]:4052",
+                "localhost:4052",
+                "127.0.0.1:8080",
+                "[::1]:8080",
+                "localhost:8080",
+            ],
+            config.listen_addresses()
+        );
+    }
+
+    fn parse_application_config_with_port(data: &str, port: Option<&str>) -> ApplicationConfig {
+        let builder = config::Config::builder()
+            .add_source(config::File::from_str(data, config::FileFormat::Toml));
+
+        override_port(builder, port.map(ToString::to_string))
+            .unwrap()
+            .build()
+            .unwrap()
+            .get::<ApplicationConfig>("application")
+            .unwrap()
+    }
+
+    #[test]
+    fn port_env_var_should_override_the_config_file() {
+        let config = parse_application_config_with_port(
+            r#"
+            [application]
+            host = "127.0.0.1"
+            port = 4052
+            base_url = "http://127.0.0.1"
+            cookie_signing_key = "foobar"
+            "#,
+            Some("8080"),
+        );
+        assert_eq!(8080, config.port);
+
+        let config = parse_application_config_with_port(
+            r#"
+            [application]
+            host = "127.0.0.1"
+            port = 4052
+            base_url = "http://127.0.0.1"
+            cookie_signing_key = "foobar"
+            "#,
+            None,
+        );
+        assert_eq!(4052, config.port);
+    }
+
+    #[test]
+    fn port_env_var_should_make_the_port_optional() {
+        let config = parse_application_config_with_port(
+            r#"
+            [application]
+            host = "127.0.0.1"
+            base_url = "http://127.0.0.1"
+            cookie_signing_key = "foobar"
+            "#,
+            Some("8080"),
+        );
+        assert_eq!(8080, config.port);
+    }
+
+    #[test]
+    fn minimal_config_should_be_valid() {
+        let config = config::Config::builder()
+            .add_source(config::File::from_str(
+                r#"
+                [application]
+                host = "127.0.0.1"
+                port = 4052
+                base_url = "http://127.0.0.1"
+                cookie_signing_key = "foobar"
+
+                [job]
+                run_interval_seconds = 1
+
+                [session]
+                ttl_seconds = 604800
+                cleanup_enabled = true
+                cleanup_interval_seconds = 3600
+
+                [database]
+                username = "vincent"
+                password = "vincent"
+                port = 5432
+                host = "127.0.0.1"
+                name = "servare"
+                "#,
+                config::FileFormat::Toml,
+            ))
+            .build()
+            .unwrap()
+            .try_deserialize::<Config>()
+            .unwrap();
+
+        assert!(config.tem.is_none());
+        assert!(config.jaeger.is_none());
+        assert_eq!(30, config.job.job_retention_days);
+        assert_eq!(5, config.job.max_attempts);
+        assert_eq!(3600, config.job.refresh_interval_seconds);
+        assert_eq!(1, config.job.manage_jobs_limit);
+        assert_eq!(1, config.job.run_jobs_limit);
+        assert!(!config.job.digest.enabled);
+        assert_eq!(15000, config.argon2.memory_kib);
+        assert_eq!(2, config.argon2.iterations);
+        assert_eq!(1, config.argon2.parallelism);
+        assert_eq!(90 * 24 * 3600, config.session.absolute_ttl_seconds);
+        assert_eq!(10, config.database.max_connections);
+        assert_eq!(0, config.database.min_connections);
+        assert_eq!(3000, config.database.acquire_timeout_ms);
+        assert!(config.database.idle_timeout_seconds.is_none());
+        assert!(!config.application.is_test_mode());
+        assert!(config.application.compression_enabled());
+        assert_eq!(30, config.application.shutdown_timeout_seconds);
+        assert_eq!(
+            vec!["sqlx=error".to_string(), "info".to_string()],
+            config.tracing.targets.logging.0
+        );
+        assert!(config.tracing.targets.jaeger.is_none());
+
+        // The default targets must be valid
+        let _: filter::Targets = config.tracing.targets.logging.into();
+    }
+
+    #[test]
+    fn tracing_targets_should_be_read_from_a_comma_separated_env_var() {
+        // Don't read the actual environment, it's shared with the other tests
+        let env = config::Map::from([(
+            "TRACING__TARGETS__LOGGING".to_string(),
+            "sqlx=error,info".to_string(),
+        )]);
+
+        let config = config::Config::builder()
+            .add_source(environment_source().source(Some(env)))
+            .build()
+            .unwrap()
+            .get::<TracingConfig>("tracing")
+            .unwrap();
+
+        assert_eq!(
+            vec!["sqlx=error".to_string(), "info".to_string()],
+            config.targets.logging.0
+        );
+
+        let targets: filter::Targets = config.targets.logging.into();
+        assert_eq!(Some(tracing::Level::INFO.into()), targets.default_level());
+        assert!(targets.would_enable("sqlx", &tracing::Level::ERROR));
+        assert!(!targets.would_enable("sqlx", &tracing::Level::WARN));
+    }
+
+    #[test]
+    fn tracing_targets_should_be_read_from_a_list() {
+        let config = config::Config::builder()
+            .add_source(config::File::from_str(
+                r#"
+                [targets]
+                logging = ["sqlx=error", "info"]
+                jaeger = "servare=trace, debug"
+                "#,
+                config::FileFormat::Toml,
+            ))
+            .build()
+            .unwrap()
+            .try_deserialize::<TracingConfig>()
+            .unwrap();
+
+        assert_eq!(
+            vec!["sqlx=error".to_string(), "info".to_string()],
+            config.targets.logging.0
+        );
+        assert_eq!(
+            vec!["servare=trace".to_string(), "debug".to_string()],
+            config.targets.jaeger.unwrap().0
+        );
+    }
+
+    #[test]
+    fn invalid_port_env_var_should_fail() {
+        let builder = config::Config::builder();
+
+        let result = override_port(builder, Some("foobar".to_string()));
+        assert!(result.is_err());
+    }
+
+    fn valid_config() -> Config {
+        config::Config::builder()
+            .add_source(config::File::from_str(
+                r#"
+                [application]
+                host = "127.0.0.1"
+                port = 4052
+                base_url = "http://127.0.0.1"
+                cookie_signing_key = "1a730b845426442ce64762fbd20930360a9c5099095b3275f6f89cb6b7f164fc"
+
+                [job]
+                run_interval_seconds = 1
+
+                [session]
+                ttl_seconds = 604800
+                cleanup_enabled = true
+                cleanup_interval_seconds = 3600
+
+                [database]
+                username = "vincent"
+                password = "vincent"
+                port = 5432
+                host = "127.0.0.1"
+                name = "servare"
+
+                [tem]
+                base_url = "https://api.scaleway.com/transactional-email/v1alpha1/regions/fr-par"
+                project_id = "myprojectid"
+                auth_key = "myauthkey"
+                sender_email = "vincent@rischmann.fr"
+                timeout_milliseconds = 10000
+                "#,
+                config::FileFormat::Toml,
+            ))
+            .build()
+            .unwrap()
+            .try_deserialize::<Config>()
+            .unwrap()
+    }
+
+    fn validation_errors(config: &Config) -> Vec<String> {
+        config.validate().err().unwrap_or_default()
+    }
+
+    #[test]
+    fn valid_config_should_pass_the_validation() {
+        let config = valid_config();
+        assert_eq!(Ok(()), config.validate());
+
+        let config = Config {
+            tem: None,
+            ..valid_config()
+        };
+        assert_eq!(Ok(()), config.validate());
+    }
+
+    #[test]
+    fn validation_should_reject_a_short_cookie_signing_key() {
+        let mut config = valid_config();
+        config.application.cookie_signing_key = Secret::new("a".repeat(63));
+
+        let errors = validation_errors(&config);
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("application.cookie_signing_key"));
+    }
+
+    #[test]
+    fn validation_should_reject_an_invalid_base_url() {
+        let mut config = valid_config();
+        config.application.base_url = "127.0.0.1".to_string();
+
+        let errors = validation_errors(&config);
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("application.base_url"));
+    }
+
+    #[test]
+    fn validation_should_reject_an_invalid_sender_email() {
+        let mut config = valid_config();
+        config.tem.as_mut().unwrap().sender_email = "vincent".to_string();
+
+        let errors = validation_errors(&config);
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("tem.sender_email"));
+    }
+
+    #[test]
+    fn validation_should_reject_a_zero_absolute_session_ttl() {
+        let mut config = valid_config();
+        config.session.absolute_ttl_seconds = 0;
+
+        let errors = validation_errors(&config);
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("session.absolute_ttl_seconds"));
+    }
+
+    #[test]
+    fn validation_should_reject_a_zero_database_port() {
+        let mut config = valid_config();
+        config.database.port = 0;
+
+        let errors = validation_errors(&config);
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("database.port"));
+    }
+
+    #[test]
+    fn validation_should_reject_invalid_database_pool_sizes() {
+        let mut config = valid_config();
+        config.database.max_connections = 0;
+
+        let errors = validation_errors(&config);
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("database.max_connections"));
+
+        let mut config = valid_config();
+        config.database.min_connections = config.database.max_connections + 1;
+
+        let errors = validation_errors(&config);
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("database.min_connections"));
+    }
+
+    #[test]
+    fn validation_should_reject_zero_worker_threads() {
+        let mut config = valid_config();
+        config.application.worker_threads = 0;
+
+        let errors = validation_errors(&config);
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("application.worker_threads"));
+    }
+
+    #[test]
+    fn validation_should_reject_invalid_argon2_parameters() {
+        let mut config = valid_config();
+        config.argon2.iterations = 0;
+
+        let errors = validation_errors(&config);
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("argon2"));
+    }
+
+    #[test]
+    fn validation_should_reject_a_zero_job_timeout() {
+        let mut config = valid_config();
+        config.job.job_timeout_seconds = 0;
+
+        let errors = validation_errors(&config);
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("job.job_timeout_seconds"));
+    }
+
+    #[test]
+    fn validation_should_reject_an_invalid_digest_config() {
+        let mut config = valid_config();
+        config.job.digest.send_hour = 24;
+
+        let errors = validation_errors(&config);
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("job.digest.send_hour"));
+
+        let mut config = Config {
+            tem: None,
+            ..valid_config()
+        };
+        config.job.digest.enabled = true;
+
+        let errors = validation_errors(&config);
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("job.digest.enabled"));
+    }
+
+    #[test]
+    fn validation_should_report_every_error() {
+        let mut config = valid_config();
+        config.application.cookie_signing_key = Secret::new("foobar".to_string());
+        config.application.base_url = "".to_string();
+        config.database.port = 0;
+
+        assert_eq!(3, validation_errors(&config).len());
+    }
+}
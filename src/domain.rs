@@ -1,11 +1,19 @@
 use crate::impl_typed_uuid;
-use anyhow::anyhow;
+use crate::routes::see_other;
+use crate::sessions::TypedSession;
+use actix_web::dev::Payload;
+use actix_web::error::{ErrorInternalServerError, InternalError};
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest};
+use anyhow::{anyhow, Context};
+use futures_util::future::LocalBoxFuture;
 use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool};
 use std::fmt;
+use tracing::{event, Level};
 use uuid::Uuid;
 use validator::validate_email;
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Deserialize, Serialize)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize, Serialize)]
 pub struct UserId(pub Uuid);
 impl_typed_uuid!(UserId);
 
@@ -34,12 +42,224 @@ impl fmt::Display for UserEmail {
     }
 }
 
+#[derive(Clone, Debug)]
 pub struct User {
     pub id: UserId,
     pub email: UserEmail,
+    pub name: Option<String>,
+    pub is_admin: bool,
 }
 
 impl User {}
 
+/// Returns the user identified by `user_id`, if it exists.
+#[tracing::instrument(name = "Get user", skip(executor))]
+pub async fn get_user<'e, E>(executor: E, user_id: UserId) -> Result<Option<User>, anyhow::Error>
+where
+    E: PgExecutor<'e>,
+{
+    let record = sqlx::query!(
+        r#"
+        SELECT id, email, name, is_admin FROM users WHERE id = $1
+        "#,
+        &user_id.0,
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to fetch user")?;
+
+    Ok(record.map(|record| User {
+        id: UserId(record.id),
+        email: UserEmail(record.email),
+        name: record.name,
+        is_admin: record.is_admin,
+    }))
+}
+
+/// Grants or revokes the admin rights of the user identified by `user_id`.
+#[tracing::instrument(name = "Set user is admin", skip(executor))]
+pub async fn set_user_is_admin<'e, E>(
+    executor: E,
+    user_id: UserId,
+    is_admin: bool,
+) -> Result<(), anyhow::Error>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query!(
+        r#"
+        UPDATE users SET is_admin = $2 WHERE id = $1
+        "#,
+        &user_id.0,
+        is_admin,
+    )
+    .execute(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to set the user admin flag")?;
+
+    Ok(())
+}
+
+/// Deletes the user identified by `user_id` along with its API tokens, tags and categories.
+///
+/// The feeds, jobs and sessions of the user must be deleted first.
+/// Returns false if the user doesn't exist.
+#[tracing::instrument(name = "Delete user", skip(executor))]
+pub async fn delete_user<'e, E>(executor: E, user_id: UserId) -> Result<bool, anyhow::Error>
+where
+    E: PgExecutor<'e>,
+{
+    let result = sqlx::query!(
+        r#"
+        WITH deleted_api_tokens AS (
+            DELETE FROM api_tokens WHERE user_id = $1
+        ), deleted_tags AS (
+            DELETE FROM tags WHERE user_id = $1
+        ), deleted_categories AS (
+            DELETE FROM categories WHERE user_id = $1
+        )
+        DELETE FROM users WHERE id = $1
+        "#,
+        &user_id.0,
+    )
+    .execute(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to delete user")?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Extracts the [`User`] of the current request, loading it from the database.
+///
+/// The user is identified by the [`UserId`] in the request extensions or in the session.
+/// The loaded user is stored in the request extensions so it's loaded at most once per request;
+/// once extracted, other extractors can use a `web::ReqData<User>`.
+///
+/// Redirects to the login page if there's no logged in user.
+pub struct UserExtractor(pub User);
+
+impl UserExtractor {
+    pub fn into_inner(self) -> User {
+        self.0
+    }
+}
+
+impl FromRequest for UserExtractor {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        let session = TypedSession::from_request(&req, payload);
+
+        Box::pin(async move {
+            let cached_user = req.extensions().get::<User>().cloned();
+            if let Some(user) = cached_user {
+                event!(Level::TRACE, user_id = %user.id, "found user in the request extensions");
+
+                return Ok(UserExtractor(user));
+            }
+
+            // Set by the API token middleware or by `reject_anonymous_users`
+            let user_id = req.extensions().get::<UserId>().copied();
+            let user_id = match user_id {
+                Some(user_id) => user_id,
+                None => session
+                    .await?
+                    .get_user_id()
+                    .map_err(ErrorInternalServerError)?
+                    .ok_or_else(not_logged_in)?,
+            };
+
+            let pool = req
+                .app_data::<web::Data<PgPool>>()
+                .ok_or_else(|| ErrorInternalServerError("no database pool configured"))?;
+
+            let user = get_user(pool.as_ref(), user_id)
+                .await
+                .map_err(ErrorInternalServerError)?
+                .ok_or_else(not_logged_in)?;
+
+            req.extensions_mut().insert(user.clone());
+
+            Ok(UserExtractor(user))
+        })
+    }
+}
+
+fn not_logged_in() -> actix_web::Error {
+    let response = see_other("/login");
+    let err = anyhow!("The user has not logged in");
+    InternalError::from_response(err, response).into()
+}
+
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use crate::tests::{create_user, get_pool};
+    use actix_web::test::TestRequest;
+
+    #[tokio::test]
+    async fn get_user_should_return_the_user() {
+        let pool = get_pool().await;
+        let user_id = create_user(&pool).await;
+
+        let user = get_user(&pool, user_id).await.unwrap().unwrap();
+        assert_eq!(user_id, user.id);
+        assert!(!user.is_admin);
+
+        set_user_is_admin(&pool, user_id, true).await.unwrap();
+        let user = get_user(&pool, user_id).await.unwrap().unwrap();
+        assert!(user.is_admin);
+
+        let user = get_user(&pool, UserId::default()).await.unwrap();
+        assert!(user.is_none());
+    }
+
+    #[actix_web::test]
+    async fn user_extractor_should_load_the_user_and_cache_it() {
+        let pool = get_pool().await;
+        let user_id = create_user(&pool).await;
+
+        let req = TestRequest::default()
+            .app_data(web::Data::new(pool.clone()))
+            .to_http_request();
+        req.extensions_mut().insert(user_id);
+
+        let user = UserExtractor::extract(&req).await.unwrap().into_inner();
+        assert_eq!(user_id, user.id);
+
+        let cached_user = req.extensions().get::<User>().cloned().unwrap();
+        assert_eq!(user_id, cached_user.id);
+        assert_eq!(user.email.0, cached_user.email.0);
+    }
+
+    #[actix_web::test]
+    async fn user_extractor_should_use_the_cached_user() {
+        let user = User {
+            id: UserId::default(),
+            email: UserEmail("foo@example.com".to_string()),
+            name: None,
+            is_admin: false,
+        };
+
+        // No database pool: the user must come from the request extensions
+        let req = TestRequest::default().to_http_request();
+        req.extensions_mut().insert(user.clone());
+
+        let extracted_user = UserExtractor::extract(&req).await.unwrap().into_inner();
+        assert_eq!(user.id, extracted_user.id);
+        assert_eq!("foo@example.com", extracted_user.email.as_ref());
+    }
+
+    #[actix_web::test]
+    async fn user_extractor_should_fail_without_a_logged_in_user() {
+        let req = TestRequest::default().to_http_request();
+
+        let result = UserExtractor::extract(&req).await;
+        assert!(result.is_err());
+    }
+}
@@ -1,15 +1,25 @@
 use crate::domain::UserId;
-use crate::html::{fetch_document, find_link_in_document, FindLinkCriteria};
+use crate::fetch_bytes;
+use crate::html::{fetch_document, find_links_in_document, sanitize_html, FindLinkCriteria};
+use crate::image::detect_favicon_mime;
 use crate::impl_typed_id;
+use crate::parsed_feed::{normalize_authors, parse_raw_feed};
 pub use crate::parsed_feed::{ParseError, ParsedFeed, ParsedFeedEntry};
 use anyhow::Context;
 use feed_rs::model::Feed as RawFeed;
+use lru::LruCache;
+use select::document::Document;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::collections::HashMap;
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use time::format_description::well_known::Rfc3339;
 use tracing::{event, Level};
 use url::Url;
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Deserialize, Serialize)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize, Serialize)]
 pub struct FeedId(pub i64);
 impl_typed_id!(FeedId);
 
@@ -22,14 +32,73 @@ impl_typed_id!(FeedEntryId);
 pub struct FeedEntry {
     pub id: FeedEntryId,
     pub feed_id: FeedId,
+    pub external_id: Option<String>,
     pub url: Option<Url>,
     pub title: String,
     pub summary: String,
     pub created_at: time::OffsetDateTime,
+    /// The publication date according to the feed, missing for the entries stored before it was
+    /// recorded.
+    pub published_at: Option<time::OffsetDateTime>,
     pub authors: Vec<String>,
+    pub read_at: Option<time::OffsetDateTime>,
+    pub starred_at: Option<time::OffsetDateTime>,
+}
+
+impl FeedEntry {
+    /// Returns true if the entry has been read.
+    pub fn is_read(&self) -> bool {
+        self.read_at.is_some()
+    }
+
+    /// Returns true if the entry has been starred.
+    pub fn is_starred(&self) -> bool {
+        self.starred_at.is_some()
+    }
+}
+
+/// A page of feed entries returned by [`get_feed_entries_page`].
+#[derive(Debug)]
+pub struct EntryPage {
+    pub entries: Vec<FeedEntry>,
+    /// The cursor to get the next page, if there is one.
+    pub next_cursor: Option<FeedEntryId>,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize, Serialize)]
+pub struct FeedCategoryId(pub i64);
+impl_typed_id!(FeedCategoryId);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize, Serialize)]
+pub struct FeedTagId(pub i64);
+impl_typed_id!(FeedTagId);
+
+/// A tag of a user; contrary to a category a feed can have any number of tags.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeedTag {
+    pub id: FeedTagId,
+    pub name: String,
+}
+
+/// A category of a user used to group its feeds.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeedCategory {
+    pub id: FeedCategoryId,
+    pub name: String,
 }
 
-impl FeedEntry {}
+impl FeedCategory {
+    /// Builds a [`FeedCategory`] from the nullable columns of a `LEFT JOIN` on the categories.
+    fn from_nullable(id: Option<i64>, name: Option<String>) -> Option<Self> {
+        match (id, name) {
+            (Some(id), Some(name)) => Some(Self {
+                id: FeedCategoryId(id),
+                name,
+            }),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Feed {
@@ -38,8 +107,10 @@ pub struct Feed {
     pub title: String,
     pub site_link: Option<Url>,
     pub description: String,
+    pub language: Option<String>,
     pub site_favicon: Option<Vec<u8>>,
     pub added_at: time::OffsetDateTime,
+    pub category: Option<FeedCategory>,
 }
 
 impl Feed {}
@@ -59,28 +130,50 @@ pub enum FindError {
 pub enum FoundFeed {
     Url(Url),
     Raw(RawFeed),
+    Json(ParsedFeed),
 }
 
 /// Find the feed at [`url`].
-/// TODO(vincent): return all detected feeds
+///
+/// If `data` is a HTML document linking to multiple feeds only the first one is returned,
+/// use [`find_all_feeds`] to get all of them.
 ///
 /// # Errors
 ///
-/// This function will return an error if .
+/// This function will return [`FindError::NoFeed`] if `data` is neither a feed nor a HTML
+/// document linking to a feed.
 #[tracing::instrument(name = "Find feed", skip(url, data))]
 pub fn find_feed(url: &Url, data: &[u8]) -> Result<FoundFeed, FindError> {
+    find_all_feeds(url, data)
+        .into_iter()
+        .next()
+        .ok_or(FindError::NoFeed)
+}
+
+/// Find all the feeds at [`url`].
+///
+/// If `data` is a feed it is the only one returned, otherwise `data` is parsed as a HTML
+/// document and every feed it links to is returned, in document order.
+#[tracing::instrument(name = "Find all feeds", skip(url, data))]
+pub fn find_all_feeds(url: &Url, data: &[u8]) -> Vec<FoundFeed> {
+    // Try to parse as a JSON Feed
+    if let Some((feed, _)) = parse_json_feed(url, data) {
+        event!(Level::INFO, "found a JSON feed");
+        return vec![FoundFeed::Json(feed)];
+    }
+
     // Try to parse as a feed
-    match feed_rs::parser::parse(data) {
+    match parse_raw_feed(data) {
         Ok(feed) => {
             event!(Level::INFO, "found a raw feed");
-            return Ok(FoundFeed::Raw(feed));
+            return vec![FoundFeed::Raw(feed)];
         }
         Err(err) => {
             event!(Level::WARN, %err, "unable to find a raw feed");
         }
     }
 
-    // If not a valid feed, try to parse as a HTML document to find a link
+    // If not a valid feed, try to parse as a HTML document to find the links
     match select::document::Document::from_read(data) {
         Ok(document) => {
             event!(Level::INFO, "found a HTML document, need parsing");
@@ -88,10 +181,13 @@ pub fn find_feed(url: &Url, data: &[u8]) -> Result<FoundFeed, FindError> {
             let criteria = &[
                 FindLinkCriteria::Type("application/rss+xml"),
                 FindLinkCriteria::Type("application/atom+xml"),
+                FindLinkCriteria::Type("application/feed+json"),
             ];
 
-            if let Some(url) = find_link_in_document(url, &document, criteria) {
-                return Ok(FoundFeed::Url(url));
+            let urls = find_links_in_document(url, &document, criteria);
+            if !urls.is_empty() {
+                event!(Level::INFO, count = urls.len(), "found feed links");
+                return urls.into_iter().map(FoundFeed::Url).collect();
             }
         }
         Err(err) => {
@@ -103,28 +199,124 @@ pub fn find_feed(url: &Url, data: &[u8]) -> Result<FoundFeed, FindError> {
 
     event!(Level::INFO, url = %url, "found no feed");
 
-    Err(FindError::NoFeed)
+    Vec::new()
+}
+
+/// The version prefix of the JSON Feed format, see https://www.jsonfeed.org/version/1.1/
+const JSON_FEED_VERSION_PREFIX: &str = "https://jsonfeed.org/version/";
+
+#[derive(Deserialize)]
+struct JsonFeed {
+    version: String,
+    title: String,
+    home_page_url: Option<String>,
+    description: Option<String>,
+    language: Option<String>,
+    #[serde(default)]
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(Deserialize)]
+struct JsonFeedItem {
+    id: String,
+    url: Option<String>,
+    title: Option<String>,
+    summary: Option<String>,
+    content_html: Option<String>,
+    content_text: Option<String>,
+    /// Version 1.1
+    #[serde(default)]
+    authors: Vec<JsonFeedAuthor>,
+    /// Version 1, deprecated in 1.1
+    author: Option<JsonFeedAuthor>,
+    /// RFC 3339 dates
+    date_published: Option<String>,
+    date_modified: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JsonFeedAuthor {
+    name: Option<String>,
+}
+
+/// Parses `data` as a [JSON Feed](https://www.jsonfeed.org) located at `url`.
+///
+/// Returns None if `data` is not a JSON Feed.
+pub fn parse_json_feed(url: &Url, data: &[u8]) -> Option<(ParsedFeed, Vec<ParsedFeedEntry>)> {
+    let json_feed: JsonFeed = serde_json::from_slice(data).ok()?;
+    if !json_feed.version.starts_with(JSON_FEED_VERSION_PREFIX) {
+        return None;
+    }
+
+    let feed = ParsedFeed {
+        url: url.clone(),
+        title: json_feed.title,
+        site_link: json_feed
+            .home_page_url
+            .as_ref()
+            .and_then(|v| Url::parse(v).ok()),
+        description: json_feed.description.unwrap_or_default(),
+        language: json_feed.language,
+    };
+
+    let entries = json_feed
+        .items
+        .into_iter()
+        .map(|item| {
+            let summary = item
+                .summary
+                .or(item.content_html)
+                .or(item.content_text)
+                .map(|v| sanitize_html(&v))
+                .unwrap_or_default();
+
+            let authors = item
+                .authors
+                .into_iter()
+                .chain(item.author)
+                .filter_map(|author| author.name);
+
+            let published_at = item
+                .date_published
+                .or(item.date_modified)
+                .and_then(|v| time::OffsetDateTime::parse(&v, &Rfc3339).ok());
+
+            ParsedFeedEntry {
+                external_id: item.id,
+                url: item.url.as_ref().and_then(|v| Url::parse(v).ok()),
+                title: item.title.unwrap_or_default(),
+                summary,
+                authors: normalize_authors(authors),
+                published_at,
+            }
+        })
+        .collect();
+
+    Some((feed, entries))
 }
 
 /// Create a new feed in the database for this `user_id` with the URL `url`.
 #[tracing::instrument(
     name = "Insert feed",
-    skip(pool, feed),
+    skip(executor, feed),
     fields(
         url = tracing::field::Empty,
     )
 )]
-pub async fn insert_feed(
-    pool: &PgPool,
+pub async fn insert_feed<'e, E>(
+    executor: E,
     user_id: UserId,
     feed: &ParsedFeed,
-) -> Result<FeedId, sqlx::Error> {
+) -> Result<FeedId, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
     // TODO(vincent): use a proper custom error type ?
 
     let result = sqlx::query!(
         r#"
-        INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
-        VALUES ($1, $2, $3, $4, $5, $6)
+        INSERT INTO feeds(user_id, url, title, site_link, description, language, added_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
         RETURNING id
         "#,
         &user_id.0,
@@ -135,9 +327,10 @@ pub async fn insert_feed(
             .map(|v| v.to_string())
             .unwrap_or_default(),
         &feed.description,
+        feed.language.as_deref(),
         time::OffsetDateTime::now_utc(),
     )
-    .fetch_one(pool)
+    .fetch_one(executor)
     .await?;
 
     let feed_id = FeedId(result.id);
@@ -145,28 +338,189 @@ pub async fn insert_feed(
     Ok(feed_id)
 }
 
+/// The field used to order a list of feeds.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum FeedOrderField {
+    #[default]
+    AddedAt,
+    Title,
+    UnreadCount,
+}
+
+/// The direction of an ordering.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum OrderDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// How to order a list of feeds.
+///
+/// The default is the most recently added feeds first.
+///
+/// A [`FeedOrder`] is stored as a string like `title:asc`, see its [`FromStr`](std::str::FromStr)
+/// and [`Display`](fmt::Display) implementations.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct FeedOrder {
+    pub field: FeedOrderField,
+    pub direction: OrderDirection,
+}
+
+impl FeedOrder {
+    /// All the possible orders, used to build a selector.
+    pub const ALL: [FeedOrder; 6] = [
+        FeedOrder::new(FeedOrderField::AddedAt, OrderDirection::Desc),
+        FeedOrder::new(FeedOrderField::AddedAt, OrderDirection::Asc),
+        FeedOrder::new(FeedOrderField::Title, OrderDirection::Asc),
+        FeedOrder::new(FeedOrderField::Title, OrderDirection::Desc),
+        FeedOrder::new(FeedOrderField::UnreadCount, OrderDirection::Desc),
+        FeedOrder::new(FeedOrderField::UnreadCount, OrderDirection::Asc),
+    ];
+
+    pub const fn new(field: FeedOrderField, direction: OrderDirection) -> Self {
+        Self { field, direction }
+    }
+
+    /// Returns a human readable description of the order.
+    pub fn label(&self) -> &'static str {
+        match (self.field, self.direction) {
+            (FeedOrderField::AddedAt, OrderDirection::Desc) => "Recently added first",
+            (FeedOrderField::AddedAt, OrderDirection::Asc) => "Oldest first",
+            (FeedOrderField::Title, OrderDirection::Asc) => "Title, A to Z",
+            (FeedOrderField::Title, OrderDirection::Desc) => "Title, Z to A",
+            (FeedOrderField::UnreadCount, OrderDirection::Desc) => "Most unread first",
+            (FeedOrderField::UnreadCount, OrderDirection::Asc) => "Least unread first",
+        }
+    }
+}
+
+impl fmt::Display for FeedOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let field = match self.field {
+            FeedOrderField::AddedAt => "added_at",
+            FeedOrderField::Title => "title",
+            FeedOrderField::UnreadCount => "unread_count",
+        };
+        let direction = match self.direction {
+            OrderDirection::Asc => "asc",
+            OrderDirection::Desc => "desc",
+        };
+
+        write!(f, "{}:{}", field, direction)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid feed order \"{0}\"")]
+pub struct InvalidFeedOrder(String);
+
+impl std::str::FromStr for FeedOrder {
+    type Err = InvalidFeedOrder;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidFeedOrder(s.to_string());
+
+        let (field, direction) = s.split_once(':').ok_or_else(invalid)?;
+
+        let field = match field {
+            "added_at" => FeedOrderField::AddedAt,
+            "title" => FeedOrderField::Title,
+            "unread_count" => FeedOrderField::UnreadCount,
+            _ => return Err(invalid()),
+        };
+        let direction = match direction {
+            "asc" => OrderDirection::Asc,
+            "desc" => OrderDirection::Desc,
+            _ => return Err(invalid()),
+        };
+
+        Ok(Self { field, direction })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct FeedRecord {
+    id: i64,
+    url: String,
+    title: String,
+    site_link: String,
+    description: String,
+    language: Option<String>,
+    site_favicon: Option<Vec<u8>>,
+    added_at: time::OffsetDateTime,
+    category_id: Option<i64>,
+    category_name: Option<String>,
+    unread_count: i64,
+}
+
 #[tracing::instrument(name = "Get all feeds", skip(executor))]
-pub async fn get_all_feeds<'e, E>(executor: E, user_id: UserId) -> Result<Vec<Feed>, anyhow::Error>
+pub async fn get_all_feeds<'e, E>(
+    executor: E,
+    user_id: UserId,
+    order: FeedOrder,
+) -> Result<Vec<Feed>, anyhow::Error>
 where
     E: sqlx::PgExecutor<'e>,
 {
-    let records = sqlx::query!(
+    let feeds = get_all_feeds_with_unread_counts(executor, user_id, order).await?;
+
+    Ok(feeds.into_iter().map(|(feed, _)| feed).collect())
+}
+
+/// Get all the feeds of the user `user_id` along with their number of unread entries.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred or if a stored URL is invalid.
+#[tracing::instrument(name = "Get all feeds with unread counts", skip(executor))]
+pub async fn get_all_feeds_with_unread_counts<'e, E>(
+    executor: E,
+    user_id: UserId,
+    order: FeedOrder,
+) -> Result<Vec<(Feed, i64)>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let mut query_builder = sqlx::QueryBuilder::<sqlx::Postgres>::new(
         r#"
         SELECT
-            f.id, f.url, f.title, f.site_link, f.description,
-            f.site_favicon, f.has_favicon,
-            f.added_at
+            f.id, f.url, f.title, f.site_link, f.description, f.language,
+            f.site_favicon,
+            f.added_at,
+            c.id AS category_id, c.name AS category_name,
+            uc.unread_count
         FROM feeds f
         INNER JOIN users u ON f.user_id = u.id
-        WHERE u.id = $1
-        ORDER BY f.added_at DESC
-        "#,
-        &user_id.0,
-    )
-    .fetch_all(executor)
-    .await
-    .map_err(Into::<anyhow::Error>::into)
-    .context("unable to fetch all feeds")?;
+        LEFT JOIN categories c ON c.id = f.category_id
+        LEFT JOIN LATERAL (
+            SELECT COUNT(fe.id) AS unread_count
+            FROM feed_entries fe
+            WHERE fe.feed_id = f.id AND fe.read_at IS NULL
+        ) uc ON true
+        WHERE u.id = "#,
+    );
+    query_builder.push_bind(user_id.0);
+
+    query_builder.push(" ORDER BY ");
+    query_builder.push(match order.field {
+        FeedOrderField::AddedAt => "f.added_at",
+        FeedOrderField::Title => "lower(f.title)",
+        FeedOrderField::UnreadCount => "uc.unread_count",
+    });
+    query_builder.push(match order.direction {
+        OrderDirection::Asc => " ASC",
+        OrderDirection::Desc => " DESC",
+    });
+    // Make the order deterministic when the field is the same
+    query_builder.push(", f.id DESC");
+
+    let records = query_builder
+        .build_query_as::<FeedRecord>()
+        .fetch_all(executor)
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .context("unable to fetch all feeds")?;
 
     let mut feeds = Vec::with_capacity(records.len());
     for record in records {
@@ -176,423 +530,3311 @@ where
 
         let site_link = Url::parse(&record.site_link).ok();
 
-        feeds.push(Feed {
+        let feed = Feed {
             id: FeedId(record.id),
             url,
             title: record.title,
             site_link,
             description: record.description,
+            language: record.language,
             site_favicon: record.site_favicon,
             added_at: record.added_at,
-        });
+            category: FeedCategory::from_nullable(record.category_id, record.category_name),
+        };
+
+        feeds.push((feed, record.unread_count));
     }
 
     Ok(feeds)
 }
 
-#[tracing::instrument(name = "Get feed", skip(executor))]
-pub async fn get_feed<'e, E>(
+/// Count the unread entries of all the feeds of the user `user_id`.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Get total unread count", skip(executor))]
+pub async fn get_total_unread_count<'e, E>(
     executor: E,
     user_id: UserId,
-    feed_id: &FeedId,
-) -> Result<Option<Feed>, anyhow::Error>
+) -> Result<i64, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let record = sqlx::query!(
+        r#"
+        SELECT COUNT(fe.id) AS "count!"
+        FROM feed_entries fe
+        INNER JOIN feeds f ON f.id = fe.feed_id
+        WHERE f.user_id = $1 AND fe.read_at IS NULL
+        "#,
+        &user_id.0,
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to count the unread entries")?;
+
+    Ok(record.count)
+}
+
+/// Count the unread entries of all the feeds of the user `user_id` created since `since`, if set.
+///
+/// This is the number of entries [`get_unread_entries`] would return without a limit.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Get unread count since", skip(executor))]
+pub async fn get_unread_count_since<'e, E>(
+    executor: E,
+    user_id: UserId,
+    since: Option<time::OffsetDateTime>,
+) -> Result<i64, anyhow::Error>
 where
     E: sqlx::PgExecutor<'e>,
 {
     let record = sqlx::query!(
+        r#"
+        SELECT COUNT(fe.id) AS "count!"
+        FROM feed_entries fe
+        INNER JOIN feeds f ON f.id = fe.feed_id
+        WHERE f.user_id = $1 AND fe.read_at IS NULL
+          AND ($2::timestamptz IS NULL OR fe.created_at >= $2)
+        "#,
+        &user_id.0,
+        since,
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to count the unread entries")?;
+
+    Ok(record.count)
+}
+
+/// Get the `limit` feeds of the user `user_id` added most recently, the most recent first.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * a SQL error occurred
+/// * a stored feed URL is invalid somehow
+#[tracing::instrument(name = "Get recently added feeds", skip(executor))]
+pub async fn get_recently_added_feeds<'e, E>(
+    executor: E,
+    user_id: UserId,
+    limit: usize,
+) -> Result<Vec<Feed>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+
+    let records = sqlx::query!(
         r#"
         SELECT
-            f.id, f.url, f.title, f.site_link, f.description,
+            f.id, f.url, f.title, f.site_link, f.description, f.language,
             f.site_favicon, f.has_favicon,
-            f.added_at
+            f.added_at,
+            c.id AS "category_id?", c.name AS "category_name?"
         FROM feeds f
         INNER JOIN users u ON f.user_id = u.id
-        WHERE u.id = $1 AND f.id = $2
-
+        LEFT JOIN categories c ON c.id = f.category_id
+        WHERE u.id = $1
+        ORDER BY f.added_at DESC, f.id DESC
+        LIMIT $2
         "#,
         &user_id.0,
-        &feed_id.0,
+        limit,
     )
-    .fetch_optional(executor)
+    .fetch_all(executor)
     .await
     .map_err(Into::<anyhow::Error>::into)
-    .context("unable to fetch feed")?;
+    .context("unable to fetch the recently added feeds")?;
 
-    if let Some(record) = record {
+    let mut feeds = Vec::with_capacity(records.len());
+    for record in records {
         let url = Url::parse(&record.url)
             .map_err(Into::<anyhow::Error>::into)
-            .context("unable to parse the stored feed URL")?;
+            .context("stored feed URL is invalid")?;
 
         let site_link = Url::parse(&record.site_link).ok();
 
-        let feed = Feed {
+        feeds.push(Feed {
             id: FeedId(record.id),
             url,
             title: record.title,
             site_link,
             description: record.description,
+            language: record.language,
             site_favicon: record.site_favicon,
             added_at: record.added_at,
-        };
-
-        Ok(Some(feed))
-    } else {
-        Ok(None)
+            category: FeedCategory::from_nullable(record.category_id, record.category_name),
+        });
     }
+
+    Ok(feeds)
 }
 
-#[tracing::instrument(
-    name = "Get feed favicon",
-    skip(pool),
-    fields(
-        user_id = %user_id,
-        feed_id = %feed_id,
-    ),
-)]
-pub async fn get_feed_favicon(
-    pool: &PgPool,
+/// Get the [`FeedOrder`] preferred by the user `user_id`.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Get user feed order", skip(executor))]
+pub async fn get_user_feed_order<'e, E>(
+    executor: E,
     user_id: UserId,
-    feed_id: &FeedId,
-) -> Result<Option<Vec<u8>>, anyhow::Error> {
-    let result = sqlx::query!(
+) -> Result<FeedOrder, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let record = sqlx::query!(
         r#"
-        SELECT f.site_favicon
-        FROM feeds f
-        INNER JOIN users u ON f.user_id = u.id
-        WHERE u.id = $1 AND f.id = $2
+        SELECT feed_order FROM users WHERE id = $1
         "#,
         &user_id.0,
-        &feed_id.0,
     )
-    .fetch_optional(pool)
+    .fetch_one(executor)
     .await
     .map_err(Into::<anyhow::Error>::into)
-    .context("unable to fetch the feed favicon")?;
-
-    if let Some(record) = result {
-        Ok(record.site_favicon)
-    } else {
-        Ok(None)
-    }
-}
-
-/// Given a website at [`url`], try to find its favicon URL.
-///
-/// Returns ['None'] if no favicon is found.
-#[tracing::instrument(name = "Find favicon", skip(client, url))]
-pub async fn find_favicon(client: &reqwest::Client, url: &Url) -> Option<Url> {
-    // 1) First try to find the favicon in the HTML document
+    .context("unable to fetch the user feed order")?;
 
-    match fetch_document(client, url).await {
-        Ok(document) => {
-            event!(Level::DEBUG, "found a HTML document");
-
-            let criterias = &[
-                FindLinkCriteria::Type("image/x-icon"),
-                FindLinkCriteria::Type("image/icon"),
-                FindLinkCriteria::Rel("icon"),
-            ];
-            find_link_in_document(url, &document, criterias)
-        }
-        Err(err) => {
-            event!(Level::ERROR, %err, "failed to parse URL as an HTML document");
-            None
-        }
-    }
+    // An invalid stored value is not worth failing for
+    Ok(record.feed_order.parse().unwrap_or_default())
 }
 
-/// Get all entries for the feed `feed_id`.
+/// Set the [`FeedOrder`] preferred by the user `user_id`.
 ///
 /// # Errors
 ///
-/// This function will return an error if:
-/// * a SQL error occurred
-/// * the stored feed entry URL is invalid somehow
-#[tracing::instrument(
-    name = "Get feed entries",
-    skip(executor),
-    fields(
-        user_id = %user_id,
-        feed_id = %feed_id,
-    ),
-)]
-pub async fn get_feed_entries<'e, E>(
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Set user feed order", skip(executor))]
+pub async fn set_user_feed_order<'e, E>(
     executor: E,
     user_id: UserId,
-    feed_id: &FeedId,
-) -> Result<Vec<FeedEntry>, anyhow::Error>
+    order: FeedOrder,
+) -> Result<(), anyhow::Error>
 where
     E: sqlx::PgExecutor<'e>,
 {
-    let records = sqlx::query!(
+    sqlx::query!(
         r#"
-        SELECT
-          fe.id, fe.title, fe.url, fe.summary, fe.created_at, fe.authors
-        FROM feeds f
-        INNER JOIN feed_entries fe ON fe.feed_id = f.id
-        INNER JOIN users u ON f.user_id = u.id
-        WHERE u.id = $1 AND f.id = $2
+        UPDATE users SET feed_order = $2 WHERE id = $1
         "#,
         &user_id.0,
-        &feed_id.0,
+        order.to_string(),
     )
-    .fetch_all(executor)
+    .execute(executor)
     .await
     .map_err(Into::<anyhow::Error>::into)
-    .context("unable to fetch the feed entries")?;
+    .context("unable to update the user feed order")?;
 
-    let mut entries = Vec::with_capacity(records.len());
+    Ok(())
+}
+
+/// Returns true if the user `user_id` wants the entries of a feed to be marked as read when viewing them.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Get user auto mark read on view", skip(executor))]
+pub async fn get_user_auto_mark_read_on_view<'e, E>(
+    executor: E,
+    user_id: UserId,
+) -> Result<bool, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let record = sqlx::query!(
+        r#"
+        SELECT auto_mark_read_on_view FROM users WHERE id = $1
+        "#,
+        &user_id.0,
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to fetch the user auto mark read on view preference")?;
+
+    Ok(record.auto_mark_read_on_view)
+}
+
+/// Set whether the user `user_id` wants the entries of a feed to be marked as read when viewing them.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Set user auto mark read on view", skip(executor))]
+pub async fn set_user_auto_mark_read_on_view<'e, E>(
+    executor: E,
+    user_id: UserId,
+    enabled: bool,
+) -> Result<(), anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        r#"
+        UPDATE users SET auto_mark_read_on_view = $2 WHERE id = $1
+        "#,
+        &user_id.0,
+        enabled,
+    )
+    .execute(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to update the user auto mark read on view preference")?;
+
+    Ok(())
+}
+
+/// Returns the number of days of unread entries the user `user_id` wants to see, if limited.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Get user unread since days", skip(executor))]
+pub async fn get_user_unread_since_days<'e, E>(
+    executor: E,
+    user_id: UserId,
+) -> Result<Option<i32>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let record = sqlx::query!(
+        r#"
+        SELECT unread_since_days FROM users WHERE id = $1
+        "#,
+        &user_id.0,
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to fetch the user unread since days preference")?;
+
+    Ok(record.unread_since_days)
+}
+
+/// Set the number of days of unread entries the user `user_id` wants to see, `None` meaning all.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Set user unread since days", skip(executor))]
+pub async fn set_user_unread_since_days<'e, E>(
+    executor: E,
+    user_id: UserId,
+    days: Option<i32>,
+) -> Result<(), anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        r#"
+        UPDATE users SET unread_since_days = $2 WHERE id = $1
+        "#,
+        &user_id.0,
+        days,
+    )
+    .execute(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to update the user unread since days preference")?;
+
+    Ok(())
+}
+
+/// Get all feeds of the user `user_id` tagged with `tag`.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * a SQL error occurred
+/// * a stored feed URL is invalid somehow
+#[tracing::instrument(name = "Get feeds by tag", skip(executor))]
+pub async fn get_feeds_by_tag<'e, E>(
+    executor: E,
+    user_id: UserId,
+    tag: &str,
+) -> Result<Vec<Feed>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let records = sqlx::query!(
+        r#"
+        SELECT
+            f.id, f.url, f.title, f.site_link, f.description, f.language,
+            f.site_favicon, f.has_favicon,
+            f.added_at,
+            c.id AS "category_id?", c.name AS "category_name?"
+        FROM feeds f
+        INNER JOIN users u ON f.user_id = u.id
+        INNER JOIN feed_tags ft ON ft.feed_id = f.id
+        INNER JOIN tags t ON t.id = ft.tag_id
+        LEFT JOIN categories c ON c.id = f.category_id
+        WHERE u.id = $1 AND t.user_id = $1 AND t.name = $2
+        ORDER BY lower(f.title) ASC, f.id DESC
+        "#,
+        &user_id.0,
+        tag,
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to fetch the feeds by tag")?;
+
+    let mut feeds = Vec::with_capacity(records.len());
     for record in records {
-        entries.push(FeedEntry {
-            id: FeedEntryId(record.id),
-            feed_id: *feed_id,
-            url: parse_url_from_record(record.url)?,
+        let url = Url::parse(&record.url)
+            .map_err(Into::<anyhow::Error>::into)
+            .context("stored feed URL is invalid")?;
+
+        let site_link = Url::parse(&record.site_link).ok();
+
+        feeds.push(Feed {
+            id: FeedId(record.id),
+            url,
             title: record.title,
-            summary: record.summary,
-            created_at: record.created_at,
-            authors: record.authors.unwrap_or_default(),
-        })
+            site_link,
+            description: record.description,
+            language: record.language,
+            site_favicon: record.site_favicon,
+            added_at: record.added_at,
+            category: FeedCategory::from_nullable(record.category_id, record.category_name),
+        });
     }
 
-    Ok(entries)
+    Ok(feeds)
 }
 
-/// Get the entry `entry_id` for the feed `feed_id`.
+/// Get all feeds of the user `user_id` with at least one entry written by `author`.
 ///
 /// # Errors
 ///
 /// This function will return an error if:
 /// * a SQL error occurred
-/// * the stored feed entry URL is invalid somehow
-#[tracing::instrument(
-    name = "Get feed entry",
-    skip(executor),
-    fields(
-        user_id = %user_id,
-        feed_id = %feed_id,
-        entry_id = %entry_id,
-    ),
-)]
-pub async fn get_feed_entry<'e, E>(
+/// * a stored feed URL is invalid somehow
+#[tracing::instrument(name = "Get feeds by author", skip(executor))]
+pub async fn get_feeds_by_author<'e, E>(
     executor: E,
     user_id: UserId,
-    feed_id: &FeedId,
-    entry_id: &FeedEntryId,
-) -> Result<Option<FeedEntry>, anyhow::Error>
+    author: &str,
+) -> Result<Vec<Feed>, anyhow::Error>
 where
     E: sqlx::PgExecutor<'e>,
 {
-    let record = sqlx::query!(
+    let records = sqlx::query!(
         r#"
         SELECT
-          fe.id, fe.title, fe.url, fe.summary, fe.created_at, fe.authors
+            f.id, f.url, f.title, f.site_link, f.description, f.language,
+            f.site_favicon, f.has_favicon,
+            f.added_at,
+            c.id AS "category_id?", c.name AS "category_name?"
         FROM feeds f
-        INNER JOIN feed_entries fe ON fe.feed_id = f.id
         INNER JOIN users u ON f.user_id = u.id
-        WHERE u.id = $1 AND f.id = $2 AND fe.id = $3
+        LEFT JOIN categories c ON c.id = f.category_id
+        WHERE u.id = $1 AND EXISTS (
+            SELECT 1 FROM feed_entries fe
+            WHERE fe.feed_id = f.id AND fe.authors @> $2::jsonb
+        )
+        ORDER BY f.added_at DESC
         "#,
         &user_id.0,
-        &feed_id.0,
-        &entry_id.0,
+        serde_json::json!([author]),
     )
-    .fetch_optional(executor)
+    .fetch_all(executor)
     .await
     .map_err(Into::<anyhow::Error>::into)
-    .context("unable to fetch the feed entry")?;
+    .context("unable to fetch the feeds by author")?;
 
-    let result = if let Some(record) = record {
-        Some(FeedEntry {
-            id: FeedEntryId(record.id),
-            feed_id: *feed_id,
-            url: parse_url_from_record(record.url)?,
+    let mut feeds = Vec::with_capacity(records.len());
+    for record in records {
+        let url = Url::parse(&record.url)
+            .map_err(Into::<anyhow::Error>::into)
+            .context("stored feed URL is invalid")?;
+
+        let site_link = Url::parse(&record.site_link).ok();
+
+        feeds.push(Feed {
+            id: FeedId(record.id),
+            url,
             title: record.title,
-            summary: record.summary,
-            created_at: record.created_at,
-            authors: record.authors.unwrap_or_default(),
-        })
-    } else {
-        None
-    };
+            site_link,
+            description: record.description,
+            language: record.language,
+            site_favicon: record.site_favicon,
+            added_at: record.added_at,
+            category: FeedCategory::from_nullable(record.category_id, record.category_name),
+        });
+    }
 
-    Ok(result)
+    Ok(feeds)
 }
 
-/// Get the unread feed entries.
-///
-/// TODO(vincent): this might need some pagination ?
+/// Get all feeds of the user `user_id` written in `language`.
 ///
 /// # Errors
 ///
 /// This function will return an error if:
 /// * a SQL error occurred
-/// * the stored feed entry URL is invalid somehow
-#[tracing::instrument(
-    name = "Get unread entries",
-    skip(executor),
-    fields(
-        user_id = %user_id,
-    ),
-)]
-pub async fn get_unread_entries<'e, E>(
+/// * a stored feed URL is invalid somehow
+#[tracing::instrument(name = "Get feeds by language", skip(executor))]
+pub async fn get_feeds_by_language<'e, E>(
     executor: E,
     user_id: UserId,
-) -> Result<Vec<FeedEntry>, anyhow::Error>
+    language: &str,
+) -> Result<Vec<Feed>, anyhow::Error>
 where
     E: sqlx::PgExecutor<'e>,
 {
     let records = sqlx::query!(
         r#"
         SELECT
-          fe.id, fe.feed_id, fe.title, fe.url, fe.summary, fe.created_at, fe.authors
+            f.id, f.url, f.title, f.site_link, f.description, f.language,
+            f.site_favicon, f.has_favicon,
+            f.added_at,
+            c.id AS "category_id?", c.name AS "category_name?"
         FROM feeds f
-        INNER JOIN feed_entries fe ON fe.feed_id = f.id
         INNER JOIN users u ON f.user_id = u.id
-        WHERE u.id = $1 AND fe.read_at IS NULL
-        ORDER BY created_at DESC
+        LEFT JOIN categories c ON c.id = f.category_id
+        WHERE u.id = $1 AND f.language = $2
+        ORDER BY f.added_at DESC
         "#,
         &user_id.0,
+        language,
     )
     .fetch_all(executor)
     .await
     .map_err(Into::<anyhow::Error>::into)
-    .context("unable to fetch the feed entries")?;
+    .context("unable to fetch the feeds by language")?;
 
-    let mut result = Vec::new();
+    let mut feeds = Vec::with_capacity(records.len());
     for record in records {
-        let feed_entry = FeedEntry {
-            id: FeedEntryId(record.id),
-            feed_id: FeedId(record.feed_id),
-            url: parse_url_from_record(record.url)?,
+        let url = Url::parse(&record.url)
+            .map_err(Into::<anyhow::Error>::into)
+            .context("stored feed URL is invalid")?;
+
+        let site_link = Url::parse(&record.site_link).ok();
+
+        feeds.push(Feed {
+            id: FeedId(record.id),
+            url,
             title: record.title,
-            summary: record.summary,
-            created_at: record.created_at,
-            authors: record.authors.unwrap_or_default(),
-        };
-        result.push(feed_entry);
+            site_link,
+            description: record.description,
+            language: record.language,
+            site_favicon: record.site_favicon,
+            added_at: record.added_at,
+            category: FeedCategory::from_nullable(record.category_id, record.category_name),
+        });
     }
 
-    Ok(result)
+    Ok(feeds)
 }
 
+/// Update the metadata of the feed `feed_id` with the freshly parsed `feed`.
+///
+/// For now only the language is updated, and only if it changed.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
 #[tracing::instrument(
-    name = "Mark a feed entry as read",
-    skip(executor),
+    name = "Update feed metadata",
+    skip(executor, feed),
     fields(
         user_id = %user_id,
         feed_id = %feed_id,
-        entry_id = %entry_id,
     ),
 )]
-pub async fn mark_feed_entry_as_read<'e, E>(
+pub async fn update_feed_metadata<'e, E>(
     executor: E,
     user_id: UserId,
     feed_id: &FeedId,
-    entry_id: &FeedEntryId,
+    feed: &ParsedFeed,
 ) -> Result<(), anyhow::Error>
 where
     E: sqlx::PgExecutor<'e>,
 {
     sqlx::query!(
         r#"
-        UPDATE feed_entries
-        SET read_at = now()
-        FROM feeds f
-        INNER JOIN users u ON f.user_id = u.id
-        WHERE u.id = $1 AND f.id = $2 AND feed_entries.id = $3
+        UPDATE feeds
+        SET language = $3
+        WHERE user_id = $1 AND id = $2 AND language IS DISTINCT FROM $3
         "#,
         &user_id.0,
         &feed_id.0,
-        &entry_id.0,
+        feed.language.as_deref(),
     )
     .execute(executor)
     .await
     .map_err(Into::<anyhow::Error>::into)
-    .context("unable to fetch the feed entry")?;
+    .context("unable to update the feed metadata")?;
 
     Ok(())
 }
 
-/// Check if a feed with the given `url` already exists.
+/// The HTTP cache validators of a feed, returned by the server the last time it was fetched.
+///
+/// They're sent back in a conditional GET to avoid downloading a feed that didn't change.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FeedCacheValidators {
+    /// The value of the `ETag` header
+    pub etag: Option<String>,
+    /// The value of the `Last-Modified` header
+    pub last_modified: Option<String>,
+}
+
+/// Get the HTTP cache validators of the feed `feed_id`.
+///
+/// Returns `None` if the feed doesn't exist.
 ///
 /// # Errors
 ///
-/// This function will return an error if there's a SQL error.
-pub async fn feed_with_url_exists<'e, E>(
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Get feed cache validators", skip(executor))]
+pub async fn get_feed_cache_validators<'e, E>(
     executor: E,
     user_id: UserId,
-    url: &Url,
-) -> Result<bool, anyhow::Error>
+    feed_id: &FeedId,
+) -> Result<Option<FeedCacheValidators>, anyhow::Error>
 where
     E: sqlx::PgExecutor<'e>,
 {
     let record = sqlx::query!(
         r#"
-        SELECT f.id FROM feeds f
-        INNER JOIN users u ON f.user_id = u.id
-        WHERE u.id = $1 AND f.url = $2
+        SELECT last_etag, last_modified
+        FROM feeds
+        WHERE user_id = $1 AND id = $2
         "#,
         &user_id.0,
-        url.to_string(),
+        &feed_id.0,
     )
     .fetch_optional(executor)
     .await
     .map_err(Into::<anyhow::Error>::into)
-    .context("unable to find the feed")?;
+    .context("unable to get the feed cache validators")?;
 
-    Ok(record.is_some())
+    Ok(record.map(|record| FeedCacheValidators {
+        etag: record.last_etag,
+        last_modified: record.last_modified,
+    }))
 }
 
-/// Parse a URL as it is stored in a record generated by sqlx.
+/// Set the HTTP cache validators of the feed `feed_id`.
 ///
 /// # Errors
 ///
-/// This function will return an error if the URL is invalid somehow.
-pub fn parse_url_from_record(s: Option<String>) -> Result<Option<Url>, url::ParseError> {
-    // This ugly thing goes from:
-    // Option<String> to Option<&str> to Result<Option<Url>, _>
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Set feed cache validators", skip(executor))]
+pub async fn set_feed_cache_validators<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: &FeedId,
+    validators: &FeedCacheValidators,
+) -> Result<(), anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        r#"
+        UPDATE feeds SET last_etag = $1, last_modified = $2
+        WHERE id = $3 AND user_id = $4
+        "#,
+        validators.etag.as_deref(),
+        validators.last_modified.as_deref(),
+        &feed_id.0,
+        &user_id.0,
+    )
+    .execute(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to set the feed cache validators")?;
 
-    let url_str: Option<&str> = s.as_deref();
-    url_str.map(Url::parse).transpose()
+    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::tests::fetch;
-    use wiremock::matchers::any;
-    use wiremock::{Mock, MockServer, ResponseTemplate};
+/// Records that the feed `feed_id` was just refreshed.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Set feed last refreshed at", skip(executor))]
+pub async fn set_feed_last_refreshed_at<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: &FeedId,
+) -> Result<(), anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        r#"
+        UPDATE feeds SET last_refreshed_at = now()
+        WHERE id = $1 AND user_id = $2
+        "#,
+        &feed_id.0,
+        &user_id.0,
+    )
+    .execute(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to set the feed last refreshed at")?;
 
-    #[derive(rust_embed::RustEmbed)]
-    #[folder = "testdata/"]
-    struct TestData;
+    Ok(())
+}
 
-    #[tokio::test]
-    async fn find_feed_should_work() {
-        let mock_server = MockServer::start().await;
-        let mock_uri = mock_server.uri();
-        let mock_url = Url::parse(&mock_uri).unwrap();
+#[tracing::instrument(name = "Get feed", skip(executor))]
+pub async fn get_feed<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: &FeedId,
+) -> Result<Option<Feed>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let record = sqlx::query!(
+        r#"
+        SELECT
+            f.id, f.url, f.title, f.site_link, f.description, f.language,
+            f.site_favicon, f.has_favicon,
+            f.added_at,
+            c.id AS "category_id?", c.name AS "category_name?"
+        FROM feeds f
+        INNER JOIN users u ON f.user_id = u.id
+        LEFT JOIN categories c ON c.id = f.category_id
+        WHERE u.id = $1 AND f.id = $2
 
-        Mock::given(any())
-            .respond_with(ResponseTemplate::new(200).set_body_raw(
-                TestData::get("tailscale_rss_feed.xml").unwrap().data,
-                "application/xml",
-            ))
-            .expect(1)
-            .mount(&mock_server)
-            .await;
+        "#,
+        &user_id.0,
+        &feed_id.0,
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to fetch feed")?;
 
-        let data = fetch(&mock_url).await;
-        let found_feed = find_feed(&mock_url, &data[..]).unwrap();
+    if let Some(record) = record {
+        let url = Url::parse(&record.url)
+            .map_err(Into::<anyhow::Error>::into)
+            .context("unable to parse the stored feed URL")?;
 
-        let feed = match found_feed {
-            FoundFeed::Raw(raw_feed) => ParsedFeed::from_raw_feed(&mock_url, raw_feed),
-            FoundFeed::Url(_) => panic!("expected a FoundFeed::Raw"),
+        let site_link = Url::parse(&record.site_link).ok();
+
+        let feed = Feed {
+            id: FeedId(record.id),
+            url,
+            title: record.title,
+            site_link,
+            description: record.description,
+            language: record.language,
+            site_favicon: record.site_favicon,
+            added_at: record.added_at,
+            category: FeedCategory::from_nullable(record.category_id, record.category_name),
         };
 
-        let site_link = feed.site_link.map(|v| v.to_string()).unwrap_or_default();
+        Ok(Some(feed))
+    } else {
+        Ok(None)
+    }
+}
 
-        assert_eq!("Blog on Tailscale", feed.title);
-        assert_eq!("https://tailscale.com/blog/", site_link);
-        assert_eq!("Recent content in Blog on Tailscale", feed.description);
+/// Get the category named `name` of the user `user_id`, creating it if it doesn't exist.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Get or create category", skip(executor))]
+pub async fn get_or_create_category<'e, E>(
+    executor: E,
+    user_id: UserId,
+    name: &str,
+) -> Result<FeedCategoryId, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    // The no-op update makes RETURNING work when the category already exists
+    let record = sqlx::query!(
+        r#"
+        INSERT INTO categories(user_id, name) VALUES($1, $2)
+        ON CONFLICT (user_id, name) DO UPDATE SET name = EXCLUDED.name
+        RETURNING id
+        "#,
+        &user_id.0,
+        name,
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to get or create the category")?;
+
+    Ok(FeedCategoryId(record.id))
+}
+
+/// Set the category of the feed `feed_id` to `category_id`, or remove it if `None`.
+///
+/// Returns false if the user `user_id` has no feed `feed_id`.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Set feed category", skip(executor))]
+pub async fn set_feed_category<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: &FeedId,
+    category_id: Option<FeedCategoryId>,
+) -> Result<bool, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let result = sqlx::query!(
+        r#"
+        UPDATE feeds SET category_id = $3
+        WHERE user_id = $1 AND id = $2
+        "#,
+        &user_id.0,
+        &feed_id.0,
+        category_id.map(|id| id.0),
+    )
+    .execute(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to set the feed category")?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Delete the feed `feed_id` and all its entries.
+///
+/// Returns false if the user `user_id` has no feed `feed_id`.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Delete feed", skip(executor))]
+pub async fn delete_feed<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: &FeedId,
+) -> Result<bool, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    // The entries are deleted in the same statement to satisfy the foreign key
+    let result = sqlx::query!(
+        r#"
+        WITH deleted_entries AS (
+            DELETE FROM feed_entries
+            WHERE feed_id IN (SELECT id FROM feeds WHERE user_id = $1 AND id = $2)
+        )
+        DELETE FROM feeds
+        WHERE user_id = $1 AND id = $2
+        "#,
+        &user_id.0,
+        &feed_id.0,
+    )
+    .execute(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to delete the feed")?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Delete all the feeds of the user `user_id` and all their entries.
+///
+/// Returns the number of deleted feeds.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Delete all feeds", skip(executor))]
+pub async fn delete_all_feeds<'e, E>(executor: E, user_id: UserId) -> Result<u64, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    // The entries are deleted in the same statement to satisfy the foreign key
+    let result = sqlx::query!(
+        r#"
+        WITH deleted_entries AS (
+            DELETE FROM feed_entries
+            WHERE feed_id IN (SELECT id FROM feeds WHERE user_id = $1)
+        )
+        DELETE FROM feeds
+        WHERE user_id = $1
+        "#,
+        &user_id.0,
+    )
+    .execute(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to delete the feeds")?;
+
+    Ok(result.rows_affected())
+}
+
+/// Get the tag named `name` of the user `user_id`, creating it if it doesn't exist.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Get or create tag", skip(executor))]
+pub async fn get_or_create_tag<'e, E>(
+    executor: E,
+    user_id: UserId,
+    name: &str,
+) -> Result<FeedTagId, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    // The no-op update makes RETURNING work when the tag already exists
+    let record = sqlx::query!(
+        r#"
+        INSERT INTO tags(user_id, name) VALUES($1, $2)
+        ON CONFLICT (user_id, name) DO UPDATE SET name = EXCLUDED.name
+        RETURNING id
+        "#,
+        &user_id.0,
+        name,
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to get or create the tag")?;
+
+    Ok(FeedTagId(record.id))
+}
+
+/// Add the tag `tag_id` to the feed `feed_id`.
+///
+/// Adding a tag the feed already has does nothing.
+/// Returns false if the user `user_id` has no feed `feed_id` or no tag `tag_id`.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Add feed tag", skip(executor))]
+pub async fn add_feed_tag<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: &FeedId,
+    tag_id: FeedTagId,
+) -> Result<bool, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    // The no-op update makes RETURNING work when the feed already has the tag
+    let record = sqlx::query!(
+        r#"
+        INSERT INTO feed_tags(feed_id, tag_id)
+        SELECT f.id, t.id
+        FROM feeds f
+        INNER JOIN tags t ON t.user_id = f.user_id
+        WHERE f.user_id = $1 AND f.id = $2 AND t.id = $3
+        ON CONFLICT (feed_id, tag_id) DO UPDATE SET tag_id = EXCLUDED.tag_id
+        RETURNING feed_id
+        "#,
+        &user_id.0,
+        &feed_id.0,
+        &tag_id.0,
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to add the feed tag")?;
+
+    Ok(record.is_some())
+}
+
+/// Remove the tag `tag_id` from the feed `feed_id`.
+///
+/// Returns false if the feed `feed_id` of the user `user_id` doesn't have the tag.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Remove feed tag", skip(executor))]
+pub async fn remove_feed_tag<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: &FeedId,
+    tag_id: FeedTagId,
+) -> Result<bool, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM feed_tags ft
+        USING feeds f
+        WHERE ft.feed_id = f.id AND f.user_id = $1 AND f.id = $2 AND ft.tag_id = $3
+        "#,
+        &user_id.0,
+        &feed_id.0,
+        &tag_id.0,
+    )
+    .execute(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to remove the feed tag")?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Get the tags of all feeds of the user `user_id`, sorted by name.
+///
+/// Feeds without tags are not in the returned map.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Get feeds tags", skip(executor))]
+pub async fn get_feeds_tags<'e, E>(
+    executor: E,
+    user_id: UserId,
+) -> Result<HashMap<FeedId, Vec<FeedTag>>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let records = sqlx::query!(
+        r#"
+        SELECT ft.feed_id, t.id, t.name
+        FROM feed_tags ft
+        INNER JOIN tags t ON t.id = ft.tag_id
+        WHERE t.user_id = $1
+        ORDER BY t.name
+        "#,
+        &user_id.0,
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to fetch the feeds tags")?;
+
+    let mut result: HashMap<FeedId, Vec<FeedTag>> = HashMap::new();
+    for record in records {
+        result
+            .entry(FeedId(record.feed_id))
+            .or_default()
+            .push(FeedTag {
+                id: FeedTagId(record.id),
+                name: record.name,
+            });
+    }
+
+    Ok(result)
+}
+
+/// A favicon with its MIME type.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Favicon {
+    pub data: Vec<u8>,
+    pub mime: String,
+}
+
+#[tracing::instrument(
+    name = "Get feed favicon",
+    skip(pool),
+    fields(
+        user_id = %user_id,
+        feed_id = %feed_id,
+    ),
+)]
+pub async fn get_feed_favicon(
+    pool: &PgPool,
+    user_id: UserId,
+    feed_id: &FeedId,
+) -> Result<Option<Favicon>, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        SELECT f.site_favicon, f.site_favicon_mime
+        FROM feeds f
+        INNER JOIN users u ON f.user_id = u.id
+        WHERE u.id = $1 AND f.id = $2
+        "#,
+        &user_id.0,
+        &feed_id.0,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to fetch the feed favicon")?;
+
+    let favicon = result.and_then(|record| {
+        let data = record.site_favicon?;
+
+        // Favicons stored before the MIME type was detected don't have one
+        let mime = record
+            .site_favicon_mime
+            .unwrap_or_else(|| detect_favicon_mime(&data).to_string());
+
+        Some(Favicon { data, mime })
+    });
+
+    Ok(favicon)
+}
+
+type FaviconLruCache = LruCache<(UserId, FeedId), Arc<Favicon>>;
+
+/// An in-memory LRU cache of the feed favicons, used to avoid hitting the database every time a
+/// favicon is served.
+///
+/// Entries are keyed by both the user and the feed so that a cache hit never serves a favicon of a
+/// feed the user doesn't own.
+#[derive(Clone)]
+pub struct FaviconCache {
+    inner: Arc<Mutex<FaviconLruCache>>,
+}
+
+impl FaviconCache {
+    /// Creates a new cache holding at most `capacity` favicons (at least one).
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity.max(1)).expect("capacity should never be zero");
+
+        Self {
+            inner: Arc::new(Mutex::new(LruCache::new(capacity))),
+        }
+    }
+
+    pub fn get(&self, user_id: UserId, feed_id: &FeedId) -> Option<Arc<Favicon>> {
+        let mut inner = self.inner.lock().expect("favicon cache lock poisoned");
+        inner.get(&(user_id, *feed_id)).cloned()
+    }
+
+    pub fn insert(&self, user_id: UserId, feed_id: FeedId, favicon: Arc<Favicon>) {
+        let mut inner = self.inner.lock().expect("favicon cache lock poisoned");
+        inner.put((user_id, feed_id), favicon);
+    }
+
+    pub fn invalidate(&self, user_id: UserId, feed_id: &FeedId) {
+        let mut inner = self.inner.lock().expect("favicon cache lock poisoned");
+        inner.pop(&(user_id, *feed_id));
+    }
+}
+
+/// A link to the favicon of a site, found in its HTML document.
+#[derive(Debug, PartialEq)]
+enum FaviconLink {
+    /// A link to the icon itself.
+    Icon(Url),
+    /// A link to a web app manifest listing the icons of the site.
+    Manifest(Url),
+}
+
+/// Find the link to the favicon of the site at `url` in its HTML `document`.
+///
+/// The explicit icon links are preferred, then the Apple touch icons and finally the web app manifest.
+fn find_favicon_link(url: &Url, document: &Document) -> Option<FaviconLink> {
+    let icon_criteria = &[
+        FindLinkCriteria::Type("image/x-icon"),
+        FindLinkCriteria::Type("image/icon"),
+        FindLinkCriteria::Rel("icon"),
+    ];
+    let apple_touch_icon_criteria = &[
+        FindLinkCriteria::Rel("apple-touch-icon"),
+        FindLinkCriteria::Rel("apple-touch-icon-precomposed"),
+    ];
+
+    for criteria in [&icon_criteria[..], &apple_touch_icon_criteria[..]] {
+        if let Some(icon_url) = find_links_in_document(url, document, criteria)
+            .into_iter()
+            .next()
+        {
+            return Some(FaviconLink::Icon(icon_url));
+        }
+    }
+
+    find_links_in_document(url, document, &[FindLinkCriteria::Rel("manifest")])
+        .into_iter()
+        .next()
+        .map(FaviconLink::Manifest)
+}
+
+#[derive(Deserialize)]
+struct WebAppManifest {
+    #[serde(default)]
+    icons: Vec<WebAppManifestIcon>,
+}
+
+#[derive(Deserialize)]
+struct WebAppManifestIcon {
+    src: String,
+    #[serde(default)]
+    sizes: Option<String>,
+}
+
+impl WebAppManifestIcon {
+    /// Returns the area of the largest size of this icon.
+    ///
+    /// A scalable icon (with the size `any`) is larger than everything else, an icon without a
+    /// valid size is smaller than everything else.
+    fn largest_area(&self) -> u64 {
+        let sizes = self.sizes.as_deref().unwrap_or_default();
+
+        sizes
+            .split_whitespace()
+            .filter_map(|size| {
+                if size.eq_ignore_ascii_case("any") {
+                    return Some(u64::MAX);
+                }
+
+                let (width, height) = size.split_once(['x', 'X'])?;
+                let width: u64 = width.parse().ok()?;
+                let height: u64 = height.parse().ok()?;
+
+                Some(width * height)
+            })
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+/// Find the URL of the highest resolution icon in the web app manifest `data`, located at `manifest_url`.
+///
+/// Returns [`None`] if the manifest is invalid or doesn't have any icon.
+pub fn find_manifest_icon(manifest_url: &Url, data: &[u8]) -> Option<Url> {
+    let manifest: WebAppManifest = match serde_json::from_slice(data) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            event!(Level::WARN, %err, "invalid web app manifest");
+            return None;
+        }
+    };
+
+    // On equal sizes the first icon wins
+    let (_, icon) = manifest
+        .icons
+        .iter()
+        .enumerate()
+        .max_by_key(|(i, icon)| (icon.largest_area(), std::cmp::Reverse(*i)))?;
+
+    // The icon URLs are relative to the manifest, not to the site
+    manifest_url.join(&icon.src).ok()
+}
+
+/// Given a website at [`url`], try to find its favicon URL.
+///
+/// The favicon is searched in the explicit icon links, then in the Apple touch icons and finally in
+/// the web app manifest.
+///
+/// Returns ['None'] if no favicon is found.
+#[tracing::instrument(name = "Find favicon", skip(client, url))]
+pub async fn find_favicon(client: &reqwest::Client, url: &Url) -> Option<Url> {
+    // 1) First try to find the favicon link in the HTML document
+
+    let link = match fetch_document(client, url).await {
+        Ok(document) => {
+            event!(Level::DEBUG, "found a HTML document");
+
+            find_favicon_link(url, &document)
+        }
+        Err(err) => {
+            event!(Level::ERROR, %err, "unable to get the site HTML document");
+            None
+        }
+    };
+
+    // 2) The link might be a web app manifest, in which case the icon is in the manifest
+
+    match link? {
+        FaviconLink::Icon(icon_url) => Some(icon_url),
+        FaviconLink::Manifest(manifest_url) => match fetch_bytes(client, &manifest_url).await {
+            Ok(data) => find_manifest_icon(&manifest_url, &data),
+            Err(err) => {
+                event!(Level::ERROR, %err, "unable to get the web app manifest");
+                None
+            }
+        },
+    }
+}
+
+/// Get all entries for the feed `feed_id`.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * a SQL error occurred
+/// * the stored feed entry URL is invalid somehow
+#[tracing::instrument(
+    name = "Get feed entries",
+    skip(executor),
+    fields(
+        user_id = %user_id,
+        feed_id = %feed_id,
+    ),
+)]
+pub async fn get_feed_entries<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: &FeedId,
+) -> Result<Vec<FeedEntry>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let records = sqlx::query!(
+        r#"
+        SELECT
+          fe.id, fe.external_id, fe.title, fe.url, fe.summary, fe.created_at, fe.authors,
+          fe.read_at, fe.starred_at, fe.published_at
+        FROM feeds f
+        INNER JOIN feed_entries fe ON fe.feed_id = f.id
+        INNER JOIN users u ON f.user_id = u.id
+        WHERE u.id = $1 AND f.id = $2
+        "#,
+        &user_id.0,
+        &feed_id.0,
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to fetch the feed entries")?;
+
+    let mut entries = Vec::with_capacity(records.len());
+    for record in records {
+        entries.push(FeedEntry {
+            id: FeedEntryId(record.id),
+            feed_id: *feed_id,
+            external_id: record.external_id,
+            url: parse_url_from_record(record.url)?,
+            title: record.title,
+            summary: record.summary,
+            created_at: record.created_at,
+            authors: parse_authors_from_record(record.authors)?,
+            read_at: record.read_at,
+            starred_at: record.starred_at,
+            published_at: record.published_at,
+        })
+    }
+
+    Ok(entries)
+}
+
+/// Get a page of at most `limit` entries for the feed `feed_id`, from the most recent to the oldest.
+///
+/// If `after` is set, only the entries coming after it in this order are returned, that is the
+/// entries with a lower id. Pass the `next_cursor` of a page to get the next one.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * a SQL error occurred
+/// * the stored feed entry URL is invalid somehow
+#[tracing::instrument(
+    name = "Get feed entries page",
+    skip(executor),
+    fields(
+        user_id = %user_id,
+        feed_id = %feed_id,
+    ),
+)]
+pub async fn get_feed_entries_page<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: &FeedId,
+    after: Option<FeedEntryId>,
+    limit: usize,
+) -> Result<EntryPage, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    // Fetch one more entry than requested to know if there's a next page
+    let records = sqlx::query!(
+        r#"
+        SELECT
+          fe.id, fe.external_id, fe.title, fe.url, fe.summary, fe.created_at, fe.authors,
+          fe.read_at, fe.starred_at, fe.published_at
+        FROM feeds f
+        INNER JOIN feed_entries fe ON fe.feed_id = f.id
+        INNER JOIN users u ON f.user_id = u.id
+        WHERE u.id = $1 AND f.id = $2 AND ($3::bigint IS NULL OR fe.id < $3)
+        ORDER BY fe.id DESC
+        LIMIT $4
+        "#,
+        &user_id.0,
+        &feed_id.0,
+        after.map(|id| id.0),
+        limit as i64 + 1,
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to fetch the feed entries")?;
+
+    let mut entries = Vec::with_capacity(records.len());
+    for record in records {
+        entries.push(FeedEntry {
+            id: FeedEntryId(record.id),
+            feed_id: *feed_id,
+            external_id: record.external_id,
+            url: parse_url_from_record(record.url)?,
+            title: record.title,
+            summary: record.summary,
+            created_at: record.created_at,
+            authors: parse_authors_from_record(record.authors)?,
+            read_at: record.read_at,
+            starred_at: record.starred_at,
+            published_at: record.published_at,
+        })
+    }
+
+    let next_cursor = if entries.len() > limit {
+        entries.truncate(limit);
+        entries.last().map(|entry| entry.id)
+    } else {
+        None
+    };
+
+    Ok(EntryPage {
+        entries,
+        next_cursor,
+    })
+}
+
+/// Count all entries for the feed `feed_id`.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(
+    name = "Count feed entries",
+    skip(executor),
+    fields(
+        user_id = %user_id,
+        feed_id = %feed_id,
+    ),
+)]
+pub async fn count_feed_entries<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: &FeedId,
+) -> Result<i64, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let record = sqlx::query!(
+        r#"
+        SELECT COUNT(fe.id) AS "count!"
+        FROM feeds f
+        INNER JOIN feed_entries fe ON fe.feed_id = f.id
+        INNER JOIN users u ON f.user_id = u.id
+        WHERE u.id = $1 AND f.id = $2
+        "#,
+        &user_id.0,
+        &feed_id.0,
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to count the feed entries")?;
+
+    Ok(record.count)
+}
+
+/// Count the entries of the feed `feed_id` up to `entry_id` included, in the order of
+/// [`get_feed_entries_page`].
+///
+/// This is the number of entries listed before the page starting after `entry_id`.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(
+    name = "Count feed entries up to",
+    skip(executor),
+    fields(
+        user_id = %user_id,
+        feed_id = %feed_id,
+    ),
+)]
+pub async fn count_feed_entries_up_to<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: &FeedId,
+    entry_id: FeedEntryId,
+) -> Result<i64, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let record = sqlx::query!(
+        r#"
+        SELECT COUNT(fe.id) AS "count!"
+        FROM feeds f
+        INNER JOIN feed_entries fe ON fe.feed_id = f.id
+        INNER JOIN users u ON f.user_id = u.id
+        WHERE u.id = $1 AND f.id = $2 AND fe.id >= $3
+        "#,
+        &user_id.0,
+        &feed_id.0,
+        &entry_id.0,
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to count the feed entries")?;
+
+    Ok(record.count)
+}
+
+/// Get the entry `entry_id` for the feed `feed_id`.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * a SQL error occurred
+/// * the stored feed entry URL is invalid somehow
+#[tracing::instrument(
+    name = "Get feed entry",
+    skip(executor),
+    fields(
+        user_id = %user_id,
+        feed_id = %feed_id,
+        entry_id = %entry_id,
+    ),
+)]
+pub async fn get_feed_entry<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: &FeedId,
+    entry_id: &FeedEntryId,
+) -> Result<Option<FeedEntry>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let record = sqlx::query!(
+        r#"
+        SELECT
+          fe.id, fe.external_id, fe.title, fe.url, fe.summary, fe.created_at, fe.authors,
+          fe.read_at, fe.starred_at, fe.published_at
+        FROM feeds f
+        INNER JOIN feed_entries fe ON fe.feed_id = f.id
+        INNER JOIN users u ON f.user_id = u.id
+        WHERE u.id = $1 AND f.id = $2 AND fe.id = $3
+        "#,
+        &user_id.0,
+        &feed_id.0,
+        &entry_id.0,
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to fetch the feed entry")?;
+
+    let result = if let Some(record) = record {
+        Some(FeedEntry {
+            id: FeedEntryId(record.id),
+            feed_id: *feed_id,
+            external_id: record.external_id,
+            url: parse_url_from_record(record.url)?,
+            title: record.title,
+            summary: record.summary,
+            created_at: record.created_at,
+            authors: parse_authors_from_record(record.authors)?,
+            read_at: record.read_at,
+            starred_at: record.starred_at,
+            published_at: record.published_at,
+        })
+    } else {
+        None
+    };
+
+    Ok(result)
+}
+
+/// Filters the entries returned by [`get_unread_entries`].
+///
+/// The default filter doesn't filter anything.
+#[derive(Clone, Copy, Debug)]
+pub struct UnreadFilter {
+    /// Only keep the entries created since this date.
+    pub since: Option<time::OffsetDateTime>,
+    /// The maximum number of entries to return.
+    pub limit: usize,
+}
+
+impl Default for UnreadFilter {
+    fn default() -> Self {
+        Self {
+            since: None,
+            limit: usize::MAX,
+        }
+    }
+}
+
+impl UnreadFilter {
+    /// Builds a filter keeping at most `limit` entries created in the last `days` days, if set.
+    pub fn last_days(days: Option<i32>, limit: usize) -> Self {
+        Self {
+            since: days.map(|days| {
+                time::OffsetDateTime::now_utc() - time::Duration::days(i64::from(days))
+            }),
+            limit,
+        }
+    }
+}
+
+/// Get the unread feed entries matching `filter`, the most recent first.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * a SQL error occurred
+/// * the stored feed entry URL is invalid somehow
+#[tracing::instrument(
+    name = "Get unread entries",
+    skip(executor),
+    fields(
+        user_id = %user_id,
+    ),
+)]
+pub async fn get_unread_entries<'e, E>(
+    executor: E,
+    user_id: UserId,
+    filter: UnreadFilter,
+) -> Result<Vec<FeedEntry>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let records = sqlx::query!(
+        r#"
+        SELECT
+          fe.id, fe.feed_id, fe.external_id, fe.title, fe.url, fe.summary, fe.created_at,
+          fe.authors, fe.read_at, fe.starred_at, fe.published_at
+        FROM feeds f
+        INNER JOIN feed_entries fe ON fe.feed_id = f.id
+        INNER JOIN users u ON f.user_id = u.id
+        WHERE u.id = $1 AND fe.read_at IS NULL
+          AND ($2::timestamptz IS NULL OR fe.created_at >= $2)
+        ORDER BY fe.created_at DESC
+        LIMIT $3
+        "#,
+        &user_id.0,
+        filter.since,
+        i64::try_from(filter.limit).unwrap_or(i64::MAX),
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to fetch the feed entries")?;
+
+    let mut result = Vec::new();
+    for record in records {
+        let feed_entry = FeedEntry {
+            id: FeedEntryId(record.id),
+            feed_id: FeedId(record.feed_id),
+            external_id: record.external_id,
+            url: parse_url_from_record(record.url)?,
+            title: record.title,
+            summary: record.summary,
+            created_at: record.created_at,
+            authors: parse_authors_from_record(record.authors)?,
+            read_at: record.read_at,
+            starred_at: record.starred_at,
+            published_at: record.published_at,
+        };
+        result.push(feed_entry);
+    }
+
+    Ok(result)
+}
+
+/// Get the unread entries of the feeds tagged with `tag`, the most recent first.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * a SQL error occurred
+/// * the stored feed entry URL is invalid somehow
+#[tracing::instrument(
+    name = "Get unread entries by tag",
+    skip(executor),
+    fields(
+        user_id = %user_id,
+    ),
+)]
+pub async fn get_unread_entries_by_tag<'e, E>(
+    executor: E,
+    user_id: UserId,
+    tag: &str,
+) -> Result<Vec<FeedEntry>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let records = sqlx::query!(
+        r#"
+        SELECT
+          fe.id, fe.feed_id, fe.external_id, fe.title, fe.url, fe.summary, fe.created_at,
+          fe.authors, fe.read_at, fe.starred_at, fe.published_at
+        FROM feeds f
+        INNER JOIN feed_entries fe ON fe.feed_id = f.id
+        INNER JOIN feed_tags ft ON ft.feed_id = f.id
+        INNER JOIN tags t ON t.id = ft.tag_id
+        WHERE f.user_id = $1 AND t.user_id = $1 AND t.name = $2 AND fe.read_at IS NULL
+        ORDER BY fe.created_at DESC
+        "#,
+        &user_id.0,
+        tag,
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to fetch the feed entries")?;
+
+    let mut result = Vec::new();
+    for record in records {
+        let feed_entry = FeedEntry {
+            id: FeedEntryId(record.id),
+            feed_id: FeedId(record.feed_id),
+            external_id: record.external_id,
+            url: parse_url_from_record(record.url)?,
+            title: record.title,
+            summary: record.summary,
+            created_at: record.created_at,
+            authors: parse_authors_from_record(record.authors)?,
+            read_at: record.read_at,
+            starred_at: record.starred_at,
+            published_at: record.published_at,
+        };
+        result.push(feed_entry);
+    }
+
+    Ok(result)
+}
+
+/// Search the feed entries of the user `user_id` matching `query`, the most relevant first.
+///
+/// `query` is free text, every word must match either the title or the summary of an entry.
+/// An empty query matches nothing.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * a SQL error occurred
+/// * the stored feed entry URL is invalid somehow
+#[tracing::instrument(
+    name = "Search feed entries",
+    skip(executor),
+    fields(
+        user_id = %user_id,
+    ),
+)]
+pub async fn search_feed_entries<'e, E>(
+    executor: E,
+    user_id: UserId,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<FeedEntry>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let records = sqlx::query!(
+        r#"
+        SELECT
+          fe.id, fe.feed_id, fe.external_id, fe.title, fe.url, fe.summary, fe.created_at,
+          fe.authors, fe.read_at, fe.starred_at, fe.published_at
+        FROM feeds f
+        INNER JOIN feed_entries fe ON fe.feed_id = f.id
+        INNER JOIN users u ON f.user_id = u.id
+        CROSS JOIN plainto_tsquery('english', $2) AS query
+        WHERE u.id = $1 AND fe.search_tsv @@ query
+        ORDER BY ts_rank(fe.search_tsv, query) DESC, fe.created_at DESC
+        LIMIT $3
+        "#,
+        &user_id.0,
+        query,
+        i64::try_from(limit).unwrap_or(i64::MAX),
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to search the feed entries")?;
+
+    let mut result = Vec::with_capacity(records.len());
+    for record in records {
+        result.push(FeedEntry {
+            id: FeedEntryId(record.id),
+            feed_id: FeedId(record.feed_id),
+            external_id: record.external_id,
+            url: parse_url_from_record(record.url)?,
+            title: record.title,
+            summary: record.summary,
+            created_at: record.created_at,
+            authors: parse_authors_from_record(record.authors)?,
+            read_at: record.read_at,
+            starred_at: record.starred_at,
+            published_at: record.published_at,
+        });
+    }
+
+    Ok(result)
+}
+
+#[tracing::instrument(
+    name = "Mark a feed entry as read",
+    skip(executor),
+    fields(
+        user_id = %user_id,
+        feed_id = %feed_id,
+        entry_id = %entry_id,
+    ),
+)]
+pub async fn mark_feed_entry_as_read<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: &FeedId,
+    entry_id: &FeedEntryId,
+) -> Result<(), anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        r#"
+        UPDATE feed_entries
+        SET read_at = now()
+        FROM feeds f
+        INNER JOIN users u ON f.user_id = u.id
+        WHERE u.id = $1 AND f.id = $2 AND feed_entries.id = $3
+        "#,
+        &user_id.0,
+        &feed_id.0,
+        &entry_id.0,
+    )
+    .execute(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to fetch the feed entry")?;
+
+    Ok(())
+}
+
+/// Star the entry `entry_id` of the feed `feed_id`.
+///
+/// Starring an already starred entry keeps its original star date.
+///
+/// Returns false if the entry doesn't exist.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(
+    name = "Mark a feed entry as starred",
+    skip(executor),
+    fields(
+        user_id = %user_id,
+        feed_id = %feed_id,
+        entry_id = %entry_id,
+    ),
+)]
+pub async fn mark_feed_entry_as_starred<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: &FeedId,
+    entry_id: &FeedEntryId,
+) -> Result<bool, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let result = sqlx::query!(
+        r#"
+        UPDATE feed_entries
+        SET starred_at = COALESCE(feed_entries.starred_at, now())
+        FROM feeds f
+        WHERE f.user_id = $1 AND f.id = $2 AND feed_entries.feed_id = f.id AND feed_entries.id = $3
+        "#,
+        &user_id.0,
+        &feed_id.0,
+        &entry_id.0,
+    )
+    .execute(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to star the feed entry")?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Unstar the entry `entry_id` of the feed `feed_id`.
+///
+/// Returns false if the entry doesn't exist.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(
+    name = "Unstar a feed entry",
+    skip(executor),
+    fields(
+        user_id = %user_id,
+        feed_id = %feed_id,
+        entry_id = %entry_id,
+    ),
+)]
+pub async fn unstar_feed_entry<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: &FeedId,
+    entry_id: &FeedEntryId,
+) -> Result<bool, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let result = sqlx::query!(
+        r#"
+        UPDATE feed_entries
+        SET starred_at = NULL
+        FROM feeds f
+        WHERE f.user_id = $1 AND f.id = $2 AND feed_entries.feed_id = f.id AND feed_entries.id = $3
+        "#,
+        &user_id.0,
+        &feed_id.0,
+        &entry_id.0,
+    )
+    .execute(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to unstar the feed entry")?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Get the starred entries of all the feeds of the user `user_id`, the most recently starred first.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * a SQL error occurred
+/// * the stored feed entry URL is invalid somehow
+#[tracing::instrument(
+    name = "Get starred entries",
+    skip(executor),
+    fields(
+        user_id = %user_id,
+    ),
+)]
+pub async fn get_starred_entries<'e, E>(
+    executor: E,
+    user_id: UserId,
+) -> Result<Vec<FeedEntry>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let records = sqlx::query!(
+        r#"
+        SELECT
+          fe.id, fe.feed_id, fe.external_id, fe.title, fe.url, fe.summary, fe.created_at,
+          fe.authors, fe.read_at, fe.starred_at, fe.published_at
+        FROM feeds f
+        INNER JOIN feed_entries fe ON fe.feed_id = f.id
+        WHERE f.user_id = $1 AND fe.starred_at IS NOT NULL
+        ORDER BY fe.starred_at DESC
+        "#,
+        &user_id.0,
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to fetch the starred entries")?;
+
+    let mut result = Vec::with_capacity(records.len());
+    for record in records {
+        result.push(FeedEntry {
+            id: FeedEntryId(record.id),
+            feed_id: FeedId(record.feed_id),
+            external_id: record.external_id,
+            url: parse_url_from_record(record.url)?,
+            title: record.title,
+            summary: record.summary,
+            created_at: record.created_at,
+            authors: parse_authors_from_record(record.authors)?,
+            read_at: record.read_at,
+            starred_at: record.starred_at,
+            published_at: record.published_at,
+        });
+    }
+
+    Ok(result)
+}
+
+/// Mark the unread entries `entry_ids` of the feed `feed_id` as read.
+///
+/// Returns the number of entries marked as read.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(
+    name = "Mark feed entries as read",
+    skip(executor, entry_ids),
+    fields(
+        user_id = %user_id,
+        feed_id = %feed_id,
+    ),
+)]
+pub async fn mark_feed_entries_as_read<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: &FeedId,
+    entry_ids: &[FeedEntryId],
+) -> Result<u64, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let entry_ids = entry_ids.iter().map(|id| id.0).collect::<Vec<_>>();
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE feed_entries
+        SET read_at = now()
+        FROM feeds f
+        INNER JOIN users u ON f.user_id = u.id
+        WHERE u.id = $1 AND f.id = $2
+          AND feed_entries.feed_id = f.id
+          AND feed_entries.id = ANY($3)
+          AND feed_entries.read_at IS NULL
+        "#,
+        &user_id.0,
+        &feed_id.0,
+        &entry_ids[..],
+    )
+    .execute(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to mark the feed entries as read")?;
+
+    Ok(result.rows_affected())
+}
+
+/// Mark all unread entries of the feed `feed_id` as read.
+///
+/// Returns the number of entries marked as read.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(
+    name = "Mark all feed entries as read",
+    skip(executor),
+    fields(
+        user_id = %user_id,
+        feed_id = %feed_id,
+    ),
+)]
+pub async fn mark_all_feed_entries_as_read<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: &FeedId,
+) -> Result<u64, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let result = sqlx::query!(
+        r#"
+        UPDATE feed_entries
+        SET read_at = now()
+        FROM feeds f
+        INNER JOIN users u ON f.user_id = u.id
+        WHERE u.id = $1 AND f.id = $2
+          AND feed_entries.feed_id = f.id
+          AND feed_entries.read_at IS NULL
+        "#,
+        &user_id.0,
+        &feed_id.0,
+    )
+    .execute(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to mark all the feed entries as read")?;
+
+    Ok(result.rows_affected())
+}
+
+/// Mark all unread entries of all feeds of the user `user_id` as read.
+///
+/// Returns the number of entries marked as read.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(
+    name = "Mark all entries as read",
+    skip(executor),
+    fields(
+        user_id = %user_id,
+    ),
+)]
+pub async fn mark_all_entries_as_read<'e, E>(
+    executor: E,
+    user_id: UserId,
+) -> Result<u64, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let result = sqlx::query!(
+        r#"
+        UPDATE feed_entries
+        SET read_at = now()
+        FROM feeds f
+        INNER JOIN users u ON f.user_id = u.id
+        WHERE u.id = $1
+          AND feed_entries.feed_id = f.id
+          AND feed_entries.read_at IS NULL
+        "#,
+        &user_id.0,
+    )
+    .execute(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to mark all the entries as read")?;
+
+    Ok(result.rows_affected())
+}
+
+/// Normalizes a feed `url` so that equivalent URLs compare equal once stored.
+///
+/// This:
+/// * removes an empty query, that is a trailing `?`
+/// * lowercases the host
+/// * removes the default port for HTTP and HTTPS
+/// * sorts the query parameters by name, keeping the order of repeated parameters
+///
+/// Note that [`Url::parse`] already lowercases the host and removes the default port of HTTP
+/// and HTTPS URLs; this doesn't rely on it.
+pub fn normalize_feed_url(url: &Url) -> Url {
+    let mut url = url.clone();
+
+    if let Some(host) = url.host_str() {
+        let host = host.to_lowercase();
+        let _ = url.set_host(Some(&host));
+    }
+
+    if matches!(
+        (url.scheme(), url.port()),
+        ("http", Some(80)) | ("https", Some(443))
+    ) {
+        let _ = url.set_port(None);
+    }
+
+    match url.query() {
+        Some("") => url.set_query(None),
+        Some(_) => {
+            let mut pairs = url.query_pairs().into_owned().collect::<Vec<_>>();
+            pairs.sort_by(|a, b| a.0.cmp(&b.0));
+
+            url.query_pairs_mut().clear().extend_pairs(pairs);
+        }
+        None => {}
+    }
+
+    url
+}
+
+/// Check if a feed with the given `url` already exists.
+///
+/// # Errors
+///
+/// This function will return an error if there's a SQL error.
+pub async fn feed_with_url_exists<'e, E>(
+    executor: E,
+    user_id: UserId,
+    url: &Url,
+) -> Result<bool, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let record = sqlx::query!(
+        r#"
+        SELECT f.id FROM feeds f
+        INNER JOIN users u ON f.user_id = u.id
+        WHERE u.id = $1 AND f.url = $2
+        "#,
+        &user_id.0,
+        url.to_string(),
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to find the feed")?;
+
+    Ok(record.is_some())
+}
+
+/// Parse the authors as they are stored in a record generated by sqlx.
+///
+/// # Errors
+///
+/// This function will return an error if the authors are not a JSON array of strings.
+pub fn parse_authors_from_record(
+    value: Option<serde_json::Value>,
+) -> Result<Vec<String>, serde_json::Error> {
+    value
+        .map(serde_json::from_value::<Vec<String>>)
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+/// Parse a URL as it is stored in a record generated by sqlx.
+///
+/// # Errors
+///
+/// This function will return an error if the URL is invalid somehow.
+pub fn parse_url_from_record(s: Option<String>) -> Result<Option<Url>, url::ParseError> {
+    // This ugly thing goes from:
+    // Option<String> to Option<&str> to Result<Option<Url>, _>
+
+    let url_str: Option<&str> = s.as_deref();
+    url_str.map(Url::parse).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{create_feed, create_feed_entry, create_user, fetch, get_pool};
+    use serde_json::json;
+    use wiremock::matchers::{any, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[derive(rust_embed::RustEmbed)]
+    #[folder = "testdata/"]
+    struct TestData;
+
+    #[tokio::test]
+    async fn find_feed_should_work() {
+        let mock_server = MockServer::start().await;
+        let mock_uri = mock_server.uri();
+        let mock_url = Url::parse(&mock_uri).unwrap();
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                TestData::get("tailscale_rss_feed.xml").unwrap().data,
+                "application/xml",
+            ))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let data = fetch(&mock_url).await;
+        let found_feed = find_feed(&mock_url, &data[..]).unwrap();
+
+        let feed = match found_feed {
+            FoundFeed::Raw(raw_feed) => ParsedFeed::from_raw_feed(&mock_url, raw_feed),
+            _ => panic!("expected a FoundFeed::Raw"),
+        };
+
+        let site_link = feed.site_link.map(|v| v.to_string()).unwrap_or_default();
+
+        assert_eq!("Blog on Tailscale", feed.title);
+        assert_eq!("https://tailscale.com/blog/", site_link);
+        assert_eq!("Recent content in Blog on Tailscale", feed.description);
+    }
+
+    #[test]
+    fn find_all_feeds_should_return_every_linked_feed() {
+        let url = Url::parse("https://example.org/").unwrap();
+        let data = r#"
+            <html>
+            <head>
+            <link type="application/rss+xml" href="/rss.xml">
+            <link type="application/atom+xml" href="/atom.xml">
+            <link type="application/feed+json" href="https://feeds.example.org/feed.json">
+            </head>
+            </html>
+        "#;
+
+        let found_feeds = find_all_feeds(&url, data.as_bytes());
+
+        let urls: Vec<String> = found_feeds
+            .into_iter()
+            .map(|found_feed| match found_feed {
+                FoundFeed::Url(url) => url.to_string(),
+                _ => panic!("expected a FoundFeed::Url"),
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                "https://example.org/rss.xml",
+                "https://example.org/atom.xml",
+                "https://feeds.example.org/feed.json",
+            ],
+            urls
+        );
+
+        // find_feed only returns the first one
+
+        match find_feed(&url, data.as_bytes()).unwrap() {
+            FoundFeed::Url(url) => assert_eq!("https://example.org/rss.xml", url.to_string()),
+            _ => panic!("expected a FoundFeed::Url"),
+        }
+
+        // A HTML document without links has no feed
+
+        let data = "<html><head></head></html>";
+        assert!(find_all_feeds(&url, data.as_bytes()).is_empty());
+        assert!(matches!(
+            find_feed(&url, data.as_bytes()),
+            Err(FindError::NoFeed)
+        ));
+    }
+
+    #[test]
+    fn find_feed_should_find_a_json_feed() {
+        let url = Url::parse("https://example.org/feed.json").unwrap();
+        let data = TestData::get("json_feed_example.json").unwrap().data;
+
+        let found_feed = find_feed(&url, &data[..]).unwrap();
+
+        let feed = match found_feed {
+            FoundFeed::Json(feed) => feed,
+            _ => panic!("expected a FoundFeed::Json"),
+        };
+
+        let site_link = feed.site_link.map(|v| v.to_string()).unwrap_or_default();
+
+        assert_eq!("My Example Feed", feed.title);
+        assert_eq!("https://example.org/", site_link);
+        assert_eq!("An example of a JSON Feed", feed.description);
+        assert_eq!(Some("en".to_string()), feed.language);
+
+        // Check the entries too
+
+        let (_, entries) = parse_json_feed(&url, &data[..]).unwrap();
+        assert_eq!(2, entries.len());
+
+        assert_eq!("2", entries[0].external_id);
+        assert_eq!("This is a second item.", entries[0].summary);
+        assert_eq!(vec!["Alice"], entries[0].authors);
+
+        assert_eq!("1", entries[1].external_id);
+        assert_eq!("Hello, world!", entries[1].title);
+        assert_eq!("<p>Hello, world!</p>", entries[1].summary);
+        assert_eq!(
+            Some("https://example.org/initial-post"),
+            entries[1].url.as_ref().map(Url::as_str)
+        );
+    }
+
+    #[test]
+    fn parse_json_feed_should_ignore_other_json_documents() {
+        let url = Url::parse("https://example.org/feed.json").unwrap();
+
+        let data = br#"{"version": "1.0", "title": "Foo"}"#;
+        assert!(parse_json_feed(&url, &data[..]).is_none());
+
+        let data = br#"<rss version="2.0"><channel><title>Foo</title></channel></rss>"#;
+        assert!(parse_json_feed(&url, &data[..]).is_none());
+    }
+
+    #[tokio::test]
+    async fn count_feed_entries_should_return_zero_without_entries() {
+        let pool = get_pool().await;
+
+        let url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let user_id = create_user(&pool).await;
+        let feed_id = create_feed(&pool, user_id, &url, &site_link).await;
+
+        let count = count_feed_entries(&pool, user_id, &feed_id).await.unwrap();
+        assert_eq!(0, count);
+    }
+
+    #[tokio::test]
+    async fn count_feed_entries_should_return_the_number_of_entries() {
+        let pool = get_pool().await;
+
+        let url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let user_id = create_user(&pool).await;
+        let feed_id = create_feed(&pool, user_id, &url, &site_link).await;
+        for _ in 0..3 {
+            create_feed_entry(&pool, &feed_id).await;
+        }
+
+        // Entries of another user's feed must not be counted
+        let other_user_id = create_user(&pool).await;
+        let other_feed_id = create_feed(&pool, other_user_id, &url, &site_link).await;
+        create_feed_entry(&pool, &other_feed_id).await;
+
+        let count = count_feed_entries(&pool, user_id, &feed_id).await.unwrap();
+        assert_eq!(3, count);
+    }
+
+    #[tokio::test]
+    async fn count_feed_entries_up_to_should_count_the_entries_of_the_previous_pages() {
+        let pool = get_pool().await;
+
+        let url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let user_id = create_user(&pool).await;
+        let feed_id = create_feed(&pool, user_id, &url, &site_link).await;
+        for _ in 0..5 {
+            create_feed_entry(&pool, &feed_id).await;
+        }
+
+        let page = get_feed_entries_page(&pool, user_id, &feed_id, None, 2)
+            .await
+            .unwrap();
+        let next_cursor = page.next_cursor.expect("there should be a next page");
+
+        let count = count_feed_entries_up_to(&pool, user_id, &feed_id, next_cursor)
+            .await
+            .unwrap();
+        assert_eq!(2, count);
+    }
+
+    #[tokio::test]
+    async fn get_feed_entries_page_should_paginate_from_the_most_recent_entry() {
+        let pool = get_pool().await;
+
+        let url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let user_id = create_user(&pool).await;
+        let feed_id = create_feed(&pool, user_id, &url, &site_link).await;
+
+        let mut entry_ids = Vec::new();
+        for _ in 0..3 {
+            entry_ids.push(create_feed_entry(&pool, &feed_id).await);
+        }
+        entry_ids.reverse();
+
+        let page = get_feed_entries_page(&pool, user_id, &feed_id, None, 2)
+            .await
+            .unwrap();
+        let page_ids = page
+            .entries
+            .iter()
+            .map(|entry| entry.id)
+            .collect::<Vec<_>>();
+        assert_eq!(&entry_ids[0..2], &page_ids[..]);
+        assert_eq!(Some(entry_ids[1]), page.next_cursor);
+
+        let page = get_feed_entries_page(&pool, user_id, &feed_id, page.next_cursor, 2)
+            .await
+            .unwrap();
+        let page_ids = page
+            .entries
+            .iter()
+            .map(|entry| entry.id)
+            .collect::<Vec<_>>();
+        assert_eq!(&entry_ids[2..], &page_ids[..]);
+        assert_eq!(None, page.next_cursor);
+
+        // A page exactly as large as the remaining entries is the last one
+        let page = get_feed_entries_page(&pool, user_id, &feed_id, None, 3)
+            .await
+            .unwrap();
+        assert_eq!(3, page.entries.len());
+        assert_eq!(None, page.next_cursor);
+    }
+
+    #[tokio::test]
+    async fn feed_entry_should_be_read_after_being_marked_as_read() {
+        let pool = get_pool().await;
+
+        let url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let user_id = create_user(&pool).await;
+        let feed_id = create_feed(&pool, user_id, &url, &site_link).await;
+        let entry_id = create_feed_entry(&pool, &feed_id).await;
+
+        let entry = get_feed_entry(&pool, user_id, &feed_id, &entry_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!entry.is_read());
+
+        mark_feed_entry_as_read(&pool, user_id, &feed_id, &entry_id)
+            .await
+            .unwrap();
+
+        let entry = get_feed_entry(&pool, user_id, &feed_id, &entry_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(entry.is_read());
+    }
+
+    #[tokio::test]
+    async fn starred_feed_entries_should_be_listed_until_unstarred() {
+        let pool = get_pool().await;
+
+        let url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let user_id = create_user(&pool).await;
+        let feed_id = create_feed(&pool, user_id, &url, &site_link).await;
+        let entry_id = create_feed_entry(&pool, &feed_id).await;
+        let other_entry_id = create_feed_entry(&pool, &feed_id).await;
+
+        assert!(get_starred_entries(&pool, user_id)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let found = mark_feed_entry_as_starred(&pool, user_id, &feed_id, &entry_id)
+            .await
+            .unwrap();
+        assert!(found);
+
+        let entry = get_feed_entry(&pool, user_id, &feed_id, &entry_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(entry.is_starred());
+
+        let starred_entries = get_starred_entries(&pool, user_id).await.unwrap();
+        let starred_ids: Vec<FeedEntryId> = starred_entries.iter().map(|entry| entry.id).collect();
+        assert_eq!(vec![entry_id], starred_ids);
+
+        // Another user can't star the entry
+
+        let other_user_id = create_user(&pool).await;
+        let found = mark_feed_entry_as_starred(&pool, other_user_id, &feed_id, &other_entry_id)
+            .await
+            .unwrap();
+        assert!(!found);
+
+        // Unstar it
+
+        let found = unstar_feed_entry(&pool, user_id, &feed_id, &entry_id)
+            .await
+            .unwrap();
+        assert!(found);
+
+        assert!(get_starred_entries(&pool, user_id)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    fn favicon(data: &[u8]) -> Arc<Favicon> {
+        Arc::new(Favicon {
+            data: data.to_vec(),
+            mime: "image/x-icon".to_string(),
+        })
+    }
+
+    #[test]
+    fn favicon_cache_should_miss_when_empty() {
+        let cache = FaviconCache::new(10);
+
+        assert!(cache.get(UserId::default(), &FeedId(1)).is_none());
+    }
+
+    #[test]
+    fn favicon_cache_should_hit_after_insert() {
+        let cache = FaviconCache::new(10);
+        let user_id = UserId::default();
+
+        cache.insert(user_id, FeedId(1), favicon(b"foobar"));
+
+        let favicon = cache.get(user_id, &FeedId(1));
+        assert_eq!(
+            Some(b"foobar".to_vec()),
+            favicon.map(|favicon| favicon.data.clone())
+        );
+
+        // Another user must not get the favicon
+        assert!(cache.get(UserId::default(), &FeedId(1)).is_none());
+    }
+
+    #[test]
+    fn favicon_cache_should_miss_after_invalidation() {
+        let cache = FaviconCache::new(10);
+        let user_id = UserId::default();
+
+        cache.insert(user_id, FeedId(1), favicon(b"foobar"));
+        cache.invalidate(user_id, &FeedId(1));
+
+        assert!(cache.get(user_id, &FeedId(1)).is_none());
+    }
+
+    #[test]
+    fn favicon_cache_should_evict_the_least_recently_used_favicon() {
+        let cache = FaviconCache::new(1);
+        let user_id = UserId::default();
+
+        cache.insert(user_id, FeedId(1), favicon(b"foo"));
+        cache.insert(user_id, FeedId(2), favicon(b"bar"));
+
+        assert!(cache.get(user_id, &FeedId(1)).is_none());
+        assert!(cache.get(user_id, &FeedId(2)).is_some());
+    }
+
+    fn favicon_link(html: &str) -> Option<FaviconLink> {
+        let url = Url::parse("https://example.com/blog/").unwrap();
+        find_favicon_link(&url, &Document::from(html))
+    }
+
+    #[test]
+    fn find_favicon_link_should_prefer_the_icon() {
+        const HTML: &str = r#"<html><head>
+<link rel="manifest" href="/manifest.json">
+<link rel="apple-touch-icon" href="/apple-touch-icon.png">
+<link rel="icon" href="/icon.png">
+</head></html>"#;
+
+        assert_eq!(
+            Some(FaviconLink::Icon(
+                Url::parse("https://example.com/icon.png").unwrap()
+            )),
+            favicon_link(HTML)
+        );
+    }
+
+    #[test]
+    fn find_favicon_link_should_fallback_to_the_apple_touch_icon() {
+        const HTML: &str = r#"<html><head>
+<link rel="manifest" href="/manifest.json">
+<link rel="apple-touch-icon-precomposed" href="/apple-touch-icon-precomposed.png">
+</head></html>"#;
+
+        assert_eq!(
+            Some(FaviconLink::Icon(
+                Url::parse("https://example.com/apple-touch-icon-precomposed.png").unwrap()
+            )),
+            favicon_link(HTML)
+        );
+    }
+
+    #[test]
+    fn find_favicon_link_should_fallback_to_the_manifest() {
+        const HTML: &str = r#"<html><head>
+<link rel="stylesheet" href="/style.css">
+<link rel="manifest" href="manifest.json">
+</head></html>"#;
+
+        assert_eq!(
+            Some(FaviconLink::Manifest(
+                Url::parse("https://example.com/blog/manifest.json").unwrap()
+            )),
+            favicon_link(HTML)
+        );
+    }
+
+    #[test]
+    fn find_favicon_link_should_return_none_without_links() {
+        assert_eq!(None, favicon_link("<html><head></head></html>"));
+    }
+
+    #[test]
+    fn find_manifest_icon_should_return_the_highest_resolution_icon() {
+        let manifest_url = Url::parse("https://example.com/static/manifest.json").unwrap();
+
+        let data = json!({
+            "name": "Example",
+            "icons": [
+                {"src": "icon-192.png", "sizes": "192x192", "type": "image/png"},
+                {"src": "icon-512.png", "sizes": "256x256 512x512", "type": "image/png"},
+                {"src": "/icon-128.png", "sizes": "128x128", "type": "image/png"},
+                {"src": "icon-unknown.png"}
+            ]
+        });
+        let icon_url = find_manifest_icon(&manifest_url, data.to_string().as_bytes());
+        assert_eq!(
+            Some(Url::parse("https://example.com/static/icon-512.png").unwrap()),
+            icon_url
+        );
+
+        // A scalable icon wins over everything else
+
+        let data = json!({
+            "icons": [
+                {"src": "icon-512.png", "sizes": "512x512"},
+                {"src": "icon.svg", "sizes": "any", "type": "image/svg+xml"}
+            ]
+        });
+        let icon_url = find_manifest_icon(&manifest_url, data.to_string().as_bytes());
+        assert_eq!(
+            Some(Url::parse("https://example.com/static/icon.svg").unwrap()),
+            icon_url
+        );
+    }
+
+    #[test]
+    fn find_manifest_icon_should_return_none_without_icons() {
+        let manifest_url = Url::parse("https://example.com/manifest.json").unwrap();
+
+        assert!(find_manifest_icon(&manifest_url, br#"{"name": "Example"}"#).is_none());
+        assert!(find_manifest_icon(&manifest_url, br#"{"icons": []}"#).is_none());
+        assert!(find_manifest_icon(&manifest_url, b"<html></html>").is_none());
+    }
+
+    #[tokio::test]
+    async fn find_favicon_should_use_the_manifest_icon() {
+        let mock_server = MockServer::start().await;
+        let mock_url = Url::parse(&mock_server.uri()).unwrap();
+
+        Mock::given(path("/"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"<html><head><link rel="manifest" href="/site.webmanifest"></head></html>"#,
+                "text/html",
+            ))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/site.webmanifest"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "icons": [
+                    {"src": "/android-chrome-192x192.png", "sizes": "192x192"},
+                    {"src": "/android-chrome-512x512.png", "sizes": "512x512"}
+                ]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = reqwest::Client::new();
+        let icon_url = find_favicon(&client, &mock_url).await;
+
+        assert_eq!(
+            Some(mock_url.join("/android-chrome-512x512.png").unwrap()),
+            icon_url
+        );
+    }
+
+    #[tokio::test]
+    async fn feed_language_should_be_stored_and_updated() {
+        const DATA: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom" xml:lang="de">
+<title>Foo</title>
+<link href="https://example.com/blog/"/>
+<id>https://example.com/blog/</id>
+<updated>2023-03-06T10:00:00Z</updated>
+</feed>"#;
+
+        let pool = get_pool().await;
+        let user_id = create_user(&pool).await;
+
+        let url = Url::parse("https://example.com/blog/feed.xml").unwrap();
+        let mut parsed_feed = ParsedFeed::parse(&url, DATA.as_bytes()).unwrap();
+
+        // Insert
+
+        let feed_id = insert_feed(&pool, user_id, &parsed_feed).await.unwrap();
+
+        let feed = get_feed(&pool, user_id, &feed_id).await.unwrap().unwrap();
+        assert_eq!(Some("de".to_string()), feed.language);
+
+        let feeds = get_feeds_by_language(&pool, user_id, "de").await.unwrap();
+        assert_eq!(1, feeds.len());
+        assert_eq!(feed_id, feeds[0].id);
+
+        // Update
+
+        parsed_feed.language = Some("fr".to_string());
+        update_feed_metadata(&pool, user_id, &feed_id, &parsed_feed)
+            .await
+            .unwrap();
+
+        let feed = get_feed(&pool, user_id, &feed_id).await.unwrap().unwrap();
+        assert_eq!(Some("fr".to_string()), feed.language);
+
+        let feeds = get_feeds_by_language(&pool, user_id, "de").await.unwrap();
+        assert!(feeds.is_empty());
+    }
+
+    #[test]
+    fn feed_order_should_round_trip_through_a_string() {
+        for order in FeedOrder::ALL {
+            let parsed: FeedOrder = order.to_string().parse().unwrap();
+            assert_eq!(order, parsed);
+        }
+
+        assert_eq!(FeedOrder::default(), "added_at:desc".parse().unwrap());
+        assert!("title".parse::<FeedOrder>().is_err());
+        assert!("title:up".parse::<FeedOrder>().is_err());
+        assert!("foobar:asc".parse::<FeedOrder>().is_err());
+    }
+
+    #[tokio::test]
+    async fn get_all_feeds_should_honor_the_order() {
+        let pool = get_pool().await;
+
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let user_id = create_user(&pool).await;
+
+        let mut feed_ids = Vec::new();
+        for (i, title) in ["b", "C", "a"].iter().enumerate() {
+            let url = Url::parse(&format!("https://example.com/feed{}.xml", i)).unwrap();
+            let feed_id = create_feed(&pool, user_id, &url, &site_link).await;
+
+            sqlx::query!(
+                "UPDATE feeds SET title = $2 WHERE id = $1",
+                &feed_id.0,
+                title
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+            for _ in 0..i {
+                create_feed_entry(&pool, &feed_id).await;
+            }
+
+            feed_ids.push(feed_id);
+        }
+
+        let get_titles = |order: &'static str| {
+            let pool = pool.clone();
+            async move {
+                get_all_feeds(&pool, user_id, order.parse().unwrap())
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|feed| feed.title)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(vec!["a", "C", "b"], get_titles("added_at:desc").await);
+        assert_eq!(vec!["b", "C", "a"], get_titles("added_at:asc").await);
+        assert_eq!(vec!["a", "b", "C"], get_titles("title:asc").await);
+        assert_eq!(vec!["C", "b", "a"], get_titles("title:desc").await);
+        assert_eq!(vec!["a", "C", "b"], get_titles("unread_count:desc").await);
+        assert_eq!(vec!["b", "C", "a"], get_titles("unread_count:asc").await);
+    }
+
+    #[tokio::test]
+    async fn user_feed_order_should_be_stored() {
+        let pool = get_pool().await;
+
+        let user_id = create_user(&pool).await;
+
+        let order = get_user_feed_order(&pool, user_id).await.unwrap();
+        assert_eq!(FeedOrder::default(), order);
+
+        let new_order = FeedOrder::new(FeedOrderField::Title, OrderDirection::Asc);
+        set_user_feed_order(&pool, user_id, new_order)
+            .await
+            .unwrap();
+
+        let order = get_user_feed_order(&pool, user_id).await.unwrap();
+        assert_eq!(new_order, order);
+    }
+
+    #[tokio::test]
+    async fn feed_category_should_be_stored() {
+        let pool = get_pool().await;
+
+        let url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let user_id = create_user(&pool).await;
+        let feed_id = create_feed(&pool, user_id, &url, &site_link).await;
+
+        let feed = get_feed(&pool, user_id, &feed_id).await.unwrap().unwrap();
+        assert!(feed.category.is_none());
+
+        // Creating the same category twice should return the same id
+
+        let category_id = get_or_create_category(&pool, user_id, "Tech")
+            .await
+            .unwrap();
+        let category_id2 = get_or_create_category(&pool, user_id, "Tech")
+            .await
+            .unwrap();
+        assert_eq!(category_id, category_id2);
+
+        let found = set_feed_category(&pool, user_id, &feed_id, Some(category_id))
+            .await
+            .unwrap();
+        assert!(found);
+
+        let feeds = get_all_feeds(&pool, user_id, FeedOrder::default())
+            .await
+            .unwrap();
+        assert_eq!(1, feeds.len());
+        assert_eq!(
+            Some(FeedCategory {
+                id: category_id,
+                name: "Tech".to_string(),
+            }),
+            feeds[0].category,
+        );
+
+        // Another user can't set the category of the feed
+
+        let other_user_id = create_user(&pool).await;
+        let found = set_feed_category(&pool, other_user_id, &feed_id, None)
+            .await
+            .unwrap();
+        assert!(!found);
+
+        // Remove the category
+
+        set_feed_category(&pool, user_id, &feed_id, None)
+            .await
+            .unwrap();
+
+        let feed = get_feed(&pool, user_id, &feed_id).await.unwrap().unwrap();
+        assert!(feed.category.is_none());
+    }
+
+    #[tokio::test]
+    async fn delete_feed_should_delete_the_feed_and_its_entries() {
+        let pool = get_pool().await;
+
+        let url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let user_id = create_user(&pool).await;
+        let feed_id = create_feed(&pool, user_id, &url, &site_link).await;
+        create_feed_entry(&pool, &feed_id).await;
+        create_feed_entry(&pool, &feed_id).await;
+
+        // Another user can't delete the feed
+
+        let other_user_id = create_user(&pool).await;
+        let found = delete_feed(&pool, other_user_id, &feed_id).await.unwrap();
+        assert!(!found);
+        assert!(get_feed(&pool, user_id, &feed_id).await.unwrap().is_some());
+
+        // Delete the feed
+
+        let found = delete_feed(&pool, user_id, &feed_id).await.unwrap();
+        assert!(found);
+        assert!(get_feed(&pool, user_id, &feed_id).await.unwrap().is_none());
+
+        let record = sqlx::query!(
+            r#"SELECT count(*) AS "count!" FROM feed_entries WHERE feed_id = $1"#,
+            &feed_id.0,
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(0, record.count);
+    }
+
+    #[tokio::test]
+    async fn get_unread_entries_should_honor_the_filter() {
+        let pool = get_pool().await;
+
+        let url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let user_id = create_user(&pool).await;
+        let feed_id = create_feed(&pool, user_id, &url, &site_link).await;
+
+        // Create entries 1, 5 and 30 days old
+
+        let mut entry_ids = Vec::new();
+        for days in [1, 5, 30] {
+            let entry_id = create_feed_entry(&pool, &feed_id).await;
+
+            sqlx::query!(
+                "UPDATE feed_entries SET created_at = now() - make_interval(days => $2) WHERE id = $1",
+                &entry_id.0,
+                days,
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+
+            entry_ids.push(entry_id);
+        }
+
+        let get_ids = |filter: UnreadFilter| {
+            let pool = pool.clone();
+            async move {
+                get_unread_entries(&pool, user_id, filter)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|entry| entry.id)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(entry_ids, get_ids(UnreadFilter::default()).await);
+        assert_eq!(
+            entry_ids[..2].to_vec(),
+            get_ids(UnreadFilter::last_days(Some(7), 100)).await
+        );
+        assert_eq!(
+            entry_ids[..1].to_vec(),
+            get_ids(UnreadFilter::last_days(Some(7), 1)).await
+        );
+        assert_eq!(entry_ids, get_ids(UnreadFilter::last_days(None, 100)).await);
+    }
+
+    #[tokio::test]
+    async fn user_unread_since_days_should_be_stored() {
+        let pool = get_pool().await;
+
+        let user_id = create_user(&pool).await;
+
+        let days = get_user_unread_since_days(&pool, user_id).await.unwrap();
+        assert_eq!(None, days);
+
+        set_user_unread_since_days(&pool, user_id, Some(7))
+            .await
+            .unwrap();
+
+        let days = get_user_unread_since_days(&pool, user_id).await.unwrap();
+        assert_eq!(Some(7), days);
+    }
+
+    #[tokio::test]
+    async fn mark_feed_entries_as_read_should_only_mark_the_given_entries() {
+        let pool = get_pool().await;
+
+        let url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let user_id = create_user(&pool).await;
+        let feed_id = create_feed(&pool, user_id, &url, &site_link).await;
+
+        let mut entry_ids = Vec::new();
+        for _ in 0..3 {
+            entry_ids.push(create_feed_entry(&pool, &feed_id).await);
+        }
+
+        let marked = mark_feed_entries_as_read(&pool, user_id, &feed_id, &entry_ids[0..2])
+            .await
+            .unwrap();
+        assert_eq!(2, marked);
+
+        // Already read entries are not marked again
+        let marked = mark_feed_entries_as_read(&pool, user_id, &feed_id, &entry_ids)
+            .await
+            .unwrap();
+        assert_eq!(1, marked);
+
+        // Another user can't mark the entries
+        let other_user_id = create_user(&pool).await;
+        let other_entry_id = create_feed_entry(&pool, &feed_id).await;
+        let marked = mark_feed_entries_as_read(&pool, other_user_id, &feed_id, &[other_entry_id])
+            .await
+            .unwrap();
+        assert_eq!(0, marked);
+    }
+
+    #[tokio::test]
+    async fn mark_all_entries_as_read_should_mark_the_unread_entries() {
+        let pool = get_pool().await;
+
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let user_id = create_user(&pool).await;
+
+        let mut feed_ids = Vec::new();
+        for i in 0..2 {
+            let url = Url::parse(&format!("https://example.com/feed{}.xml", i)).unwrap();
+            let feed_id = create_feed(&pool, user_id, &url, &site_link).await;
+            for _ in 0..3 {
+                create_feed_entry(&pool, &feed_id).await;
+            }
+            feed_ids.push(feed_id);
+        }
+
+        // Another user can't mark the entries
+        let other_user_id = create_user(&pool).await;
+        let marked = mark_all_feed_entries_as_read(&pool, other_user_id, &feed_ids[0])
+            .await
+            .unwrap();
+        assert_eq!(0, marked);
+        let marked = mark_all_entries_as_read(&pool, other_user_id)
+            .await
+            .unwrap();
+        assert_eq!(0, marked);
+
+        // Mark the entries of a single feed
+        let marked = mark_all_feed_entries_as_read(&pool, user_id, &feed_ids[0])
+            .await
+            .unwrap();
+        assert_eq!(3, marked);
+
+        // Already read entries are not marked again
+        let marked = mark_all_entries_as_read(&pool, user_id).await.unwrap();
+        assert_eq!(3, marked);
+        let marked = mark_all_entries_as_read(&pool, user_id).await.unwrap();
+        assert_eq!(0, marked);
+    }
+
+    #[tokio::test]
+    async fn feed_tags_should_be_stored() {
+        let pool = get_pool().await;
+
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let user_id = create_user(&pool).await;
+
+        let mut feed_ids = Vec::new();
+        for i in 0..2 {
+            let url = Url::parse(&format!("https://example.com/feed{}.xml", i)).unwrap();
+            let feed_id = create_feed(&pool, user_id, &url, &site_link).await;
+            create_feed_entry(&pool, &feed_id).await;
+            feed_ids.push(feed_id);
+        }
+
+        // Tag the first feed twice with the same tag, and the second feed with another tag
+
+        let tech = get_or_create_tag(&pool, user_id, "tech").await.unwrap();
+        let news = get_or_create_tag(&pool, user_id, "news").await.unwrap();
+        assert_eq!(
+            tech,
+            get_or_create_tag(&pool, user_id, "tech").await.unwrap()
+        );
+
+        assert!(add_feed_tag(&pool, user_id, &feed_ids[0], tech)
+            .await
+            .unwrap());
+        assert!(add_feed_tag(&pool, user_id, &feed_ids[0], tech)
+            .await
+            .unwrap());
+        assert!(add_feed_tag(&pool, user_id, &feed_ids[0], news)
+            .await
+            .unwrap());
+        assert!(add_feed_tag(&pool, user_id, &feed_ids[1], news)
+            .await
+            .unwrap());
+
+        let tags = get_feeds_tags(&pool, user_id).await.unwrap();
+        let names = |feed_id: &FeedId| {
+            tags[feed_id]
+                .iter()
+                .map(|tag| tag.name.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec!["news", "tech"], names(&feed_ids[0]));
+        assert_eq!(vec!["news"], names(&feed_ids[1]));
+
+        let feeds = get_feeds_by_tag(&pool, user_id, "tech").await.unwrap();
+        assert_eq!(1, feeds.len());
+        assert_eq!(feed_ids[0], feeds[0].id);
+
+        let entries = get_unread_entries_by_tag(&pool, user_id, "news")
+            .await
+            .unwrap();
+        assert_eq!(2, entries.len());
+
+        // Another user can't use the feeds or the tags
+
+        let other_user_id = create_user(&pool).await;
+        let other_tag = get_or_create_tag(&pool, other_user_id, "tech")
+            .await
+            .unwrap();
+        assert!(!add_feed_tag(&pool, other_user_id, &feed_ids[1], other_tag)
+            .await
+            .unwrap());
+        assert!(!add_feed_tag(&pool, user_id, &feed_ids[1], other_tag)
+            .await
+            .unwrap());
+        assert!(get_feeds_by_tag(&pool, other_user_id, "tech")
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Remove a tag
+
+        assert!(remove_feed_tag(&pool, user_id, &feed_ids[0], news)
+            .await
+            .unwrap());
+        assert!(!remove_feed_tag(&pool, user_id, &feed_ids[0], news)
+            .await
+            .unwrap());
+
+        let entries = get_unread_entries_by_tag(&pool, user_id, "news")
+            .await
+            .unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!(feed_ids[1], entries[0].feed_id);
+    }
+
+    #[tokio::test]
+    async fn get_feeds_by_author_should_return_the_feeds_with_an_entry_by_the_author() {
+        let pool = get_pool().await;
+
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let user_id = create_user(&pool).await;
+
+        let authors = [
+            json!(["Vincent", "Alice"]),
+            json!(["Bob"]),
+            json!(["Alice"]),
+        ];
+        let mut feed_ids = Vec::new();
+        for (i, entry_authors) in authors.iter().enumerate() {
+            let url = Url::parse(&format!("https://example.com/feed{}.xml", i)).unwrap();
+            let feed_id = create_feed(&pool, user_id, &url, &site_link).await;
+            let entry_id = create_feed_entry(&pool, &feed_id).await;
+
+            sqlx::query!(
+                "UPDATE feed_entries SET authors = $2 WHERE id = $1",
+                &entry_id.0,
+                entry_authors,
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+
+            feed_ids.push(feed_id);
+        }
+
+        let get_feed_ids = |author: &'static str| {
+            let pool = pool.clone();
+            async move {
+                get_feeds_by_author(&pool, user_id, author)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|feed| feed.id)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(vec![feed_ids[2], feed_ids[0]], get_feed_ids("Alice").await);
+        assert_eq!(vec![feed_ids[1]], get_feed_ids("Bob").await);
+        assert!(get_feed_ids("Eve").await.is_empty());
+
+        // The feeds of another user must not be returned
+        let other_user_id = create_user(&pool).await;
+        let feeds = get_feeds_by_author(&pool, other_user_id, "Alice")
+            .await
+            .unwrap();
+        assert!(feeds.is_empty());
+    }
+
+    #[test]
+    fn normalize_feed_url_should_remove_an_empty_query() {
+        let url = Url::parse("https://example.com/feed?").unwrap();
+        assert_eq!(
+            "https://example.com/feed",
+            normalize_feed_url(&url).as_str()
+        );
+    }
+
+    #[test]
+    fn normalize_feed_url_should_lowercase_the_host() {
+        let url = Url::parse("https://EXAMPLE.com/Feed").unwrap();
+        assert_eq!(
+            "https://example.com/Feed",
+            normalize_feed_url(&url).as_str()
+        );
+
+        // The URL parser doesn't lowercase the host of non special schemes
+        let url = Url::parse("feed://EXAMPLE.com/feed").unwrap();
+        assert_eq!("feed://example.com/feed", normalize_feed_url(&url).as_str());
+    }
+
+    #[test]
+    fn normalize_feed_url_should_remove_the_default_port() {
+        let url = Url::parse("http://example.com:80/feed").unwrap();
+        assert_eq!("http://example.com/feed", normalize_feed_url(&url).as_str());
+
+        let url = Url::parse("https://example.com:443/feed").unwrap();
+        assert_eq!(
+            "https://example.com/feed",
+            normalize_feed_url(&url).as_str()
+        );
+
+        let url = Url::parse("https://example.com:8443/feed").unwrap();
+        assert_eq!(
+            "https://example.com:8443/feed",
+            normalize_feed_url(&url).as_str()
+        );
+    }
+
+    #[test]
+    fn normalize_feed_url_should_sort_the_query_parameters() {
+        let url = Url::parse("https://example.com/feed?tag=b&format=rss&tag=a").unwrap();
+        assert_eq!(
+            "https://example.com/feed?format=rss&tag=b&tag=a",
+            normalize_feed_url(&url).as_str()
+        );
+    }
+
+    #[test]
+    fn parse_authors_from_record_should_parse_a_json_array() {
+        let authors = parse_authors_from_record(Some(json!(["Vincent", "Alice"]))).unwrap();
+        assert_eq!(vec!["Vincent".to_string(), "Alice".to_string()], authors);
+
+        let authors = parse_authors_from_record(None).unwrap();
+        assert!(authors.is_empty());
+
+        assert!(parse_authors_from_record(Some(json!({ "name": "Vincent" }))).is_err());
+    }
+
+    async fn insert_feed_entry(pool: &PgPool, feed_id: &FeedId, title: &str, summary: &str) {
+        sqlx::query!(
+            r#"
+            INSERT INTO feed_entries(feed_id, external_id, title, created_at, summary)
+            VALUES ($1, $2, $3, now(), $4)
+            "#,
+            &feed_id.0,
+            uuid::Uuid::new_v4().to_string(),
+            title,
+            summary,
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn search_feed_entries_should_rank_the_matching_entries() {
+        let pool = get_pool().await;
+
+        let url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let user_id = create_user(&pool).await;
+        let feed_id = create_feed(&pool, user_id, &url, &site_link).await;
+
+        insert_feed_entry(&pool, &feed_id, "Cooking pasta", "Boil the water").await;
+        insert_feed_entry(&pool, &feed_id, "Gardening", "Tomatoes and pasta sauce").await;
+        insert_feed_entry(&pool, &feed_id, "Pasta recipes", "More pasta, always pasta").await;
+
+        // Another user's entries are never returned
+        let other_user_id = create_user(&pool).await;
+        let other_feed_id = create_feed(&pool, other_user_id, &url, &site_link).await;
+        insert_feed_entry(&pool, &other_feed_id, "Pasta", "Pasta").await;
+
+        let entries = search_feed_entries(&pool, user_id, "pasta", 10)
+            .await
+            .unwrap();
+        let titles = entries
+            .iter()
+            .map(|entry| entry.title.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(3, titles.len());
+        assert_eq!("Pasta recipes", titles[0]);
+
+        let entries = search_feed_entries(&pool, user_id, "pasta sauce", 10)
+            .await
+            .unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!("Gardening", entries[0].title);
+    }
+
+    #[tokio::test]
+    async fn search_feed_entries_should_return_nothing_for_an_empty_query() {
+        let pool = get_pool().await;
+
+        let url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let user_id = create_user(&pool).await;
+        let feed_id = create_feed(&pool, user_id, &url, &site_link).await;
+        create_feed_entry(&pool, &feed_id).await;
+
+        for query in ["", "   ", "the", "&|!("] {
+            let entries = search_feed_entries(&pool, user_id, query, 10)
+                .await
+                .unwrap();
+            assert!(entries.is_empty(), "query {:?} should match nothing", query);
+        }
     }
 }
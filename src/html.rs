@@ -1,17 +1,35 @@
 use crate::fetch_bytes;
 use select::document::Document;
-use select::predicate::Name;
+use select::predicate::{Name, Text};
 use std::io;
 use url::Url;
 
+/// The error returned by [`fetch_document`], with the URL that failed.
 #[derive(Debug, thiserror::Error)]
-pub enum FetchDocumentError {
+#[error("failed to fetch {url}: {kind}")]
+pub struct FetchDocumentError {
+    pub url: Url,
+    #[source]
+    pub kind: FetchDocumentErrorKind,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FetchDocumentErrorKind {
     #[error(transparent)]
     IO(#[from] io::Error),
     #[error(transparent)]
     HTTP(#[from] reqwest::Error),
 }
 
+impl FetchDocumentError {
+    fn new(url: &Url, kind: impl Into<FetchDocumentErrorKind>) -> Self {
+        Self {
+            url: url.clone(),
+            kind: kind.into(),
+        }
+    }
+}
+
 /// Fetch the document at `url` using `client`.
 ///
 /// # Errors
@@ -24,9 +42,12 @@ pub async fn fetch_document(
     client: &reqwest::Client,
     url: &Url,
 ) -> Result<Document, FetchDocumentError> {
-    let response = fetch_bytes(client, url).await?;
+    let response = fetch_bytes(client, url)
+        .await
+        .map_err(|err| FetchDocumentError::new(url, err))?;
 
-    let document = Document::from_read(&response[..])?;
+    let document =
+        Document::from_read(&response[..]).map_err(|err| FetchDocumentError::new(url, err))?;
 
     Ok(document)
 }
@@ -39,51 +60,255 @@ pub enum FindLinkCriteria {
     Type(&'static str),
 }
 
-/// Find the first link in a [`select::document::Document`] matching a [`FindLinkCriteria`].
-pub fn find_link_in_document(
+/// Find all the links in a [`select::document::Document`] matching any of the `criteria`.
+///
+/// The links are returned in document order, without duplicates: callers pick the one they
+/// prefer.
+pub fn find_links_in_document(
     url: &Url,
     document: &Document,
-    criterias: &'static [FindLinkCriteria],
-) -> Option<Url> {
+    criteria: &[FindLinkCriteria],
+) -> Vec<Url> {
+    let mut links: Vec<Url> = Vec::new();
+
     for link in document.find(Name("link")) {
         let link_href = link.attr("href").unwrap_or_default();
 
-        // The href might be absolute
-        let url = if !link_href.starts_with("http") {
+        // The href might be absolute, or protocol-relative in which case it uses the scheme of `url`
+        let url = if link_href.starts_with("//") {
+            Url::parse(&[url.scheme(), ":", link_href].concat())
+        } else if !link_href.starts_with("http") {
             url.join(link_href)
         } else {
             Url::parse(link_href)
         };
 
-        if let Ok(url) = url {
-            for criteria in criterias {
-                match criteria {
-                    FindLinkCriteria::Rel(rel) => {
-                        let link_rel = link.attr("rel").unwrap_or_default();
-                        if link_rel == *rel {
-                            return Some(url);
-                        }
-                    }
-                    FindLinkCriteria::Type(typ) => {
-                        let link_type = link.attr("type").unwrap_or_default();
-                        if link_type == *typ {
-                            return Some(url);
-                        }
-                    }
-                }
-            }
+        let url = match url {
+            Ok(url) => url,
+            Err(_) => continue,
+        };
+
+        let matches = criteria.iter().any(|criteria| match criteria {
+            FindLinkCriteria::Rel(rel) => link.attr("rel").unwrap_or_default() == *rel,
+            FindLinkCriteria::Type(typ) => link.attr("type").unwrap_or_default() == *typ,
+        });
+        if matches && !links.contains(&url) {
+            links.push(url);
+        }
+    }
+
+    links
+}
+
+/// Find the first link in a [`select::document::Document`] matching any of the `criteria`.
+#[deprecated(note = "use `find_links_in_document` and pick the preferred link")]
+pub fn find_link_in_document(
+    url: &Url,
+    document: &Document,
+    criteria: &[FindLinkCriteria],
+) -> Option<Url> {
+    find_links_in_document(url, document, criteria)
+        .into_iter()
+        .next()
+}
+
+/// Convert the HTML fragment `html` to plain text.
+///
+/// All tags are stripped and whitespace is normalized: runs of whitespace are collapsed into a single space.
+pub fn html_to_text(html: &str) -> String {
+    let document = Document::from(html);
+
+    let text = document
+        .find(Text)
+        .filter_map(|node| node.as_text())
+        .collect::<String>();
+
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The tags kept by [`sanitize_html`], all other tags are stripped.
+const SANITIZE_ALLOWED_TAGS: &[&str] = &[
+    "p",
+    "br",
+    "a",
+    "ul",
+    "ol",
+    "li",
+    "blockquote",
+    "code",
+    "pre",
+    "em",
+    "strong",
+    "img",
+    "figure",
+    "figcaption",
+    "picture",
+    "source",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+];
+
+/// Sanitize the HTML fragment `raw` so that it's safe to render in a page.
+///
+/// Only the tags in [`SANITIZE_ALLOWED_TAGS`] are kept and only a small set of attributes is
+/// allowed, so event attributes like `onclick` are always stripped. The content of `script` and
+/// `style` tags is dropped entirely.
+pub fn sanitize_html(raw: &str) -> String {
+    ammonia::Builder::default()
+        .tags(SANITIZE_ALLOWED_TAGS.iter().copied().collect())
+        .add_tag_attributes("img", &["srcset"])
+        .add_tag_attributes("source", &["src", "srcset", "type", "media"])
+        .clean(raw)
+        .to_string()
+}
+
+/// Elements which never have children nor a closing tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Make all links in the HTML fragment `html` absolute, resolving them against `base_url`.
+///
+/// This rewrites the `href` attribute of `a` tags, the `src` attribute of `img` and `source` tags
+/// and the `srcset` attribute of `img` and `source` tags. Links already absolute are left as is.
+///
+/// The fragment is parsed and serialized again: comments are dropped.
+pub fn make_links_absolute(html: &str, base_url: &Url) -> String {
+    let document = Document::from(html);
+
+    let mut result = String::with_capacity(html.len());
+    if let Some(body) = document.find(Name("body")).next() {
+        for child in body.children() {
+            write_node_with_absolute_links(&mut result, &child, base_url);
         }
     }
 
-    None
+    result
+}
+
+fn write_node_with_absolute_links(out: &mut String, node: &select::node::Node, base_url: &Url) {
+    if let Some(text) = node.as_text() {
+        out.push_str(&escape_html(text, false));
+        return;
+    }
+
+    let name = match node.name() {
+        Some(name) => name,
+        None => return,
+    };
+
+    out.push('<');
+    out.push_str(name);
+    for (attr_name, attr_value) in node.attrs() {
+        let value = match (name, attr_name) {
+            ("a", "href") | ("img", "src") | ("source", "src") => {
+                resolve_link(attr_value, base_url)
+            }
+            ("img", "srcset") | ("source", "srcset") => resolve_srcset(attr_value, base_url),
+            _ => attr_value.to_string(),
+        };
+
+        out.push(' ');
+        out.push_str(attr_name);
+        out.push_str("=\"");
+        out.push_str(&escape_html(&value, true));
+        out.push('"');
+    }
+    out.push('>');
+
+    if VOID_ELEMENTS.contains(&name) {
+        return;
+    }
+
+    for child in node.children() {
+        write_node_with_absolute_links(out, &child, base_url);
+    }
+
+    out.push_str("</");
+    out.push_str(name);
+    out.push('>');
+}
+
+fn resolve_link(link: &str, base_url: &Url) -> String {
+    base_url
+        .join(link.trim())
+        .map(|url| url.to_string())
+        .unwrap_or_else(|_| link.to_string())
+}
+
+/// Resolve every URL of a `srcset` attribute, keeping their descriptors.
+fn resolve_srcset(srcset: &str, base_url: &Url) -> String {
+    srcset
+        .split(',')
+        .filter_map(|candidate| {
+            let mut parts = candidate.split_whitespace();
+            let url = resolve_link(parts.next()?, base_url);
+
+            Some(std::iter::once(url).chain(parts.map(ToString::to_string)))
+        })
+        .map(|parts| parts.collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn escape_html(s: &str, in_attribute: bool) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' if !in_attribute => result.push_str("&lt;"),
+            '>' if !in_attribute => result.push_str("&gt;"),
+            '"' if in_attribute => result.push_str("&quot;"),
+            c => result.push(c),
+        }
+    }
+    result
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use select::predicate::Predicate;
+
+    #[test]
+    fn fetch_document_error_should_display_the_url() {
+        let err = FetchDocumentError::new(
+            &Url::parse("https://example.com/foo").unwrap(),
+            io::Error::new(io::ErrorKind::Other, "boom"),
+        );
+
+        assert_eq!(
+            "failed to fetch https://example.com/foo: boom",
+            err.to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_document_should_fail_with_the_url() {
+        // Get a free port with nothing listening on it
+        let port = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().port()
+        };
+        let url = Url::parse(&format!("http://127.0.0.1:{}/foo", port)).unwrap();
+
+        let client = reqwest::Client::new();
+        let err = fetch_document(&client, &url).await.unwrap_err();
+
+        assert_eq!(url, err.url);
+        assert!(matches!(err.kind, FetchDocumentErrorKind::HTTP(_)));
+        assert!(err
+            .to_string()
+            .starts_with(&format!("failed to fetch {}: ", url)));
+    }
 
     #[test]
-    fn find_link_in_document_with_rel() {
+    fn find_links_in_document_with_rel() {
         let url = Url::parse("https://example.com").unwrap();
         let document = Document::from(
             r#"
@@ -95,13 +320,13 @@ mod tests {
         "#,
         );
 
-        let link = find_link_in_document(&url, &document, &[FindLinkCriteria::Rel("foobar")]);
-        assert!(link.is_some());
-        assert_eq!("https://example.com/hello", link.unwrap().to_string())
+        let links = find_links_in_document(&url, &document, &[FindLinkCriteria::Rel("foobar")]);
+        assert_eq!(1, links.len());
+        assert_eq!("https://example.com/hello", links[0].to_string())
     }
 
     #[test]
-    fn find_link_in_document_with_type() {
+    fn find_links_in_document_with_type() {
         let url = Url::parse("https://example.com").unwrap();
         let document = Document::from(
             r#"
@@ -113,8 +338,192 @@ mod tests {
         "#,
         );
 
-        let link = find_link_in_document(&url, &document, &[FindLinkCriteria::Type("foo")]);
-        assert!(link.is_some());
-        assert_eq!("https://example.com/yesterday", link.unwrap().to_string())
+        let links = find_links_in_document(&url, &document, &[FindLinkCriteria::Type("foo")]);
+        assert_eq!(1, links.len());
+        assert_eq!("https://example.com/yesterday", links[0].to_string())
+    }
+
+    #[test]
+    fn find_links_in_document_with_protocol_relative_url() {
+        let document = Document::from(
+            r#"
+            <html>
+            <head>
+            <link rel="alternate" href="//example.com/rss.xml">
+            </head>
+            </html>
+        "#,
+        );
+
+        let url = Url::parse("https://base.com").unwrap();
+        let links = find_links_in_document(&url, &document, &[FindLinkCriteria::Rel("alternate")]);
+        assert_eq!(
+            vec!["https://example.com/rss.xml".to_string()],
+            links.iter().map(Url::to_string).collect::<Vec<_>>()
+        );
+
+        let url = Url::parse("http://base.com").unwrap();
+        let links = find_links_in_document(&url, &document, &[FindLinkCriteria::Rel("alternate")]);
+        assert_eq!(
+            vec!["http://example.com/rss.xml".to_string()],
+            links.iter().map(Url::to_string).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn find_links_in_document_should_return_all_the_links() {
+        let url = Url::parse("https://example.com").unwrap();
+        let document = Document::from(
+            r#"
+            <html>
+            <head>
+            <link type="application/rss+xml" href="/rss.xml">
+            <link rel="stylesheet" href="/style.css">
+            <link type="application/atom+xml" href="/atom.xml">
+            <link type="application/rss+xml" href="https://example.com/rss.xml">
+            </head>
+            </html>
+        "#,
+        );
+
+        let criterias = &[
+            FindLinkCriteria::Type("application/rss+xml"),
+            FindLinkCriteria::Type("application/atom+xml"),
+        ];
+        let links = find_links_in_document(&url, &document, criterias);
+        assert_eq!(
+            vec![
+                "https://example.com/rss.xml".to_string(),
+                "https://example.com/atom.xml".to_string(),
+            ],
+            links.iter().map(Url::to_string).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn find_links_in_document_should_return_every_link_matching_a_criteria() {
+        let url = Url::parse("https://example.com").unwrap();
+        let document = Document::from(
+            r#"
+            <html>
+            <head>
+            <link type="application/rss+xml" href="/posts.xml">
+            <link type="application/rss+xml" href="/comments.xml">
+            </head>
+            </html>
+        "#,
+        );
+
+        let criteria = [FindLinkCriteria::Type("application/rss+xml")];
+
+        let links = find_links_in_document(&url, &document, &criteria);
+        assert_eq!(
+            vec![
+                "https://example.com/posts.xml".to_string(),
+                "https://example.com/comments.xml".to_string(),
+            ],
+            links.iter().map(Url::to_string).collect::<Vec<_>>()
+        );
+
+        #[allow(deprecated)]
+        let link = find_link_in_document(&url, &document, &criteria);
+        assert_eq!(links.first(), link.as_ref());
+    }
+
+    #[test]
+    fn html_to_text_should_strip_tags_and_normalize_whitespace() {
+        let html = r#"
+            <p>Hello   <b>world</b>,</p>
+            <p>this is
+            a <a href="https://example.com">link</a>.</p>
+        "#;
+
+        assert_eq!("Hello world, this is a link.", html_to_text(html));
+    }
+
+    #[test]
+    fn html_to_text_should_keep_plain_text() {
+        assert_eq!("Hello world", html_to_text("Hello world"));
+    }
+
+    #[test]
+    fn sanitize_html_should_strip_scripts() {
+        let html = sanitize_html(r#"<p>Hello</p><script>alert(1)</script>"#);
+        assert_eq!("<p>Hello</p>", html);
+    }
+
+    #[test]
+    fn sanitize_html_should_strip_event_attributes() {
+        let html = sanitize_html(r#"<img src="https://example.com/a.png" onerror="alert(1)">"#);
+        assert_eq!(r#"<img src="https://example.com/a.png">"#, html);
+    }
+
+    #[test]
+    fn sanitize_html_should_keep_links() {
+        let html = sanitize_html(r#"<a href="http://example.com">link</a>"#);
+
+        let document = Document::from(html.as_str());
+        let link = document.find(Name("a")).next().unwrap();
+        assert_eq!(Some("http://example.com"), link.attr("href"));
+        assert_eq!("link", link.text());
+    }
+
+    #[derive(rust_embed::RustEmbed)]
+    #[folder = "testdata/"]
+    struct TestData;
+
+    #[test]
+    fn make_links_absolute_should_resolve_all_links() {
+        let data = TestData::get("relative_links_summary.html").unwrap().data;
+        let html = std::str::from_utf8(&data).unwrap();
+
+        let base_url = Url::parse("https://example.com/blog/post/").unwrap();
+
+        let result = make_links_absolute(html, &base_url);
+        let document = Document::from(result.as_str());
+
+        let hrefs = document
+            .find(Name("a"))
+            .filter_map(|node| node.attr("href"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "https://example.com/about",
+                "https://example.com/blog/post/details",
+                "https://other.example.org/page",
+                "https://cdn.example.net/page",
+            ],
+            hrefs
+        );
+
+        let sources = document
+            .find(Name("img").or(Name("source")))
+            .filter_map(|node| node.attr("src"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "https://example.com/blog/post/image.png",
+                "https://example.com/images/logo.png",
+                "https://example.com/video.mp4",
+                "https://other.example.org/video.webm",
+            ],
+            sources
+        );
+
+        let srcsets = document
+            .find(Name("img").or(Name("source")))
+            .filter_map(|node| node.attr("srcset"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "https://example.com/blog/post/small.png 1x, https://example.com/large.png 2x",
+                "https://example.com/blog/post/photo.webp 480w, https://other.example.org/photo.webp 800w",
+            ],
+            srcsets
+        );
+
+        // The rest of the document is unchanged
+
+        assert!(result.contains("<p>A paragraph with <strong>bold</strong> &amp; text</p>"));
     }
 }
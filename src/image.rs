@@ -0,0 +1,159 @@
+/// How many bytes at the start of a document are looked at to detect a SVG image.
+const SVG_SNIFF_LEN: usize = 512;
+
+/// Returns the MIME type of the favicon `data` based on its magic bytes.
+///
+/// PNG, ICO, GIF, JPEG and SVG are detected, anything else is assumed to be an ICO. In
+/// particular a HTML document is not a SVG image.
+pub fn detect_favicon_mime(data: &[u8]) -> &'static str {
+    const ICO: &str = "image/x-icon";
+
+    if data.starts_with(b"\x89PNG") {
+        "image/png"
+    } else if data.starts_with(b"\x00\x00\x01\x00") {
+        ICO
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        "image/gif"
+    } else if data.starts_with(b"\xFF\xD8") {
+        "image/jpeg"
+    } else if is_svg(data) {
+        "image/svg+xml"
+    } else {
+        ICO
+    }
+}
+
+/// Returns true if the favicon `data` is an image we can serve: PNG, ICO, GIF, JPEG or SVG.
+///
+/// This is stricter than [`detect_favicon_mime`], in particular a HTML document like an error
+/// page served instead of the favicon is not valid.
+pub fn is_valid_favicon(data: &[u8]) -> bool {
+    data.starts_with(b"\x89PNG")
+        || data.starts_with(b"\x00\x00\x01\x00")
+        || data.starts_with(b"GIF87a")
+        || data.starts_with(b"GIF89a")
+        || data.starts_with(b"\xFF\xD8\xFF")
+        || is_svg(data)
+}
+
+/// Returns true if `data` looks like a SVG document: a `svg` root element, optionally preceded
+/// by a XML declaration or a SVG doctype.
+fn is_svg(data: &[u8]) -> bool {
+    let head = &data[..data.len().min(SVG_SNIFF_LEN)];
+    let head = String::from_utf8_lossy(head)
+        .trim_start()
+        .to_ascii_lowercase();
+
+    if head.starts_with("<svg") {
+        return true;
+    }
+
+    (head.starts_with("<?xml") || head.starts_with("<!doctype svg")) && head.contains("<svg")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_favicon_mime_should_detect_png() {
+        assert_eq!(
+            "image/png",
+            detect_favicon_mime(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR")
+        );
+    }
+
+    #[test]
+    fn detect_favicon_mime_should_detect_ico() {
+        assert_eq!(
+            "image/x-icon",
+            detect_favicon_mime(b"\x00\x00\x01\x00\x01\x00\x10\x10")
+        );
+    }
+
+    #[test]
+    fn detect_favicon_mime_should_detect_jpeg() {
+        assert_eq!(
+            "image/jpeg",
+            detect_favicon_mime(b"\xFF\xD8\xFF\xE0\x00\x10JFIF")
+        );
+    }
+
+    #[test]
+    fn detect_favicon_mime_should_detect_svg() {
+        assert_eq!(
+            "image/svg+xml",
+            detect_favicon_mime(br#"<svg xmlns="http://www.w3.org/2000/svg"></svg>"#)
+        );
+        assert_eq!(
+            "image/svg+xml",
+            detect_favicon_mime(b"\n  <?xml version=\"1.0\"?><svg></svg>")
+        );
+    }
+
+    #[test]
+    fn detect_favicon_mime_should_detect_gif() {
+        assert_eq!("image/gif", detect_favicon_mime(b"GIF89a\x10\x00\x10\x00"));
+    }
+
+    #[test]
+    fn detect_favicon_mime_should_fall_back_to_ico() {
+        assert_eq!("image/x-icon", detect_favicon_mime(b"\xde\xad\xbe\xef"));
+        assert_eq!("image/x-icon", detect_favicon_mime(b""));
+        assert_eq!(
+            "image/x-icon",
+            detect_favicon_mime(b"<html><script>alert(1)</script></html>")
+        );
+    }
+
+    #[test]
+    fn is_valid_favicon_should_accept_png() {
+        assert!(is_valid_favicon(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR"));
+    }
+
+    #[test]
+    fn is_valid_favicon_should_accept_ico() {
+        assert!(is_valid_favicon(b"\x00\x00\x01\x00\x01\x00\x10\x10"));
+    }
+
+    #[test]
+    fn is_valid_favicon_should_accept_gif() {
+        assert!(is_valid_favicon(b"GIF87a\x10\x00\x10\x00"));
+        assert!(is_valid_favicon(b"GIF89a\x10\x00\x10\x00"));
+    }
+
+    #[test]
+    fn is_valid_favicon_should_accept_jpeg() {
+        assert!(is_valid_favicon(b"\xFF\xD8\xFF\xE0\x00\x10JFIF"));
+    }
+
+    #[test]
+    fn is_valid_favicon_should_accept_svg() {
+        assert!(is_valid_favicon(
+            br#"<svg xmlns="http://www.w3.org/2000/svg"></svg>"#
+        ));
+        assert!(is_valid_favicon(
+            b"\n  <?xml version=\"1.0\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>"
+        ));
+        assert!(is_valid_favicon(
+            b"<!DOCTYPE svg PUBLIC \"-//W3C//DTD SVG 1.1//EN\">\n<svg></svg>"
+        ));
+    }
+
+    #[test]
+    fn is_valid_favicon_should_reject_a_html_error_page() {
+        assert!(!is_valid_favicon(
+            b"<!DOCTYPE html>\n<html><head><title>404 Not Found</title></head></html>"
+        ));
+        assert!(!is_valid_favicon(b"<html><body>Not Found</body></html>"));
+        assert!(!is_valid_favicon(
+            b"<?xml version=\"1.0\"?>\n<html xmlns=\"http://www.w3.org/1999/xhtml\"></html>"
+        ));
+    }
+
+    #[test]
+    fn is_valid_favicon_should_reject_unknown_data() {
+        assert!(!is_valid_favicon(b"\xde\xad\xbe\xef"));
+        assert!(!is_valid_favicon(b""));
+    }
+}
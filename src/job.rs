@@ -1,32 +1,43 @@
 use crate::configuration::JobConfig;
-use crate::domain::UserId;
-use crate::feed::{find_favicon, FeedId, ParsedFeed, ParsedFeedEntry};
-use crate::fetch_bytes;
+use crate::domain::{get_user, UserId};
+use crate::feed::{
+    find_favicon, get_all_feeds, get_feed_cache_validators, get_unread_count_since,
+    get_unread_entries, parse_json_feed, set_feed_cache_validators, set_feed_last_refreshed_at,
+    update_feed_metadata,
+};
+use crate::feed::{FaviconCache, FeedCacheValidators, FeedId, ParsedFeed, ParsedFeedEntry};
+use crate::feed::{FeedEntry, FeedOrder, UnreadFilter};
+use crate::fetch_bytes_with_timeout;
+use crate::html::make_links_absolute;
+use crate::image::{detect_favicon_mime, is_valid_favicon};
+use crate::impl_typed_uuid;
+use crate::parsed_feed::parse_raw_feed;
 use crate::run_group::Shutdown;
+use crate::security::{dns_resolver, redirect_policy, AllowPrivateUrls};
+use crate::tem;
+use crate::unread_updates::UnreadUpdates;
+use anyhow::anyhow;
+use askama::Template;
 use blake2::{Blake2b512, Digest};
+use reqwest::header;
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use sqlx::types::Json;
 use sqlx::PgPool;
 use std::fmt;
 use std::io::Write;
-use tracing::{error, event, info, Level};
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+use time::{OffsetDateTime, Time};
+use tokio::sync::Semaphore;
+use tracing::{error, event, info, warn, Instrument, Level};
 use url::Url;
 use uuid::Uuid;
 
-#[derive(Clone)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
 pub struct JobId(pub Uuid);
-
-impl Default for JobId {
-    fn default() -> Self {
-        Self(Uuid::new_v4())
-    }
-}
-
-impl fmt::Display for JobId {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
-    }
-}
+impl_typed_uuid!(JobId);
 
 #[derive(Debug, thiserror::Error)]
 enum RunJobError {
@@ -46,31 +57,59 @@ enum RunJobError {
 /// refreshes of a feed, retries, etc.
 ///
 /// Running jobs is self explanatory: it will pop jobs from the queue and run them, handling any
-/// errors that occur.
+/// errors that occur. The jobs popped in one tick run concurrently.
+#[derive(Clone)]
 pub struct JobRunner {
     http_client: reqwest::Client,
     config: JobConfig,
     pool: PgPool,
+    handler: Arc<dyn JobHandler + Send + Sync>,
+    semaphore: Arc<Semaphore>,
+    /// A job running for longer than this is aborted.
+    job_timeout: StdDuration,
 }
 
-// Hardcode some limits on the number of jobs to run in one tick.
-const MANAGE_JOBS_LIMIT: usize = 1;
-const RUN_JOBS_LIMIT: usize = 1;
+// Favicons are tiny images, they don't deserve as much time as a feed.
+const REFRESH_FEED_FETCH_TIMEOUT: StdDuration = StdDuration::from_secs(30);
+const FETCH_FAVICON_FETCH_TIMEOUT: StdDuration = StdDuration::from_secs(5);
 
 impl JobRunner {
-    pub fn new(config: JobConfig, pool: PgPool) -> anyhow::Result<Self> {
-        let http_client = reqwest::Client::builder()
-            .redirect(reqwest::redirect::Policy::limited(10))
-            .cookie_store(true)
-            .build()?;
+    /// Creates a new [`JobRunner`] which runs the jobs with `handler`.
+    ///
+    /// Use a [`DefaultJobHandler`] to actually run the jobs.
+    pub fn new(
+        config: JobConfig,
+        pool: PgPool,
+        handler: Box<dyn JobHandler + Send + Sync>,
+    ) -> anyhow::Result<Self> {
+        let http_client = build_http_client(AllowPrivateUrls(false))?;
+        let semaphore = Arc::new(Semaphore::new(config.run_jobs_limit));
+        let job_timeout = config.job_timeout();
 
         Ok(Self {
             http_client,
             config,
             pool,
+            handler: Arc::from(handler),
+            semaphore,
+            job_timeout,
         })
     }
 
+    /// Allows the jobs to fetch URLs pointing to private addresses, see [`dns_resolver`] and
+    /// [`redirect_policy`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the HTTP client can't be built.
+    pub fn with_allow_private_urls(
+        mut self,
+        allow_private_urls: AllowPrivateUrls,
+    ) -> anyhow::Result<Self> {
+        self.http_client = build_http_client(allow_private_urls)?;
+        Ok(self)
+    }
+
     pub async fn run(mut self, mut shutdown: Shutdown) -> anyhow::Result<()> {
         let mut interval = tokio::time::interval(self.config.run_interval());
 
@@ -81,12 +120,8 @@ impl JobRunner {
                     break 'outer_loop;
                 },
                 _ = interval.tick() => {
-                    if let Err(err) = self.manage_jobs().await {
-                        error!(%err, "failed while managing jobs");
-                    }
-
-                    if let Err(err) = self.run_jobs().await {
-                        error!(%err, "failed while managing jobs");
+                    if let Err(err) = self.tick().await {
+                        error!(%err, "failed while running the job runner");
                     }
                 },
             }
@@ -95,136 +130,465 @@ impl JobRunner {
         Ok(())
     }
 
+    /// Runs exactly one cycle of the job runner: first manage the jobs, then run them.
+    ///
+    /// This is what [`JobRunner::run`] does periodically; it's also useful in tests to run jobs
+    /// deterministically.
+    pub async fn tick(&mut self) -> anyhow::Result<()> {
+        if let Err(err) = self.manage_jobs().await {
+            error!(%err, "failed while managing jobs");
+        }
+
+        self.run_jobs()
+            .await
+            .map_err(|err| err.context("failed while running jobs"))
+    }
+
     #[tracing::instrument(name = "Manage jobs", level = "TRACE", skip(self))]
     async fn manage_jobs(&mut self) -> anyhow::Result<()> {
-        let mut remaining = MANAGE_JOBS_LIMIT;
+        let mut remaining = self.config.manage_jobs_limit;
 
         create_fetch_favicons_jobs(&self.pool, &mut remaining).await?;
+        create_refresh_feed_jobs(&self.pool, &mut remaining, self.config.refresh_interval())
+            .await?;
+        if self.config.digest.enabled {
+            create_digest_jobs(
+                &self.pool,
+                &mut remaining,
+                self.config.digest.send_hour,
+                OffsetDateTime::now_utc(),
+            )
+            .await?;
+        }
+
+        cleanup_stale_jobs(&self.pool, self.config.job_retention_days).await?;
 
         Ok(())
     }
 
     #[tracing::instrument(name = "Run jobs", level = "TRACE", skip(self))]
     async fn run_jobs(&mut self) -> anyhow::Result<()> {
+        let max_attempts = self.config.max_attempts as i32;
+
+        // 0) Put back in the queue the jobs interrupted while running, for example by a crash.
+        //
+        // Other job runners may be running jobs right now, only the jobs running for longer than
+        // the timeout are known to be interrupted.
+
+        let reset = reset_running_jobs(&self.pool, self.job_timeout).await?;
+        if reset > 0 {
+            info!(count = reset, "reset interrupted jobs");
+        }
+
+        // 1) Pop the jobs to run and mark them as running.
+        //
+        // The transaction is committed before running the jobs so that the rows aren't locked
+        // while they run; the running status is what keeps them from being picked up again.
+
         let mut tx = self.pool.begin().await?;
 
         let records = sqlx::query!(
             r#"
             SELECT id, data, status as "status: String", attempts
             FROM jobs
-            WHERE status = 'pending'
+            WHERE status = 'pending' AND next_run_at <= now()
             FOR UPDATE
             SKIP LOCKED
             LIMIT $1
             "#,
-            RUN_JOBS_LIMIT as i64,
+            self.config.run_jobs_limit as i64,
         )
         .fetch_all(&mut tx)
         .await?;
 
-        // TODO(vincent): use an exponential backoff
-        const MAX_JOBS_ATTEMPTS: i32 = 5;
-
+        let mut jobs = Vec::with_capacity(records.len());
         for record in records {
-            // 1) Sanity checks
-            if record.attempts >= MAX_JOBS_ATTEMPTS {
-                sqlx::query!("UPDATE jobs SET status = 'failed' WHERE id = $1", record.id)
-                    .execute(&mut tx)
-                    .await?;
+            // Sanity checks
+            if record.attempts >= max_attempts {
+                move_to_dead_letter(&mut tx, JobId(record.id)).await?;
 
                 continue;
             }
 
-            // 2) The job is valid; run it
-
-            let job: Job = serde_json::from_value(record.data)?;
-            let result: anyhow::Result<()> = match job {
-                Job::FetchFavicon(data) => {
-                    run_fetch_favicon_job(&self.http_client, &self.pool, data).await
-                }
-                Job::RefreshFeed(data) => {
-                    run_refresh_feed_job(&self.http_client, &self.pool, data).await
-                }
+            // The job is valid; migrate its data to the current schema if needed
+            let data = match job_schema_version(&record.data) {
+                1 => migrate_job_v1_to_v2(record.data),
+                _ => record.data,
             };
 
-            // 2) The job was run but it may have failed.
-            // Update its status accordingly
+            let job: Job = serde_json::from_value(data)?;
+
+            sqlx::query!(
+                "UPDATE jobs SET status = 'running', started_at = now() WHERE id = $1",
+                record.id
+            )
+            .execute(&mut tx)
+            .await?;
+
+            jobs.push((JobId(record.id), record.attempts, job));
+        }
 
-            if let Err(err) = result {
-                error!(%err, "job failed to run, retrying at a later time");
+        tx.commit().await?;
 
-                sqlx::query!(
-                    "UPDATE jobs SET attempts = attempts + 1 WHERE id = $1",
-                    record.id
+        // 2) Run each job in its own task, at most `run_jobs_limit` at a time
+
+        let mut handles = Vec::with_capacity(jobs.len());
+        for (job_id, attempts, job) in jobs {
+            let permit = self.semaphore.clone().acquire_owned().await?;
+
+            let handler = self.handler.clone();
+            let http_client = self.http_client.clone();
+            let pool = self.pool.clone();
+            let job_timeout = self.job_timeout;
+
+            let handle = tokio::spawn(async move {
+                let _permit = permit;
+
+                let job_description = job.to_string();
+                let job_type = job.type_name();
+
+                event!(Level::DEBUG, id = %job_id, job = %job_description, "running job");
+
+                // Each job gets its own span
+                let span = tracing::info_span!(
+                    "Run job",
+                    job_id = %job_id,
+                    job_type = job_type,
+                    attempts = attempts,
+                    success = tracing::field::Empty,
+                    error = tracing::field::Empty,
+                );
+
+                // A hung job must not block its worker forever: a timeout is a failed attempt
+                let result = tokio::time::timeout(
+                    job_timeout,
+                    handler
+                        .handle(job, &http_client, &pool)
+                        .instrument(span.clone()),
                 )
-                .execute(&mut tx)
-                .await?;
-            } else {
-                // Job has finished successfully, delete it.
+                .await
+                .unwrap_or_else(|_| {
+                    warn!(id = %job_id, job_type, timeout = ?job_timeout, "job timed out");
+                    Err(anyhow!("job timed out after {:?}", job_timeout))
+                });
+
+                match &result {
+                    Ok(()) => span.record("success", &true),
+                    Err(err) => span.record("error", &tracing::field::display(err)),
+                };
+
+                finish_job(
+                    &pool,
+                    job_id,
+                    attempts,
+                    max_attempts,
+                    &job_description,
+                    result,
+                )
+                .await
+            });
 
-                sqlx::query!("DELETE FROM jobs WHERE id = $1", record.id)
-                    .execute(&mut tx)
-                    .await?;
-            }
+            handles.push((job_id, handle));
         }
 
-        tx.commit().await?;
+        for (job_id, handle) in handles {
+            match handle.await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => error!(%err, id = %job_id, "failed to update the job"),
+                Err(err) => error!(%err, id = %job_id, "job task panicked"),
+            }
+        }
 
         Ok(())
     }
 }
 
+/// Updates the job `job_id` according to the `result` of its run.
+///
+/// A successful job is deleted. A failed job is put back in the queue with an exponential back
+/// off, or moved to the dead letter queue if it failed too many times.
+async fn finish_job(
+    pool: &PgPool,
+    job_id: JobId,
+    attempts: i32,
+    max_attempts: i32,
+    job_description: &str,
+    result: anyhow::Result<()>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    if let Err(err) = result {
+        // Back off exponentially: 1s, 2s, 4s, etc
+        sqlx::query!(
+            r#"
+            UPDATE jobs
+            SET attempts = attempts + 1,
+                next_run_at = now() + interval '1 second' * pow(2, attempts),
+                error = $2,
+                status = 'pending'
+            WHERE id = $1
+            "#,
+            &job_id.0,
+            format!("{:#}", err),
+        )
+        .execute(&mut tx)
+        .await?;
+
+        if attempts + 1 >= max_attempts {
+            error!(
+                %err,
+                id = %job_id,
+                job = %job_description,
+                "job failed to run too many times, moving it to the dead letter queue"
+            );
+
+            move_to_dead_letter(&mut tx, job_id).await?;
+        } else {
+            error!(
+                %err,
+                id = %job_id,
+                job = %job_description,
+                "job failed to run, retrying at a later time"
+            );
+        }
+    } else {
+        // Job has finished successfully, delete it.
+
+        sqlx::query!("DELETE FROM jobs WHERE id = $1", &job_id.0)
+            .execute(&mut tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Puts back in the queue the jobs left running, for example after a crash.
+///
+/// A job is aborted once it runs for longer than `job_timeout`: a job started before that was
+/// interrupted. The more recent ones may still be running in another job runner.
+///
+/// # Errors
+///
+/// This function will return an error if there was a SQL error.
+#[tracing::instrument(name = "Reset running jobs", level = "TRACE", skip(pool))]
+async fn reset_running_jobs(pool: &PgPool, job_timeout: StdDuration) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE jobs SET status = 'pending'
+        WHERE status = 'running' AND started_at < now() - interval '1 second' * $1
+        "#,
+        job_timeout.as_secs_f64(),
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// A [`JobHandler`] runs a single job popped from the queue by the [`JobRunner`].
+#[async_trait::async_trait]
+pub trait JobHandler {
+    async fn handle(
+        &self,
+        job: Job,
+        http_client: &reqwest::Client,
+        pool: &PgPool,
+    ) -> anyhow::Result<()>;
+}
+
+/// The [`JobHandler`] that actually runs every job type.
+pub struct DefaultJobHandler {
+    favicon_cache: FaviconCache,
+    unread_updates: UnreadUpdates,
+    tem_client: Option<tem::Client>,
+}
+
+impl DefaultJobHandler {
+    pub fn new(favicon_cache: FaviconCache, unread_updates: UnreadUpdates) -> Self {
+        Self {
+            favicon_cache,
+            unread_updates,
+            tem_client: None,
+        }
+    }
+
+    /// Sets the [`tem::Client`] used to send emails. Without it the jobs sending emails fail.
+    pub fn with_tem_client(mut self, tem_client: tem::Client) -> Self {
+        self.tem_client = Some(tem_client);
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl JobHandler for DefaultJobHandler {
+    async fn handle(
+        &self,
+        job: Job,
+        http_client: &reqwest::Client,
+        pool: &PgPool,
+    ) -> anyhow::Result<()> {
+        match job {
+            Job::FetchFavicon(data) => {
+                run_fetch_favicon_job(http_client, pool, &self.favicon_cache, data).await
+            }
+            Job::RefreshFeed(data) => {
+                run_refresh_feed_job(http_client, pool, &self.unread_updates, data).await
+            }
+            Job::SendDigest(data) => match &self.tem_client {
+                Some(tem_client) => run_send_digest_job(pool, tem_client, data).await,
+                None => Err(anyhow!("emails are disabled, unable to send the digest")),
+            },
+        }
+    }
+}
+
+/// Builds the HTTP client used by the jobs.
+///
+/// Some feed servers compress their response even if the client didn't ask for it, so
+/// decompression is explicitly enabled for all supported encodings.
+///
+/// The feed URLs come from the users: private addresses are only reached, directly or through a
+/// redirect, if `allow_private_urls` is set.
+fn build_http_client(allow_private_urls: AllowPrivateUrls) -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .redirect(redirect_policy(allow_private_urls))
+        .dns_resolver(dns_resolver(allow_private_urls))
+        .cookie_store(true)
+        .gzip(true)
+        .brotli(true)
+        .deflate(true)
+        .build()
+}
+
 //
 // Define the job types
 //
 
+/// The current version of the schema used to store the job data.
+///
+/// Bump this whenever a job data struct changes in a way that makes existing queued jobs
+/// impossible to deserialize, and add the corresponding migration function.
+const JOB_SCHEMA_VERSION: u32 = 2;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct RefreshFeedJobData {
+pub struct RefreshFeedJobData {
+    schema_version: u32,
     user_id: UserId,
     feed_id: FeedId,
     feed_url: Url,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct FetchFaviconJobData {
+pub struct FetchFaviconJobData {
+    schema_version: u32,
     user_id: UserId,
     feed_id: FeedId,
     site_link: Url,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendDigestJobData {
+    schema_version: u32,
+    user_id: UserId,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(tag = "type")]
-enum Job {
+pub enum Job {
     FetchFavicon(FetchFaviconJobData),
     RefreshFeed(RefreshFeedJobData),
+    SendDigest(SendDigestJobData),
 }
 
 impl Job {
+    /// Returns the name of the type of this [`Job`].
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Job::FetchFavicon(_) => "FetchFavicon",
+            Job::RefreshFeed(_) => "RefreshFeed",
+            Job::SendDigest(_) => "SendDigest",
+        }
+    }
+
     /// Returns the key of this [`Job`].
     ///
     /// The key is a [`Blake2b512`] hash computed on relevant data for each job type.
     ///
     /// This key is used to avoid adding the same job twice in the job queue.
+    /// Jobs are scoped to a user so the key always includes the user id.
     fn key(&self) -> [u8; 64] {
-        let mut hasher = Blake2b512::new();
-
         match self {
-            Job::FetchFavicon(data) => {
-                write!(hasher, "fetch_favicon").unwrap();
+            Job::FetchFavicon(data) => feed_job_key("fetch_favicon", data.user_id, data.feed_id),
+            Job::RefreshFeed(data) => feed_job_key("refresh_feed", data.user_id, data.feed_id),
+            Job::SendDigest(data) => user_job_key("send_digest", data.user_id),
+        }
+    }
+}
 
-                let feed_id_bytes: [u8; 8] = data.feed_id.into();
-                hasher.update(feed_id_bytes);
-            }
-            Job::RefreshFeed(data) => {
-                write!(hasher, "refresh_feed").unwrap();
+/// Returns the key of the job of type `kind` working on the feed `feed_id` of the user `user_id`.
+///
+/// See [`Job::key`].
+fn feed_job_key(kind: &str, user_id: UserId, feed_id: FeedId) -> [u8; 64] {
+    let mut hasher = Blake2b512::new();
 
-                let feed_id_bytes: [u8; 8] = data.feed_id.into();
-                hasher.update(feed_id_bytes);
-            }
+    write!(hasher, "{}", kind).unwrap();
+
+    hasher.update(user_id.0.as_bytes());
+
+    let feed_id_bytes: [u8; 8] = feed_id.into();
+    hasher.update(feed_id_bytes);
+
+    hasher.finalize().into()
+}
+
+/// Returns the key of the job of type `kind` working on the user `user_id` as a whole.
+///
+/// See [`Job::key`].
+fn user_job_key(kind: &str, user_id: UserId) -> [u8; 64] {
+    let mut hasher = Blake2b512::new();
+
+    write!(hasher, "{}", kind).unwrap();
+
+    hasher.update(user_id.0.as_bytes());
+
+    hasher.finalize().into()
+}
+
+impl fmt::Display for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Job::FetchFavicon(data) => write!(f, "FetchFavicon {{ feed_id={} }}", data.feed_id),
+            Job::RefreshFeed(data) => write!(
+                f,
+                "RefreshFeed {{ feed_id={}, url={} }}",
+                data.feed_id, data.feed_url
+            ),
+            Job::SendDigest(data) => write!(f, "SendDigest {{ user_id={} }}", data.user_id),
         }
+    }
+}
+
+/// Returns the schema version of the raw job data `value`.
+///
+/// Jobs stored before the schema was versioned don't have a `schema_version` field: they're
+/// version 1.
+fn job_schema_version(value: &serde_json::Value) -> u64 {
+    value
+        .get("schema_version")
+        .and_then(serde_json::Value::as_u64)
+        .unwrap_or(1)
+}
 
-        hasher.finalize().into()
+/// Migrates the raw job data `value` from the version 1 schema to the version 2 schema.
+///
+/// Version 2 only adds the `schema_version` field.
+fn migrate_job_v1_to_v2(mut value: serde_json::Value) -> serde_json::Value {
+    if let Some(object) = value.as_object_mut() {
+        object.insert("schema_version".to_string(), json!(2));
     }
+    value
 }
 
 //
@@ -237,7 +601,7 @@ pub enum PostError {
     SQLx(#[from] sqlx::Error),
 }
 
-type PostResult = Result<JobId, PostError>;
+type PostResult = Result<Option<JobId>, PostError>;
 
 pub async fn post_fetch_favicon_job<'e, E>(
     executor: E,
@@ -251,6 +615,7 @@ where
     post_job(
         executor,
         Job::FetchFavicon(FetchFaviconJobData {
+            schema_version: JOB_SCHEMA_VERSION,
             user_id,
             feed_id,
             site_link,
@@ -271,6 +636,7 @@ where
     post_job(
         executor,
         Job::RefreshFeed(RefreshFeedJobData {
+            schema_version: JOB_SCHEMA_VERSION,
             user_id,
             feed_id,
             feed_url,
@@ -279,151 +645,683 @@ where
     .await
 }
 
+/// Add a refresh feed job for each feed in `feeds`, in a single batch.
+///
+/// Like [`post_refresh_feed_job`] a job isn't added if the same one is already in the queue.
+/// Only the ids of the jobs actually added are returned.
+pub async fn post_refresh_feed_jobs<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feeds: impl IntoIterator<Item = (FeedId, Url)>,
+) -> Result<Vec<JobId>, PostError>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let jobs = feeds
+        .into_iter()
+        .map(|(feed_id, feed_url)| {
+            Job::RefreshFeed(RefreshFeedJobData {
+                schema_version: JOB_SCHEMA_VERSION,
+                user_id,
+                feed_id,
+                feed_url,
+            })
+        })
+        .collect();
+
+    post_jobs_batch(executor, jobs).await
+}
+
 /// Add a job to the job queue.
 ///
-/// Each job has a key associated
+/// Each job has a key associated, see [`Job::key`]. A job is not added if a job with the same key
+/// is already in the queue or in the dead letter queue: a job which failed too many times is
+/// only run again if an admin requeues it.
+///
+/// Returns the id of the job if it was added.
 ///
 /// # Errors
 ///
-/// This function will return an error if .
+/// This function will return an error if there was a SQL error.
 #[tracing::instrument(
         name = "Add job",
         skip(executor, job),
         fields(
             id = tracing::field::Empty,
+            job = %job,
         ),
     )]
-async fn post_job<'e, E>(executor: E, job: Job) -> Result<JobId, PostError>
+async fn post_job<'e, E>(executor: E, job: Job) -> PostResult
 where
     E: sqlx::PgExecutor<'e>,
 {
     let job_id = JobId::default();
 
-    tracing::Span::current().record("id", &tracing::field::display(&job_id));
-
-    sqlx::query!(
+    let record = sqlx::query!(
         r#"
-            INSERT INTO jobs(id, key, data) VALUES($1, $2, $3)
+            INSERT INTO jobs(id, key, data)
+            SELECT $1::uuid, $2::bytea, $3::jsonb
+            WHERE NOT EXISTS (SELECT 1 FROM dead_letter_jobs WHERE key = $2)
             ON CONFLICT DO NOTHING
+            RETURNING id
             "#,
         &job_id.0,
         &job.key(),
-        json!(job)
+        // Serialize directly to the wire format, without an intermediate serde_json::Value
+        Json(&job) as _,
     )
-    .execute(executor)
+    .fetch_optional(executor)
     .await?;
 
+    let job_id = record.map(|record| JobId(record.id));
+    if let Some(job_id) = &job_id {
+        tracing::Span::current().record("id", &tracing::field::display(job_id));
+    }
+
     Ok(job_id)
 }
 
-/// Add as many as `remaining` jobs to fetch the favicon of a feed.
+/// Add all `jobs` to the job queue using a single query.
+///
+/// Like with [`post_job`], a job whose key is already in the queue or in the dead letter queue
+/// is not added. Returns the ids of the jobs added.
 ///
 /// # Errors
 ///
-/// This function will return an error if there was an error adding a job to the queue
+/// This function will return an error if there was a SQL error.
 #[tracing::instrument(
-    name = "Add fetch favicons jobs",
-    level = "TRACE",
-    skip(pool, remaining)
-)]
-async fn create_fetch_favicons_jobs(pool: &PgPool, remaining: &mut usize) -> anyhow::Result<()> {
+        name = "Add jobs batch",
+        skip(executor, jobs),
+        fields(
+            count = jobs.len(),
+        ),
+    )]
+async fn post_jobs_batch<'e, E>(executor: E, jobs: Vec<Job>) -> Result<Vec<JobId>, PostError>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let ids: Vec<Uuid> = jobs.iter().map(|_| JobId::default().0).collect();
+    let keys: Vec<Vec<u8>> = jobs.iter().map(|job| job.key().to_vec()).collect();
+    let data: Vec<Json<&Job>> = jobs.iter().map(Json).collect();
+
     let records = sqlx::query!(
         r#"
-            SELECT user_id, id, site_link
-            FROM feeds f
-            WHERE has_favicon IS NULL
-            LIMIT $1
+            INSERT INTO jobs(id, key, data)
+            SELECT j.id, j.key, j.data
+            FROM UNNEST($1::uuid[], $2::bytea[], $3::jsonb[]) AS j(id, key, data)
+            WHERE NOT EXISTS (SELECT 1 FROM dead_letter_jobs d WHERE d.key = j.key)
+            ON CONFLICT DO NOTHING
+            RETURNING id
             "#,
-        *remaining as i64,
+        &ids[..],
+        &keys[..],
+        &data[..] as _,
     )
-    .fetch_all(pool)
+    .fetch_all(executor)
     .await?;
 
-    let mut tx = pool.begin().await?;
-
-    for record in records {
-        let user_id = UserId(record.user_id);
-        let feed_id = FeedId(record.id);
-        let site_link = Url::parse(&record.site_link)?;
-
-        post_job(
-            &mut tx,
-            Job::FetchFavicon(FetchFaviconJobData {
-                user_id,
-                feed_id,
-                site_link,
-            }),
-        )
-        .await?;
-    }
+    Ok(records.into_iter().map(|record| JobId(record.id)).collect())
+}
 
-    tx.commit().await?;
+/// Cancel the job `job_id` if it's still pending.
+///
+/// Returns true if the job was deleted from the queue; a job that already failed is left alone.
+///
+/// # Errors
+///
+/// This function will return an error if there was a SQL error.
+#[tracing::instrument(name = "Cancel job", skip(executor))]
+pub async fn cancel_job<'e, E>(executor: E, job_id: JobId) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM jobs WHERE id = $1 AND status = 'pending'
+        "#,
+        &job_id.0,
+    )
+    .execute(executor)
+    .await?;
 
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
 
-#[tracing::instrument(
-    name = "Run refresh feed job",
-    skip(http_client, pool, data),
-    fields(
-        feed_id = %data.feed_id,
-        feed_url = %data.feed_url,
+/// Returns the ids of the pending jobs working on the feed `feed_id` of the user `user_id`.
+///
+/// The jobs are found using their key, see [`Job::key`].
+///
+/// # Errors
+///
+/// This function will return an error if there was a SQL error.
+#[tracing::instrument(name = "Get pending feed jobs", skip(executor))]
+pub async fn get_pending_feed_jobs<'e, E>(
+    executor: E,
+    user_id: UserId,
+    feed_id: FeedId,
+) -> Result<Vec<JobId>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let keys: Vec<Vec<u8>> = ["fetch_favicon", "refresh_feed"]
+        .into_iter()
+        .map(|kind| feed_job_key(kind, user_id, feed_id).to_vec())
+        .collect();
+
+    let records = sqlx::query!(
+        r#"
+        SELECT id FROM jobs WHERE key = ANY($1) AND status = 'pending'
+        "#,
+        &keys[..],
     )
-)]
-async fn run_refresh_feed_job(
-    http_client: &reqwest::Client,
-    pool: &PgPool,
-    data: RefreshFeedJobData,
-) -> anyhow::Result<()> {
-    let response_bytes = fetch_bytes(http_client, &data.feed_url)
-        .await
-        .map_err(Into::<anyhow::Error>::into)?;
+    .fetch_all(executor)
+    .await?;
 
-    // 1) Try to parse as a feed
-    let (feed, feed_entries) = {
-        let mut raw_feed =
-            feed_rs::parser::parse(&response_bytes[..]).map_err(Into::<anyhow::Error>::into)?;
-        let raw_entries = std::mem::take(&mut raw_feed.entries);
+    Ok(records.into_iter().map(|record| JobId(record.id)).collect())
+}
 
-        (
-            ParsedFeed::from_raw_feed(&data.feed_url, raw_feed),
-            raw_entries,
+/// Deletes all the jobs of the user `user_id`, including the ones in the dead letter queue.
+///
+/// Returns the number of deleted jobs.
+///
+/// # Errors
+///
+/// This function will return an error if there was a SQL error.
+#[tracing::instrument(name = "Delete user jobs", skip(executor))]
+pub async fn delete_user_jobs<'e, E>(executor: E, user_id: UserId) -> Result<u64, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let record = sqlx::query!(
+        r#"
+        WITH deleted_jobs AS (
+          DELETE FROM jobs WHERE data->>'user_id' = $1
+          RETURNING id
+        ), deleted_dead_letter_jobs AS (
+          DELETE FROM dead_letter_jobs WHERE data->>'user_id' = $1
+          RETURNING id
         )
-    };
+        SELECT
+          (SELECT count(*) FROM deleted_jobs) +
+          (SELECT count(*) FROM deleted_dead_letter_jobs) AS "count!"
+        "#,
+        user_id.0.to_string(),
+    )
+    .fetch_one(executor)
+    .await?;
 
-    event!(
-        Level::INFO,
-        title = %feed.title,
-        entries = %feed_entries.len(),
-        "found a raw feed",
-    );
+    Ok(record.count as u64)
+}
+
+/// Moves the job `job_id` from the queue to the dead letter queue.
+///
+/// This is used for jobs that failed too many times: they are never run again unless an admin
+/// requeues them with [`requeue_dead_letter_job`]. The job keeps its key while it's in the dead
+/// letter queue so that the same job can't be added again, see [`post_job`].
+///
+/// # Errors
+///
+/// This function will return an error if there was a SQL error.
+#[tracing::instrument(name = "Move to dead letter", skip(executor))]
+pub async fn move_to_dead_letter<'e, E>(executor: E, job_id: JobId) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        r#"
+        WITH deleted AS (
+          DELETE FROM jobs WHERE id = $1
+          RETURNING id, data, created_at, attempts, error, key
+        )
+        INSERT INTO dead_letter_jobs(id, data, created_at, attempts, error, key)
+        SELECT id, data, created_at, attempts, error, key FROM deleted
+        ON CONFLICT (key) DO UPDATE
+        SET id = excluded.id,
+            data = excluded.data,
+            created_at = excluded.created_at,
+            attempts = excluded.attempts,
+            error = excluded.error,
+            failed_at = now()
+        "#,
+        &job_id.0,
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// A job which failed too many times and was moved to the dead letter queue.
+pub struct DeadLetterJob {
+    pub id: JobId,
+    pub data: serde_json::Value,
+    pub created_at: time::OffsetDateTime,
+    pub failed_at: time::OffsetDateTime,
+    pub attempts: i32,
+    pub error: Option<String>,
+}
+
+/// Returns all the jobs in the dead letter queue, the most recently failed first.
+///
+/// # Errors
+///
+/// This function will return an error if there was a SQL error.
+#[tracing::instrument(name = "Get dead letter jobs", skip(executor))]
+pub async fn get_dead_letter_jobs<'e, E>(executor: E) -> Result<Vec<DeadLetterJob>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let records = sqlx::query!(
+        r#"
+        SELECT id, data, created_at, failed_at, attempts, error
+        FROM dead_letter_jobs
+        ORDER BY failed_at DESC
+        "#,
+    )
+    .fetch_all(executor)
+    .await?;
+
+    let jobs = records
+        .into_iter()
+        .map(|record| DeadLetterJob {
+            id: JobId(record.id),
+            data: record.data,
+            created_at: record.created_at,
+            failed_at: record.failed_at,
+            attempts: record.attempts,
+            error: record.error,
+        })
+        .collect();
+
+    Ok(jobs)
+}
+
+/// Moves the job `job_id` from the dead letter queue back to the queue, as a new pending job.
+///
+/// Returns false if there's no such job in the dead letter queue.
+/// If an identical job is already in the queue, the dead letter job is simply dropped.
+///
+/// # Errors
+///
+/// This function will return an error if there was a SQL error.
+#[tracing::instrument(name = "Requeue dead letter job", skip(executor))]
+pub async fn requeue_dead_letter_job<'e, E>(executor: E, job_id: JobId) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let record = sqlx::query!(
+        r#"
+        WITH deleted AS (
+          DELETE FROM dead_letter_jobs WHERE id = $1
+          RETURNING id, data, created_at, key
+        ), inserted AS (
+          INSERT INTO jobs(id, data, created_at, key, attempts, status)
+          SELECT id, data, created_at, key, 0, 'pending' FROM deleted
+          ON CONFLICT (key) DO NOTHING
+        )
+        SELECT count(*) AS "count!" FROM deleted
+        "#,
+        &job_id.0,
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(record.count > 0)
+}
+
+/// Add as many as `remaining` jobs to fetch the favicon of a feed, the oldest feeds first.
+///
+/// Feeds whose fetch favicon job is already queued or in the dead letter queue are skipped.
+/// `remaining` is decremented by the number of jobs added.
+///
+/// # Errors
+///
+/// This function will return an error if there was an error adding a job to the queue
+#[tracing::instrument(
+    name = "Add fetch favicons jobs",
+    level = "TRACE",
+    skip(pool, remaining)
+)]
+async fn create_fetch_favicons_jobs(pool: &PgPool, remaining: &mut usize) -> anyhow::Result<()> {
+    if *remaining == 0 {
+        return Ok(());
+    }
+
+    let records = sqlx::query!(
+        r#"
+            SELECT user_id, id, site_link
+            FROM feeds f
+            WHERE has_favicon IS NULL
+            AND NOT EXISTS (
+              SELECT 1 FROM jobs j
+              WHERE j.data @> jsonb_build_object('type', 'FetchFavicon', 'feed_id', f.id)
+            )
+            AND NOT EXISTS (
+              SELECT 1 FROM dead_letter_jobs d
+              WHERE d.data @> jsonb_build_object('type', 'FetchFavicon', 'feed_id', f.id)
+            )
+            ORDER BY added_at ASC
+            LIMIT $1
+            "#,
+        *remaining as i64,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut tx = pool.begin().await?;
+
+    for record in records {
+        let user_id = UserId(record.user_id);
+        let feed_id = FeedId(record.id);
+        let site_link = Url::parse(&record.site_link)?;
+
+        if post_fetch_favicon_job(&mut tx, user_id, feed_id, site_link)
+            .await?
+            .is_some()
+        {
+            *remaining -= 1;
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Add a refresh feed job for every feed not refreshed in the last `refresh_interval`.
+///
+/// Feeds never refreshed come first, then the ones refreshed the longest time ago. Feeds whose
+/// refresh job is already queued or in the dead letter queue are skipped: they would take the
+/// place of the other feeds without adding anything. At most `remaining` jobs are added and
+/// `remaining` is decremented accordingly.
+///
+/// # Errors
+///
+/// This function will return an error if there was an error adding a job to the queue
+#[tracing::instrument(name = "Add refresh feed jobs", level = "TRACE", skip(pool, remaining))]
+async fn create_refresh_feed_jobs(
+    pool: &PgPool,
+    remaining: &mut usize,
+    refresh_interval: StdDuration,
+) -> anyhow::Result<()> {
+    if *remaining == 0 {
+        return Ok(());
+    }
+
+    let records = sqlx::query!(
+        r#"
+            SELECT user_id, id, url
+            FROM feeds f
+            WHERE (
+              last_refreshed_at IS NULL
+              OR last_refreshed_at < now() - interval '1 second' * $1
+            )
+            AND NOT EXISTS (
+              SELECT 1 FROM jobs j
+              WHERE j.data @> jsonb_build_object('type', 'RefreshFeed', 'feed_id', f.id)
+            )
+            AND NOT EXISTS (
+              SELECT 1 FROM dead_letter_jobs d
+              WHERE d.data @> jsonb_build_object('type', 'RefreshFeed', 'feed_id', f.id)
+            )
+            ORDER BY last_refreshed_at ASC NULLS FIRST
+            LIMIT $2
+            "#,
+        refresh_interval.as_secs_f64(),
+        *remaining as i64,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut tx = pool.begin().await?;
+
+    for record in records {
+        let user_id = UserId(record.user_id);
+        let feed_id = FeedId(record.id);
+        let feed_url = Url::parse(&record.url)?;
+
+        if post_refresh_feed_job(&mut tx, user_id, feed_id, feed_url)
+            .await?
+            .is_some()
+        {
+            *remaining -= 1;
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Returns the time from which the digest of the day of `now` is sent, `send_hour` UTC.
+fn digest_send_time(now: OffsetDateTime, send_hour: u8) -> OffsetDateTime {
+    let send_time = Time::from_hms(send_hour.min(23), 0, 0).expect("hour should be valid");
 
-    // 2) Process all entries
+    now.to_offset(time::UtcOffset::UTC).replace_time(send_time)
+}
+
+/// Add a send digest job for every user who didn't get the digest of the day of `now` yet.
+///
+/// Nothing is added before the send time of the day, see [`digest_send_time`]. Users whose send
+/// digest job is already queued or in the dead letter queue are skipped. At most `remaining` jobs
+/// are added and `remaining` is decremented accordingly.
+///
+/// # Errors
+///
+/// This function will return an error if there was an error adding a job to the queue
+#[tracing::instrument(name = "Add send digest jobs", level = "TRACE", skip(pool, remaining))]
+async fn create_digest_jobs(
+    pool: &PgPool,
+    remaining: &mut usize,
+    send_hour: u8,
+    now: OffsetDateTime,
+) -> anyhow::Result<()> {
+    let send_time = digest_send_time(now, send_hour);
+    if *remaining == 0 || now < send_time {
+        return Ok(());
+    }
+
+    let records = sqlx::query!(
+        r#"
+            SELECT id
+            FROM users u
+            WHERE (digest_last_sent_at IS NULL OR digest_last_sent_at < $1)
+            AND NOT EXISTS (
+              SELECT 1 FROM jobs j
+              WHERE j.data @> jsonb_build_object('type', 'SendDigest', 'user_id', u.id)
+            )
+            AND NOT EXISTS (
+              SELECT 1 FROM dead_letter_jobs d
+              WHERE d.data @> jsonb_build_object('type', 'SendDigest', 'user_id', u.id)
+            )
+            ORDER BY digest_last_sent_at ASC NULLS FIRST
+            LIMIT $2
+            "#,
+        send_time,
+        *remaining as i64,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut tx = pool.begin().await?;
+
+    for record in records {
+        let job = Job::SendDigest(SendDigestJobData {
+            schema_version: JOB_SCHEMA_VERSION,
+            user_id: UserId(record.id),
+        });
+
+        if post_job(&mut tx, job).await?.is_some() {
+            *remaining -= 1;
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Deletes the jobs moved to the dead letter queue more than `retention_days` days ago.
+///
+/// Returns the number of deleted jobs.
+///
+/// Successful jobs are deleted right after running and failed jobs are retried until they're
+/// moved to the dead letter queue, so that's the only place where jobs pile up. Once deleted, the
+/// key of a job is free again and the same job can be added back to the queue.
+#[tracing::instrument(name = "Cleanup stale jobs", level = "TRACE", skip(pool))]
+pub async fn cleanup_stale_jobs(pool: &PgPool, retention_days: u32) -> anyhow::Result<u64> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM dead_letter_jobs
+        WHERE failed_at < now() - make_interval(days => $1)
+        "#,
+        retention_days as i32,
+    )
+    .execute(pool)
+    .await?;
+
+    let deleted = result.rows_affected();
+    if deleted > 0 {
+        info!(deleted, retention_days, "deleted stale jobs");
+    }
+
+    Ok(deleted)
+}
+
+#[tracing::instrument(
+    name = "Run refresh feed job",
+    skip(http_client, pool, unread_updates, data),
+    fields(
+        feed_id = %data.feed_id,
+        feed_url = %data.feed_url,
+    )
+)]
+async fn run_refresh_feed_job(
+    http_client: &reqwest::Client,
+    pool: &PgPool,
+    unread_updates: &UnreadUpdates,
+    data: RefreshFeedJobData,
+) -> anyhow::Result<()> {
+    // 1) Fetch the feed, only if it changed since the last fetch
+    //
+    // The feed may have been deleted after the job was added: there's nothing left to refresh.
+    // If it's deleted while the job runs, storing the entries fails; the next attempt ends here.
+
+    let validators = match get_feed_cache_validators(pool, data.user_id, &data.feed_id).await? {
+        Some(validators) => validators,
+        None => {
+            event!(Level::INFO, "feed deleted, nothing to refresh");
+            return Ok(());
+        }
+    };
+
+    let mut request = http_client
+        .get(data.feed_url.to_string())
+        .timeout(REFRESH_FEED_FETCH_TIMEOUT);
+    if let Some(etag) = &validators.etag {
+        request = request.header(header::IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &validators.last_modified {
+        request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+    }
+
+    let response = request.send().await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        event!(Level::INFO, "feed not modified");
+        set_feed_last_refreshed_at(pool, data.user_id, &data.feed_id).await?;
+        return Ok(());
+    }
+
+    let header_value = |name: header::HeaderName| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string)
+    };
+    let validators = FeedCacheValidators {
+        etag: header_value(header::ETAG),
+        last_modified: header_value(header::LAST_MODIFIED),
+    };
+
+    let response_bytes = response.bytes().await?;
+
+    // 2) Try to parse as a feed
+    let (feed, feed_entries) = match parse_json_feed(&data.feed_url, &response_bytes[..]) {
+        Some(json_feed) => json_feed,
+        None => {
+            let mut raw_feed =
+                parse_raw_feed(&response_bytes[..]).map_err(Into::<anyhow::Error>::into)?;
+            let raw_entries = std::mem::take(&mut raw_feed.entries);
+
+            (
+                ParsedFeed::from_raw_feed(&data.feed_url, raw_feed),
+                raw_entries
+                    .into_iter()
+                    .map(ParsedFeedEntry::from_raw_feed_entry)
+                    .collect::<Vec<_>>(),
+            )
+        }
+    };
+
+    event!(
+        Level::INFO,
+        title = %feed.title,
+        entries = %feed_entries.len(),
+        "found a raw feed",
+    );
+
+    // 3) Update the feed metadata and process all entries
     //
     // For every entry we check if it already exists in the database; to do that we use the
-    // `external_id` field which maps to the `id` field of the [`feed_rs::model::Entry`] struct.
+    // `external_id` field which maps to the `id` field of the [`feed_rs::model::Entry`] struct
+    // or of the JSON Feed item.
     // If the entry doesn't exist we insert it.
 
     let mut tx = pool.begin().await?;
 
-    for entry in feed_entries {
-        let entry = ParsedFeedEntry::from_raw_feed_entry(entry);
+    update_feed_metadata(&mut tx, data.user_id, &data.feed_id, &feed).await?;
+    set_feed_cache_validators(&mut tx, data.user_id, &data.feed_id, &validators).await?;
+
+    let mut inserted: i32 = 0;
 
-        if feed_entry_with_external_id_exists(&mut tx, data.user_id, &entry.external_id).await? {
+    for mut entry in feed_entries {
+        // Relative links in the summary are relative to the entry, they must be absolute to
+        // work in the reader.
+        let base_url = entry.url.clone().unwrap_or_else(|| data.feed_url.clone());
+        entry.summary = make_links_absolute(&entry.summary, &base_url);
+
+        if feed_entry_with_external_id_exists(
+            &mut tx,
+            data.user_id,
+            &data.feed_id,
+            &entry.external_id,
+        )
+        .await?
+        {
             continue;
         }
 
         insert_feed_entry(&mut tx, &data.feed_id, entry).await?;
+        inserted += 1;
     }
 
+    set_feed_last_refreshed_at(&mut tx, data.user_id, &data.feed_id).await?;
+
     tx.commit().await?;
 
+    // 4) Notify the new unread entries, only once they're committed
+
+    if inserted > 0 {
+        unread_updates.send(data.user_id, inserted);
+    }
+
     Ok(())
 }
 
 #[tracing::instrument(
     name = "Run fetch favicon job",
-    skip(http_client, pool, data),
+    skip(http_client, pool, favicon_cache, data),
     fields(
         feed_id = %data.feed_id,
         site_link = %data.site_link,
@@ -432,10 +1330,12 @@ async fn run_refresh_feed_job(
 async fn run_fetch_favicon_job(
     http_client: &reqwest::Client,
     pool: &PgPool,
+    favicon_cache: &FaviconCache,
     data: FetchFaviconJobData,
 ) -> anyhow::Result<()> {
     let FetchFaviconJobData {
-        user_id: _,
+        schema_version: _,
+        user_id,
         feed_id,
         site_link,
     } = data;
@@ -447,82 +1347,279 @@ async fn run_fetch_favicon_job(
     if let Some(url) = favicon_url {
         // Found the favicon URL in the document, fetch it and store it.
 
-        let favicon = fetch_bytes(http_client, &url).await?;
-        set_favicon(pool, &feed_id, Some(&favicon)).await?;
+        let favicon =
+            fetch_bytes_with_timeout(http_client, &url, Some(FETCH_FAVICON_FETCH_TIMEOUT)).await?;
+        set_favicon(
+            pool,
+            favicon_cache,
+            user_id,
+            &feed_id,
+            valid_favicon(&favicon),
+        )
+        .await?;
     } else {
         // No favicon URL in the document: try to fetch the relatively standard one at favicon.ico
 
         let favicon_url = site_link.join("/favicon.ico")?;
-        let response = http_client.get(favicon_url.to_string()).send().await?;
+        let response = http_client
+            .get(favicon_url.to_string())
+            .timeout(FETCH_FAVICON_FETCH_TIMEOUT)
+            .send()
+            .await?;
 
         if response.status().is_success() {
-            // Response is a 200, assume it's a valid favicon
-            //
-            // TODO(vincent): at some point we should try to detect an image in this
+            // Response is a 200, but it might still be a HTML page: only keep actual images
 
             let response_bytes = response.bytes().await?;
-            set_favicon(pool, &feed_id, Some(&response_bytes)).await?;
+            set_favicon(
+                pool,
+                favicon_cache,
+                user_id,
+                &feed_id,
+                valid_favicon(&response_bytes),
+            )
+            .await?;
         } else {
             // No favicon for you !
 
-            set_favicon(pool, &feed_id, None).await?;
+            set_favicon(pool, favicon_cache, user_id, &feed_id, None).await?;
         }
     }
 
     Ok(())
 }
 
+/// Returns `data` if it's a valid favicon, `None` otherwise.
+///
+/// Servers sometimes answer with a HTML error page instead of the favicon, this must not be
+/// stored as the favicon.
+fn valid_favicon(data: &[u8]) -> Option<&[u8]> {
+    if is_valid_favicon(data) {
+        Some(data)
+    } else {
+        event!(Level::WARN, "data is not a valid favicon, ignoring it");
+        None
+    }
+}
+
+/// Stores the favicon `data` of the feed `feed_id`, along with its detected MIME type.
+///
+/// The favicon previously cached in `favicon_cache`, if any, is invalidated.
 #[tracing::instrument(
     name = "Set favicon",
-    skip(pool, data),
+    skip(pool, favicon_cache, data),
     fields(
         feed_id = %feed_id,
     ),
 )]
-async fn set_favicon(pool: &PgPool, feed_id: &FeedId, data: Option<&[u8]>) -> anyhow::Result<()> {
+async fn set_favicon(
+    pool: &PgPool,
+    favicon_cache: &FaviconCache,
+    user_id: UserId,
+    feed_id: &FeedId,
+    data: Option<&[u8]>,
+) -> anyhow::Result<()> {
+    let mime = data.map(detect_favicon_mime);
+
     sqlx::query!(
         r#"
         UPDATE feeds
-        SET site_favicon = $1, has_favicon = $2 WHERE id = $3
+        SET site_favicon = $1, site_favicon_mime = $2, has_favicon = $3 WHERE id = $4
         "#,
         data,
+        mime,
         data.is_some(),
         &feed_id.0,
     )
     .execute(pool)
     .await?;
 
+    favicon_cache.invalidate(user_id, feed_id);
+
     Ok(())
 }
 
-/// Create a new feed entry in the database for this `user_id`.
+/// The maximum number of entries listed in a digest.
+const DIGEST_MAX_ENTRIES: usize = 100;
+
+/// A feed and its unread entries, as listed in a digest.
+struct DigestFeed {
+    title: String,
+    entries: Vec<FeedEntry>,
+}
+
+#[derive(askama::Template)]
+#[template(path = "digest_email.html.j2")]
+struct DigestEmailTemplate<'a> {
+    unread_count: i64,
+    listed_count: usize,
+    /// True if there are more unread entries than listed.
+    truncated: bool,
+    feeds: &'a [DigestFeed],
+}
+
+/// Returns the subject of a digest for `unread_count` unread entries.
+fn digest_subject(unread_count: i64) -> String {
+    match unread_count {
+        1 => "Your Servare digest: 1 unread entry".to_string(),
+        n => format!("Your Servare digest: {} unread entries", n),
+    }
+}
+
+/// Returns the plain text content of a digest listing the `listed_count` entries of `feeds`,
+/// out of `unread_count` unread entries.
+fn digest_text(unread_count: i64, listed_count: usize, feeds: &[DigestFeed]) -> String {
+    let mut text = format!("You have {} unread entries.\n", unread_count);
+    if listed_count as i64 != unread_count {
+        text.push_str(&format!(
+            "Only the {} most recent ones are listed.\n",
+            listed_count
+        ));
+    }
+
+    for feed in feeds {
+        text.push_str(&format!("\n{}\n", feed.title));
+        for entry in &feed.entries {
+            match &entry.url {
+                Some(url) => text.push_str(&format!("  - {}: {}\n", entry.title, url)),
+                None => text.push_str(&format!("  - {}\n", entry.title)),
+            }
+        }
+    }
+
+    text
+}
+
+/// Sends the digest of the unread entries of the last day to the user `data.user_id`.
+///
+/// Nothing is sent if there's no unread entry, but the digest is still recorded as sent so that
+/// the user isn't considered again before the next day.
+///
+/// The digest is recorded as sent before sending the email: a failure after the email is sent
+/// would otherwise make the job send it again. If sending fails the job is retried and sends it
+/// regardless.
 #[tracing::instrument(
-    name = "Insert feed entry",
-    skip(executor, entry),
+    name = "Run send digest job",
+    skip(pool, tem_client, data),
     fields(
-        feed_id = %feed_id,
-        url = tracing::field::Empty,
+        user_id = %data.user_id,
     )
 )]
-async fn insert_feed_entry<'e, E>(
-    executor: E,
-    feed_id: &FeedId,
+async fn run_send_digest_job(
+    pool: &PgPool,
+    tem_client: &tem::Client,
+    data: SendDigestJobData,
+) -> anyhow::Result<()> {
+    let user = match get_user(pool, data.user_id).await? {
+        Some(user) => user,
+        None => {
+            event!(
+                Level::INFO,
+                "user doesn't exist anymore, skipping the digest"
+            );
+            return Ok(());
+        }
+    };
+
+    // 1) Group the unread entries of the last day by feed
+    //
+    // Only the most recent entries are listed, but the digest tells how many there are in total.
+
+    let filter = UnreadFilter::last_days(Some(1), DIGEST_MAX_ENTRIES);
+    let mut entries = get_unread_entries(pool, user.id, filter).await?;
+    let listed_count = entries.len();
+    let unread_count = get_unread_count_since(pool, user.id, filter.since).await?;
+
+    let mut feeds = Vec::new();
+    for feed in get_all_feeds(pool, user.id, FeedOrder::default()).await? {
+        let (feed_entries, other_entries): (Vec<_>, Vec<_>) = entries
+            .into_iter()
+            .partition(|entry| entry.feed_id == feed.id);
+        entries = other_entries;
+
+        if !feed_entries.is_empty() {
+            feeds.push(DigestFeed {
+                title: feed.title,
+                entries: feed_entries,
+            });
+        }
+    }
+
+    // 2) Record the digest as sent, then send the email
+
+    set_user_digest_last_sent_at(pool, user.id, OffsetDateTime::now_utc()).await?;
+
+    if listed_count > 0 {
+        let html = DigestEmailTemplate {
+            unread_count,
+            listed_count,
+            truncated: listed_count as i64 != unread_count,
+            feeds: &feeds,
+        }
+        .render()?;
+        let text = digest_text(unread_count, listed_count, &feeds);
+
+        tem_client
+            .send_email_single(&user.email, &digest_subject(unread_count), &html, &text)
+            .await?;
+    } else {
+        event!(Level::DEBUG, "no unread entry, not sending the digest");
+    }
+
+    Ok(())
+}
+
+/// Records that the digest of the user `user_id` was sent at `sent_at`.
+#[tracing::instrument(name = "Set user digest last sent at", skip(pool))]
+async fn set_user_digest_last_sent_at(
+    pool: &PgPool,
+    user_id: UserId,
+    sent_at: OffsetDateTime,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        "UPDATE users SET digest_last_sent_at = $1 WHERE id = $2",
+        sent_at,
+        &user_id.0,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Create a new feed entry in the database for this `user_id`.
+///
+/// The publication date is the one of the feed if there's one, otherwise the current time.
+#[tracing::instrument(
+    name = "Insert feed entry",
+    skip(executor, entry),
+    fields(
+        feed_id = %feed_id,
+        url = tracing::field::Empty,
+    )
+)]
+async fn insert_feed_entry<'e, E>(
+    executor: E,
+    feed_id: &FeedId,
     entry: ParsedFeedEntry,
 ) -> Result<(), sqlx::Error>
 where
     E: sqlx::PgExecutor<'e>,
 {
+    let now = time::OffsetDateTime::now_utc();
+
     sqlx::query!(
         r#"
-        INSERT INTO feed_entries(feed_id, external_id, title, url, created_at, authors, summary)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        INSERT INTO feed_entries(feed_id, external_id, title, url, created_at, published_at, authors, summary)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
         "#,
         &feed_id.0,
         &entry.external_id,
         &entry.title,
         entry.url.as_ref().map(Url::to_string),
-        time::OffsetDateTime::now_utc(), // TODO(vincent): use the correct time
-        &entry.authors,
+        now,
+        entry.published_at.unwrap_or(now),
+        serde_json::json!(entry.authors),
         &entry.summary,
     )
     .execute(executor)
@@ -531,7 +1628,9 @@ where
     Ok(())
 }
 
-/// Check if a feed entry belonging to `user_id` with the given `external_id` already exists.
+/// Check if a feed entry of the feed `feed_id` belonging to `user_id` with the given `external_id` already exists.
+///
+/// The external id is only unique within a single feed: two different feeds can publish entries with the same id.
 ///
 /// # Errors
 ///
@@ -539,6 +1638,7 @@ where
 async fn feed_entry_with_external_id_exists<'e, E>(
     executor: E,
     user_id: UserId,
+    feed_id: &FeedId,
     external_id: &str,
 ) -> Result<bool, sqlx::Error>
 where
@@ -549,9 +1649,10 @@ where
         SELECT fe.id FROM feed_entries fe
         INNER JOIN feeds f ON f.id = fe.feed_id
         INNER JOIN users u ON f.user_id = u.id
-        WHERE u.id = $1 AND fe.external_id = $2
+        WHERE u.id = $1 AND fe.feed_id = $2 AND fe.external_id = $3
         "#,
         &user_id.0,
+        &feed_id.0,
         external_id,
     )
     .fetch_optional(executor)
@@ -560,20 +1661,213 @@ where
     Ok(record.is_some())
 }
 
+// The job queue, the job runner and the job creation look at every job, feed or user
+// in the database, so the tests exercising them or leaving jobs behind use `#[sqlx::test]`
+// to get their own database. The other tests only look at the feed or user they create
+// and share the database of `get_pool`.
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::feed::get_feed_favicon;
-    use crate::tests::{create_feed, create_user, get_pool};
+    use crate::configuration::get_configuration;
+    use crate::feed::{delete_feed, get_feed_favicon};
+    use crate::tests::{create_feed, create_feed_entry, create_user, get_pool};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
     use select::document::Document;
     use select::predicate::Name;
-    use wiremock::matchers::path;
+    use std::sync::Mutex;
+    use wiremock::matchers::{body_partial_json, header, method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[derive(rust_embed::RustEmbed)]
     #[folder = "testdata/"]
     struct TestData;
 
+    /// A [`JobHandler`] which records the jobs it's given without running them.
+    struct TestJobHandler {
+        jobs: Arc<Mutex<Vec<Job>>>,
+        fail: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl JobHandler for TestJobHandler {
+        async fn handle(
+            &self,
+            job: Job,
+            _http_client: &reqwest::Client,
+            _pool: &PgPool,
+        ) -> anyhow::Result<()> {
+            self.jobs.lock().unwrap().push(job);
+
+            if self.fail {
+                Err(anyhow::anyhow!("job failed"))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn v1_job_data_should_be_migrated_and_deserialized() {
+        let user_id = UserId::default();
+
+        let data = json!({
+            "type": "RefreshFeed",
+            "user_id": user_id,
+            "feed_id": 20,
+            "feed_url": "https://example.com/feed.xml",
+        });
+        assert_eq!(1, job_schema_version(&data));
+
+        let data = migrate_job_v1_to_v2(data);
+        assert_eq!(2, job_schema_version(&data));
+
+        let job: Job = serde_json::from_value(data).unwrap();
+        match job {
+            Job::RefreshFeed(data) => {
+                assert_eq!(JOB_SCHEMA_VERSION, data.schema_version);
+                assert_eq!(user_id, data.user_id);
+                assert_eq!(FeedId(20), data.feed_id);
+                assert_eq!("https://example.com/feed.xml", data.feed_url.as_str());
+            }
+            _ => panic!("expected a Job::RefreshFeed"),
+        }
+    }
+
+    #[test]
+    fn job_key_should_depend_on_the_user() {
+        let user_id1 = UserId::default();
+        let user_id2 = UserId::default();
+        let feed_id = FeedId(20);
+
+        let refresh_feed = |user_id| {
+            Job::RefreshFeed(RefreshFeedJobData {
+                schema_version: JOB_SCHEMA_VERSION,
+                user_id,
+                feed_id,
+                feed_url: Url::parse("https://example.com/feed.xml").unwrap(),
+            })
+        };
+        assert_eq!(refresh_feed(user_id1).key(), refresh_feed(user_id1).key());
+        assert_ne!(refresh_feed(user_id1).key(), refresh_feed(user_id2).key());
+
+        let fetch_favicon = |user_id| {
+            Job::FetchFavicon(FetchFaviconJobData {
+                schema_version: JOB_SCHEMA_VERSION,
+                user_id,
+                feed_id,
+                site_link: Url::parse("https://example.com").unwrap(),
+            })
+        };
+        assert_eq!(fetch_favicon(user_id1).key(), fetch_favicon(user_id1).key());
+        assert_ne!(fetch_favicon(user_id1).key(), fetch_favicon(user_id2).key());
+    }
+
+    /// Fetches the job `job_id` from the queue and deserializes it.
+    async fn fetch_job(pool: &PgPool, job_id: &JobId) -> Job {
+        let record = sqlx::query!("SELECT data FROM jobs WHERE id = $1", &job_id.0)
+            .fetch_one(pool)
+            .await
+            .expect("unable to fetch the job");
+
+        serde_json::from_value(record.data).expect("unable to deserialize the job")
+    }
+
+    #[sqlx::test]
+    async fn posted_jobs_should_round_trip(pool: PgPool) {
+        let user_id = create_user(&pool).await;
+        let feed_url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_url = Url::parse("https://example.com").unwrap();
+        let feed_id = create_feed(&pool, user_id, &feed_url, &site_url).await;
+
+        // Single job
+
+        let job_id = post_fetch_favicon_job(&pool, user_id, feed_id, site_url.clone())
+            .await
+            .unwrap()
+            .expect("the job should be added");
+
+        match fetch_job(&pool, &job_id).await {
+            Job::FetchFavicon(data) => {
+                assert_eq!(JOB_SCHEMA_VERSION, data.schema_version);
+                assert_eq!(user_id, data.user_id);
+                assert_eq!(feed_id, data.feed_id);
+                assert_eq!(site_url, data.site_link);
+            }
+            _ => panic!("expected a Job::FetchFavicon"),
+        }
+
+        // The same job isn't added twice
+
+        let job_id_again = post_fetch_favicon_job(&pool, user_id, feed_id, site_url.clone())
+            .await
+            .unwrap();
+        assert!(job_id_again.is_none());
+
+        // Batch of jobs
+
+        let job_ids = post_refresh_feed_jobs(&pool, user_id, [(feed_id, feed_url.clone())])
+            .await
+            .unwrap();
+        assert_eq!(1, job_ids.len());
+
+        match fetch_job(&pool, &job_ids[0]).await {
+            Job::RefreshFeed(data) => {
+                assert_eq!(JOB_SCHEMA_VERSION, data.schema_version);
+                assert_eq!(user_id, data.user_id);
+                assert_eq!(feed_id, data.feed_id);
+                assert_eq!(feed_url, data.feed_url);
+            }
+            _ => panic!("expected a Job::RefreshFeed"),
+        }
+
+        let job_ids_again = post_refresh_feed_jobs(&pool, user_id, [(feed_id, feed_url.clone())])
+            .await
+            .unwrap();
+        assert!(job_ids_again.is_empty());
+    }
+
+    #[test]
+    fn job_should_be_displayed_concisely() {
+        let user_id = UserId::default();
+
+        let job = Job::FetchFavicon(FetchFaviconJobData {
+            schema_version: JOB_SCHEMA_VERSION,
+            user_id,
+            feed_id: FeedId(42),
+            site_link: Url::parse("https://example.com").unwrap(),
+        });
+        assert_eq!("FetchFavicon { feed_id=42 }", job.to_string());
+
+        let job = Job::RefreshFeed(RefreshFeedJobData {
+            schema_version: JOB_SCHEMA_VERSION,
+            user_id,
+            feed_id: FeedId(42),
+            feed_url: Url::parse("https://example.com/feed.xml").unwrap(),
+        });
+        assert_eq!(
+            "RefreshFeed { feed_id=42, url=https://example.com/feed.xml }",
+            job.to_string()
+        );
+        assert_eq!("RefreshFeed", job.type_name());
+
+        let job = Job::SendDigest(SendDigestJobData {
+            schema_version: JOB_SCHEMA_VERSION,
+            user_id,
+        });
+        assert_eq!(
+            format!("SendDigest {{ user_id={} }}", user_id),
+            job.to_string()
+        );
+        assert_eq!("SendDigest", job.type_name());
+    }
+
+    #[test]
+    fn job_id_should_be_displayed_as_its_uuid() {
+        let job_id = JobId::default();
+        assert_eq!(job_id.0.to_string(), job_id.to_string());
+    }
+
     #[tokio::test]
     async fn fetch_favicon_job_should_work_when_link_exists_in_site() {
         let pool = get_pool().await;
@@ -587,7 +1881,7 @@ mod tests {
         let mock_uri = mock_server.uri();
         let mock_url = Url::parse(&mock_uri).unwrap();
 
-        let fake_icon_data: &[u8] = b"\xde\xad\xbe\xef";
+        let fake_icon_data: &[u8] = b"\x00\x00\x01\x00\x01\x00\x10\x10";
 
         Mock::given(path("/icon.png"))
             .respond_with(ResponseTemplate::new(200).set_body_bytes(fake_icon_data))
@@ -616,20 +1910,70 @@ mod tests {
         // Run the job
 
         let data = FetchFaviconJobData {
+            schema_version: JOB_SCHEMA_VERSION,
             user_id,
             feed_id,
             site_link: mock_url,
         };
 
-        run_fetch_favicon_job(&http_client, &pool, data)
+        run_fetch_favicon_job(&http_client, &pool, &FaviconCache::new(10), data)
             .await
             .unwrap();
 
         // Check the result
 
         let favicon = get_feed_favicon(&pool, user_id, &feed_id).await.unwrap();
-        assert!(favicon.is_some());
-        assert_eq!(fake_icon_data, &favicon.unwrap()[..]);
+        let favicon = favicon.expect("favicon should be stored");
+        assert_eq!(fake_icon_data, &favicon.data[..]);
+        assert_eq!("image/x-icon", favicon.mime);
+    }
+
+    #[tokio::test]
+    async fn fetch_favicon_job_should_not_store_a_html_page_as_the_favicon() {
+        let pool = get_pool().await;
+        let http_client = reqwest::Client::new();
+
+        // Setup a mock server without a favicon link whose /favicon.ico is a HTML page
+
+        let mock_server = MockServer::start().await;
+        let mock_url = Url::parse(&mock_server.uri()).unwrap();
+
+        Mock::given(path("/"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw("<html></html>", "text/html"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/favicon.ico"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw("<!DOCTYPE html><html><body>Oops</body></html>", "text/html"),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let user_id = create_user(&pool).await;
+        let feed_id =
+            create_feed(&pool, user_id, &mock_url.join("/feed").unwrap(), &mock_url).await;
+
+        let data = FetchFaviconJobData {
+            schema_version: JOB_SCHEMA_VERSION,
+            user_id,
+            feed_id,
+            site_link: mock_url,
+        };
+
+        run_fetch_favicon_job(&http_client, &pool, &FaviconCache::new(10), data)
+            .await
+            .unwrap();
+
+        let favicon = get_feed_favicon(&pool, user_id, &feed_id).await.unwrap();
+        assert!(favicon.is_none(), "no favicon should be stored");
+
+        let record = sqlx::query!("SELECT has_favicon FROM feeds WHERE id = $1", &feed_id.0)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(Some(false), record.has_favicon);
     }
 
     #[tokio::test]
@@ -663,12 +2007,13 @@ mod tests {
         // Run the job
 
         let data = RefreshFeedJobData {
+            schema_version: JOB_SCHEMA_VERSION,
             user_id,
             feed_id,
             feed_url: mock_url,
         };
 
-        run_refresh_feed_job(&http_client, &pool, data)
+        run_refresh_feed_job(&http_client, &pool, &UnreadUpdates::new(10), data)
             .await
             .unwrap();
 
@@ -693,8 +2038,1168 @@ mod tests {
         let document = Document::from(summary.as_str());
 
         for image in document.find(Name("img")) {
-            let _image_src = image.attr("src").unwrap_or_default();
-            // assert!(image_src.starts_with("http"));
+            let image_src = image.attr("src").unwrap_or_default();
+            assert!(image_src.starts_with("https://tailscale.com/blog/ios-macos-shortcuts/"));
+        }
+    }
+
+    #[tokio::test]
+    async fn refresh_feed_job_should_handle_a_gzip_compressed_feed() {
+        let feed_data = TestData::get("tailscale_rss_feed.xml").unwrap().data;
+
+        let compressed_feed_data = {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&feed_data).unwrap();
+            encoder.finish().unwrap()
+        };
+
+        let pool = get_pool().await;
+        let http_client = build_http_client(AllowPrivateUrls(true)).unwrap();
+
+        // Setup a mock server that responds with a gzip compressed XML feed, even though the
+        // client didn't necessarily ask for it.
+
+        let mock_server = MockServer::start().await;
+        let mock_url = Url::parse(&mock_server.uri()).unwrap();
+
+        Mock::given(path("/feed"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Encoding", "gzip")
+                    .set_body_raw(compressed_feed_data, "application/xml"),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Create a test user and feed
+
+        let user_id = create_user(&pool).await;
+        let feed_url = mock_url.join("/feed").unwrap();
+        let feed_id = create_feed(&pool, user_id, &feed_url, &mock_url).await;
+
+        // Run the job
+
+        let data = RefreshFeedJobData {
+            schema_version: JOB_SCHEMA_VERSION,
+            user_id,
+            feed_id,
+            feed_url,
+        };
+
+        run_refresh_feed_job(&http_client, &pool, &UnreadUpdates::new(10), data)
+            .await
+            .unwrap();
+
+        // Check the entries were stored
+
+        let record = sqlx::query!(
+            r#"
+            SELECT COUNT(*) AS "count!" FROM feed_entries WHERE feed_id = $1
+            "#,
+            &feed_id.0,
+        )
+        .fetch_one(&pool)
+        .await
+        .expect("unable to count the feed entries");
+
+        assert!(record.count > 0);
+    }
+
+    /// Returns the `xmin` system column of the feed `feed_id`.
+    ///
+    /// It changes every time the row is written.
+    async fn feed_row_version(pool: &PgPool, feed_id: &FeedId) -> String {
+        let record = sqlx::query!(
+            r#"SELECT xmin::text AS "xmin!" FROM feeds WHERE id = $1"#,
+            &feed_id.0,
+        )
+        .fetch_one(pool)
+        .await
+        .expect("unable to get the feed row version");
+
+        record.xmin
+    }
+
+    #[tokio::test]
+    async fn refresh_feed_job_should_store_the_cache_validators() {
+        let feed_data = TestData::get("tailscale_rss_feed.xml").unwrap().data;
+
+        let pool = get_pool().await;
+        let http_client = build_http_client(AllowPrivateUrls(true)).unwrap();
+
+        let mock_server = MockServer::start().await;
+        let mock_url = Url::parse(&mock_server.uri()).unwrap();
+
+        Mock::given(path("/feed"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"abcdef\"")
+                    .insert_header("Last-Modified", "Wed, 15 Mar 2023 10:00:00 GMT")
+                    .set_body_raw(feed_data, "application/xml"),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let user_id = create_user(&pool).await;
+        let feed_url = mock_url.join("/feed").unwrap();
+        let feed_id = create_feed(&pool, user_id, &feed_url, &mock_url).await;
+
+        let data = RefreshFeedJobData {
+            schema_version: JOB_SCHEMA_VERSION,
+            user_id,
+            feed_id,
+            feed_url,
+        };
+
+        run_refresh_feed_job(&http_client, &pool, &UnreadUpdates::new(10), data)
+            .await
+            .unwrap();
+
+        let validators = get_feed_cache_validators(&pool, user_id, &feed_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            Some(FeedCacheValidators {
+                etag: Some("\"abcdef\"".to_string()),
+                last_modified: Some("Wed, 15 Mar 2023 10:00:00 GMT".to_string()),
+            }),
+            validators,
+        );
+    }
+
+    #[tokio::test]
+    async fn refresh_feed_job_should_store_the_publication_date_of_the_entries() {
+        let feed_data = TestData::get("tailscale_rss_feed.xml").unwrap().data;
+
+        let pool = get_pool().await;
+        let http_client = build_http_client(AllowPrivateUrls(true)).unwrap();
+
+        let mock_server = MockServer::start().await;
+        let mock_url = Url::parse(&mock_server.uri()).unwrap();
+
+        Mock::given(path("/feed"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(feed_data, "application/xml"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let user_id = create_user(&pool).await;
+        let feed_url = mock_url.join("/feed").unwrap();
+        let feed_id = create_feed(&pool, user_id, &feed_url, &mock_url).await;
+
+        let data = RefreshFeedJobData {
+            schema_version: JOB_SCHEMA_VERSION,
+            user_id,
+            feed_id,
+            feed_url,
+        };
+
+        run_refresh_feed_job(&http_client, &pool, &UnreadUpdates::new(10), data)
+            .await
+            .unwrap();
+
+        let record = sqlx::query!(
+            "SELECT published_at FROM feed_entries WHERE feed_id = $1 AND external_id = $2",
+            &feed_id.0,
+            "https://tailscale.com/blog/pulumi-connecti/",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        // <pubDate>Fri, 16 Dec 2022 21:00:00 +0000</pubDate>
+        assert_eq!(
+            Some(time::OffsetDateTime::from_unix_timestamp(1671224400).unwrap()),
+            record.published_at
+        );
+    }
+
+    async fn feed_last_refreshed_at(
+        pool: &PgPool,
+        feed_id: &FeedId,
+    ) -> Option<time::OffsetDateTime> {
+        sqlx::query!(
+            "SELECT last_refreshed_at FROM feeds WHERE id = $1",
+            &feed_id.0
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+        .last_refreshed_at
+    }
+
+    #[tokio::test]
+    async fn refresh_feed_job_should_record_the_last_refresh() {
+        let feed_data = TestData::get("tailscale_rss_feed.xml").unwrap().data;
+
+        let pool = get_pool().await;
+        let http_client = build_http_client(AllowPrivateUrls(true)).unwrap();
+
+        let mock_server = MockServer::start().await;
+        let mock_url = Url::parse(&mock_server.uri()).unwrap();
+
+        Mock::given(path("/feed"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(feed_data, "application/xml"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let user_id = create_user(&pool).await;
+        let feed_url = mock_url.join("/feed").unwrap();
+        let feed_id = create_feed(&pool, user_id, &feed_url, &mock_url).await;
+
+        assert!(feed_last_refreshed_at(&pool, &feed_id).await.is_none());
+
+        let data = RefreshFeedJobData {
+            schema_version: JOB_SCHEMA_VERSION,
+            user_id,
+            feed_id,
+            feed_url,
+        };
+
+        run_refresh_feed_job(&http_client, &pool, &UnreadUpdates::new(10), data)
+            .await
+            .unwrap();
+
+        assert!(feed_last_refreshed_at(&pool, &feed_id).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn refresh_feed_job_should_do_nothing_if_the_feed_is_deleted() {
+        let pool = get_pool().await;
+        let http_client = build_http_client(AllowPrivateUrls(true)).unwrap();
+
+        let mock_server = MockServer::start().await;
+        let mock_url = Url::parse(&mock_server.uri()).unwrap();
+
+        Mock::given(path("/feed"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let user_id = create_user(&pool).await;
+        let feed_url = mock_url.join("/feed").unwrap();
+        let feed_id = create_feed(&pool, user_id, &feed_url, &mock_url).await;
+
+        assert!(delete_feed(&pool, user_id, &feed_id).await.unwrap());
+
+        let data = RefreshFeedJobData {
+            schema_version: JOB_SCHEMA_VERSION,
+            user_id,
+            feed_id,
+            feed_url,
+        };
+
+        let result = run_refresh_feed_job(&http_client, &pool, &UnreadUpdates::new(10), data).await;
+        assert!(result.is_ok(), "result should be Ok, got {:?}", result);
+    }
+
+    #[sqlx::test]
+    async fn create_fetch_favicons_jobs_should_count_against_the_limit(pool: PgPool) {
+        let user_id = create_user(&pool).await;
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        for name in ["foo", "bar", "baz"] {
+            let feed_url = site_link.join(name).unwrap();
+            create_feed(&pool, user_id, &feed_url, &site_link).await;
+        }
+
+        let mut remaining = 2;
+        create_fetch_favicons_jobs(&pool, &mut remaining)
+            .await
+            .unwrap();
+        assert_eq!(0, remaining);
+
+        // Nothing is left for the refresh feed jobs
+
+        create_refresh_feed_jobs(&pool, &mut remaining, StdDuration::ZERO)
+            .await
+            .unwrap();
+
+        let jobs_count = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM jobs"#)
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+            .count;
+        assert_eq!(2, jobs_count);
+    }
+
+    #[sqlx::test]
+    async fn create_refresh_feed_jobs_should_only_add_jobs_for_stale_feeds(pool: PgPool) {
+        let user_id = create_user(&pool).await;
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let mut feed_ids = Vec::new();
+        for name in ["never", "stale", "fresh"] {
+            let feed_url = site_link.join(name).unwrap();
+            feed_ids.push(create_feed(&pool, user_id, &feed_url, &site_link).await);
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE feeds SET last_refreshed_at = now() - interval '2 hours' WHERE id = $1
+            "#,
+            &feed_ids[1].0,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        set_feed_last_refreshed_at(&pool, user_id, &feed_ids[2])
+            .await
+            .unwrap();
+
+        let mut remaining = 10;
+        create_refresh_feed_jobs(&pool, &mut remaining, StdDuration::from_secs(3600))
+            .await
+            .unwrap();
+        assert_eq!(8, remaining);
+
+        for (feed_id, expected_jobs) in feed_ids.iter().zip([1, 1, 0]) {
+            let jobs = get_pending_feed_jobs(&pool, user_id, *feed_id)
+                .await
+                .unwrap();
+            assert_eq!(expected_jobs, jobs.len(), "feed {}", feed_id);
+        }
+
+        // The limit is honored
+
+        let mut remaining = 0;
+        create_refresh_feed_jobs(&pool, &mut remaining, StdDuration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(0, remaining);
+    }
+
+    #[sqlx::test]
+    async fn create_refresh_feed_jobs_should_skip_feeds_with_a_dead_letter_job(pool: PgPool) {
+        let user_id = create_user(&pool).await;
+        let site_link = Url::parse("https://example.com").unwrap();
+
+        let mut feeds = Vec::new();
+        for name in ["failing", "stale"] {
+            let feed_url = site_link.join(name).unwrap();
+            let feed_id = create_feed(&pool, user_id, &feed_url, &site_link).await;
+            feeds.push((feed_id, feed_url));
+        }
+
+        // The failing feed was never refreshed so it comes first, but its job is dead
+
+        let (failing_feed_id, failing_feed_url) = feeds[0].clone();
+        let job_id = post_refresh_feed_job(&pool, user_id, failing_feed_id, failing_feed_url)
+            .await
+            .unwrap()
+            .expect("the job should be added");
+        move_to_dead_letter(&pool, job_id).await.unwrap();
+
+        let (stale_feed_id, _) = feeds[1];
+        sqlx::query!(
+            r#"
+            UPDATE feeds SET last_refreshed_at = now() - interval '2 hours' WHERE id = $1
+            "#,
+            &stale_feed_id.0,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // With a limit of 1 the stale feed still gets its job
+
+        let mut remaining = 1;
+        create_refresh_feed_jobs(&pool, &mut remaining, StdDuration::from_secs(3600))
+            .await
+            .unwrap();
+        assert_eq!(0, remaining);
+
+        let jobs = get_pending_feed_jobs(&pool, user_id, stale_feed_id)
+            .await
+            .unwrap();
+        assert_eq!(1, jobs.len());
+    }
+
+    #[sqlx::test]
+    async fn manage_jobs_should_not_add_jobs_in_the_dead_letter_queue(pool: PgPool) {
+        let user_id = create_user(&pool).await;
+        let feed_url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_url = Url::parse("https://example.com").unwrap();
+        create_feed(&pool, user_id, &feed_url, &site_url).await;
+
+        let config = JobConfig {
+            max_attempts: 1,
+            manage_jobs_limit: 1,
+            run_jobs_limit: 10,
+            ..get_configuration().unwrap().job
+        };
+
+        let jobs = Arc::new(Mutex::new(Vec::new()));
+        let mut runner = JobRunner::new(
+            config,
+            pool.clone(),
+            Box::new(TestJobHandler {
+                jobs: jobs.clone(),
+                fail: true,
+            }),
+        )
+        .unwrap();
+
+        let count_jobs = || async {
+            sqlx::query!(
+                r#"
+                SELECT
+                  (SELECT COUNT(*) FROM jobs) AS "jobs!",
+                  (SELECT COUNT(*) FROM dead_letter_jobs) AS "dead_letter_jobs!"
+                "#
+            )
+            .fetch_one(&pool)
+            .await
+            .map(|record| (record.jobs, record.dead_letter_jobs))
+            .unwrap()
+        };
+
+        // The fetch favicon job fails once and goes to the dead letter queue
+
+        runner.manage_jobs().await.unwrap();
+        assert_eq!((1, 0), count_jobs().await);
+
+        runner.run_jobs().await.unwrap();
+        assert!(matches!(jobs.lock().unwrap()[..], [Job::FetchFavicon(_)]));
+        assert_eq!((0, 1), count_jobs().await);
+
+        // It doesn't take the only slot again: the refresh feed job is added, and fails too
+
+        runner.manage_jobs().await.unwrap();
+        assert_eq!((1, 1), count_jobs().await);
+
+        runner.run_jobs().await.unwrap();
+        assert!(matches!(
+            jobs.lock().unwrap()[..],
+            [Job::FetchFavicon(_), Job::RefreshFeed(_)]
+        ));
+        assert_eq!((0, 2), count_jobs().await);
+
+        // They are not added again
+
+        runner.manage_jobs().await.unwrap();
+        assert_eq!((0, 2), count_jobs().await);
+
+        runner.run_jobs().await.unwrap();
+        assert_eq!(2, jobs.lock().unwrap().len());
+    }
+
+    async fn user_digest_last_sent_at(pool: &PgPool, user_id: UserId) -> Option<OffsetDateTime> {
+        sqlx::query!(
+            "SELECT digest_last_sent_at FROM users WHERE id = $1",
+            &user_id.0
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+        .digest_last_sent_at
+    }
+
+    async fn count_send_digest_jobs(pool: &PgPool) -> i64 {
+        sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM jobs WHERE data->>'type' = 'SendDigest'"#)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+            .count
+    }
+
+    #[sqlx::test]
+    async fn create_digest_jobs_should_only_add_jobs_once_a_day(pool: PgPool) {
+        let user_id = create_user(&pool).await;
+
+        let today = OffsetDateTime::now_utc().replace_time(Time::MIDNIGHT);
+        let before_send_time = today + time::Duration::hours(6);
+        let after_send_time = today + time::Duration::hours(9);
+
+        // Nothing is sent before the send time
+
+        let mut remaining = 10;
+        create_digest_jobs(&pool, &mut remaining, 7, before_send_time)
+            .await
+            .unwrap();
+        assert_eq!(10, remaining);
+        assert_eq!(0, count_send_digest_jobs(&pool).await);
+
+        // A job is added after the send time
+
+        create_digest_jobs(&pool, &mut remaining, 7, after_send_time)
+            .await
+            .unwrap();
+        assert_eq!(9, remaining);
+        assert_eq!(1, count_send_digest_jobs(&pool).await);
+
+        // While it's queued the user doesn't take a slot again
+
+        create_digest_jobs(&pool, &mut remaining, 7, after_send_time)
+            .await
+            .unwrap();
+        assert_eq!(9, remaining);
+        assert_eq!(1, count_send_digest_jobs(&pool).await);
+
+        // But not again once the digest of the day is sent
+
+        sqlx::query!("DELETE FROM jobs")
+            .execute(&pool)
+            .await
+            .unwrap();
+        set_user_digest_last_sent_at(&pool, user_id, after_send_time)
+            .await
+            .unwrap();
+
+        let mut remaining = 10;
+        create_digest_jobs(&pool, &mut remaining, 7, after_send_time)
+            .await
+            .unwrap();
+        assert_eq!(10, remaining);
+        assert_eq!(0, count_send_digest_jobs(&pool).await);
+
+        // The next day it's added again
+
+        create_digest_jobs(
+            &pool,
+            &mut remaining,
+            7,
+            after_send_time + time::Duration::days(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(1, count_send_digest_jobs(&pool).await);
+    }
+
+    /// Returns a [`tem::Client`] sending the emails to `mock_server`.
+    fn digest_tem_client(mock_server: &MockServer) -> tem::Client {
+        tem::Client::new(
+            mock_server.uri(),
+            tem::ProjectId::new(Uuid::new_v4().to_string()),
+            secrecy::Secret::new("authkey".to_string()),
+            crate::domain::UserEmail::parse("servare@example.com".to_string()).unwrap(),
+            StdDuration::from_secs(1),
+            tem::RetryConfig::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn send_digest_job_should_send_an_email_with_the_unread_entries() {
+        let pool = get_pool().await;
+
+        let user_id = create_user(&pool).await;
+        let feed_url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_url = Url::parse("https://example.com").unwrap();
+        let feed_id = create_feed(&pool, user_id, &feed_url, &site_url).await;
+        for _ in 0..2 {
+            create_feed_entry(&pool, &feed_id).await;
+        }
+
+        // The mock server stands in for the TEM API
+
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/emails"))
+            .and(body_partial_json(json!({
+                "subject": "Your Servare digest: 2 unread entries",
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let tem_client = digest_tem_client(&mock_server);
+
+        let data = SendDigestJobData {
+            schema_version: JOB_SCHEMA_VERSION,
+            user_id,
+        };
+
+        run_send_digest_job(&pool, &tem_client, data).await.unwrap();
+
+        assert!(user_digest_last_sent_at(&pool, user_id).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn send_digest_job_should_count_the_entries_not_listed() {
+        let pool = get_pool().await;
+
+        let user_id = create_user(&pool).await;
+        let feed_url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_url = Url::parse("https://example.com").unwrap();
+        let feed_id = create_feed(&pool, user_id, &feed_url, &site_url).await;
+        for _ in 0..DIGEST_MAX_ENTRIES + 2 {
+            create_feed_entry(&pool, &feed_id).await;
+        }
+
+        let mock_server = MockServer::start().await;
+
+        let unread_count = DIGEST_MAX_ENTRIES + 2;
+        Mock::given(method("POST"))
+            .and(path("/emails"))
+            .and(body_partial_json(json!({
+                "subject": format!("Your Servare digest: {} unread entries", unread_count),
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let tem_client = digest_tem_client(&mock_server);
+
+        let data = SendDigestJobData {
+            schema_version: JOB_SCHEMA_VERSION,
+            user_id,
+        };
+
+        run_send_digest_job(&pool, &tem_client, data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn send_digest_job_should_record_the_digest_before_sending_it() {
+        let pool = get_pool().await;
+
+        let user_id = create_user(&pool).await;
+        let feed_url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_url = Url::parse("https://example.com").unwrap();
+        let feed_id = create_feed(&pool, user_id, &feed_url, &site_url).await;
+        create_feed_entry(&pool, &feed_id).await;
+
+        // The TEM API rejects the email, the job fails and is retried
+
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/emails"))
+            .respond_with(ResponseTemplate::new(400))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let data = SendDigestJobData {
+            schema_version: JOB_SCHEMA_VERSION,
+            user_id,
+        };
+
+        let result = run_send_digest_job(&pool, &digest_tem_client(&mock_server), data).await;
+        assert!(result.is_err());
+
+        // But the digest is already recorded: no other job is added for the day
+
+        assert!(user_digest_last_sent_at(&pool, user_id).await.is_some());
+    }
+
+    #[test]
+    fn digest_text_should_say_when_the_entries_are_truncated() {
+        let text = digest_text(340, DIGEST_MAX_ENTRIES, &[]);
+        assert!(text.contains("You have 340 unread entries."));
+        assert!(text.contains("Only the 100 most recent ones are listed."));
+
+        let text = digest_text(2, 2, &[]);
+        assert!(!text.contains("Only the"));
+    }
+
+    #[tokio::test]
+    async fn refresh_feed_job_should_not_write_anything_if_the_feed_is_not_modified() {
+        let pool = get_pool().await;
+        let http_client = build_http_client(AllowPrivateUrls(true)).unwrap();
+
+        // The mock server only answers the conditional GET
+
+        let mock_server = MockServer::start().await;
+        let mock_url = Url::parse(&mock_server.uri()).unwrap();
+
+        Mock::given(path("/feed"))
+            .and(header("If-None-Match", "\"abcdef\""))
+            .and(header("If-Modified-Since", "Wed, 15 Mar 2023 10:00:00 GMT"))
+            .respond_with(ResponseTemplate::new(304))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let user_id = create_user(&pool).await;
+        let feed_url = mock_url.join("/feed").unwrap();
+        let feed_id = create_feed(&pool, user_id, &feed_url, &mock_url).await;
+
+        let validators = FeedCacheValidators {
+            etag: Some("\"abcdef\"".to_string()),
+            last_modified: Some("Wed, 15 Mar 2023 10:00:00 GMT".to_string()),
+        };
+        set_feed_cache_validators(&pool, user_id, &feed_id, &validators)
+            .await
+            .unwrap();
+
+        let version = feed_row_version(&pool, &feed_id).await;
+
+        // Run the job
+
+        let data = RefreshFeedJobData {
+            schema_version: JOB_SCHEMA_VERSION,
+            user_id,
+            feed_id,
+            feed_url,
+        };
+
+        run_refresh_feed_job(&http_client, &pool, &UnreadUpdates::new(10), data)
+            .await
+            .unwrap();
+
+        // Neither the feed nor its entries were written
+
+        assert_eq!(version, feed_row_version(&pool, &feed_id).await);
+
+        let record = sqlx::query!(
+            r#"
+            SELECT COUNT(*) AS "count!" FROM feed_entries WHERE feed_id = $1
+            "#,
+            &feed_id.0,
+        )
+        .fetch_one(&pool)
+        .await
+        .expect("unable to count the feed entries");
+        assert_eq!(0, record.count);
+    }
+
+    #[tokio::test]
+    async fn feed_entry_with_external_id_exists_should_be_scoped_to_the_feed() {
+        let pool = get_pool().await;
+
+        let user_id = create_user(&pool).await;
+        let site_url = Url::parse("https://example.com").unwrap();
+        let feed_id = create_feed(
+            &pool,
+            user_id,
+            &Url::parse("https://example.com/feed.xml").unwrap(),
+            &site_url,
+        )
+        .await;
+        let mirror_feed_id = create_feed(
+            &pool,
+            user_id,
+            &Url::parse("https://example.com/mirror.xml").unwrap(),
+            &site_url,
+        )
+        .await;
+
+        let new_entry = || ParsedFeedEntry {
+            external_id: "1".to_string(),
+            url: None,
+            title: "Foobar".to_string(),
+            summary: String::new(),
+            authors: Vec::new(),
+            published_at: None,
+        };
+
+        let mut tx = pool.begin().await.unwrap();
+        insert_feed_entry(&mut tx, &feed_id, new_entry())
+            .await
+            .unwrap();
+
+        let exists = feed_entry_with_external_id_exists(&mut tx, user_id, &feed_id, "1")
+            .await
+            .unwrap();
+        assert!(exists);
+
+        // The same external id in another feed is a different entry
+
+        let exists = feed_entry_with_external_id_exists(&mut tx, user_id, &mirror_feed_id, "1")
+            .await
+            .unwrap();
+        assert!(!exists);
+
+        insert_feed_entry(&mut tx, &mirror_feed_id, new_entry())
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+    }
+
+    async fn insert_job(pool: &PgPool, status: &str, age_days: i32) -> Uuid {
+        let id = Uuid::new_v4();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO jobs(id, data, key, status, created_at)
+            VALUES ($1, '{}', $2, $3::text::job_status, now() - make_interval(days => $4))
+            "#,
+            &id,
+            &id.as_bytes()[..],
+            status,
+            age_days,
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+
+        id
+    }
+
+    async fn insert_dead_letter_job(pool: &PgPool, age_days: i32) -> Uuid {
+        let id = Uuid::new_v4();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO dead_letter_jobs(id, data, key, created_at, attempts, failed_at)
+            VALUES ($1, '{}', $2, now(), 5, now() - make_interval(days => $3))
+            "#,
+            &id,
+            &id.as_bytes()[..],
+            age_days,
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+
+        id
+    }
+
+    #[sqlx::test]
+    async fn cleanup_stale_jobs_should_only_delete_old_dead_letter_jobs(pool: PgPool) {
+        insert_dead_letter_job(&pool, 40).await;
+        let recent_dead_letter_job = insert_dead_letter_job(&pool, 2).await;
+        let old_pending_job = insert_job(&pool, "pending", 40).await;
+
+        let deleted = cleanup_stale_jobs(&pool, 30).await.unwrap();
+        assert_eq!(1, deleted);
+
+        let remaining_dead_letter_jobs = get_dead_letter_jobs(&pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|job| job.id.0)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![recent_dead_letter_job], remaining_dead_letter_jobs);
+
+        let remaining_jobs = sqlx::query!("SELECT id FROM jobs")
+            .fetch_all(&pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|record| record.id)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![old_pending_job], remaining_jobs);
+    }
+
+    #[sqlx::test]
+    async fn reset_running_jobs_should_only_reset_the_interrupted_jobs(pool: PgPool) {
+        let interrupted_job = insert_job(&pool, "running", 0).await;
+        let running_job = insert_job(&pool, "running", 0).await;
+
+        sqlx::query!(
+            r#"
+            UPDATE jobs SET started_at = now() - interval '2 hours' WHERE id = $1
+            "#,
+            &interrupted_job,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "UPDATE jobs SET started_at = now() WHERE id = $1",
+            &running_job,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let reset = reset_running_jobs(&pool, StdDuration::from_secs(3600))
+            .await
+            .unwrap();
+        assert_eq!(1, reset);
+
+        let statuses = sqlx::query!(r#"SELECT id, status AS "status: String" FROM jobs"#)
+            .fetch_all(&pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|record| (record.id, record.status))
+            .collect::<std::collections::HashMap<_, _>>();
+        assert_eq!("pending", statuses[&interrupted_job]);
+        assert_eq!("running", statuses[&running_job]);
+    }
+
+    #[sqlx::test]
+    async fn cancel_job_should_only_delete_pending_jobs(pool: PgPool) {
+        let user_id = create_user(&pool).await;
+        let feed_url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_url = Url::parse("https://example.com").unwrap();
+        let feed_id = create_feed(&pool, user_id, &feed_url, &site_url).await;
+
+        // The pending job of the feed is found using its key
+
+        let job_id = post_refresh_feed_job(&pool, user_id, feed_id, feed_url)
+            .await
+            .unwrap()
+            .expect("the job should be added");
+
+        let job_ids = get_pending_feed_jobs(&pool, user_id, feed_id)
+            .await
+            .unwrap();
+        assert_eq!(vec![job_id], job_ids);
+
+        let other_user_id = create_user(&pool).await;
+        let job_ids = get_pending_feed_jobs(&pool, other_user_id, feed_id)
+            .await
+            .unwrap();
+        assert!(job_ids.is_empty());
+
+        // Cancel it, only once
+
+        assert!(cancel_job(&pool, job_id).await.unwrap());
+        assert!(!cancel_job(&pool, job_id).await.unwrap());
+
+        let job_ids = get_pending_feed_jobs(&pool, user_id, feed_id)
+            .await
+            .unwrap();
+        assert!(job_ids.is_empty());
+
+        // A failed job can't be cancelled
+
+        let failed_job = insert_job(&pool, "failed", 1).await;
+        assert!(!cancel_job(&pool, JobId(failed_job)).await.unwrap());
+    }
+
+    #[sqlx::test]
+    async fn job_runner_should_run_at_most_run_jobs_limit_jobs_per_tick(pool: PgPool) {
+        let user_id = create_user(&pool).await;
+        let site_url = Url::parse("https://example.com").unwrap();
+
+        for i in 0..10 {
+            let feed_url = site_url.join(&format!("/feed-{}.xml", i)).unwrap();
+            let feed_id = create_feed(&pool, user_id, &feed_url, &site_url).await;
+
+            post_refresh_feed_job(&pool, user_id, feed_id, feed_url)
+                .await
+                .unwrap();
+        }
+
+        let config = JobConfig {
+            run_jobs_limit: 5,
+            ..get_configuration().unwrap().job
+        };
+
+        let jobs = Arc::new(Mutex::new(Vec::new()));
+        let mut runner = JobRunner::new(
+            config,
+            pool.clone(),
+            Box::new(TestJobHandler {
+                jobs: jobs.clone(),
+                fail: false,
+            }),
+        )
+        .unwrap();
+
+        let count_jobs = || async {
+            sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM jobs"#)
+                .fetch_one(&pool)
+                .await
+                .unwrap()
+                .count
+        };
+
+        runner.run_jobs().await.unwrap();
+        assert_eq!(5, jobs.lock().unwrap().len());
+        assert_eq!(5, count_jobs().await);
+
+        runner.run_jobs().await.unwrap();
+        assert_eq!(10, jobs.lock().unwrap().len());
+        assert_eq!(0, count_jobs().await);
+    }
+
+    #[sqlx::test]
+    async fn job_runner_should_run_the_jobs_of_a_tick_concurrently(pool: PgPool) {
+        let feed_data = TestData::get("tailscale_rss_feed.xml").unwrap().data;
+
+        let mock_server = MockServer::start().await;
+        let mock_url = Url::parse(&mock_server.uri()).unwrap();
+
+        Mock::given(wiremock::matchers::path_regex("^/feed-[0-9]$"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(feed_data, "application/xml")
+                    .set_delay(StdDuration::from_millis(200)),
+            )
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let user_id = create_user(&pool).await;
+        for i in 0..2 {
+            let feed_url = mock_url.join(&format!("/feed-{}", i)).unwrap();
+            let feed_id = create_feed(&pool, user_id, &feed_url, &mock_url).await;
+
+            post_refresh_feed_job(&pool, user_id, feed_id, feed_url)
+                .await
+                .unwrap();
+        }
+
+        let config = JobConfig {
+            run_jobs_limit: 2,
+            ..get_configuration().unwrap().job
+        };
+
+        let mut runner = JobRunner::new(
+            config,
+            pool.clone(),
+            Box::new(DefaultJobHandler::new(
+                FaviconCache::new(10),
+                UnreadUpdates::new(10),
+            )),
+        )
+        .unwrap();
+
+        let start = std::time::Instant::now();
+        runner.run_jobs().await.unwrap();
+        let elapsed = start.elapsed();
+
+        let remaining_jobs = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM jobs"#)
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+            .count;
+
+        assert_eq!(0, remaining_jobs);
+        assert!(
+            elapsed < StdDuration::from_millis(400),
+            "jobs took {:?} to run, they should have run concurrently",
+            elapsed
+        );
+    }
+
+    #[sqlx::test]
+    async fn job_runner_should_abort_jobs_running_for_too_long(pool: PgPool) {
+        let mock_server = MockServer::start().await;
+        let mock_url = Url::parse(&mock_server.uri()).unwrap();
+
+        Mock::given(path("/feed.xml"))
+            .respond_with(ResponseTemplate::new(200).set_delay(StdDuration::from_secs(5)))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let user_id = create_user(&pool).await;
+        let feed_url = mock_url.join("/feed.xml").unwrap();
+        let feed_id = create_feed(&pool, user_id, &feed_url, &mock_url).await;
+
+        post_refresh_feed_job(&pool, user_id, feed_id, feed_url)
+            .await
+            .unwrap();
+
+        let mut runner = JobRunner::new(
+            get_configuration().unwrap().job,
+            pool.clone(),
+            Box::new(DefaultJobHandler::new(
+                FaviconCache::new(10),
+                UnreadUpdates::new(10),
+            )),
+        )
+        .unwrap();
+        runner.job_timeout = StdDuration::from_millis(100);
+
+        let start = std::time::Instant::now();
+        runner.run_jobs().await.unwrap();
+        let elapsed = start.elapsed();
+
+        assert!(
+            elapsed < StdDuration::from_secs(2),
+            "jobs took {:?} to run, the job should have been aborted",
+            elapsed
+        );
+
+        // The job is kept for a retry
+
+        let record =
+            sqlx::query!(r#"SELECT attempts, status AS "status: String", error FROM jobs"#)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+
+        assert_eq!(1, record.attempts);
+        assert_eq!("pending", record.status);
+        assert!(record.error.unwrap_or_default().contains("timed out"));
+    }
+
+    #[sqlx::test]
+    async fn job_runner_should_dispatch_jobs_to_its_handler(pool: PgPool) {
+        let config = get_configuration().unwrap();
+
+        // Create a test user, feed and a job to refresh it
+
+        let user_id = create_user(&pool).await;
+        let feed_url = Url::parse("https://example.com/feed.xml").unwrap();
+        let site_url = Url::parse("https://example.com").unwrap();
+        let feed_id = create_feed(&pool, user_id, &feed_url, &site_url).await;
+
+        post_refresh_feed_job(&pool, user_id, feed_id, feed_url)
+            .await
+            .unwrap();
+
+        // Run the job with a failing handler first, then with a succeeding one
+
+        let jobs = Arc::new(Mutex::new(Vec::new()));
+
+        let mut failing_runner = JobRunner::new(
+            config.job.clone(),
+            pool.clone(),
+            Box::new(TestJobHandler {
+                jobs: jobs.clone(),
+                fail: true,
+            }),
+        )
+        .unwrap();
+        failing_runner.run_jobs().await.unwrap();
+
+        let attempts_after_failure = sqlx::query!("SELECT attempts FROM jobs")
+            .fetch_all(&pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|record| record.attempts)
+            .collect::<Vec<_>>();
+
+        // The failed job is backing off: running the jobs again right away does nothing
+
+        failing_runner.run_jobs().await.unwrap();
+        let jobs_run_while_backing_off = jobs.lock().unwrap().len();
+
+        // Pretend the back off is over
+        sqlx::query!("UPDATE jobs SET next_run_at = now()")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let mut runner = JobRunner::new(
+            config.job,
+            pool.clone(),
+            Box::new(TestJobHandler {
+                jobs: jobs.clone(),
+                fail: false,
+            }),
+        )
+        .unwrap();
+        runner.run_jobs().await.unwrap();
+
+        let remaining_jobs = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM jobs"#)
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+            .count;
+
+        // The failed job is kept for a retry, the successful one is deleted
+
+        assert_eq!(vec![1], attempts_after_failure);
+        assert_eq!(1, jobs_run_while_backing_off);
+        assert_eq!(0, remaining_jobs);
+
+        let jobs = jobs.lock().unwrap();
+        assert_eq!(2, jobs.len());
+        for job in jobs.iter() {
+            match job {
+                Job::RefreshFeed(data) => {
+                    assert_eq!(user_id, data.user_id);
+                    assert_eq!(feed_id, data.feed_id);
+                }
+                _ => panic!("expected a Job::RefreshFeed"),
+            }
         }
     }
 }
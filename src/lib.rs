@@ -1,28 +1,41 @@
 use bytes::Bytes;
 use std::fmt;
+use std::time::Duration as StdDuration;
 use url::Url;
 
 pub mod authentication;
+mod compression;
 pub mod configuration;
 pub mod domain;
-mod feed;
+pub mod feed;
 pub mod html;
+pub mod image;
 pub mod job;
 mod parsed_feed;
+pub mod preferences;
 mod routes;
 pub mod run_group;
+pub mod security;
 mod sessions;
 pub mod startup;
 pub mod telemetry;
 pub mod tem;
 #[cfg(test)]
 pub mod tests;
+pub mod unread_updates;
 
+/// Formats `err` and its chain of causes, one per line.
+///
+/// Each cause is labelled with its position in the chain and indented according to its depth.
 pub fn error_chain_fmt(err: &impl std::error::Error, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    writeln!(f, "{}\n", err)?;
+    writeln!(f, "[0] {}", err)?;
+
+    let mut depth = 1;
     let mut current = err.source();
     while let Some(cause) = current {
-        writeln!(f, "Caused by:\n\t{}", cause)?;
+        writeln!(f, "{:indent$}[{}] {}", "", depth, cause, indent = depth * 2)?;
+
+        depth += 1;
         current = cause.source();
     }
     Ok(())
@@ -34,7 +47,27 @@ pub fn error_chain_fmt(err: &impl std::error::Error, f: &mut fmt::Formatter<'_>)
 ///
 /// This function will return an error if the fetch fails.
 pub async fn fetch_bytes(client: &reqwest::Client, url: &Url) -> Result<Bytes, reqwest::Error> {
-    let response = client.get(url.to_string()).send().await?;
+    fetch_bytes_with_timeout(client, url, None).await
+}
+
+/// Fetches the content of a URL directly as a bytes buffer, in at most `timeout` if set.
+///
+/// The timeout overrides the one of `client` for this request only.
+///
+/// # Errors
+///
+/// This function will return an error if the fetch fails or times out.
+pub async fn fetch_bytes_with_timeout(
+    client: &reqwest::Client,
+    url: &Url,
+    timeout: Option<StdDuration>,
+) -> Result<Bytes, reqwest::Error> {
+    let mut request = client.get(url.to_string());
+    if let Some(timeout) = timeout {
+        request = request.timeout(timeout);
+    }
+
+    let response = request.send().await?;
     let response_bytes = response.bytes().await?;
 
     Ok(response_bytes)
@@ -77,6 +110,12 @@ macro_rules! impl_typed_uuid {
                 self.0.fmt(f)
             }
         }
+
+        impl From<$t> for serde_json::Value {
+            fn from(id: $t) -> Self {
+                serde_json::Value::String(id.0.to_string())
+            }
+        }
     };
 }
 
@@ -106,5 +145,124 @@ macro_rules! impl_typed_id {
                 id.0.to_le_bytes()
             }
         }
+
+        impl From<$t> for serde_json::Value {
+            fn from(id: $t) -> Self {
+                serde_json::Value::Number(id.0.into())
+            }
+        }
     };
 }
+
+#[cfg(test)]
+mod typed_id_tests {
+    use crate::domain::UserId;
+    use crate::feed::FeedId;
+    use crate::job::JobId;
+    use serde_json::json;
+
+    #[test]
+    fn uuid_ids_should_convert_to_a_json_string() {
+        let user_id = UserId::default();
+        let value = serde_json::Value::from(user_id);
+        assert_eq!(Some(user_id.0.to_string().as_str()), value.as_str());
+
+        let job_id = JobId::default();
+        let value = serde_json::Value::from(job_id.clone());
+        assert_eq!(Some(job_id.0.to_string().as_str()), value.as_str());
+    }
+
+    #[test]
+    fn i64_ids_should_convert_to_a_json_integer() {
+        let value = serde_json::Value::from(FeedId(20));
+        assert_eq!(Some(20), value.as_i64());
+    }
+
+    #[test]
+    fn ids_should_be_usable_in_json_macro() {
+        let user_id = UserId::default();
+
+        let value = json!({ "user_id": user_id, "feed_id": FeedId(20) });
+        assert_eq!(
+            json!({ "user_id": user_id.0.to_string(), "feed_id": 20 }),
+            value
+        );
+    }
+}
+
+#[cfg(test)]
+mod error_chain_tests {
+    use super::*;
+
+    #[derive(thiserror::Error)]
+    enum RootError {
+        #[error("unable to refresh feed")]
+        Refresh(#[source] FetchError),
+    }
+
+    debug_with_error_chain!(RootError);
+
+    #[derive(Debug, thiserror::Error)]
+    enum FetchError {
+        #[error("unable to fetch feed")]
+        IO(#[source] std::io::Error),
+    }
+
+    #[test]
+    fn error_chain_should_be_indented() {
+        let err = RootError::Refresh(FetchError::IO(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "connection reset",
+        )));
+
+        let expected =
+            "[0] unable to refresh feed\n  [1] unable to fetch feed\n    [2] connection reset\n";
+        assert_eq!(expected, format!("{:?}", err));
+    }
+}
+
+#[cfg(test)]
+mod fetch_bytes_tests {
+    use super::*;
+    use wiremock::matchers::path;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn slow_server() -> MockServer {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(path("/slow"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string("foobar")
+                    .set_delay(StdDuration::from_millis(500)),
+            )
+            .mount(&mock_server)
+            .await;
+
+        mock_server
+    }
+
+    #[tokio::test]
+    async fn fetch_bytes_with_timeout_should_time_out() {
+        let mock_server = slow_server().await;
+        let url = Url::parse(&format!("{}/slow", mock_server.uri())).unwrap();
+
+        let client = reqwest::Client::new();
+        let result =
+            fetch_bytes_with_timeout(&client, &url, Some(StdDuration::from_millis(50))).await;
+
+        let err = result.unwrap_err();
+        assert!(err.is_timeout(), "error should be a timeout, got {:?}", err);
+    }
+
+    #[tokio::test]
+    async fn fetch_bytes_without_timeout_should_wait_for_the_response() {
+        let mock_server = slow_server().await;
+        let url = Url::parse(&format!("{}/slow", mock_server.uri())).unwrap();
+
+        let client = reqwest::Client::new();
+        let data = fetch_bytes(&client, &url).await.unwrap();
+
+        assert_eq!(&b"foobar"[..], &data[..]);
+    }
+}
@@ -1,15 +1,21 @@
 use read_input::InputBuild;
 use secrecy::Secret;
-use servare::authentication::create_user;
+use servare::authentication::{create_user, PasswordHasherConfig};
 use servare::configuration::{get_configuration, Config};
-use servare::domain::UserEmail;
-use servare::job::JobRunner;
+use servare::domain::{set_user_is_admin, UserEmail};
+use servare::feed::FaviconCache;
+use servare::job::{DefaultJobHandler, JobRunner};
 use servare::run_group::RunGroup;
-use servare::startup::get_connection_pool;
+use servare::security::AllowPrivateUrls;
 use servare::startup::Application;
+use servare::startup::{get_connection_pool, get_tem_client, run_migrations};
 use servare::telemetry;
+use servare::unread_updates::UnreadUpdates;
 use tracing::{error, info};
 
+/// Number of unread updates buffered for each WebSocket client.
+const UNREAD_UPDATES_CAPACITY: usize = 1024;
+
 async fn run_serve(config: Config, _matches: &clap::ArgMatches) -> anyhow::Result<()> {
     // Setup
 
@@ -20,12 +26,35 @@ async fn run_serve(config: Config, _matches: &clap::ArgMatches) -> anyhow::Resul
         .build(std::io::stdout);
     telemetry::init_global_default(subscriber);
 
+    if config.tem.is_none() {
+        info!("no TEM configuration, emails are disabled");
+    }
+
     //
     // Build the application
     //
 
+    // The favicon cache is shared: the job runner invalidates favicons the application serves
+    let favicon_cache = FaviconCache::new(config.application.favicon_cache_size);
+    // Same for the unread updates: the job runner sends them, the application forwards them
+    let unread_updates = UnreadUpdates::new(UNREAD_UPDATES_CAPACITY);
+
     let app_pool = get_connection_pool(&config.database).await?;
-    let app = Application::build(&config.application, &config.session, app_pool)?;
+    if let Err(err) = run_migrations(&app_pool).await {
+        error!(%err, "unable to run the migrations");
+        std::process::exit(1);
+    }
+
+    let shutdown_timeout = config.application.shutdown_timeout();
+    let app = Application::build(
+        &config.application,
+        &config.session,
+        &config.argon2,
+        config.tem.as_ref(),
+        app_pool,
+        favicon_cache.clone(),
+        unread_updates.clone(),
+    )?;
 
     info!(
         url = format!(
@@ -40,14 +69,19 @@ async fn run_serve(config: Config, _matches: &clap::ArgMatches) -> anyhow::Resul
     //
 
     let job_runner_pool = get_connection_pool(&config.database).await?;
-    let job_runner = JobRunner::new(config.job, job_runner_pool)?;
+    let mut job_handler = DefaultJobHandler::new(favicon_cache, unread_updates);
+    if let Some(tem_config) = config.tem.as_ref() {
+        job_handler = job_handler.with_tem_client(get_tem_client(tem_config)?);
+    }
+    let job_runner = JobRunner::new(config.job, job_runner_pool, Box::new(job_handler))?
+        .with_allow_private_urls(AllowPrivateUrls(config.application.allow_private_feed_urls))?;
 
     //
     // Finally start everything
     //
 
     RunGroup::new()
-        .run(|shutdown| app.run(shutdown))
+        .run(|shutdown| app.run(shutdown, shutdown_timeout))
         .run(|shutdown| job_runner.run(shutdown))
         .start()
         .await?;
@@ -74,9 +108,12 @@ async fn run_users(config: Config, matches: &clap::ArgMatches) -> anyhow::Result
             };
 
             let pool = get_connection_pool(&config.database).await?;
+            run_migrations(&pool).await?;
 
             // Create the admin user
-            let user_id = create_user(&pool, &email, password).await?;
+            let hasher_config = PasswordHasherConfig::try_from(&config.argon2)?;
+            let user_id = create_user(&pool, &hasher_config, &email, password).await?;
+            set_user_is_admin(&pool, user_id, true).await?;
 
             println!("created user {}. id={}", email, user_id);
 
@@ -105,8 +142,17 @@ fn main() {
         }
     };
 
+    // Logging isn't set up yet, print the errors directly
+    if let Err(errors) = config.validate() {
+        eprintln!("invalid configuration:");
+        for error in errors {
+            eprintln!("  * {}", error);
+        }
+        std::process::exit(1);
+    }
+
     // Build the Tokio runtime
-    let runtime = tokio::runtime::Builder::new_current_thread()
+    let runtime = tokio::runtime::Builder::new_multi_thread()
         .worker_threads(config.application.worker_threads)
         .thread_name("servare")
         .thread_stack_size(3 * 1024 * 1024)
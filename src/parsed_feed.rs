@@ -1,5 +1,7 @@
+use crate::html::sanitize_html;
 use feed_rs::model::Entry as RawFeedEntry;
 use feed_rs::model::Feed as RawFeed;
+use std::collections::HashSet;
 use url::Url;
 
 #[derive(Debug, thiserror::Error)]
@@ -8,35 +10,84 @@ pub enum ParseError {
     Unexpected(#[from] anyhow::Error),
 }
 
-/// Holds feed data parsed from a [`feed_rs::model::Feed`].
+/// Holds feed data parsed from a [`feed_rs::model::Feed`] or from a JSON Feed.
 ///
 /// This means this struct should _not_ be used to represent data from the database.
+#[derive(Debug)]
 pub struct ParsedFeed {
     pub url: Url,
     pub title: String,
     pub site_link: Option<Url>,
     pub description: String,
+    pub language: Option<String>,
+}
+
+/// Parses `data` as a RSS or Atom feed.
+///
+/// feed-rs only reads the language of RSS feeds: the language of an Atom feed is read from the
+/// `xml:lang` attribute of its root element.
+///
+/// # Errors
+///
+/// This function will return an error if `data` is not a valid feed.
+pub fn parse_raw_feed(data: &[u8]) -> Result<RawFeed, feed_rs::parser::ParseFeedError> {
+    let mut feed = feed_rs::parser::parse(data)?;
+    if feed.language.is_none() {
+        feed.language = find_root_xml_lang(data);
+    }
+
+    Ok(feed)
+}
+
+/// Returns the `xml:lang` attribute of the root element of the XML document `data`, if any.
+fn find_root_xml_lang(data: &[u8]) -> Option<String> {
+    let mut rest = std::str::from_utf8(data).ok()?;
+
+    // Skip the XML declaration, the comments and the doctype
+    loop {
+        rest = &rest[rest.find('<')?..];
+
+        if rest.starts_with("<?") {
+            rest = &rest[rest.find("?>")? + 2..];
+        } else if rest.starts_with("<!--") {
+            rest = &rest[rest.find("-->")? + 3..];
+        } else if rest.starts_with("<!") {
+            rest = &rest[rest.find('>')? + 1..];
+        } else {
+            break;
+        }
+    }
+
+    let root_tag = &rest[..rest.find('>')?];
+    let value = root_tag.split_once("xml:lang=")?.1;
+
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let value = &value[1..];
+    let language = value[..value.find(quote)?].trim();
+
+    if language.is_empty() {
+        None
+    } else {
+        Some(language.to_string())
+    }
 }
 
 impl ParsedFeed {
     pub fn parse(url: &Url, data: &[u8]) -> Result<Self, ParseError> {
-        let raw_feed = feed_rs::parser::parse(data).map_err(Into::<anyhow::Error>::into)?;
+        let raw_feed = parse_raw_feed(data).map_err(Into::<anyhow::Error>::into)?;
 
         Ok(Self::from_raw_feed(url, raw_feed))
     }
 
+    /// Returns the link to the website of `feed`.
+    ///
+    /// A RSS `<link>` has no relation and an Atom link defaults to `alternate`: other links,
+    /// like the `self` link to the feed itself, are ignored.
     fn get_site_link(feed: &RawFeed) -> Option<String> {
-        let mut site_link = None;
-
-        for link in &feed.links {
-            if link.rel.is_none() {
-                continue;
-            }
-
-            site_link = Some(link.href.clone());
-        }
-
-        site_link
+        feed.links
+            .iter()
+            .find(|link| matches!(link.rel.as_deref(), None | Some("alternate")))
+            .map(|link| link.href.clone())
     }
 
     pub fn from_raw_feed(url: &Url, feed: RawFeed) -> Self {
@@ -49,11 +100,15 @@ impl ParsedFeed {
             title: feed.title.map(|v| v.content).unwrap_or_default(),
             site_link: site_link_url,
             description: feed.description.map(|v| v.content).unwrap_or_default(),
+            language: feed.language,
         }
     }
 }
 
-/// Holds feed entry data parsed from a [`feed_rs::model::Entry`].
+/// Maximum number of authors kept for a single feed entry.
+const MAX_AUTHORS: usize = 10;
+
+/// Holds feed entry data parsed from a [`feed_rs::model::Entry`] or from a JSON Feed item.
 ///
 /// This means this struct should _not_ be used to represent data from the database.
 pub struct ParsedFeedEntry {
@@ -62,6 +117,8 @@ pub struct ParsedFeedEntry {
     pub title: String,
     pub summary: String,
     pub authors: Vec<String>,
+    /// The date the entry was published or, failing that, last updated according to the feed.
+    pub published_at: Option<time::OffsetDateTime>,
 }
 
 impl ParsedFeedEntry {
@@ -74,20 +131,24 @@ impl ParsedFeedEntry {
             .last();
 
         let title = entry.title.map(|v| v.content).unwrap_or_default();
-        let summary = entry.summary.map(|v| v.content).unwrap_or_default();
+        let summary = entry
+            .summary
+            .map(|v| sanitize_html(&v.content))
+            .unwrap_or_default();
+
+        let published_at = entry
+            .published
+            .or(entry.updated)
+            .and_then(|date| time::OffsetDateTime::from_unix_timestamp(date.timestamp()).ok());
 
         // TODO(vincent): see if there's anything better to do ?
-        let authors: Vec<String> = entry
-            .authors
-            .into_iter()
-            .map(|person| {
-                if let Some(ref email) = person.email {
-                    email.clone()
-                } else {
-                    person.name
-                }
-            })
-            .collect();
+        let authors = normalize_authors(entry.authors.into_iter().map(|person| {
+            if let Some(ref email) = person.email {
+                email.clone()
+            } else {
+                person.name
+            }
+        }));
 
         Self {
             external_id: entry.id,
@@ -95,13 +156,38 @@ impl ParsedFeedEntry {
             title,
             summary,
             authors,
+            published_at,
         }
     }
 }
 
+/// Normalize the author strings of a feed entry.
+///
+/// Each author is trimmed, empty authors are discarded and duplicates are removed, preserving the order.
+/// At most [`MAX_AUTHORS`] are kept.
+pub(crate) fn normalize_authors(authors: impl Iterator<Item = String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+
+    authors
+        .map(|author| author.trim().to_string())
+        .filter(|author| !author.is_empty())
+        .filter(|author| seen.insert(author.clone()))
+        .take(MAX_AUTHORS)
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use time::format_description::well_known::Rfc3339;
+
+    #[derive(rust_embed::RustEmbed)]
+    #[folder = "testdata/"]
+    struct TestData;
+
+    fn datetime(s: &str) -> time::OffsetDateTime {
+        time::OffsetDateTime::parse(s, &Rfc3339).unwrap()
+    }
 
     #[test]
     fn feed_parse_should_work() {
@@ -145,4 +231,151 @@ mod tests {
         assert_eq!(feed.site_link, Some(url));
         assert_eq!(feed.description, "Foo");
     }
+
+    #[test]
+    fn feed_parse_should_find_the_language() {
+        const DATA: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom" xml:lang="de">
+<title>Foo</title>
+<link href="https://example.com/blog/"/>
+<id>https://example.com/blog/</id>
+<updated>2023-03-06T10:00:00Z</updated>
+</feed>"#;
+
+        let url = Url::parse("https://example.com/blog/feed.xml").unwrap();
+
+        let feed = ParsedFeed::parse(&url, DATA.as_bytes()).unwrap();
+        assert_eq!(feed.title, "Foo");
+        assert_eq!(feed.language, Some("de".to_string()));
+    }
+
+    #[test]
+    fn feed_parse_should_prefer_the_rss_language() {
+        const DATA: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<!-- A comment -->
+<rss version="2.0" xml:lang="de">
+<channel>
+<title>Foo</title>
+<link>https://example.com/blog/</link>
+<language>fr</language>
+</channel>
+</rss>"#;
+
+        let url = Url::parse("https://example.com/blog/feed.xml").unwrap();
+
+        let feed = ParsedFeed::parse(&url, DATA.as_bytes()).unwrap();
+        assert_eq!(feed.language, Some("fr".to_string()));
+    }
+
+    #[test]
+    fn root_xml_lang_should_be_found() {
+        assert_eq!(
+            Some("en-US".to_string()),
+            find_root_xml_lang(
+                br#"<?xml version="1.0"?><!DOCTYPE feed><feed xml:lang='en-US'><title xml:lang="de"/></feed>"#
+            )
+        );
+        assert_eq!(
+            None,
+            find_root_xml_lang(br#"<feed><title xml:lang="de"/></feed>"#)
+        );
+        assert_eq!(None, find_root_xml_lang(br#"<feed xml:lang="">"#));
+        assert_eq!(None, find_root_xml_lang(b"not xml"));
+    }
+
+    fn person(name: &str) -> feed_rs::model::Person {
+        feed_rs::model::Person {
+            name: name.to_string(),
+            uri: None,
+            email: None,
+        }
+    }
+
+    #[test]
+    fn feed_entry_authors_should_be_cleaned_up() {
+        let raw_entry = RawFeedEntry {
+            id: "foobar".to_string(),
+            authors: vec![
+                person("Vincent"),
+                person("  Vincent "),
+                person(""),
+                person("   "),
+                person("Alice\t"),
+                person("Vincent"),
+                person("Alice"),
+            ],
+            ..Default::default()
+        };
+
+        let entry = ParsedFeedEntry::from_raw_feed_entry(raw_entry);
+        assert_eq!(vec!["Vincent", "Alice"], entry.authors);
+    }
+
+    #[test]
+    fn feed_entry_authors_should_be_capped() {
+        let raw_entry = RawFeedEntry {
+            id: "foobar".to_string(),
+            authors: (0..20).map(|i| person(&format!("Author {}", i))).collect(),
+            ..Default::default()
+        };
+
+        let entry = ParsedFeedEntry::from_raw_feed_entry(raw_entry);
+        assert_eq!(MAX_AUTHORS, entry.authors.len());
+        assert_eq!("Author 0", entry.authors[0]);
+        assert_eq!("Author 9", entry.authors[9]);
+    }
+
+    #[test]
+    fn feed_entry_summary_should_be_sanitized() {
+        const DATA: &str = r#"
+<rss version="2.0">
+<channel>
+<title>Foo</title>
+<item>
+<guid>foobar</guid>
+<title>Bar</title>
+<description><![CDATA[<p onclick="alert(1)">Hello</p><script>alert(1)</script>]]></description>
+</item>
+</channel>
+</rss>"#;
+
+        let mut raw_feed = feed_rs::parser::parse(DATA.as_bytes()).unwrap();
+        let raw_entry = raw_feed.entries.remove(0);
+
+        let entry = ParsedFeedEntry::from_raw_feed_entry(raw_entry);
+        assert_eq!("<p>Hello</p>", entry.summary);
+    }
+
+    #[test]
+    fn feed_entry_published_at_should_come_from_the_feed() {
+        let feed_data = TestData::get("tailscale_rss_feed.xml").unwrap().data;
+        let raw_feed = feed_rs::parser::parse(&feed_data[..]).unwrap();
+
+        let entry =
+            ParsedFeedEntry::from_raw_feed_entry(raw_feed.entries.into_iter().next().unwrap());
+
+        // <pubDate>Fri, 16 Dec 2022 21:00:00 +0000</pubDate>
+        assert_eq!(Some(datetime("2022-12-16T21:00:00Z")), entry.published_at);
+    }
+
+    #[test]
+    fn feed_entry_published_at_should_fall_back_to_updated() {
+        const DATA: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+<title>Foo</title>
+<id>https://example.com/blog/</id>
+<updated>2023-03-06T10:00:00Z</updated>
+<entry>
+<title>Hello</title>
+<id>https://example.com/blog/hello</id>
+<updated>2023-03-05T08:30:00Z</updated>
+</entry>
+</feed>"#;
+
+        let raw_feed = feed_rs::parser::parse(DATA.as_bytes()).unwrap();
+
+        let entry =
+            ParsedFeedEntry::from_raw_feed_entry(raw_feed.entries.into_iter().next().unwrap());
+        assert_eq!(Some(datetime("2023-03-05T08:30:00Z")), entry.published_at);
+    }
 }
@@ -0,0 +1,315 @@
+use crate::domain::UserId;
+use anyhow::Context;
+use std::fmt;
+use std::ops::RangeInclusive;
+use time::UtcOffset;
+
+/// The allowed number of entries shown on each page.
+pub const ENTRIES_PER_PAGE_RANGE: RangeInclusive<usize> = 1..=200;
+
+/// The preferences of a user.
+///
+/// A user who never saved their preferences gets the [`Default`] ones.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UserPreferences {
+    /// The number of entries shown on each page of the feed entries.
+    pub entries_per_page: usize,
+    /// The timezone used to display the dates.
+    pub timezone: Timezone,
+    pub theme: Theme,
+}
+
+impl Default for UserPreferences {
+    fn default() -> Self {
+        Self {
+            entries_per_page: 25,
+            timezone: Timezone::default(),
+            theme: Theme::default(),
+        }
+    }
+}
+
+/// A timezone, as a fixed offset from UTC.
+///
+/// A [`Timezone`] is stored as either `UTC` or an offset like `+02:00`, see its
+/// [`FromStr`](std::str::FromStr) and [`Display`](fmt::Display) implementations.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Timezone(pub UtcOffset);
+
+impl Timezone {
+    pub fn offset(&self) -> UtcOffset {
+        self.0
+    }
+}
+
+impl Default for Timezone {
+    fn default() -> Self {
+        Self(UtcOffset::UTC)
+    }
+}
+
+impl fmt::Display for Timezone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_utc() {
+            return write!(f, "UTC");
+        }
+
+        let (hours, minutes, _) = self.0.as_hms();
+        let sign = if self.0.is_negative() { '-' } else { '+' };
+
+        write!(f, "{}{:02}:{:02}", sign, hours.abs(), minutes.abs())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid timezone \"{0}\"")]
+pub struct InvalidTimezone(String);
+
+impl std::str::FromStr for Timezone {
+    type Err = InvalidTimezone;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidTimezone(s.to_string());
+
+        if s.eq_ignore_ascii_case("utc") {
+            return Ok(Self::default());
+        }
+
+        let (sign, offset) = match s.as_bytes().first() {
+            Some(b'+') => (1, &s[1..]),
+            Some(b'-') => (-1, &s[1..]),
+            _ => return Err(invalid()),
+        };
+
+        let (hours, minutes) = offset.split_once(':').ok_or_else(invalid)?;
+        if hours.len() != 2 || minutes.len() != 2 {
+            return Err(invalid());
+        }
+
+        let hours: i8 = hours.parse().map_err(|_| invalid())?;
+        let minutes: i8 = minutes.parse().map_err(|_| invalid())?;
+        if !(0..=14).contains(&hours) || !(0..60).contains(&minutes) {
+            return Err(invalid());
+        }
+
+        let offset = UtcOffset::from_hms(sign * hours, sign * minutes, 0).map_err(|_| invalid())?;
+
+        Ok(Self(offset))
+    }
+}
+
+/// The theme of the user interface.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Theme {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl Theme {
+    /// All the possible themes, used to build a selector.
+    pub const ALL: [Theme; 2] = [Theme::Light, Theme::Dark];
+
+    /// Returns a human readable name of the theme.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Theme::Light => "Light",
+            Theme::Dark => "Dark",
+        }
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Theme::Light => write!(f, "light"),
+            Theme::Dark => write!(f, "dark"),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid theme \"{0}\"")]
+pub struct InvalidTheme(String);
+
+impl std::str::FromStr for Theme {
+    type Err = InvalidTheme;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "light" => Ok(Theme::Light),
+            "dark" => Ok(Theme::Dark),
+            _ => Err(InvalidTheme(s.to_string())),
+        }
+    }
+}
+
+/// Get the preferences of the user `user_id`, the default ones if they were never saved.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred or if a stored preference is invalid.
+#[tracing::instrument(name = "Get user preferences", skip(executor))]
+pub async fn get_user_preferences<'e, E>(
+    executor: E,
+    user_id: UserId,
+) -> Result<UserPreferences, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let record = sqlx::query!(
+        r#"
+        SELECT entries_per_page, timezone, theme
+        FROM user_preferences
+        WHERE user_id = $1
+        "#,
+        &user_id.0,
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to fetch the user preferences")?;
+
+    let record = match record {
+        Some(record) => record,
+        None => return Ok(UserPreferences::default()),
+    };
+
+    let entries_per_page = usize::try_from(record.entries_per_page)
+        .map_err(Into::<anyhow::Error>::into)
+        .context("stored entries per page is invalid")?;
+    let timezone = record
+        .timezone
+        .parse()
+        .map_err(Into::<anyhow::Error>::into)
+        .context("stored timezone is invalid")?;
+    let theme = record
+        .theme
+        .parse()
+        .map_err(Into::<anyhow::Error>::into)
+        .context("stored theme is invalid")?;
+
+    Ok(UserPreferences {
+        entries_per_page,
+        timezone,
+        theme,
+    })
+}
+
+/// Save the `preferences` of the user `user_id`.
+///
+/// # Errors
+///
+/// This function will return an error if a SQL error occurred.
+#[tracing::instrument(name = "Upsert user preferences", skip(executor))]
+pub async fn upsert_user_preferences<'e, E>(
+    executor: E,
+    user_id: UserId,
+    preferences: &UserPreferences,
+) -> Result<(), anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let entries_per_page = i32::try_from(preferences.entries_per_page)
+        .map_err(Into::<anyhow::Error>::into)
+        .context("entries per page is too large")?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO user_preferences(user_id, entries_per_page, timezone, theme)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (user_id) DO UPDATE
+        SET entries_per_page = excluded.entries_per_page,
+            timezone = excluded.timezone,
+            theme = excluded.theme
+        "#,
+        &user_id.0,
+        entries_per_page,
+        preferences.timezone.to_string(),
+        preferences.theme.to_string(),
+    )
+    .execute(executor)
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .context("unable to save the user preferences")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{create_user, get_pool};
+
+    #[test]
+    fn timezone_should_round_trip_through_a_string() {
+        for s in ["UTC", "+02:00", "-05:30", "+14:00", "-00:30"] {
+            let timezone: Timezone = s.parse().unwrap();
+            assert_eq!(s, timezone.to_string());
+        }
+
+        assert_eq!(Timezone::default(), "utc".parse().unwrap());
+        assert_eq!(
+            UtcOffset::from_hms(-5, -30, 0).unwrap(),
+            "-05:30".parse::<Timezone>().unwrap().offset()
+        );
+
+        for s in [
+            "",
+            "Europe/Paris",
+            "02:00",
+            "+2:00",
+            "+15:00",
+            "+02:60",
+            "+02",
+        ] {
+            assert!(s.parse::<Timezone>().is_err(), "{} should be invalid", s);
+        }
+    }
+
+    #[test]
+    fn theme_should_round_trip_through_a_string() {
+        for theme in Theme::ALL {
+            let parsed: Theme = theme.to_string().parse().unwrap();
+            assert_eq!(theme, parsed);
+        }
+
+        assert!("blue".parse::<Theme>().is_err());
+    }
+
+    #[tokio::test]
+    async fn user_preferences_should_default_until_saved() {
+        let pool = get_pool().await;
+        let user_id = create_user(&pool).await;
+
+        let preferences = get_user_preferences(&pool, user_id).await.unwrap();
+        assert_eq!(UserPreferences::default(), preferences);
+
+        // Insert
+
+        let mut preferences = UserPreferences {
+            entries_per_page: 10,
+            timezone: "+02:00".parse().unwrap(),
+            theme: Theme::Dark,
+        };
+        upsert_user_preferences(&pool, user_id, &preferences)
+            .await
+            .unwrap();
+        assert_eq!(
+            preferences,
+            get_user_preferences(&pool, user_id).await.unwrap()
+        );
+
+        // Update
+
+        preferences.entries_per_page = 100;
+        preferences.timezone = Timezone::default();
+        upsert_user_preferences(&pool, user_id, &preferences)
+            .await
+            .unwrap();
+        assert_eq!(
+            preferences,
+            get_user_preferences(&pool, user_id).await.unwrap()
+        );
+    }
+}
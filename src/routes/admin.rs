@@ -0,0 +1,157 @@
+use crate::debug_with_error_chain;
+use crate::domain::{User, UserExtractor, UserId};
+use crate::job::{get_dead_letter_jobs, requeue_dead_letter_job, DeadLetterJob, JobId};
+use crate::routes::{e500, error_redirect, see_other, ADMIN_PAGE};
+use crate::sessions::CsrfToken;
+use actix_web::error::InternalError;
+use actix_web::http;
+use actix_web::web::{Data as WebData, Path as WebPath};
+use actix_web::HttpResponse;
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use askama::Template;
+use sqlx::PgPool;
+
+struct DeadLetterJobForTemplate {
+    original: DeadLetterJob,
+    data: String,
+    failed_at: String,
+    error: String,
+}
+
+impl DeadLetterJobForTemplate {
+    fn new(original: DeadLetterJob) -> Self {
+        let data = original.data.to_string();
+        let failed_at = original.failed_at.to_string();
+        let error = original.error.clone().unwrap_or_default();
+
+        Self {
+            original,
+            data,
+            failed_at,
+            error,
+        }
+    }
+}
+
+#[derive(askama::Template)]
+#[template(path = "admin_dead_letter.html.j2")]
+struct AdminDeadLetterTemplate {
+    pub page: &'static str,
+    pub user_id: Option<UserId>,
+    pub flash_messages: IncomingFlashMessages,
+    pub csrf_token: String,
+    pub jobs: Vec<DeadLetterJobForTemplate>,
+}
+
+#[derive(thiserror::Error)]
+pub enum AdminError {
+    #[error("Forbidden")]
+    Forbidden,
+    #[error("Job not found")]
+    JobNotFound,
+    #[error("Something went wrong")]
+    Unexpected(#[from] anyhow::Error),
+}
+
+debug_with_error_chain!(AdminError);
+
+/// Returns the user if it's an admin, otherwise a 403 Forbidden response.
+fn require_admin(user: UserExtractor) -> Result<User, InternalError<AdminError>> {
+    let user = user.into_inner();
+    if user.is_admin {
+        Ok(user)
+    } else {
+        let response = HttpResponse::Forbidden().finish();
+        Err(InternalError::from_response(
+            AdminError::Forbidden,
+            response,
+        ))
+    }
+}
+
+/// This is the /admin/dead-letter handler.
+///
+/// It lists the jobs which failed too many times; only admins can see it.
+#[tracing::instrument(
+    name = "Admin dead letter",
+    skip(pool, user, flash_messages, csrf_token),
+    fields(
+        user_id = %user.0.id,
+    )
+)]
+pub async fn handle_admin_dead_letter(
+    pool: WebData<PgPool>,
+    user: UserExtractor,
+    flash_messages: IncomingFlashMessages,
+    csrf_token: CsrfToken,
+) -> Result<HttpResponse, InternalError<AdminError>> {
+    let user = require_admin(user)?;
+
+    let jobs = get_dead_letter_jobs(pool.as_ref())
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(AdminError::Unexpected)
+        .map_err(e500)?
+        .into_iter()
+        .map(DeadLetterJobForTemplate::new)
+        .collect();
+
+    // Render
+
+    let tpl = AdminDeadLetterTemplate {
+        page: ADMIN_PAGE,
+        user_id: Some(user.id),
+        flash_messages,
+        csrf_token: csrf_token.0,
+        jobs,
+    };
+    let tpl_rendered = tpl
+        .render()
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(AdminError::Unexpected)
+        .map_err(e500)?;
+
+    let response = HttpResponse::Ok()
+        .content_type(http::header::ContentType::html())
+        .body(tpl_rendered);
+
+    Ok(response)
+}
+
+/// This is the /admin/dead-letter/:job_id/requeue handler.
+///
+/// It moves the job back to the queue with its attempts reset; only admins can do it.
+#[tracing::instrument(
+    name = "Admin dead letter requeue",
+    skip(pool, user, job_id),
+    fields(
+        user_id = %user.0.id,
+        job_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_admin_dead_letter_requeue(
+    pool: WebData<PgPool>,
+    user: UserExtractor,
+    job_id: WebPath<JobId>,
+) -> Result<HttpResponse, InternalError<AdminError>> {
+    require_admin(user)?;
+    let job_id = job_id.into_inner();
+
+    tracing::Span::current().record("job_id", &tracing::field::display(&job_id));
+
+    let found = requeue_dead_letter_job(pool.as_ref(), job_id)
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(AdminError::Unexpected)
+        .map_err(e500)?;
+    if !found {
+        return Err(error_redirect(
+            AdminError::JobNotFound,
+            "/admin/dead-letter",
+        ));
+    }
+
+    FlashMessage::success("Job requeued").send();
+
+    Ok(see_other("/admin/dead-letter"))
+}
@@ -0,0 +1,293 @@
+use crate::debug_with_error_chain;
+use crate::feed::{get_all_feeds, get_feed, get_feed_entries_page};
+use crate::feed::{Feed, FeedEntry, FeedEntryId, FeedId, FeedOrder};
+use crate::routes::api::{api_e500, api_error, get_api_user_id, PaginationCursor};
+use crate::routes::feeds::{add_feed, discover_feed, parse_pasted_url};
+use crate::routes::{DiscoveryCache, FeedAddError};
+use crate::security::AllowPrivateUrls;
+use actix_web::error::InternalError;
+use actix_web::http::StatusCode;
+use actix_web::web::Data as WebData;
+use actix_web::web::Json as WebJson;
+use actix_web::web::Path as WebPath;
+use actix_web::web::Query as WebQuery;
+use actix_web::{HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use url::Url;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// Represents a feed in a JSON API response.
+#[derive(Debug, Serialize)]
+pub struct FeedApiResponse {
+    pub id: FeedId,
+    pub url: Url,
+    pub title: String,
+    pub site_link: Option<Url>,
+    pub description: String,
+    pub language: Option<String>,
+    pub category: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub added_at: OffsetDateTime,
+}
+
+impl From<Feed> for FeedApiResponse {
+    fn from(feed: Feed) -> Self {
+        Self {
+            id: feed.id,
+            url: feed.url,
+            title: feed.title,
+            site_link: feed.site_link,
+            description: feed.description,
+            language: feed.language,
+            category: feed.category.map(|category| category.name),
+            added_at: feed.added_at,
+        }
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum FeedsApiError {
+    #[error("Something went wrong")]
+    Unexpected(#[from] anyhow::Error),
+}
+
+debug_with_error_chain!(FeedsApiError);
+
+/// This is the GET /api/v1/feeds handler.
+///
+/// It returns all the feeds of the user as JSON, in the default feed order.
+#[tracing::instrument(
+    name = "API feeds",
+    skip(req, pool),
+    fields(
+        user_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_api_feeds(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+) -> Result<HttpResponse, InternalError<FeedsApiError>> {
+    let user_id = get_api_user_id(&req)?;
+
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    let feeds = get_all_feeds(pool.as_ref(), user_id, FeedOrder::default())
+        .await
+        .map_err(FeedsApiError::Unexpected)
+        .map_err(api_e500)?;
+
+    let response = feeds
+        .into_iter()
+        .map(Into::into)
+        .collect::<Vec<FeedApiResponse>>();
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[derive(Deserialize)]
+pub struct FeedsAddApiRequest {
+    url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FeedsAddApiResponse {
+    pub id: FeedId,
+}
+
+/// Converts a [`FeedAddError`] to an API error with the appropriate status and code.
+fn feed_add_api_error(err: FeedAddError) -> InternalError<FeedAddError> {
+    let (status, code) = match &err {
+        FeedAddError::NoFeed(_) => (StatusCode::UNPROCESSABLE_ENTITY, "no_feed"),
+        FeedAddError::URLNotAValidRSSFeed(_) => (StatusCode::UNPROCESSABLE_ENTITY, "invalid_feed"),
+        FeedAddError::URLInaccessible(_) => (StatusCode::BAD_GATEWAY, "url_inaccessible"),
+        FeedAddError::URLTimeout => (StatusCode::GATEWAY_TIMEOUT, "url_timeout"),
+        FeedAddError::URLInvalid(_) => (StatusCode::BAD_REQUEST, "invalid_url"),
+        FeedAddError::URLBlocked => (StatusCode::UNPROCESSABLE_ENTITY, "url_blocked"),
+        FeedAddError::FeedAlreadyExists => (StatusCode::CONFLICT, "feed_already_exists"),
+        FeedAddError::Unexpected(_) => return api_e500(err),
+    };
+
+    api_error(err, status, code)
+}
+
+/// This is the POST /api/v1/feeds handler.
+///
+/// It discovers the feed at the URL in the request body and adds it, like the /feeds/add handler.
+/// The response contains the id of the new feed.
+#[tracing::instrument(
+    name = "API feeds add",
+    skip(req, pool, http_client, discovery_cache, allow_private_urls, body),
+    fields(
+        user_id = tracing::field::Empty,
+        url = tracing::field::Empty,
+    )
+)]
+pub async fn handle_api_feeds_add(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    http_client: WebData<reqwest::Client>,
+    discovery_cache: WebData<DiscoveryCache>,
+    allow_private_urls: WebData<AllowPrivateUrls>,
+    body: WebJson<FeedsAddApiRequest>,
+) -> Result<HttpResponse, InternalError<FeedAddError>> {
+    let user_id = get_api_user_id(&req)?;
+
+    let original_url = parse_pasted_url(&body.url)
+        .map_err(FeedAddError::URLInvalid)
+        .map_err(feed_add_api_error)?;
+
+    tracing::Span::current()
+        .record("user_id", &tracing::field::display(&user_id))
+        .record("url", &tracing::field::display(&original_url));
+
+    let feed = discover_feed(
+        &http_client,
+        &discovery_cache,
+        &allow_private_urls,
+        &original_url,
+    )
+    .await
+    .map_err(feed_add_api_error)?;
+
+    let feed_id = add_feed(pool.as_ref(), user_id, feed)
+        .await
+        .map_err(feed_add_api_error)?;
+
+    Ok(HttpResponse::Created().json(FeedsAddApiResponse { id: feed_id }))
+}
+
+/// Represents a feed entry in a JSON API response.
+#[derive(Debug, Serialize)]
+pub struct FeedEntryApiResponse {
+    pub id: FeedEntryId,
+    pub external_id: Option<String>,
+    pub title: String,
+    pub url: Option<Url>,
+    pub summary: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub published_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub read_at: Option<OffsetDateTime>,
+    pub authors: Vec<String>,
+}
+
+impl From<FeedEntry> for FeedEntryApiResponse {
+    fn from(entry: FeedEntry) -> Self {
+        Self {
+            id: entry.id,
+            external_id: entry.external_id,
+            title: entry.title,
+            url: entry.url,
+            summary: entry.summary,
+            published_at: entry.published_at.unwrap_or(entry.created_at),
+            read_at: entry.read_at,
+            authors: entry.authors,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FeedEntriesApiResponse {
+    pub entries: Vec<FeedEntryApiResponse>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct FeedEntriesApiQuery {
+    cursor: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(thiserror::Error)]
+pub enum FeedEntriesApiError {
+    #[error("Invalid cursor")]
+    InvalidCursor,
+    #[error("Feed not found")]
+    FeedNotFound,
+    #[error("Something went wrong")]
+    Unexpected(#[from] anyhow::Error),
+}
+
+debug_with_error_chain!(FeedEntriesApiError);
+
+/// This is the /api/v1/feeds/:feed_id/entries handler.
+///
+/// It returns a page of entries of the feed as JSON, from the most recent to the oldest.
+/// The `next_cursor` field of the response can be used to fetch the next page.
+#[tracing::instrument(
+    name = "API feed entries",
+    skip(req, pool, feed_id, query),
+    fields(
+        user_id = tracing::field::Empty,
+        feed_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_api_feed_entries(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    feed_id: WebPath<FeedId>,
+    query: WebQuery<FeedEntriesApiQuery>,
+) -> Result<HttpResponse, InternalError<FeedEntriesApiError>> {
+    let user_id = get_api_user_id(&req)?;
+    let feed_id = feed_id.into_inner();
+
+    tracing::Span::current()
+        .record("user_id", &tracing::field::display(&user_id))
+        .record("feed_id", &tracing::field::display(&feed_id));
+
+    let cursor = match query.cursor {
+        Some(ref data) => match PaginationCursor::decode(data) {
+            Some(cursor) => Some(cursor),
+            None => {
+                return Err(api_error(
+                    FeedEntriesApiError::InvalidCursor,
+                    StatusCode::BAD_REQUEST,
+                    "invalid_cursor",
+                ))
+            }
+        },
+        None => None,
+    };
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    // Check the feed exists
+
+    let feed = get_feed(pool.as_ref(), user_id, &feed_id)
+        .await
+        .map_err(FeedEntriesApiError::Unexpected)
+        .map_err(api_e500)?;
+    if feed.is_none() {
+        return Err(api_error(
+            FeedEntriesApiError::FeedNotFound,
+            StatusCode::NOT_FOUND,
+            "feed_not_found",
+        ));
+    }
+
+    let page = get_feed_entries_page(
+        pool.as_ref(),
+        user_id,
+        &feed_id,
+        cursor.map(|cursor| cursor.0),
+        limit as usize,
+    )
+    .await
+    .map_err(FeedEntriesApiError::Unexpected)
+    .map_err(api_e500)?;
+
+    let response = FeedEntriesApiResponse {
+        entries: page.entries.into_iter().map(Into::into).collect(),
+        next_cursor: page
+            .next_cursor
+            .map(|entry_id| PaginationCursor(entry_id).encode()),
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
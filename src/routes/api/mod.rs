@@ -0,0 +1,118 @@
+use crate::domain::UserId;
+use crate::feed::FeedEntryId;
+use actix_web::error::InternalError;
+use actix_web::http::StatusCode;
+use actix_web::web::JsonConfig;
+use actix_web::{HttpMessage, HttpRequest, HttpResponse};
+use anyhow::anyhow;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::Serialize;
+use std::fmt;
+
+mod feeds;
+
+pub use feeds::*;
+
+/// An opaque cursor used to paginate through a list of feed entries.
+///
+/// It holds the id of the last entry returned and is exposed to clients as a base64 string.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PaginationCursor(pub FeedEntryId);
+
+impl PaginationCursor {
+    /// Encode the cursor as an opaque base64 string.
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(<[u8; 8]>::from(self.0))
+    }
+
+    /// Decode a cursor previously encoded with [`PaginationCursor::encode`].
+    ///
+    /// Returns None if `data` is not a valid cursor.
+    pub fn decode(data: &str) -> Option<Self> {
+        let bytes = URL_SAFE_NO_PAD.decode(data).ok()?;
+        let bytes: [u8; 8] = bytes.try_into().ok()?;
+
+        Some(Self(FeedEntryId(i64::from_le_bytes(bytes))))
+    }
+}
+
+/// The body of an API error response.
+#[derive(Debug, Serialize)]
+pub struct ApiErrorResponse {
+    /// A human readable description of the error
+    pub error: String,
+    /// A stable, machine readable code identifying the error
+    pub code: &'static str,
+}
+
+/// Builds an [`InternalError<E>`] responding with `status` and a JSON [`ApiErrorResponse`].
+///
+/// The error message is the [`fmt::Display`] of `err`.
+pub fn api_error<E>(err: E, status: StatusCode, code: &'static str) -> InternalError<E>
+where
+    E: fmt::Display,
+{
+    let response = HttpResponse::build(status).json(ApiErrorResponse {
+        error: err.to_string(),
+        code,
+    });
+
+    InternalError::from_response(err, response)
+}
+
+/// Like [`crate::routes::e500`] but responds with a JSON [`ApiErrorResponse`].
+pub fn api_e500<E>(err: E) -> InternalError<E>
+where
+    E: fmt::Display,
+{
+    api_error(err, StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+}
+
+/// Returns the [`JsonConfig`] of the API, which responds with a JSON [`ApiErrorResponse`] if
+/// the request body is invalid.
+pub fn api_json_config() -> JsonConfig {
+    JsonConfig::default()
+        .error_handler(|err, _req| api_error(err, StatusCode::BAD_REQUEST, "invalid_body").into())
+}
+
+/// This is a helper function used to extract the [`UserId`] of an API request.
+///
+/// API requests must be authenticated with an API token (see [`crate::authentication::ApiTokenMiddleware`]),
+/// if there's no user id in the request extensions this returns a [`InternalError<E>`] with the code 401 Unauthorized.
+pub fn get_api_user_id<E>(req: &HttpRequest) -> Result<UserId, InternalError<E>>
+where
+    E: From<anyhow::Error> + fmt::Display + fmt::Debug + 'static,
+{
+    match req.extensions().get::<UserId>().copied() {
+        Some(user_id) => Ok(user_id),
+        None => {
+            let err = anyhow!("The request is not authenticated with an API token");
+
+            Err(api_error(
+                err.into(),
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pagination_cursor_should_roundtrip() {
+        let cursor = PaginationCursor(FeedEntryId(20394));
+
+        let data = cursor.encode();
+        assert_eq!(Some(cursor), PaginationCursor::decode(&data));
+    }
+
+    #[test]
+    fn pagination_cursor_should_reject_invalid_data() {
+        assert_eq!(None, PaginationCursor::decode("foobar"));
+        assert_eq!(None, PaginationCursor::decode("!!!"));
+    }
+}
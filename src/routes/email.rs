@@ -0,0 +1,40 @@
+use crate::domain::UserExtractor;
+use crate::routes::e500;
+use crate::tem;
+use actix_web::error::InternalError;
+use actix_web::web::Data as WebData;
+use actix_web::HttpResponse;
+use anyhow::anyhow;
+
+/// Sends a test email to the current user.
+///
+/// This is a smoke test of the email configuration, only registered in test mode.
+#[tracing::instrument(
+    name = "Test email",
+    skip(user, tem_client),
+    fields(
+        user_id = %user.0.id,
+    )
+)]
+pub async fn handle_test_email(
+    user: UserExtractor,
+    tem_client: Option<WebData<tem::Client>>,
+) -> Result<HttpResponse, InternalError<anyhow::Error>> {
+    let tem_client = tem_client
+        .ok_or_else(|| anyhow!("emails are disabled"))
+        .map_err(e500)?;
+    let user = user.into_inner();
+
+    tem_client
+        .send_email_single(
+            &user.email,
+            "Servare test email",
+            "<p>This is a test email sent by Servare.</p>",
+            "This is a test email sent by Servare.",
+        )
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -1,28 +1,47 @@
 use crate::domain::UserId;
-use crate::feed::{feed_with_url_exists, find_feed, insert_feed};
+use crate::feed::{add_feed_tag, get_feeds_tags, get_or_create_tag, remove_feed_tag};
 use crate::feed::{
-    get_all_feeds, get_feed, get_feed_entries, get_feed_entry, get_feed_favicon,
-    mark_feed_entry_as_read,
+    count_feed_entries, count_feed_entries_up_to, delete_feed, get_all_feeds,
+    get_all_feeds_with_unread_counts, get_feed, get_feed_entries_page, get_feed_entry,
+    get_feed_favicon, get_or_create_category, get_user_auto_mark_read_on_view, get_user_feed_order,
+    mark_all_feed_entries_as_read, mark_feed_entries_as_read, mark_feed_entry_as_read,
+    mark_feed_entry_as_starred, set_feed_category, unstar_feed_entry,
 };
-use crate::feed::{Feed, FeedId, FindError, FoundFeed, ParseError, ParsedFeed};
-use crate::feed::{FeedEntry, FeedEntryId};
-use crate::job::{post_fetch_favicon_job, post_refresh_feed_job};
+use crate::feed::{
+    feed_with_url_exists, find_all_feeds, insert_feed, normalize_feed_url, parse_json_feed,
+};
+use crate::feed::{
+    FaviconCache, Feed, FeedCategory, FeedId, FeedOrder, FindError, FoundFeed, ParseError,
+    ParsedFeed,
+};
+use crate::feed::{FeedEntry, FeedEntryId, FeedTag, FeedTagId};
+use crate::html::sanitize_html;
+use crate::job::{cancel_job, get_pending_feed_jobs};
+use crate::job::{post_fetch_favicon_job, post_refresh_feed_job, post_refresh_feed_jobs};
+use crate::preferences::{get_user_preferences, Timezone};
 use crate::routes::FEEDS_PAGE;
 use crate::routes::{e500, error_redirect, get_user_id_or_redirect, see_other};
-use crate::sessions::TypedSession;
+use crate::security::{is_safe_url, AllowPrivateUrls};
+use crate::sessions::{CsrfToken, TypedSession};
 use crate::telemetry::spawn_blocking_with_tracing;
 use crate::{debug_with_error_chain, fetch_bytes};
 use actix_web::error::InternalError;
 use actix_web::http;
-use actix_web::web::{Data as WebData, Form as WebForm, Path as WebPath};
-use actix_web::HttpResponse;
+use actix_web::web::{Data as WebData, Form as WebForm, Path as WebPath, Query as WebQuery};
+use actix_web::{HttpRequest, HttpResponse};
 use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
 use anyhow::Context;
 use askama::Template;
+use bytes::Bytes;
+use futures_util::future::try_join_all;
+use lru::LruCache;
 use serde::Deserialize;
 use sqlx::PgPool;
 use std::fmt;
-use tracing::{event, warn, Level};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration as StdDuration, Instant};
+use tracing::{event, Level};
 use url::Url;
 
 #[derive(askama::Template)]
@@ -31,50 +50,123 @@ struct FeedsTemplate {
     pub page: &'static str,
     pub user_id: Option<UserId>,
     pub flash_messages: IncomingFlashMessages,
-    pub feeds: Vec<FeedForTemplate>,
+    pub csrf_token: String,
+    pub groups: Vec<FeedGroupForTemplate>,
 }
 
 struct FeedForTemplate {
     original: Feed,
-    site_link: Option<Url>,
+    /// The site link, or the feed URL if the site link is missing or invalid.
+    site_link_or_url: Url,
     has_favicon: bool,
+    category_name: String,
+    tags: Vec<FeedTag>,
+    unread_count: i64,
 }
 
 impl FeedForTemplate {
     fn new(feed: Feed) -> Self {
+        let site_link_or_url = match &feed.site_link {
+            Some(site_link) => site_link.clone(),
+            None => {
+                event!(
+                    Level::WARN,
+                    feed_id = %feed.id,
+                    url = %feed.url,
+                    "feed has no valid site link, using the feed URL instead"
+                );
+                feed.url.clone()
+            }
+        };
+
         Self {
-            site_link: feed.site_link.clone(),
+            site_link_or_url,
             has_favicon: feed.site_favicon.is_some(),
+            category_name: feed
+                .category
+                .as_ref()
+                .map(|category| category.name.clone())
+                .unwrap_or_default(),
+            tags: Vec::new(),
+            unread_count: 0,
             original: feed,
         }
     }
 }
 
+struct FeedGroupForTemplate {
+    category: Option<FeedCategory>,
+    feeds: Vec<FeedForTemplate>,
+}
+
+/// Groups the `feeds` by category.
+///
+/// The groups are sorted by category name, the feeds without a category come last.
+/// Within a group the feeds keep their original order.
+fn group_feeds_by_category(feeds: Vec<FeedForTemplate>) -> Vec<FeedGroupForTemplate> {
+    let mut groups: Vec<FeedGroupForTemplate> = Vec::new();
+
+    for feed in feeds {
+        let category = feed.original.category.clone();
+
+        match groups.iter_mut().find(|group| group.category == category) {
+            Some(group) => group.feeds.push(feed),
+            None => groups.push(FeedGroupForTemplate {
+                category,
+                feeds: vec![feed],
+            }),
+        }
+    }
+
+    groups.sort_by_cached_key(|group| match &group.category {
+        Some(category) => (false, category.name.to_lowercase()),
+        None => (true, String::new()),
+    });
+
+    groups
+}
+
 #[tracing::instrument(
     name = "Feeds",
-    skip(pool, session, flash_messages),
+    skip(req, pool, session, flash_messages, csrf_token),
     fields(
         user_id = tracing::field::Empty,
     )
 )]
 pub async fn handle_feeds(
+    req: HttpRequest,
     pool: WebData<PgPool>,
     session: TypedSession,
     flash_messages: IncomingFlashMessages,
+    csrf_token: CsrfToken,
 ) -> Result<HttpResponse, InternalError<anyhow::Error>> {
-    let user_id = get_user_id_or_redirect(&session)?;
+    let user_id = get_user_id_or_redirect(&req, &session)?;
 
     tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
 
     //
 
     // TODO(vincent): can we handle this better ?
-    let original_feeds = get_all_feeds(pool.as_ref(), user_id).await.map_err(e500)?;
+    let feed_order = get_user_feed_order(pool.as_ref(), user_id)
+        .await
+        .map_err(e500)?;
+    let original_feeds = get_all_feeds_with_unread_counts(pool.as_ref(), user_id, feed_order)
+        .await
+        .map_err(e500)?;
+    let mut feeds_tags = get_feeds_tags(pool.as_ref(), user_id).await.map_err(e500)?;
 
     let feeds = original_feeds
         .into_iter()
-        .map(FeedForTemplate::new)
+        .map(|(feed, unread_count)| {
+            let tags = feeds_tags.remove(&feed.id).unwrap_or_default();
+            FeedForTemplate {
+                tags,
+                unread_count,
+                ..FeedForTemplate::new(feed)
+            }
+        })
         .collect();
+    let groups = group_feeds_by_category(feeds);
 
     //
 
@@ -82,7 +174,8 @@ pub async fn handle_feeds(
         page: FEEDS_PAGE,
         user_id: Some(user_id),
         flash_messages,
-        feeds,
+        csrf_token: csrf_token.0,
+        groups,
     };
     let tpl_rendered = tpl
         .render()
@@ -109,8 +202,12 @@ pub enum FeedAddError {
     URLNotAValidRSSFeed(#[from] ParseError),
     #[error("URL is inaccessible")]
     URLInaccessible(#[source] reqwest::Error),
+    #[error("URL took too long to respond")]
+    URLTimeout,
     #[error("URL is invalid")]
     URLInvalid(#[source] url::ParseError),
+    #[error("URL points to a private network address, which is not allowed")]
+    URLBlocked,
     #[error("Feed already exists")]
     FeedAlreadyExists,
     #[error("Something went wrong")]
@@ -119,6 +216,14 @@ pub enum FeedAddError {
 
 debug_with_error_chain!(FeedAddError);
 
+/// Removes what usually surrounds a URL pasted by a user: ASCII whitespace, angle brackets and
+/// double quotes.
+fn normalize_pasted_url(input: &str) -> String {
+    input
+        .trim_matches(|c: char| c.is_ascii_whitespace() || matches!(c, '<' | '>' | '"'))
+        .to_string()
+}
+
 fn guess_url(url: String) -> Result<Url, url::ParseError> {
     if url.starts_with("https://") || url.starts_with("http://") {
         return Url::parse(&url);
@@ -131,6 +236,389 @@ fn guess_url(url: String) -> Result<Url, url::ParseError> {
     }
 }
 
+/// How long the data fetched at a URL stays in the [`DiscoveryCache`].
+pub const DISCOVERY_CACHE_TTL: StdDuration = StdDuration::from_secs(30);
+
+/// The maximum number of URLs in the [`DiscoveryCache`].
+pub const DISCOVERY_CACHE_CAPACITY: usize = 100;
+
+/// An in-memory LRU cache of the data fetched while discovering a feed, used to avoid fetching
+/// the same URL again when a user submits it multiple times in a short time.
+///
+/// Entries expire after a TTL; expired entries are only removed when accessed.
+#[derive(Clone)]
+pub struct DiscoveryCache {
+    inner: Arc<Mutex<LruCache<Url, (Instant, Bytes)>>>,
+    ttl: StdDuration,
+}
+
+impl DiscoveryCache {
+    /// Creates a new cache holding at most `capacity` URLs (at least one) for `ttl`.
+    pub fn new(capacity: usize, ttl: StdDuration) -> Self {
+        let capacity = NonZeroUsize::new(capacity.max(1)).expect("capacity should never be zero");
+
+        Self {
+            inner: Arc::new(Mutex::new(LruCache::new(capacity))),
+            ttl,
+        }
+    }
+
+    pub fn get(&self, url: &Url) -> Option<Bytes> {
+        let mut inner = self.inner.lock().expect("discovery cache lock poisoned");
+
+        match inner.get(url) {
+            Some((fetched_at, data)) if fetched_at.elapsed() < self.ttl => Some(data.clone()),
+            Some(_) => {
+                inner.pop(url);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, url: Url, data: Bytes) {
+        let mut inner = self.inner.lock().expect("discovery cache lock poisoned");
+        inner.put(url, (Instant::now(), data));
+    }
+}
+
+/// Fetches the content of `url` like [`fetch_bytes`], using `discovery_cache` if possible.
+async fn fetch_bytes_cached(
+    http_client: &reqwest::Client,
+    discovery_cache: &DiscoveryCache,
+    url: &Url,
+) -> Result<Bytes, reqwest::Error> {
+    if let Some(data) = discovery_cache.get(url) {
+        event!(Level::DEBUG, url = %url, "found the URL data in the discovery cache");
+        return Ok(data);
+    }
+
+    let data = fetch_bytes(http_client, url).await?;
+    discovery_cache.insert(url.clone(), data.clone());
+
+    Ok(data)
+}
+
+/// Checks that `url` doesn't point to the internal network of the server, see [`is_safe_url`].
+///
+/// The check is skipped if `allow_private_urls` is set.
+///
+/// # Errors
+///
+/// This function will return [`FeedAddError::URLBlocked`] if the URL is not safe to fetch.
+async fn check_url_is_safe(
+    allow_private_urls: &AllowPrivateUrls,
+    url: &Url,
+) -> Result<(), FeedAddError> {
+    if allow_private_urls.0 {
+        return Ok(());
+    }
+
+    // Resolving the host is blocking
+    let url_to_check = url.clone();
+
+    let is_safe = spawn_blocking_with_tracing(move || is_safe_url(&url_to_check))
+        .await
+        .context("Failed to spawn blocking task")
+        .map_err(FeedAddError::Unexpected)?;
+    if !is_safe {
+        event!(Level::WARN, url = %url, "URL points to a private address");
+        return Err(FeedAddError::URLBlocked);
+    }
+
+    Ok(())
+}
+
+/// The maximum time spent fetching a feed found in a HTML document.
+const FETCH_FEED_TIMEOUT: StdDuration = StdDuration::from_secs(10);
+
+/// Fetch the feed at `url` and parse it.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * the fetch fails or takes longer than [`FETCH_FEED_TIMEOUT`]
+/// * the data is not a valid feed
+async fn fetch_and_parse_feed(
+    http_client: &reqwest::Client,
+    discovery_cache: &DiscoveryCache,
+    url: &Url,
+) -> Result<ParsedFeed, FeedAddError> {
+    let response_bytes = tokio::time::timeout(
+        FETCH_FEED_TIMEOUT,
+        fetch_bytes_cached(http_client, discovery_cache, url),
+    )
+    .await
+    .map_err(|_| FeedAddError::URLTimeout)?
+    .map_err(FeedAddError::URLInaccessible)?;
+
+    let feed = match parse_json_feed(url, &response_bytes[..]) {
+        Some((feed, _)) => feed,
+        None => ParsedFeed::parse(url, &response_bytes[..])?,
+    };
+
+    Ok(feed)
+}
+
+/// Parses a URL pasted by a user, guessing its scheme if it doesn't have one.
+pub(super) fn parse_pasted_url(input: &str) -> Result<Url, url::ParseError> {
+    guess_url(normalize_pasted_url(input))
+}
+
+/// Discovers the feed at `original_url`.
+///
+/// The URL can either be a RSS, Atom or JSON feed or a website containing a link to such a feed.
+/// If the website links to multiple feeds the first one is used.
+///
+/// # Errors
+///
+/// This function will return an error if the URL is inaccessible or blocked or if no valid feed
+/// is found.
+pub(super) async fn discover_feed(
+    http_client: &reqwest::Client,
+    discovery_cache: &DiscoveryCache,
+    allow_private_urls: &AllowPrivateUrls,
+    original_url: &Url,
+) -> Result<ParsedFeed, FeedAddError> {
+    let found_feed = discover_feeds(
+        http_client,
+        discovery_cache,
+        allow_private_urls,
+        original_url,
+    )
+    .await?
+    .into_iter()
+    .next()
+    .ok_or(FeedAddError::NoFeed(FindError::NoFeed))?;
+
+    resolve_found_feed(
+        http_client,
+        discovery_cache,
+        allow_private_urls,
+        original_url,
+        found_feed,
+    )
+    .await
+}
+
+/// Discovers all the candidate feeds at `original_url`.
+///
+/// If the URL is a feed it is the only candidate, otherwise every feed linked from the website is
+/// a candidate. Use [`resolve_found_feed`] to get the actual feed of a candidate.
+///
+/// # Errors
+///
+/// This function will return an error if the URL is inaccessible or blocked or if no feed is
+/// found.
+async fn discover_feeds(
+    http_client: &reqwest::Client,
+    discovery_cache: &DiscoveryCache,
+    allow_private_urls: &AllowPrivateUrls,
+    original_url: &Url,
+) -> Result<Vec<FoundFeed>, FeedAddError> {
+    // 1) Fetch the data at the URL
+    // We don't know yet if it's a website or a straight-up feed.
+
+    check_url_is_safe(allow_private_urls, original_url).await?;
+
+    let response_bytes = fetch_bytes_cached(http_client, discovery_cache, original_url)
+        .await
+        .map_err(FeedAddError::URLInaccessible)?;
+
+    // 2) Find the feeds
+    //
+    // Note we spawn a blocking task to avoid taking too much time parsing the data
+
+    // TODO(vincent): how can we avoid a clone here ?
+    let find_feed_url = original_url.clone();
+
+    let found_feeds =
+        spawn_blocking_with_tracing(move || find_all_feeds(&find_feed_url, &response_bytes[..]))
+            .await
+            .context("Failed to spawn blocking task")
+            .map_err(Into::<anyhow::Error>::into)
+            .map_err(FeedAddError::Unexpected)?;
+    if found_feeds.is_empty() {
+        return Err(FeedAddError::NoFeed(FindError::NoFeed));
+    }
+
+    Ok(found_feeds)
+}
+
+/// Returns the feed of `found_feed`, discovered at `original_url`.
+///
+/// If `found_feed` is a URL linked from a website the feed is fetched and parsed, provided the
+/// URL is safe to fetch.
+async fn resolve_found_feed(
+    http_client: &reqwest::Client,
+    discovery_cache: &DiscoveryCache,
+    allow_private_urls: &AllowPrivateUrls,
+    original_url: &Url,
+    found_feed: FoundFeed,
+) -> Result<ParsedFeed, FeedAddError> {
+    let feed = match found_feed {
+        FoundFeed::Url(url) => {
+            event!(Level::INFO,
+                url = %url,
+                "original URL was a HTML document containing a RSS feed URL",
+            );
+
+            check_url_is_safe(allow_private_urls, &url).await?;
+
+            fetch_and_parse_feed(http_client, discovery_cache, &url).await?
+        }
+        FoundFeed::Raw(raw_feed) => {
+            event!(Level::INFO, "original URL was a RSS feed");
+
+            ParsedFeed::from_raw_feed(original_url, raw_feed)
+        }
+        FoundFeed::Json(feed) => {
+            event!(Level::INFO, "original URL was a JSON feed");
+
+            feed
+        }
+    };
+
+    event!(Level::INFO,
+        title = %feed.title,
+        site_link = feed.site_link.as_ref().map(|v|v.to_string()).unwrap_or_default(),
+        "Fetched feed",
+    );
+
+    Ok(feed)
+}
+
+/// The maximum time spent fetching all the candidate feeds of a website.
+const FETCH_CANDIDATE_FEEDS_TIMEOUT: StdDuration = StdDuration::from_secs(15);
+
+/// A feed found at a URL linking to multiple feeds.
+struct CandidateFeed {
+    url: Url,
+    /// The title of the feed, missing if the feed couldn't be fetched.
+    title: Option<String>,
+}
+
+/// Fetches all the `found_feeds` discovered at `original_url` in parallel, to get their titles.
+///
+/// A candidate which can't be fetched or parsed is still returned, without a title. The same
+/// goes for all the candidates if fetching them takes longer than
+/// [`FETCH_CANDIDATE_FEEDS_TIMEOUT`].
+///
+/// # Errors
+///
+/// This function will only return an error if something unexpected happened.
+async fn fetch_candidate_feeds(
+    http_client: &reqwest::Client,
+    discovery_cache: &DiscoveryCache,
+    allow_private_urls: &AllowPrivateUrls,
+    original_url: &Url,
+    found_feeds: Vec<FoundFeed>,
+) -> Result<Vec<CandidateFeed>, FeedAddError> {
+    let urls: Vec<Url> = found_feeds
+        .iter()
+        .map(|found_feed| match found_feed {
+            FoundFeed::Url(url) => url.clone(),
+            FoundFeed::Raw(_) | FoundFeed::Json(_) => original_url.clone(),
+        })
+        .collect();
+
+    let fetches = found_feeds.into_iter().map(|found_feed| async move {
+        let result = resolve_found_feed(
+            http_client,
+            discovery_cache,
+            allow_private_urls,
+            original_url,
+            found_feed,
+        )
+        .await;
+
+        match result {
+            Ok(feed) => Ok(Some(feed.title)),
+            Err(err @ FeedAddError::Unexpected(_)) => Err(err),
+            Err(err) => {
+                event!(Level::WARN, %err, "unable to fetch a candidate feed");
+                Ok(None)
+            }
+        }
+    });
+
+    let titles =
+        match tokio::time::timeout(FETCH_CANDIDATE_FEEDS_TIMEOUT, try_join_all(fetches)).await {
+            Ok(titles) => titles?,
+            Err(_) => {
+                event!(Level::WARN, "fetching the candidate feeds took too long");
+                vec![None; urls.len()]
+            }
+        };
+
+    let candidates = urls
+        .into_iter()
+        .zip(titles)
+        .map(|(url, title)| CandidateFeed { url, title })
+        .collect();
+
+    Ok(candidates)
+}
+
+/// Stores the discovered `feed` for the user `user_id`.
+///
+/// The feed URL is normalized with [`normalize_feed_url`] first so that equivalent URLs are
+/// detected as duplicates.
+///
+/// The jobs needed to fetch the favicon and the entries of the feed are added in the same
+/// transaction: a feed without its jobs would never be refreshed.
+///
+/// # Errors
+///
+/// This function will return an error if the feed already exists or if there's a SQL error.
+pub(super) async fn add_feed(
+    pool: &PgPool,
+    user_id: UserId,
+    mut feed: ParsedFeed,
+) -> Result<FeedId, FeedAddError> {
+    feed.url = normalize_feed_url(&feed.url);
+
+    // 1) Check if the feed already exists
+
+    let feed_exists = feed_with_url_exists(pool, user_id, &feed.url)
+        .await
+        .map_err(FeedAddError::Unexpected)?;
+    if feed_exists {
+        return Err(FeedAddError::FeedAlreadyExists);
+    }
+
+    // 2) Insert the feed and add the needed background jobs
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(FeedAddError::Unexpected)?;
+
+    let feed_id = insert_feed(&mut tx, user_id, &feed)
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .context("unable to save feed")?;
+
+    if let Some(url) = feed.site_link {
+        post_fetch_favicon_job(&mut tx, user_id, feed_id, url)
+            .await
+            .map_err(Into::<anyhow::Error>::into)
+            .context("unable to add fetch favicon job")?;
+    }
+    post_refresh_feed_job(&mut tx, user_id, feed_id, feed.url)
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .context("unable to add refresh feed job")?;
+
+    tx.commit()
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .context("unable to commit the feed")?;
+
+    Ok(feed_id)
+}
+
 /// This is the handler for /feeds/add.
 /// Its job is to:
 /// * find a feed for a given URL
@@ -145,7 +633,7 @@ fn guess_url(url: String) -> Result<Url, url::ParseError> {
 /// This function will return an error if .
 #[tracing::instrument(
     name = "Add feed",
-    skip(pool, http_client, session, form_data),
+    skip(req, pool, http_client, discovery_cache, allow_private_urls, session, form_data),
     fields(
         user_id = tracing::field::Empty,
         url = tracing::field::Empty,
@@ -153,16 +641,19 @@ fn guess_url(url: String) -> Result<Url, url::ParseError> {
     )
 )]
 pub async fn handle_feeds_add(
+    req: HttpRequest,
     pool: WebData<PgPool>,
     http_client: WebData<reqwest::Client>,
+    discovery_cache: WebData<DiscoveryCache>,
+    allow_private_urls: WebData<AllowPrivateUrls>,
     session: TypedSession,
     form_data: WebForm<FeedAddFormData>,
 ) -> Result<HttpResponse, InternalError<FeedAddError>> {
-    let user_id = get_user_id_or_redirect(&session)?;
+    let user_id = get_user_id_or_redirect(&req, &session)?;
 
     // The URL might not have a scheme, try to guess it
 
-    let original_url = guess_url(form_data.0.url)
+    let original_url = parse_pasted_url(&form_data.0.url)
         .map_err(FeedAddError::URLInvalid)
         .map_err(feeds_page_redirect)?;
 
@@ -172,234 +663,728 @@ pub async fn handle_feeds_add(
         .record("user_id", &tracing::field::display(&user_id))
         .record("url", &tracing::field::display(&original_url));
 
-    // 1) Fetch the data at the URL
-    // We don't know yet if it's a website or a straight-up feed.
+    let mut found_feeds = discover_feeds(
+        &http_client,
+        &discovery_cache,
+        &allow_private_urls,
+        &original_url,
+    )
+    .await
+    .map_err(feeds_page_redirect)?;
+
+    // The website links to multiple feeds, let the user choose one
 
-    let response_bytes = fetch_bytes(&http_client, &original_url)
+    if found_feeds.len() > 1 {
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("url", original_url.as_str())
+            .finish();
+
+        return Ok(see_other(&format!("/feeds/add/choose?{}", query)));
+    }
+
+    let feed = resolve_found_feed(
+        &http_client,
+        &discovery_cache,
+        &allow_private_urls,
+        &original_url,
+        found_feeds.remove(0),
+    )
+    .await
+    .map_err(feeds_page_redirect)?;
+
+    add_feed(pool.as_ref(), user_id, feed)
         .await
-        .map_err(FeedAddError::URLInaccessible)
         .map_err(feeds_page_redirect)?;
 
-    // 1) Find the feed
+    FlashMessage::success("Found a feed").send();
+
+    Ok(see_other("/feeds"))
+}
+
+#[derive(askama::Template)]
+#[template(path = "feeds_add_choose.html.j2")]
+struct FeedsAddChooseTemplate {
+    pub page: &'static str,
+    pub user_id: Option<UserId>,
+    pub flash_messages: IncomingFlashMessages,
+    pub csrf_token: String,
+    pub original_url: Url,
+    pub candidates: Vec<CandidateFeed>,
+}
+
+#[derive(Deserialize)]
+pub struct FeedAddChooseQuery {
+    pub url: String,
+}
+
+/// This is the handler for /feeds/add/choose.
+///
+/// It lists the feeds found at a URL linking to multiple feeds so that the user can add one.
+/// The feeds are fetched to show their titles.
+#[tracing::instrument(
+    name = "Add feed choose",
+    skip(
+        req,
+        http_client,
+        discovery_cache,
+        allow_private_urls,
+        session,
+        flash_messages,
+        csrf_token,
+        query
+    ),
+    fields(
+        user_id = tracing::field::Empty,
+        url = tracing::field::Empty,
+    )
+)]
+pub async fn handle_feeds_add_choose(
+    req: HttpRequest,
+    http_client: WebData<reqwest::Client>,
+    discovery_cache: WebData<DiscoveryCache>,
+    allow_private_urls: WebData<AllowPrivateUrls>,
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+    csrf_token: CsrfToken,
+    query: WebQuery<FeedAddChooseQuery>,
+) -> Result<HttpResponse, InternalError<FeedAddError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+
+    let original_url = Url::parse(&query.0.url)
+        .map_err(FeedAddError::URLInvalid)
+        .map_err(feeds_page_redirect)?;
+
+    tracing::Span::current()
+        .record("user_id", &tracing::field::display(&user_id))
+        .record("url", &tracing::field::display(&original_url));
+
+    // The data was just fetched by the add handler so this should hit the discovery cache
+
+    let found_feeds = discover_feeds(
+        &http_client,
+        &discovery_cache,
+        &allow_private_urls,
+        &original_url,
+    )
+    .await
+    .map_err(feeds_page_redirect)?;
+
+    let candidates = fetch_candidate_feeds(
+        &http_client,
+        &discovery_cache,
+        &allow_private_urls,
+        &original_url,
+        found_feeds,
+    )
+    .await
+    .map_err(feeds_page_redirect)?;
+
     //
-    // Note we spawn a blocking task to avoid taking too much time parsing the data
 
-    // TODO(vincent): how can we avoid a clone here ?
-    let find_feed_url = original_url.clone();
+    let tpl = FeedsAddChooseTemplate {
+        page: FEEDS_PAGE,
+        user_id: Some(user_id),
+        flash_messages,
+        csrf_token: csrf_token.0,
+        original_url,
+        candidates,
+    };
+    let tpl_rendered = tpl
+        .render()
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(FeedAddError::Unexpected)
+        .map_err(e500)?;
+
+    let response = HttpResponse::Ok()
+        .content_type(http::header::ContentType::html())
+        .body(tpl_rendered);
+
+    Ok(response)
+}
+
+#[derive(askama::Template)]
+#[template(path = "feeds_add.html.j2")]
+struct FeedsAddTemplate {
+    pub page: &'static str,
+    pub user_id: Option<UserId>,
+    pub flash_messages: IncomingFlashMessages,
+    pub csrf_token: String,
+}
+
+#[tracing::instrument(
+    name = "Feeds add form",
+    skip(req, session, flash_messages, csrf_token),
+    fields(
+        user_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_feeds_add_form(
+    req: HttpRequest,
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+    csrf_token: CsrfToken,
+) -> Result<HttpResponse, InternalError<anyhow::Error>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    let tpl = FeedsAddTemplate {
+        page: FEEDS_PAGE,
+        user_id: Some(user_id),
+        flash_messages,
+        csrf_token: csrf_token.0,
+    };
+    let tpl_rendered = tpl
+        .render()
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(e500)?;
+
+    let response = HttpResponse::Ok()
+        .content_type(http::header::ContentType::html())
+        .body(tpl_rendered);
+
+    Ok(response)
+}
+
+#[derive(thiserror::Error)]
+pub enum FeedRefreshError {
+    #[error("Something went wrong")]
+    Unexpected(#[from] anyhow::Error),
+}
+
+debug_with_error_chain!(FeedRefreshError);
+
+/// This is the /feeds/refresh handler.
+///
+/// Adds a refresh feed job for every feed, regardless of when it was last refreshed.
+///
+/// Feeds are refreshed periodically by the job runner, this is only useful to force a refresh.
+#[tracing::instrument(
+    name = "Feeds refresh",
+    skip(req, pool, session),
+    fields(
+        user_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_feeds_refresh(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    session: TypedSession,
+) -> Result<HttpResponse, InternalError<FeedRefreshError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+
+    // Collect all feeds first: the read doesn't need to be in the same transaction as the
+    // insertion of the jobs, which would hold locks for longer than necessary.
+
+    let feeds = get_all_feeds(pool.as_ref(), user_id, FeedOrder::default())
+        .await
+        .map_err(FeedRefreshError::Unexpected)
+        .map_err(feeds_page_redirect)?;
+
+    // Then add a refresh job for every feed in a single batch
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(FeedRefreshError::Unexpected)
+        .map_err(feeds_page_redirect)?;
+
+    post_refresh_feed_jobs(
+        &mut tx,
+        user_id,
+        feeds.into_iter().map(|feed| (feed.id, feed.url)),
+    )
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .map_err(FeedRefreshError::Unexpected)
+    .map_err(feeds_page_redirect)?;
+
+    tx.commit()
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(FeedRefreshError::Unexpected)
+        .map_err(feeds_page_redirect)?;
+
+    // Done, redirect to the feed list
+
+    FlashMessage::success("Refresh started").send();
+
+    let response = HttpResponse::SeeOther()
+        .insert_header((http::header::LOCATION, "/feeds"))
+        .finish();
+
+    Ok(response)
+}
+
+#[derive(Deserialize)]
+pub struct FeedCategoryFormData {
+    pub category: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum FeedCategoryError {
+    #[error("Feed not found")]
+    NotFound,
+    #[error("Something went wrong")]
+    Unexpected(#[from] anyhow::Error),
+}
+
+debug_with_error_chain!(FeedCategoryError);
+
+/// This is the /feeds/:feed_id/category handler.
+///
+/// Sets the category of the feed, creating the category if necessary.
+/// An empty category name removes the feed from its category.
+#[tracing::instrument(
+    name = "Feed category",
+    skip(req, pool, session, feed_id, form_data),
+    fields(
+        user_id = tracing::field::Empty,
+        feed_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_feed_category(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    session: TypedSession,
+    feed_id: WebPath<FeedId>,
+    form_data: WebForm<FeedCategoryFormData>,
+) -> Result<HttpResponse, InternalError<FeedCategoryError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+    let feed_id = feed_id.into_inner();
+
+    tracing::Span::current()
+        .record("user_id", &tracing::field::display(&user_id))
+        .record("feed_id", &tracing::field::display(&feed_id));
+
+    let name = form_data.0.category.trim().to_string();
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(FeedCategoryError::Unexpected)
+        .map_err(feeds_page_redirect)?;
+
+    let category_id = if name.is_empty() {
+        None
+    } else {
+        let category_id = get_or_create_category(&mut tx, user_id, &name)
+            .await
+            .map_err(FeedCategoryError::Unexpected)
+            .map_err(feeds_page_redirect)?;
+
+        Some(category_id)
+    };
+
+    let found = set_feed_category(&mut tx, user_id, &feed_id, category_id)
+        .await
+        .map_err(FeedCategoryError::Unexpected)
+        .map_err(feeds_page_redirect)?;
+    if !found {
+        return Err(feeds_page_redirect(FeedCategoryError::NotFound));
+    }
+
+    tx.commit()
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(FeedCategoryError::Unexpected)
+        .map_err(feeds_page_redirect)?;
+
+    FlashMessage::success("Category updated").send();
+
+    Ok(see_other("/feeds"))
+}
+
+#[derive(thiserror::Error)]
+pub enum FeedDeleteError {
+    #[error("Feed not found")]
+    NotFound,
+    #[error("Something went wrong")]
+    Unexpected(#[from] anyhow::Error),
+}
+
+debug_with_error_chain!(FeedDeleteError);
+
+/// This is the DELETE /feeds/:feed_id handler.
+///
+/// Deletes the feed and its entries, and cancels its pending jobs so they're not run anymore.
+#[tracing::instrument(
+    name = "Feed delete",
+    skip(req, pool, session, feed_id),
+    fields(
+        user_id = tracing::field::Empty,
+        feed_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_feed_delete(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    session: TypedSession,
+    feed_id: WebPath<FeedId>,
+) -> Result<HttpResponse, InternalError<FeedDeleteError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+    let feed_id = feed_id.into_inner();
+
+    tracing::Span::current()
+        .record("user_id", &tracing::field::display(&user_id))
+        .record("feed_id", &tracing::field::display(&feed_id));
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(FeedDeleteError::Unexpected)
+        .map_err(feeds_page_redirect)?;
+
+    // Cancel the pending jobs of the feed.
+    // A job already running isn't cancelled: it finds the feed deleted and does nothing.
+
+    let job_ids = get_pending_feed_jobs(&mut tx, user_id, feed_id)
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(FeedDeleteError::Unexpected)
+        .map_err(feeds_page_redirect)?;
 
-    let found_feed_result =
-        spawn_blocking_with_tracing(move || find_feed(&find_feed_url, &response_bytes[..]))
+    for job_id in job_ids {
+        cancel_job(&mut tx, job_id)
             .await
-            .context("Failed to spawn blocking task")
             .map_err(Into::<anyhow::Error>::into)
-            .map_err(FeedAddError::Unexpected)
+            .map_err(FeedDeleteError::Unexpected)
             .map_err(feeds_page_redirect)?;
-    let found_feed = found_feed_result
-        .map_err(FeedAddError::NoFeed)
+    }
+
+    let found = delete_feed(&mut tx, user_id, &feed_id)
+        .await
+        .map_err(FeedDeleteError::Unexpected)
         .map_err(feeds_page_redirect)?;
+    if !found {
+        return Err(feeds_page_redirect(FeedDeleteError::NotFound));
+    }
 
-    // 2) Process the result
+    tx.commit()
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(FeedDeleteError::Unexpected)
+        .map_err(feeds_page_redirect)?;
 
-    let feed = match found_feed {
-        FoundFeed::Url(url) => {
-            event!(Level::INFO,
-                url = %url,
-                "original URL was a HTML document containing a RSS feed URL",
-            );
+    FlashMessage::success("Feed deleted").send();
 
-            let response_bytes = fetch_bytes(&http_client, &url)
-                .await
-                .map_err(FeedAddError::URLInaccessible)
-                .map_err(feeds_page_redirect)?;
+    Ok(see_other("/feeds"))
+}
 
-            ParsedFeed::parse(&url, &response_bytes[..])
-                .map_err(FeedAddError::URLNotAValidRSSFeed)
-                .map_err(feeds_page_redirect)?
-        }
-        FoundFeed::Raw(raw_feed) => {
-            event!(Level::INFO, "original URL was a RSS feed");
+#[derive(thiserror::Error)]
+pub enum FeedMarkReadError {
+    #[error("Something went wrong")]
+    Unexpected(#[from] anyhow::Error),
+}
 
-            ParsedFeed::from_raw_feed(&original_url, raw_feed)
-        }
-    };
+debug_with_error_chain!(FeedMarkReadError);
 
-    event!(Level::INFO,
-        title = %feed.title,
-        site_link = feed.site_link.as_ref().map(|v|v.to_string()).unwrap_or_default(),
-        "Fetched feed",
-    );
+/// This is the /feeds/:feed_id/mark-read handler.
+///
+/// Marks all unread entries of the feed as read.
+#[tracing::instrument(
+    name = "Feed mark read",
+    skip(req, pool, session, feed_id),
+    fields(
+        user_id = tracing::field::Empty,
+        feed_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_feed_mark_read(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    session: TypedSession,
+    feed_id: WebPath<FeedId>,
+) -> Result<HttpResponse, InternalError<FeedMarkReadError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+    let feed_id = feed_id.into_inner();
 
-    // 3) Check if the feed already exists
+    tracing::Span::current()
+        .record("user_id", &tracing::field::display(&user_id))
+        .record("feed_id", &tracing::field::display(&feed_id));
 
-    let feed_exists = feed_with_url_exists(pool.as_ref(), user_id, &feed.url)
+    let marked = mark_all_feed_entries_as_read(pool.as_ref(), user_id, &feed_id)
         .await
-        .map_err(FeedAddError::Unexpected)
-        .map_err(feeds_page_redirect)?;
-    if feed_exists {
-        return Err(feeds_page_redirect(FeedAddError::FeedAlreadyExists));
-    }
+        .map_err(FeedMarkReadError::Unexpected)
+        .map_err(|err| feed_page_redirect(err, feed_id))?;
 
-    // 4) Insert the feed
+    FlashMessage::success(format!("Marked {} entries as read", marked)).send();
 
-    let feed_id = insert_feed(&pool, user_id, &feed)
-        .await
-        .map_err(Into::<anyhow::Error>::into)
-        .context("unable to save feed")
-        .map_err(Into::<FeedAddError>::into)
-        .map_err(feeds_page_redirect)?;
+    Ok(see_other(&format!("/feeds/{}/entries", feed_id)))
+}
 
-    // 5) Add needed background jobs
-    //
-    // Note we don't fail if these return an error, it's only a backgroun job
+#[derive(thiserror::Error)]
+pub enum FeedEntryStarError {
+    #[error("Entry not found")]
+    EntryNotFound,
+    #[error("Something went wrong")]
+    Unexpected(#[from] anyhow::Error),
+}
 
-    if let Some(url) = feed.site_link {
-        if let Err(err) = post_fetch_favicon_job(pool.as_ref(), user_id, feed_id, url).await {
-            warn!(%err, "unable to add fetch favicon job");
-        }
-    }
-    if let Err(err) = post_refresh_feed_job(pool.as_ref(), user_id, feed_id, feed.url).await {
-        warn!(%err, "unable to add refresh feed job");
-    }
+debug_with_error_chain!(FeedEntryStarError);
 
-    FlashMessage::success("Found a feed").send();
+/// This is the /feeds/:feed_id/entries/:entry_id/star handler.
+///
+/// Stars the entry, then redirects back to it.
+#[tracing::instrument(
+    name = "Feed entry star",
+    skip(req, pool, session, route_params),
+    fields(
+        user_id = tracing::field::Empty,
+        feed_id = tracing::field::Empty,
+        entry_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_feed_entry_star(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    session: TypedSession,
+    route_params: WebPath<(FeedId, FeedEntryId)>,
+) -> Result<HttpResponse, InternalError<FeedEntryStarError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+    let (feed_id, entry_id) = route_params.into_inner();
 
-    Ok(see_other("/feeds"))
-}
+    tracing::Span::current()
+        .record("user_id", &tracing::field::display(&user_id))
+        .record("feed_id", &tracing::field::display(&feed_id))
+        .record("entry_id", &tracing::field::display(&entry_id));
 
-#[derive(askama::Template)]
-#[template(path = "feeds_add.html.j2")]
-struct FeedsAddTemplate {
-    pub page: &'static str,
-    pub user_id: Option<UserId>,
-    pub flash_messages: IncomingFlashMessages,
+    let found = mark_feed_entry_as_starred(pool.as_ref(), user_id, &feed_id, &entry_id)
+        .await
+        .map_err(FeedEntryStarError::Unexpected)
+        .map_err(|err| feed_page_redirect(err, feed_id))?;
+    if !found {
+        return Err(feed_page_redirect(
+            FeedEntryStarError::EntryNotFound,
+            feed_id,
+        ));
+    }
+
+    Ok(see_other(&format!(
+        "/feeds/{}/entries/{}",
+        feed_id, entry_id
+    )))
 }
 
+/// This is the /feeds/:feed_id/entries/:entry_id/unstar handler.
+///
+/// Unstars the entry, then redirects back to it.
 #[tracing::instrument(
-    name = "Feeds add form",
-    skip(session, flash_messages),
+    name = "Feed entry unstar",
+    skip(req, pool, session, route_params),
     fields(
         user_id = tracing::field::Empty,
+        feed_id = tracing::field::Empty,
+        entry_id = tracing::field::Empty,
     )
 )]
-pub async fn handle_feeds_add_form(
+pub async fn handle_feed_entry_unstar(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
     session: TypedSession,
-    flash_messages: IncomingFlashMessages,
-) -> Result<HttpResponse, InternalError<anyhow::Error>> {
-    let user_id = get_user_id_or_redirect(&session)?;
+    route_params: WebPath<(FeedId, FeedEntryId)>,
+) -> Result<HttpResponse, InternalError<FeedEntryStarError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+    let (feed_id, entry_id) = route_params.into_inner();
 
-    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+    tracing::Span::current()
+        .record("user_id", &tracing::field::display(&user_id))
+        .record("feed_id", &tracing::field::display(&feed_id))
+        .record("entry_id", &tracing::field::display(&entry_id));
 
-    let tpl = FeedsAddTemplate {
-        page: FEEDS_PAGE,
-        user_id: Some(user_id),
-        flash_messages,
-    };
-    let tpl_rendered = tpl
-        .render()
-        .map_err(Into::<anyhow::Error>::into)
-        .map_err(e500)?;
+    let found = unstar_feed_entry(pool.as_ref(), user_id, &feed_id, &entry_id)
+        .await
+        .map_err(FeedEntryStarError::Unexpected)
+        .map_err(|err| feed_page_redirect(err, feed_id))?;
+    if !found {
+        return Err(feed_page_redirect(
+            FeedEntryStarError::EntryNotFound,
+            feed_id,
+        ));
+    }
 
-    let response = HttpResponse::Ok()
-        .content_type(http::header::ContentType::html())
-        .body(tpl_rendered);
+    Ok(see_other(&format!(
+        "/feeds/{}/entries/{}",
+        feed_id, entry_id
+    )))
+}
 
-    Ok(response)
+#[derive(Deserialize)]
+pub struct FeedTagFormData {
+    pub tag: String,
 }
 
 #[derive(thiserror::Error)]
-pub enum FeedRefreshError {
+pub enum FeedTagError {
+    #[error("Tag name is empty")]
+    EmptyName,
+    #[error("Feed not found")]
+    FeedNotFound,
+    #[error("Tag not found")]
+    TagNotFound,
     #[error("Something went wrong")]
     Unexpected(#[from] anyhow::Error),
 }
 
-debug_with_error_chain!(FeedRefreshError);
+debug_with_error_chain!(FeedTagError);
 
-/// This is the /feeds/refresh handler.
+/// This is the POST /feeds/:feed_id/tags handler.
 ///
-/// Adds a refresh feed job for every feed.
+/// Adds a tag to the feed, creating the tag if necessary.
 #[tracing::instrument(
-    name = "Feeds refresh",
-    skip(pool, session),
+    name = "Feed add tag",
+    skip(req, pool, session, feed_id, form_data),
     fields(
         user_id = tracing::field::Empty,
+        feed_id = tracing::field::Empty,
     )
 )]
-pub async fn handle_feeds_refresh(
+pub async fn handle_feed_tags_add(
+    req: HttpRequest,
     pool: WebData<PgPool>,
     session: TypedSession,
-) -> Result<HttpResponse, InternalError<FeedRefreshError>> {
-    let user_id = get_user_id_or_redirect(&session)?;
+    feed_id: WebPath<FeedId>,
+    form_data: WebForm<FeedTagFormData>,
+) -> Result<HttpResponse, InternalError<FeedTagError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+    let feed_id = feed_id.into_inner();
+
+    tracing::Span::current()
+        .record("user_id", &tracing::field::display(&user_id))
+        .record("feed_id", &tracing::field::display(&feed_id));
 
-    // Iterate over all feeds and add a refresh job for it
+    let name = form_data.0.tag.trim().to_string();
+    if name.is_empty() {
+        return Err(feeds_page_redirect(FeedTagError::EmptyName));
+    }
 
     let mut tx = pool
         .begin()
         .await
         .map_err(Into::<anyhow::Error>::into)
-        .map_err(FeedRefreshError::Unexpected)
+        .map_err(FeedTagError::Unexpected)
         .map_err(feeds_page_redirect)?;
 
-    let feeds = get_all_feeds(&mut tx, user_id)
+    let tag_id = get_or_create_tag(&mut tx, user_id, &name)
         .await
-        .map_err(FeedRefreshError::Unexpected)
+        .map_err(FeedTagError::Unexpected)
         .map_err(feeds_page_redirect)?;
 
-    for feed in feeds {
-        post_refresh_feed_job(pool.as_ref(), user_id, feed.id, feed.url)
-            .await
-            .map_err(Into::<anyhow::Error>::into)
-            .map_err(FeedRefreshError::Unexpected)
-            .map_err(feeds_page_redirect)?;
+    let found = add_feed_tag(&mut tx, user_id, &feed_id, tag_id)
+        .await
+        .map_err(FeedTagError::Unexpected)
+        .map_err(feeds_page_redirect)?;
+    if !found {
+        return Err(feeds_page_redirect(FeedTagError::FeedNotFound));
     }
 
     tx.commit()
         .await
         .map_err(Into::<anyhow::Error>::into)
-        .map_err(FeedRefreshError::Unexpected)
+        .map_err(FeedTagError::Unexpected)
         .map_err(feeds_page_redirect)?;
 
-    // Done, redirect to the feed list
+    FlashMessage::success("Tag added").send();
 
-    FlashMessage::success("Refresh started").send();
+    Ok(see_other("/feeds"))
+}
 
-    let response = HttpResponse::SeeOther()
-        .insert_header((http::header::LOCATION, "/feeds"))
-        .finish();
+/// This is the DELETE /feeds/:feed_id/tags/:tag_id handler.
+///
+/// Removes a tag from the feed.
+#[tracing::instrument(
+    name = "Feed remove tag",
+    skip(req, pool, session, path),
+    fields(
+        user_id = tracing::field::Empty,
+        feed_id = tracing::field::Empty,
+        tag_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_feed_tags_remove(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    session: TypedSession,
+    path: WebPath<(FeedId, FeedTagId)>,
+) -> Result<HttpResponse, InternalError<FeedTagError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+    let (feed_id, tag_id) = path.into_inner();
 
-    Ok(response)
+    tracing::Span::current()
+        .record("user_id", &tracing::field::display(&user_id))
+        .record("feed_id", &tracing::field::display(&feed_id))
+        .record("tag_id", &tracing::field::display(&tag_id));
+
+    let found = remove_feed_tag(pool.as_ref(), user_id, &feed_id, tag_id)
+        .await
+        .map_err(FeedTagError::Unexpected)
+        .map_err(feeds_page_redirect)?;
+    if !found {
+        return Err(feeds_page_redirect(FeedTagError::TagNotFound));
+    }
+
+    FlashMessage::success("Tag removed").send();
+
+    Ok(see_other("/feeds"))
 }
 
 /// This is the /feeds/:feed_id/favicon handler.
 ///
-/// It serves the feed's favicon data.
+/// It serves the feed's favicon data, from the [`FaviconCache`] if possible.
+///
+/// The favicon is served from our origin but comes from a remote site: a SVG favicon can contain
+/// scripts. The response forbids any script and content sniffing, so that opening the favicon
+/// directly can't run anything.
 #[tracing::instrument(
     name = "Feed favicon",
-    skip(pool, session, feed_id),
+    skip(req, pool, favicon_cache, session, feed_id),
     fields(
         user_id = tracing::field::Empty,
         feed_id = tracing::field::Empty,
     )
 )]
 pub async fn handle_feed_favicon(
+    req: HttpRequest,
     pool: WebData<PgPool>,
+    favicon_cache: WebData<FaviconCache>,
     session: TypedSession,
     feed_id: WebPath<FeedId>,
 ) -> Result<HttpResponse, InternalError<anyhow::Error>> {
-    let user_id = get_user_id_or_redirect(&session)?;
+    let user_id = get_user_id_or_redirect(&req, &session)?;
     let feed_id = feed_id.into_inner();
 
     tracing::Span::current()
         .record("user_id", &tracing::field::display(&user_id))
         .record("feed_id", &tracing::field::display(&feed_id));
 
-    let favicon = get_feed_favicon(&pool, user_id, &feed_id)
-        .await
-        .map_err(e500)?;
+    let favicon = match favicon_cache.get(user_id, &feed_id) {
+        Some(favicon) => Some(favicon),
+        None => {
+            let favicon = get_feed_favicon(&pool, user_id, &feed_id)
+                .await
+                .map_err(e500)?
+                .map(Arc::new);
+
+            if let Some(ref favicon) = favicon {
+                favicon_cache.insert(user_id, feed_id, favicon.clone());
+            }
+
+            favicon
+        }
+    };
 
     if let Some(favicon) = favicon {
         let response = HttpResponse::Ok()
-            .content_type("image/x-icon")
-            .body(favicon);
+            .content_type(favicon.mime.as_str())
+            .insert_header((
+                http::header::CONTENT_SECURITY_POLICY,
+                "default-src 'none'; style-src 'unsafe-inline'; sandbox",
+            ))
+            .insert_header((http::header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+            .body(favicon.data.clone());
 
         Ok(response)
     } else {
@@ -411,26 +1396,35 @@ pub async fn handle_feed_favicon(
 
 struct FeedEntryForTemplate {
     original: FeedEntry,
-    created_at: String,
+    published_at: String,
     author: String,
+    is_read: bool,
+    is_starred: bool,
 }
 
 impl FeedEntryForTemplate {
-    fn new(original: FeedEntry) -> Self {
+    /// The publication date is shown in the `timezone` of the user.
+    fn new(original: FeedEntry, timezone: Timezone) -> Self {
         // TODO(vincent): this is ugly, can we replace the unwrap() ?
-        let created_at = original
-            .created_at
+        let published_at = original
+            .published_at
+            .unwrap_or(original.created_at)
+            .to_offset(timezone.offset())
             .replace_nanosecond(0_000_000)
             .unwrap()
             .format(&time::format_description::well_known::Rfc3339)
             .unwrap_or_else(|_| "unknown".to_string()); // TODO(vincent): can this really fail ?
 
         let author = original.authors.first().cloned().unwrap_or_default();
+        let is_read = original.is_read();
+        let is_starred = original.is_starred();
 
         Self {
             original,
-            created_at,
+            published_at,
             author,
+            is_read,
+            is_starred,
         }
     }
 }
@@ -441,8 +1435,20 @@ struct FeedEntriesTemplate {
     pub page: &'static str,
     pub user_id: Option<UserId>,
     pub flash_messages: IncomingFlashMessages,
+    pub csrf_token: String,
     pub feed: FeedForTemplate,
     pub entries: Vec<FeedEntryForTemplate>,
+    /// The positions of the first and last entries of the page, starting at 1.
+    pub first_entry: i64,
+    pub last_entry: i64,
+    pub total_entries: i64,
+    pub next_cursor: Option<FeedEntryId>,
+}
+
+#[derive(Deserialize)]
+pub struct FeedEntriesQuery {
+    /// Only show the entries after this one.
+    after: Option<FeedEntryId>,
 }
 
 #[derive(thiserror::Error)]
@@ -457,40 +1463,31 @@ debug_with_error_chain!(FeedEntriesError);
 
 #[tracing::instrument(
     name = "Feed entries",
-    skip(pool, session, flash_messages, feed_id),
+    skip(req, pool, session, flash_messages, csrf_token, feed_id, query),
     fields(
         user_id = tracing::field::Empty,
         feed_id = tracing::field::Empty,
     )
 )]
 pub async fn handle_feed_entries(
+    req: HttpRequest,
     pool: WebData<PgPool>,
     session: TypedSession,
     flash_messages: IncomingFlashMessages,
+    csrf_token: CsrfToken,
     feed_id: WebPath<FeedId>,
+    query: WebQuery<FeedEntriesQuery>,
 ) -> Result<HttpResponse, InternalError<FeedEntriesError>> {
-    let user_id = get_user_id_or_redirect(&session)?;
+    let user_id = get_user_id_or_redirect(&req, &session)?;
     let feed_id = feed_id.into_inner();
 
     tracing::Span::current()
         .record("user_id", &tracing::field::display(&user_id))
         .record("feed_id", &tracing::field::display(&feed_id));
 
-    // NOTE(vincent): do we need a transaction here since we don't write anything ?
-    let mut tx = {
-        let tx_begin_span = tracing::span!(Level::TRACE, "tx_begin");
-        let _guard = tx_begin_span.enter();
-
-        pool.begin()
-            .await
-            .map_err(Into::<anyhow::Error>::into)
-            .map_err(FeedEntriesError::Unexpected)
-            .map_err(e500)?
-    };
-
     // 1) Get the feed data
 
-    let feed = get_feed(&mut tx, user_id, &feed_id)
+    let feed = get_feed(pool.as_ref(), user_id, &feed_id)
         .await
         .map_err(FeedEntriesError::Unexpected)
         .map_err(feeds_page_redirect)?;
@@ -499,16 +1496,60 @@ pub async fn handle_feed_entries(
         .ok_or(FeedEntriesError::NotFound)
         .map_err(feeds_page_redirect)?;
 
-    // 2) Get the feed entries
+    // 2) Get a page of feed entries of the size preferred by the user, the number of entries on
+    // the previous pages and the total number of entries
+
+    let preferences = get_user_preferences(pool.as_ref(), user_id)
+        .await
+        .map_err(FeedEntriesError::Unexpected)
+        .map_err(feeds_page_redirect)?;
+
+    let previous_entries = async {
+        match query.after {
+            Some(after) => count_feed_entries_up_to(pool.as_ref(), user_id, &feed_id, after).await,
+            None => Ok(0),
+        }
+    };
+
+    let (entries_page, previous_entries, total_entries) = tokio::try_join!(
+        get_feed_entries_page(
+            pool.as_ref(),
+            user_id,
+            &feed_id,
+            query.after,
+            preferences.entries_per_page,
+        ),
+        previous_entries,
+        count_feed_entries(pool.as_ref(), user_id, &feed_id),
+    )
+    .map_err(FeedEntriesError::Unexpected)
+    .map_err(feeds_page_redirect)?;
+
+    // 3) Mark the entries as read if the user wants to
 
-    let raw_entries = get_feed_entries(&mut tx, user_id, &feed_id)
+    let auto_mark_read_on_view = get_user_auto_mark_read_on_view(pool.as_ref(), user_id)
         .await
         .map_err(FeedEntriesError::Unexpected)
         .map_err(feeds_page_redirect)?;
 
-    let entries = raw_entries
+    if auto_mark_read_on_view {
+        let entry_ids = entries_page
+            .entries
+            .iter()
+            .filter(|entry| !entry.is_read())
+            .map(|entry| entry.id)
+            .collect::<Vec<_>>();
+
+        mark_feed_entries_as_read(pool.as_ref(), user_id, &feed_id, &entry_ids)
+            .await
+            .map_err(FeedEntriesError::Unexpected)
+            .map_err(feeds_page_redirect)?;
+    }
+
+    let entries: Vec<_> = entries_page
+        .entries
         .into_iter()
-        .map(FeedEntryForTemplate::new)
+        .map(|entry| FeedEntryForTemplate::new(entry, preferences.timezone))
         .collect();
 
     // Render
@@ -517,8 +1558,13 @@ pub async fn handle_feed_entries(
         page: FEEDS_PAGE,
         user_id: Some(user_id),
         flash_messages,
+        csrf_token: csrf_token.0,
         feed: FeedForTemplate::new(feed),
+        first_entry: previous_entries + 1,
+        last_entry: previous_entries + entries.len() as i64,
         entries,
+        total_entries,
+        next_cursor: entries_page.next_cursor,
     };
     let tpl_rendered = tpl
         .render()
@@ -543,8 +1589,11 @@ struct FeedEntryTemplate {
     pub page: &'static str,
     pub user_id: Option<UserId>,
     pub flash_messages: IncomingFlashMessages,
+    pub csrf_token: String,
     pub feed: FeedForTemplate,
     pub entry: FeedEntryForTemplate,
+    /// The sanitized summary, safe to render as is
+    pub summary: String,
 }
 
 #[derive(thiserror::Error)]
@@ -561,7 +1610,7 @@ debug_with_error_chain!(FeedEntryError);
 
 #[tracing::instrument(
     name = "Feed entry",
-    skip(pool, session, flash_messages, route_params),
+    skip(req, pool, session, flash_messages, csrf_token, route_params),
     fields(
         user_id = tracing::field::Empty,
         feed_id = tracing::field::Empty,
@@ -569,12 +1618,14 @@ debug_with_error_chain!(FeedEntryError);
     )
 )]
 pub async fn handle_feed_entry(
+    req: HttpRequest,
     pool: WebData<PgPool>,
     session: TypedSession,
     flash_messages: IncomingFlashMessages,
+    csrf_token: CsrfToken,
     route_params: WebPath<(FeedId, FeedEntryId)>,
 ) -> Result<HttpResponse, InternalError<FeedEntryError>> {
-    let user_id = get_user_id_or_redirect(&session)?;
+    let user_id = get_user_id_or_redirect(&req, &session)?;
     let feed_id = route_params.0;
     let entry_id = route_params.1;
 
@@ -616,6 +1667,11 @@ pub async fn handle_feed_entry(
         .ok_or(FeedEntryError::EntryNotFound)
         .map_err(|err| feed_page_redirect(err, feed_id))?;
 
+    let preferences = get_user_preferences(&mut tx, user_id)
+        .await
+        .map_err(FeedEntryError::Unexpected)
+        .map_err(|err| feed_page_redirect(err, feed_id))?;
+
     // 2) Set its read date
 
     mark_feed_entry_as_read(&mut tx, user_id, &feed_id, &entry_id)
@@ -635,8 +1691,10 @@ pub async fn handle_feed_entry(
         page: FEEDS_PAGE,
         user_id: Some(user_id),
         flash_messages,
+        csrf_token: csrf_token.0,
         feed: FeedForTemplate::new(feed),
-        entry: FeedEntryForTemplate::new(entry),
+        summary: sanitize_html(&entry.summary),
+        entry: FeedEntryForTemplate::new(entry, preferences.timezone),
     };
     let tpl_rendered = tpl
         .render()
@@ -663,6 +1721,7 @@ fn feed_page_redirect<E: fmt::Display>(err: E, feed_id: FeedId) -> InternalError
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::feed::FeedCategoryId;
 
     #[test]
     fn guess_uri_should_work_with_and_without_a_scheme() {
@@ -678,4 +1737,130 @@ mod tests {
         let url2 = guess_url("example.com/foo".to_string()).unwrap();
         assert_eq!(url1, url2);
     }
+
+    #[test]
+    fn normalize_pasted_url_should_strip_surrounding_characters() {
+        const URL: &str = "https://example.com/feed";
+
+        let inputs = [
+            URL,
+            "  https://example.com/feed  ",
+            "<https://example.com/feed>",
+            r#""https://example.com/feed""#,
+            "\t\n https://example.com/feed\r\n",
+            r#" "<https://example.com/feed>" "#,
+        ];
+        for input in inputs {
+            assert_eq!(URL, normalize_pasted_url(input), "input: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn normalize_pasted_url_should_keep_inner_characters() {
+        let input = r#"https://example.com/search?q="foo bar"&a=<b>c"#;
+        assert_eq!(input, normalize_pasted_url(input));
+    }
+
+    #[test]
+    fn discovery_cache_should_hit_within_the_ttl() {
+        let cache = DiscoveryCache::new(10, StdDuration::from_secs(30));
+        let url = Url::parse("https://example.com/feed.xml").unwrap();
+
+        assert!(cache.get(&url).is_none());
+
+        cache.insert(url.clone(), Bytes::from_static(b"foobar"));
+        assert_eq!(Some(Bytes::from_static(b"foobar")), cache.get(&url));
+    }
+
+    #[test]
+    fn discovery_cache_should_miss_after_the_ttl() {
+        let cache = DiscoveryCache::new(10, StdDuration::ZERO);
+        let url = Url::parse("https://example.com/feed.xml").unwrap();
+
+        cache.insert(url.clone(), Bytes::from_static(b"foobar"));
+        assert!(cache.get(&url).is_none());
+    }
+
+    #[test]
+    fn discovery_cache_should_evict_the_least_recently_used_url() {
+        let cache = DiscoveryCache::new(1, StdDuration::from_secs(30));
+        let url1 = Url::parse("https://example.com/feed1.xml").unwrap();
+        let url2 = Url::parse("https://example.com/feed2.xml").unwrap();
+
+        cache.insert(url1.clone(), Bytes::from_static(b"foo"));
+        cache.insert(url2.clone(), Bytes::from_static(b"bar"));
+
+        assert!(cache.get(&url1).is_none());
+        assert!(cache.get(&url2).is_some());
+    }
+
+    fn feed_with_category(id: i64, category: Option<(i64, &str)>) -> FeedForTemplate {
+        FeedForTemplate::new(Feed {
+            id: FeedId(id),
+            url: Url::parse(&format!("https://example.com/feed{}.xml", id)).unwrap(),
+            title: format!("Feed {}", id),
+            site_link: None,
+            description: String::new(),
+            language: None,
+            site_favicon: None,
+            added_at: time::OffsetDateTime::now_utc(),
+            category: category.map(|(id, name)| FeedCategory {
+                id: FeedCategoryId(id),
+                name: name.to_string(),
+            }),
+        })
+    }
+
+    #[test]
+    fn feed_for_template_should_fall_back_to_the_feed_url() {
+        let feed = feed_with_category(1, None);
+        assert_eq!(feed.original.url, feed.site_link_or_url);
+
+        let site_link = Url::parse("https://example.com").unwrap();
+        let feed = FeedForTemplate::new(Feed {
+            site_link: Some(site_link.clone()),
+            ..feed.original
+        });
+        assert_eq!(site_link, feed.site_link_or_url);
+    }
+
+    #[test]
+    fn feeds_should_be_grouped_by_category() {
+        let feeds = vec![
+            feed_with_category(1, Some((10, "tech"))),
+            feed_with_category(2, None),
+            feed_with_category(3, Some((20, "Cooking"))),
+            feed_with_category(4, Some((10, "tech"))),
+            feed_with_category(5, None),
+        ];
+
+        let groups = group_feeds_by_category(feeds)
+            .into_iter()
+            .map(|group| {
+                (
+                    group.category.map(|category| category.name),
+                    group
+                        .feeds
+                        .iter()
+                        .map(|feed| feed.original.id.0)
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                (Some("Cooking".to_string()), vec![3]),
+                (Some("tech".to_string()), vec![1, 4]),
+                (None, vec![2, 5]),
+            ],
+            groups,
+        );
+    }
+
+    #[test]
+    fn grouping_no_feeds_should_return_no_groups() {
+        let groups = group_feeds_by_category(Vec::new());
+        assert!(groups.is_empty());
+    }
 }
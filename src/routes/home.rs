@@ -1,12 +1,18 @@
 use crate::domain::UserId;
+use crate::feed::{get_recently_added_feeds, get_total_unread_count, Feed};
 use crate::routes::e500;
 use crate::routes::HOME_PAGE;
-use crate::sessions::TypedSession;
+use crate::sessions::{CsrfToken, TypedSession};
 use actix_web::error::InternalError;
 use actix_web::http::header::ContentType;
+use actix_web::web::Data as WebData;
 use actix_web::HttpResponse;
 use actix_web_flash_messages::IncomingFlashMessages;
 use askama::Template;
+use sqlx::PgPool;
+
+/// The number of recently added feeds shown on the home page.
+const RECENT_FEEDS_LIMIT: usize = 5;
 
 #[derive(askama::Template)]
 #[template(path = "home.html.j2")]
@@ -14,18 +20,23 @@ struct HomeTemplate {
     pub page: &'static str,
     pub user_id: Option<UserId>,
     pub flash_messages: IncomingFlashMessages,
+    pub csrf_token: String,
+    pub unread_count: i64,
+    pub recent_feeds: Vec<Feed>,
 }
 
 #[tracing::instrument(
     name = "Home",
-    skip(session, flash_messages),
+    skip(pool, session, flash_messages, csrf_token),
     fields(
         user_id = tracing::field::Empty,
     )
 )]
 pub async fn handle_home(
+    pool: WebData<PgPool>,
     session: TypedSession,
     flash_messages: IncomingFlashMessages,
+    csrf_token: CsrfToken,
 ) -> Result<HttpResponse, InternalError<anyhow::Error>> {
     let user_id = session
         .get_user_id()
@@ -37,10 +48,29 @@ pub async fn handle_home(
 
     //
 
+    let (unread_count, recent_feeds) = match user_id {
+        Some(user_id) => {
+            let unread_count = get_total_unread_count(pool.as_ref(), user_id)
+                .await
+                .map_err(e500)?;
+            let recent_feeds = get_recently_added_feeds(pool.as_ref(), user_id, RECENT_FEEDS_LIMIT)
+                .await
+                .map_err(e500)?;
+
+            (unread_count, recent_feeds)
+        }
+        None => (0, Vec::new()),
+    };
+
+    //
+
     let tpl = HomeTemplate {
         page: HOME_PAGE,
         user_id,
         flash_messages,
+        csrf_token: csrf_token.0,
+        unread_count,
+        recent_feeds,
     };
     let tpl_rendered = tpl
         .render()
@@ -1,9 +1,9 @@
-use crate::authentication::{authenticate, AuthError, Credentials};
+use crate::authentication::{authenticate, AuthError, Credentials, PasswordHasherConfig};
 use crate::debug_with_error_chain;
 use crate::domain::{UserEmail, UserId};
 use crate::routes::LOGIN_PAGE;
 use crate::routes::{e500, see_other};
-use crate::sessions::TypedSession;
+use crate::sessions::{CsrfToken, RememberMeTtl, TypedSession};
 use actix_web::error::InternalError;
 use actix_web::HttpResponse;
 use actix_web::{http, web};
@@ -11,6 +11,7 @@ use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
 use askama::Template;
 use secrecy::Secret;
 use sqlx::PgPool;
+use time::OffsetDateTime;
 use tracing::{event, Level};
 
 // Login
@@ -21,11 +22,12 @@ struct LoginTemplate {
     pub page: &'static str,
     pub user_id: Option<UserId>,
     pub flash_messages: IncomingFlashMessages,
+    pub csrf_token: String,
 }
 
 #[tracing::instrument(
     name = "Login form",
-    skip(session, flash_messages),
+    skip(session, flash_messages, csrf_token),
     fields(
         user_id = tracing::field::Empty,
     )
@@ -33,6 +35,7 @@ struct LoginTemplate {
 pub async fn handle_login_form(
     session: TypedSession,
     flash_messages: IncomingFlashMessages,
+    csrf_token: CsrfToken,
 ) -> Result<HttpResponse, InternalError<anyhow::Error>> {
     let user_id = session
         .get_user_id()
@@ -48,6 +51,7 @@ pub async fn handle_login_form(
         page: LOGIN_PAGE,
         user_id,
         flash_messages,
+        csrf_token: csrf_token.0,
     };
     let tpl_rendered = tpl
         .render()
@@ -75,11 +79,13 @@ debug_with_error_chain!(LoginError);
 pub struct LoginFormData {
     pub email: UserEmail,
     pub password: String,
+    #[serde(default)]
+    pub remember_me: bool,
 }
 
 #[tracing::instrument(
     name = "Login submit",
-    skip(pool, session, form_data),
+    skip(pool, hasher_config, remember_me_ttl, session, form_data),
     fields(
         username = tracing::field::Empty,
         user_id = tracing::field::Empty,
@@ -87,6 +93,8 @@ pub struct LoginFormData {
 )]
 pub async fn handle_login_submit(
     pool: web::Data<PgPool>,
+    hasher_config: web::Data<PasswordHasherConfig>,
+    remember_me_ttl: web::Data<RememberMeTtl>,
     session: TypedSession,
     form_data: web::Form<LoginFormData>,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
@@ -94,12 +102,13 @@ pub async fn handle_login_submit(
 
     tracing::Span::current().record("email", &tracing::field::display(&form_data.email));
 
+    let remember_me = form_data.0.remember_me;
     let credentials = Credentials {
         email: form_data.0.email,
         password: Secret::from(form_data.0.password),
     };
 
-    match authenticate(pool, credentials).await {
+    match authenticate(pool, &hasher_config, credentials).await {
         Ok(user_id) => {
             tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
 
@@ -110,6 +119,20 @@ pub async fn handle_login_submit(
             session
                 .insert_user_id(user_id)
                 .map_err(|err| login_redirect(LoginError::Unexpected(err.into())))?;
+            session
+                .insert_logged_in_at(OffsetDateTime::now_utc())
+                .map_err(|err| login_redirect(LoginError::Unexpected(err.into())))?;
+            session
+                .renew_csrf_token()
+                .map_err(|err| login_redirect(LoginError::Unexpected(err.into())))?;
+
+            // By default the session only lasts as long as the browser session.
+            // If the user wants to be remembered, use a longer TTL.
+            if remember_me {
+                session
+                    .set_ttl(remember_me_ttl.0)
+                    .map_err(|err| login_redirect(LoginError::Unexpected(err.into())))?;
+            }
 
             Ok(see_other("/"))
         }
@@ -119,7 +142,9 @@ pub async fn handle_login_submit(
 
             let err = match err {
                 AuthError::InvalidCredentials(_) => LoginError::Auth(err.into()),
-                AuthError::Unexpected(_) => LoginError::Unexpected(err.into()),
+                AuthError::WeakPassword(_) | AuthError::Unexpected(_) => {
+                    LoginError::Unexpected(err.into())
+                }
             };
 
             Err(login_redirect(err))
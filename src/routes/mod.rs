@@ -3,7 +3,7 @@ use crate::sessions::TypedSession;
 use actix_web::error::InternalError;
 use actix_web::http;
 use actix_web::http::{header, StatusCode};
-use actix_web::HttpResponse;
+use actix_web::{HttpMessage, HttpRequest, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use anyhow::anyhow;
 use std::convert::From;
@@ -34,7 +34,11 @@ pub fn see_other(location: &str) -> HttpResponse {
         .finish()
 }
 
-/// This is a helper function used to extract the [`UserId`] from a [`TypedSession`].
+/// This is a helper function used to extract the [`UserId`] of the current request.
+///
+/// The [`UserId`] is first looked up in the request extensions, where it is stored when the
+/// request is authenticated with an API token (see [`crate::authentication::ApiTokenMiddleware`]).
+/// Otherwise it is extracted from the [`TypedSession`].
 ///
 /// If there's no user id in the session _or_ the session is somehow corrupted, this returns a
 /// [`InternalError<E>`] that will redirect to the login page.
@@ -42,11 +46,20 @@ pub fn see_other(location: &str) -> HttpResponse {
 /// # Errors
 ///
 /// Actual errors are converted to a 500 Internal Server Error using the [`e500`] function.
-#[tracing::instrument(name = "Get user id or redirect", level = "TRACE", skip(session))]
-pub fn get_user_id_or_redirect<E>(session: &TypedSession) -> Result<UserId, InternalError<E>>
+#[tracing::instrument(name = "Get user id or redirect", level = "TRACE", skip(req, session))]
+pub fn get_user_id_or_redirect<E>(
+    req: &HttpRequest,
+    session: &TypedSession,
+) -> Result<UserId, InternalError<E>>
 where
     E: From<anyhow::Error> + fmt::Display + fmt::Debug + 'static,
 {
+    if let Some(user_id) = req.extensions().get::<UserId>().copied() {
+        event!(Level::TRACE, %user_id, "found user in the request extensions");
+
+        return Ok(user_id);
+    }
+
     let user_id = session
         .get_user_id()
         .map_err(Into::<anyhow::Error>::into)
@@ -84,24 +97,39 @@ where
     InternalError::from_response(err, response)
 }
 
-pub async fn handle_status() -> HttpResponse {
-    HttpResponse::Ok().finish()
-}
-
+pub(crate) const ADMIN_PAGE: &str = "admin";
 pub(crate) const FEEDS_PAGE: &str = "feeds";
 pub(crate) const HOME_PAGE: &str = "home";
 pub(crate) const LOGIN_PAGE: &str = "login";
+pub(crate) const SEARCH_PAGE: &str = "search";
 pub(crate) const SETTINGS_PAGE: &str = "settings";
+pub(crate) const STARRED_PAGE: &str = "starred";
 pub(crate) const UNREAD_PAGE: &str = "unread";
 
+mod admin;
+mod api;
+mod email;
 mod feeds;
 mod home;
 mod login;
+mod search;
 mod settings;
+mod starred;
+mod status;
+mod tags;
 mod unread;
+mod ws;
 
+pub use admin::*;
+pub use api::*;
+pub use email::*;
 pub use feeds::*;
 pub use home::handle_home;
 pub use login::*;
+pub use search::*;
 pub use settings::*;
+pub use starred::*;
+pub use status::*;
+pub use tags::*;
 pub use unread::*;
+pub use ws::*;
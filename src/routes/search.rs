@@ -0,0 +1,101 @@
+use crate::debug_with_error_chain;
+use crate::domain::UserId;
+use crate::feed::search_feed_entries;
+use crate::preferences::get_user_preferences;
+use crate::routes::unread::FeedEntryForTemplate;
+use crate::routes::{e500, get_user_id_or_redirect, SEARCH_PAGE};
+use crate::sessions::{CsrfToken, TypedSession};
+use actix_web::error::InternalError;
+use actix_web::http;
+use actix_web::web::{Data as WebData, Query as WebQuery};
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+#[derive(askama::Template)]
+#[template(path = "search.html.j2")]
+struct SearchTemplate {
+    pub page: &'static str,
+    pub user_id: Option<UserId>,
+    pub flash_messages: IncomingFlashMessages,
+    pub csrf_token: String,
+    pub query: String,
+    pub entries: Vec<FeedEntryForTemplate>,
+}
+
+/// The maximum number of entries shown in the search results.
+const SEARCH_RESULTS_LIMIT: usize = 100;
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    q: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum SearchError {
+    #[error("Something went wrong")]
+    Unexpected(#[from] anyhow::Error),
+}
+
+debug_with_error_chain!(SearchError);
+
+#[tracing::instrument(
+    name = "Search",
+    skip(req, pool, session, flash_messages, csrf_token, query),
+    fields(
+        user_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_search(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+    csrf_token: CsrfToken,
+    query: WebQuery<SearchQuery>,
+) -> Result<HttpResponse, InternalError<SearchError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    // Search the entries; an empty query matches nothing
+
+    let query = query.into_inner().q.trim().to_string();
+
+    let (original_feed_entries, preferences) = tokio::try_join!(
+        search_feed_entries(pool.as_ref(), user_id, &query, SEARCH_RESULTS_LIMIT),
+        get_user_preferences(pool.as_ref(), user_id),
+    )
+    .map_err(SearchError::Unexpected)
+    .map_err(e500)?;
+
+    let feed_entries = original_feed_entries
+        .into_iter()
+        .map(|entry| FeedEntryForTemplate::new(entry, preferences.timezone))
+        .collect();
+
+    // Render
+
+    let tpl = SearchTemplate {
+        page: SEARCH_PAGE,
+        user_id: Some(user_id),
+        flash_messages,
+        csrf_token: csrf_token.0,
+        query,
+        entries: feed_entries,
+    };
+    let tpl_rendered = tpl
+        .render()
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(SearchError::Unexpected)
+        .map_err(e500)?;
+
+    let response = HttpResponse::Ok()
+        .content_type(http::header::ContentType::html())
+        .body(tpl_rendered);
+
+    Ok(response)
+}
@@ -1,12 +1,64 @@
-use crate::domain::UserId;
+use crate::authentication::{authenticate, AuthError, Credentials, PasswordHasherConfig};
+use crate::authentication::{confirm_email_change, get_pending_email_change};
+use crate::authentication::{create_api_token, delete_api_token, get_api_tokens};
+use crate::authentication::{set_pending_email_change, EmailChangeToken};
+use crate::authentication::{ApiToken, ApiTokenId};
+use crate::debug_with_error_chain;
+use crate::domain::{delete_user, get_user, UserEmail, UserId};
+use crate::feed::delete_all_feeds;
+use crate::feed::{get_user_auto_mark_read_on_view, set_user_auto_mark_read_on_view};
+use crate::feed::{get_user_feed_order, set_user_feed_order, FeedOrder, InvalidFeedOrder};
+use crate::feed::{get_user_unread_since_days, set_user_unread_since_days};
+use crate::job::delete_user_jobs;
+use crate::preferences::{get_user_preferences, upsert_user_preferences, ENTRIES_PER_PAGE_RANGE};
+use crate::preferences::{InvalidTheme, InvalidTimezone, Theme, UserPreferences};
 use crate::routes::SETTINGS_PAGE;
-use crate::routes::{e500, get_user_id_or_redirect};
-use crate::sessions::TypedSession;
+use crate::routes::{e500, error_redirect, get_user_id_or_redirect, see_other};
+use crate::sessions::{delete_user_session, delete_user_sessions, get_user_sessions};
+use crate::sessions::{CsrfToken, TypedSession, UserSession};
+use crate::startup::{ApplicationBaseUrl, HmacSecret};
+use crate::tem;
 use actix_web::error::InternalError;
-use actix_web::http::header::ContentType;
-use actix_web::HttpResponse;
-use actix_web_flash_messages::IncomingFlashMessages;
+use actix_web::http::header::{CacheControl, CacheDirective, ContentType};
+use actix_web::web::Data as WebData;
+use actix_web::web::Form as WebForm;
+use actix_web::web::Path as WebPath;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::anyhow;
 use askama::Template;
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+struct FeedOrderOption {
+    value: String,
+    label: &'static str,
+    selected: bool,
+}
+
+struct ThemeOption {
+    value: String,
+    label: &'static str,
+    selected: bool,
+}
+
+struct ApiTokenForTemplate {
+    original: ApiToken,
+    created_at: String,
+}
+
+impl ApiTokenForTemplate {
+    fn new(original: ApiToken) -> Self {
+        let created_at = original.created_at.date().to_string();
+
+        Self {
+            original,
+            created_at,
+        }
+    }
+}
 
 #[derive(askama::Template)]
 #[template(path = "settings.html.j2")]
@@ -14,29 +66,459 @@ struct SettingsTemplate {
     pub page: &'static str,
     pub user_id: Option<UserId>,
     pub flash_messages: IncomingFlashMessages,
+    pub csrf_token: String,
+    pub feed_orders: Vec<FeedOrderOption>,
+    pub auto_mark_read_on_view: bool,
+    pub unread_since_days: Option<i32>,
+    pub entries_per_page: usize,
+    pub timezone: String,
+    pub themes: Vec<ThemeOption>,
+    pub api_tokens: Vec<ApiTokenForTemplate>,
+    /// When the user logged in, shown in their timezone.
+    pub logged_in_since: Option<String>,
 }
 
 #[tracing::instrument(
     name = "Settings",
-    skip(session, flash_messages),
+    skip(req, pool, session, flash_messages, csrf_token),
     fields(
         user_id = tracing::field::Empty,
     )
 )]
 pub async fn handle_settings(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
     session: TypedSession,
     flash_messages: IncomingFlashMessages,
+    csrf_token: CsrfToken,
 ) -> Result<HttpResponse, InternalError<anyhow::Error>> {
-    let user_id = get_user_id_or_redirect(&session)?;
+    let user_id = get_user_id_or_redirect(&req, &session)?;
 
     tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
 
     //
 
+    let (feed_order, auto_mark_read_on_view, unread_since_days, preferences, api_tokens) =
+        tokio::try_join!(
+            get_user_feed_order(pool.as_ref(), user_id),
+            get_user_auto_mark_read_on_view(pool.as_ref(), user_id),
+            get_user_unread_since_days(pool.as_ref(), user_id),
+            get_user_preferences(pool.as_ref(), user_id),
+            get_api_tokens(pool.as_ref(), user_id),
+        )
+        .map_err(e500)?;
+
+    let feed_orders = FeedOrder::ALL
+        .iter()
+        .map(|order| FeedOrderOption {
+            value: order.to_string(),
+            label: order.label(),
+            selected: *order == feed_order,
+        })
+        .collect();
+
+    let themes = Theme::ALL
+        .iter()
+        .map(|theme| ThemeOption {
+            value: theme.to_string(),
+            label: theme.label(),
+            selected: *theme == preferences.theme,
+        })
+        .collect();
+
+    let logged_in_since = session
+        .get_logged_in_at()
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(e500)?
+        .map(|logged_in_at| {
+            logged_in_at
+                .to_offset(preferences.timezone.offset())
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or_else(|_| "unknown".to_string())
+        });
+
+    //
+
     let tpl = SettingsTemplate {
         page: SETTINGS_PAGE,
         user_id: Some(user_id),
         flash_messages,
+        csrf_token: csrf_token.0,
+        feed_orders,
+        auto_mark_read_on_view,
+        unread_since_days,
+        entries_per_page: preferences.entries_per_page,
+        timezone: preferences.timezone.to_string(),
+        themes,
+        api_tokens: api_tokens
+            .into_iter()
+            .map(ApiTokenForTemplate::new)
+            .collect(),
+        logged_in_since,
+    };
+    let tpl_rendered = tpl
+        .render()
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(e500)?;
+
+    let response = HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(tpl_rendered);
+
+    Ok(response)
+}
+
+#[derive(Deserialize)]
+pub struct PreferencesFormData {
+    pub feed_order: String,
+    /// An unchecked checkbox is not sent at all.
+    #[serde(default)]
+    pub auto_mark_read_on_view: bool,
+    /// Empty to show all the unread entries.
+    #[serde(default)]
+    pub unread_since_days: String,
+    /// Empty to use the default number of entries per page.
+    #[serde(default)]
+    pub entries_per_page: String,
+    /// Empty to use UTC.
+    #[serde(default)]
+    pub timezone: String,
+    /// Empty to use the default theme.
+    #[serde(default)]
+    pub theme: String,
+}
+
+/// Parses the number of days of unread entries to show, an empty value meaning all of them.
+fn parse_unread_since_days(value: &str) -> Result<Option<i32>, PreferencesError> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    match value.parse::<i32>() {
+        Ok(days) if days > 0 => Ok(Some(days)),
+        _ => Err(PreferencesError::InvalidUnreadSinceDays(value.to_string())),
+    }
+}
+
+/// Parses the number of entries shown on each page, an empty value meaning the default.
+fn parse_entries_per_page(value: &str) -> Result<usize, PreferencesError> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(UserPreferences::default().entries_per_page);
+    }
+
+    match value.parse::<usize>() {
+        Ok(count) if ENTRIES_PER_PAGE_RANGE.contains(&count) => Ok(count),
+        _ => Err(PreferencesError::InvalidEntriesPerPage(value.to_string())),
+    }
+}
+
+/// Parses the preferences stored in the `user_preferences` table from the `form`.
+fn parse_user_preferences(form: &PreferencesFormData) -> Result<UserPreferences, PreferencesError> {
+    let entries_per_page = parse_entries_per_page(&form.entries_per_page)?;
+
+    let timezone = match form.timezone.trim() {
+        "" => Default::default(),
+        timezone => timezone
+            .parse()
+            .map_err(PreferencesError::InvalidTimezone)?,
+    };
+    let theme = match form.theme.as_str() {
+        "" => Default::default(),
+        theme => theme.parse().map_err(PreferencesError::InvalidTheme)?,
+    };
+
+    Ok(UserPreferences {
+        entries_per_page,
+        timezone,
+        theme,
+    })
+}
+
+#[derive(thiserror::Error)]
+pub enum PreferencesError {
+    #[error("Invalid feed order")]
+    InvalidFeedOrder(#[source] InvalidFeedOrder),
+    #[error("Invalid number of days \"{0}\"")]
+    InvalidUnreadSinceDays(String),
+    #[error("Invalid number of entries per page \"{0}\"")]
+    InvalidEntriesPerPage(String),
+    #[error("Invalid timezone, use UTC or an offset like +02:00")]
+    InvalidTimezone(#[source] InvalidTimezone),
+    #[error("Invalid theme")]
+    InvalidTheme(#[source] InvalidTheme),
+    #[error("Something went wrong")]
+    Unexpected(#[from] anyhow::Error),
+}
+
+debug_with_error_chain!(PreferencesError);
+
+fn settings_page_redirect(err: PreferencesError) -> InternalError<PreferencesError> {
+    error_redirect(err, "/settings")
+}
+
+/// This is the POST /settings/preferences handler.
+///
+/// It saves the preferences of the user.
+#[tracing::instrument(
+    name = "Settings preferences",
+    skip(req, pool, session, form),
+    fields(
+        user_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_settings_preferences(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    session: TypedSession,
+    form: WebForm<PreferencesFormData>,
+) -> Result<HttpResponse, InternalError<PreferencesError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    let feed_order: FeedOrder = form
+        .0
+        .feed_order
+        .parse()
+        .map_err(PreferencesError::InvalidFeedOrder)
+        .map_err(settings_page_redirect)?;
+    let unread_since_days =
+        parse_unread_since_days(&form.0.unread_since_days).map_err(settings_page_redirect)?;
+    let preferences = parse_user_preferences(&form.0).map_err(settings_page_redirect)?;
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(PreferencesError::Unexpected)
+        .map_err(settings_page_redirect)?;
+
+    set_user_feed_order(&mut tx, user_id, feed_order)
+        .await
+        .map_err(PreferencesError::Unexpected)
+        .map_err(settings_page_redirect)?;
+    set_user_auto_mark_read_on_view(&mut tx, user_id, form.0.auto_mark_read_on_view)
+        .await
+        .map_err(PreferencesError::Unexpected)
+        .map_err(settings_page_redirect)?;
+    set_user_unread_since_days(&mut tx, user_id, unread_since_days)
+        .await
+        .map_err(PreferencesError::Unexpected)
+        .map_err(settings_page_redirect)?;
+    upsert_user_preferences(&mut tx, user_id, &preferences)
+        .await
+        .map_err(PreferencesError::Unexpected)
+        .map_err(settings_page_redirect)?;
+
+    tx.commit()
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(PreferencesError::Unexpected)
+        .map_err(settings_page_redirect)?;
+
+    FlashMessage::success("Preferences saved").send();
+
+    Ok(see_other("/settings"))
+}
+
+#[derive(askama::Template)]
+#[template(path = "settings_api_token.html.j2")]
+struct ApiTokenCreatedTemplate {
+    pub page: &'static str,
+    pub user_id: Option<UserId>,
+    pub flash_messages: IncomingFlashMessages,
+    pub csrf_token: String,
+    pub name: String,
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct ApiTokenFormData {
+    pub name: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum ApiTokenError {
+    #[error("API token name is empty")]
+    EmptyName,
+    #[error("API token not found")]
+    NotFound,
+    #[error("Something went wrong")]
+    Unexpected(#[from] anyhow::Error),
+}
+
+debug_with_error_chain!(ApiTokenError);
+
+/// This is the POST /settings/tokens handler.
+///
+/// It creates a new API token and shows it to the user, only this one time.
+#[tracing::instrument(
+    name = "Settings create API token",
+    skip(req, pool, session, flash_messages, csrf_token, form),
+    fields(
+        user_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_settings_tokens_create(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+    csrf_token: CsrfToken,
+    form: WebForm<ApiTokenFormData>,
+) -> Result<HttpResponse, InternalError<ApiTokenError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    let name = form.0.name.trim().to_string();
+    if name.is_empty() {
+        return Err(error_redirect(ApiTokenError::EmptyName, "/settings"));
+    }
+
+    let token = create_api_token(pool.as_ref(), user_id, &name)
+        .await
+        .map_err(ApiTokenError::Unexpected)
+        .map_err(|err| error_redirect(err, "/settings"))?;
+
+    //
+
+    let tpl = ApiTokenCreatedTemplate {
+        page: SETTINGS_PAGE,
+        user_id: Some(user_id),
+        flash_messages,
+        csrf_token: csrf_token.0,
+        name,
+        token: token.expose_secret().clone(),
+    };
+    let tpl_rendered = tpl
+        .render()
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(ApiTokenError::Unexpected)
+        .map_err(e500)?;
+
+    // The token must never end up in a cache
+    let response = HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .body(tpl_rendered);
+
+    Ok(response)
+}
+
+/// This is the DELETE /settings/tokens/:token_id handler.
+///
+/// It revokes the API token, requests using it are rejected from now on.
+#[tracing::instrument(
+    name = "Settings delete API token",
+    skip(req, pool, session, token_id),
+    fields(
+        user_id = tracing::field::Empty,
+        token_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_settings_tokens_delete(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    session: TypedSession,
+    token_id: WebPath<ApiTokenId>,
+) -> Result<HttpResponse, InternalError<ApiTokenError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+    let token_id = token_id.into_inner();
+
+    tracing::Span::current()
+        .record("user_id", &tracing::field::display(&user_id))
+        .record("token_id", &tracing::field::display(&token_id));
+
+    let found = delete_api_token(pool.as_ref(), user_id, token_id)
+        .await
+        .map_err(ApiTokenError::Unexpected)
+        .map_err(|err| error_redirect(err, "/settings"))?;
+    if !found {
+        return Err(error_redirect(ApiTokenError::NotFound, "/settings"));
+    }
+
+    FlashMessage::success("API token revoked").send();
+
+    Ok(see_other("/settings"))
+}
+
+struct SessionForTemplate {
+    id: Uuid,
+    created_at: String,
+    expires_at: String,
+    is_current: bool,
+}
+
+impl SessionForTemplate {
+    fn new(original: UserSession, current_session_id: Option<Uuid>) -> Self {
+        let format = |date: time::OffsetDateTime| {
+            date.replace_nanosecond(0)
+                .unwrap_or(date)
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or_else(|_| "unknown".to_string())
+        };
+
+        Self {
+            id: original.id,
+            created_at: format(original.created_at),
+            expires_at: format(original.expires_at),
+            is_current: current_session_id == Some(original.id),
+        }
+    }
+}
+
+#[derive(askama::Template)]
+#[template(path = "settings_sessions.html.j2")]
+struct SettingsSessionsTemplate {
+    pub page: &'static str,
+    pub user_id: Option<UserId>,
+    pub flash_messages: IncomingFlashMessages,
+    pub csrf_token: String,
+    pub sessions: Vec<SessionForTemplate>,
+}
+
+/// This is the GET /settings/sessions handler.
+///
+/// It lists the active sessions of the user so that it can log out the ones it doesn't know.
+#[tracing::instrument(
+    name = "Settings sessions",
+    skip(req, pool, session, flash_messages, csrf_token),
+    fields(
+        user_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_settings_sessions(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+    csrf_token: CsrfToken,
+) -> Result<HttpResponse, InternalError<anyhow::Error>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    //
+
+    let current_session_id = session.session_id();
+    let sessions = get_user_sessions(pool.as_ref(), user_id)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(|user_session| SessionForTemplate::new(user_session, current_session_id))
+        .collect();
+
+    //
+
+    let tpl = SettingsSessionsTemplate {
+        page: SETTINGS_PAGE,
+        user_id: Some(user_id),
+        flash_messages,
+        csrf_token: csrf_token.0,
+        sessions,
     };
     let tpl_rendered = tpl
         .render()
@@ -49,3 +531,343 @@ pub async fn handle_settings(
 
     Ok(response)
 }
+
+#[derive(thiserror::Error)]
+pub enum SessionDeleteError {
+    #[error("Session not found")]
+    NotFound,
+    #[error("Something went wrong")]
+    Unexpected(#[from] anyhow::Error),
+}
+
+debug_with_error_chain!(SessionDeleteError);
+
+/// This is the DELETE /settings/sessions/:session_id handler.
+///
+/// It deletes the session from the session store: the device using it is logged out.
+#[tracing::instrument(
+    name = "Settings delete session",
+    skip(req, pool, session, session_id),
+    fields(
+        user_id = tracing::field::Empty,
+        session_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_settings_session_delete(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    session: TypedSession,
+    session_id: WebPath<Uuid>,
+) -> Result<HttpResponse, InternalError<SessionDeleteError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+    let session_id = session_id.into_inner();
+
+    tracing::Span::current()
+        .record("user_id", &tracing::field::display(&user_id))
+        .record("session_id", &tracing::field::display(&session_id));
+
+    let found = delete_user_session(pool.as_ref(), user_id, session_id)
+        .await
+        .map_err(SessionDeleteError::Unexpected)
+        .map_err(|err| error_redirect(err, "/settings/sessions"))?;
+    if !found {
+        return Err(error_redirect(
+            SessionDeleteError::NotFound,
+            "/settings/sessions",
+        ));
+    }
+
+    FlashMessage::success("Session logged out").send();
+
+    Ok(see_other("/settings/sessions"))
+}
+
+#[derive(Deserialize)]
+pub struct AccountDeleteFormData {
+    pub password: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum AccountDeleteError {
+    #[error("Invalid password")]
+    InvalidPassword(#[source] anyhow::Error),
+    #[error("Something went wrong")]
+    Unexpected(#[from] anyhow::Error),
+}
+
+debug_with_error_chain!(AccountDeleteError);
+
+/// This is the DELETE /settings/account handler.
+///
+/// It deletes the account of the user and everything it owns once the user has confirmed its
+/// password. The user is logged out everywhere.
+#[tracing::instrument(
+    name = "Settings delete account",
+    skip(req, pool, hasher_config, session, form),
+    fields(
+        user_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_settings_account_delete(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    hasher_config: WebData<PasswordHasherConfig>,
+    session: TypedSession,
+    form: WebForm<AccountDeleteFormData>,
+) -> Result<HttpResponse, InternalError<AccountDeleteError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    // 1) Check the password
+
+    let user = get_user(pool.as_ref(), user_id)
+        .await
+        .map_err(AccountDeleteError::Unexpected)
+        .map_err(e500)?
+        .ok_or_else(|| anyhow!("user {} not found", user_id))
+        .map_err(AccountDeleteError::Unexpected)
+        .map_err(e500)?;
+
+    let credentials = Credentials {
+        email: user.email,
+        password: Secret::new(form.0.password),
+    };
+    match authenticate(&pool, &hasher_config, credentials).await {
+        Ok(authenticated_user_id) if authenticated_user_id == user_id => {}
+        Ok(_) => {
+            let err = anyhow!("the credentials belong to another user");
+            return Err(error_redirect(
+                AccountDeleteError::InvalidPassword(err),
+                "/settings",
+            ));
+        }
+        Err(AuthError::InvalidCredentials(err)) => {
+            return Err(error_redirect(
+                AccountDeleteError::InvalidPassword(err),
+                "/settings",
+            ));
+        }
+        Err(err @ AuthError::WeakPassword(_)) => {
+            return Err(e500(AccountDeleteError::Unexpected(err.into())));
+        }
+        Err(AuthError::Unexpected(err)) => {
+            return Err(e500(AccountDeleteError::Unexpected(err)));
+        }
+    }
+
+    // 2) Delete everything owned by the user, then the user itself
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(AccountDeleteError::Unexpected)
+        .map_err(e500)?;
+
+    delete_all_feeds(&mut tx, user_id)
+        .await
+        .map_err(AccountDeleteError::Unexpected)
+        .map_err(e500)?;
+    delete_user_jobs(&mut tx, user_id)
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(AccountDeleteError::Unexpected)
+        .map_err(e500)?;
+    delete_user_sessions(&mut tx, user_id)
+        .await
+        .map_err(AccountDeleteError::Unexpected)
+        .map_err(e500)?;
+    delete_user(&mut tx, user_id)
+        .await
+        .map_err(AccountDeleteError::Unexpected)
+        .map_err(e500)?;
+
+    tx.commit()
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(AccountDeleteError::Unexpected)
+        .map_err(e500)?;
+
+    // 3) The session is gone from the database, also drop the cookie.
+    // Flash messages are stored in a cookie of their own so this one survives.
+
+    session.logout();
+
+    FlashMessage::info("Your account has been deleted").send();
+
+    Ok(see_other("/"))
+}
+
+#[derive(Deserialize)]
+pub struct EmailChangeFormData {
+    pub new_email: String,
+    pub password: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum EmailChangeError {
+    #[error("Invalid email")]
+    InvalidEmail(#[source] anyhow::Error),
+    #[error("Invalid password")]
+    InvalidPassword(#[source] anyhow::Error),
+    #[error("Invalid or expired confirmation link")]
+    InvalidLink,
+    #[error("Emails are not enabled")]
+    EmailsDisabled,
+    #[error("Something went wrong")]
+    Unexpected(#[from] anyhow::Error),
+}
+
+debug_with_error_chain!(EmailChangeError);
+
+/// This is the POST /settings/email handler.
+///
+/// Once the user has confirmed its password it sends a confirmation link to the new email.
+/// The email is only changed when the link is visited, see [`handle_settings_email_confirm`].
+#[tracing::instrument(
+    name = "Settings change email",
+    skip(req, pool, hasher_config, tem_client, hmac_secret, base_url, session, form),
+    fields(
+        user_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_settings_email(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    hasher_config: WebData<PasswordHasherConfig>,
+    tem_client: Option<WebData<tem::Client>>,
+    hmac_secret: WebData<HmacSecret>,
+    base_url: WebData<ApplicationBaseUrl>,
+    session: TypedSession,
+    form: WebForm<EmailChangeFormData>,
+) -> Result<HttpResponse, InternalError<EmailChangeError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    let tem_client =
+        tem_client.ok_or_else(|| error_redirect(EmailChangeError::EmailsDisabled, "/settings"))?;
+
+    let new_email = UserEmail::parse(form.0.new_email.trim().to_string())
+        .map_err(EmailChangeError::InvalidEmail)
+        .map_err(|err| error_redirect(err, "/settings"))?;
+
+    // 1) Check the password
+
+    let user = get_user(pool.as_ref(), user_id)
+        .await
+        .map_err(EmailChangeError::Unexpected)
+        .map_err(e500)?
+        .ok_or_else(|| anyhow!("user {} not found", user_id))
+        .map_err(EmailChangeError::Unexpected)
+        .map_err(e500)?;
+
+    let credentials = Credentials {
+        email: user.email,
+        password: Secret::new(form.0.password),
+    };
+    match authenticate(&pool, &hasher_config, credentials).await {
+        Ok(authenticated_user_id) if authenticated_user_id == user_id => {}
+        Ok(_) => {
+            let err = anyhow!("the credentials belong to another user");
+            return Err(error_redirect(
+                EmailChangeError::InvalidPassword(err),
+                "/settings",
+            ));
+        }
+        Err(AuthError::InvalidCredentials(err)) => {
+            return Err(error_redirect(
+                EmailChangeError::InvalidPassword(err),
+                "/settings",
+            ));
+        }
+        Err(err @ AuthError::WeakPassword(_)) => {
+            return Err(e500(EmailChangeError::Unexpected(err.into())));
+        }
+        Err(AuthError::Unexpected(err)) => {
+            return Err(e500(EmailChangeError::Unexpected(err)));
+        }
+    }
+
+    // 2) Store the pending change and send the confirmation link to the new email
+
+    set_pending_email_change(pool.as_ref(), user_id, &new_email)
+        .await
+        .map_err(EmailChangeError::Unexpected)
+        .map_err(e500)?;
+
+    let token = EmailChangeToken::new(&hmac_secret.0, user_id, &new_email);
+    let link = format!(
+        "{}/settings/email/confirm/{}",
+        base_url.0.trim_end_matches('/'),
+        token
+    );
+
+    tem_client
+        .send_email_single(
+            &new_email,
+            "Confirm your new Servare email",
+            &format!(
+                "<p>Visit <a href=\"{}\">this link</a> to confirm your new email.</p>",
+                link
+            ),
+            &format!("Visit {} to confirm your new email.", link),
+        )
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(EmailChangeError::Unexpected)
+        .map_err(e500)?;
+
+    FlashMessage::success(format!(
+        "A confirmation email has been sent to {}",
+        new_email
+    ))
+    .send();
+
+    Ok(see_other("/settings"))
+}
+
+/// This is the GET /settings/email/confirm/:token handler.
+///
+/// It changes the email of the user if the token is valid for its pending email change.
+#[tracing::instrument(
+    name = "Settings confirm email change",
+    skip(pool, hmac_secret, token),
+    fields(
+        user_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_settings_email_confirm(
+    pool: WebData<PgPool>,
+    hmac_secret: WebData<HmacSecret>,
+    token: WebPath<String>,
+) -> Result<HttpResponse, InternalError<EmailChangeError>> {
+    let token = EmailChangeToken::parse(&token.into_inner())
+        .ok_or_else(|| error_redirect(EmailChangeError::InvalidLink, "/settings"))?;
+    let user_id = token.user_id;
+
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    let new_email = get_pending_email_change(pool.as_ref(), user_id)
+        .await
+        .map_err(EmailChangeError::Unexpected)
+        .map_err(e500)?
+        .ok_or_else(|| error_redirect(EmailChangeError::InvalidLink, "/settings"))?;
+
+    if !token.verify(&hmac_secret.0, &new_email) {
+        return Err(error_redirect(EmailChangeError::InvalidLink, "/settings"));
+    }
+
+    let changed = confirm_email_change(pool.as_ref(), user_id, &new_email)
+        .await
+        .map_err(EmailChangeError::Unexpected)
+        .map_err(e500)?;
+    if !changed {
+        return Err(error_redirect(EmailChangeError::InvalidLink, "/settings"));
+    }
+
+    FlashMessage::success(format!("Your email is now {}", new_email)).send();
+
+    Ok(see_other("/settings"))
+}
@@ -0,0 +1,87 @@
+use crate::debug_with_error_chain;
+use crate::domain::UserId;
+use crate::feed::get_starred_entries;
+use crate::preferences::get_user_preferences;
+use crate::routes::unread::FeedEntryForTemplate;
+use crate::routes::{e500, get_user_id_or_redirect, STARRED_PAGE};
+use crate::sessions::{CsrfToken, TypedSession};
+use actix_web::error::InternalError;
+use actix_web::http;
+use actix_web::web::Data as WebData;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use sqlx::PgPool;
+
+#[derive(askama::Template)]
+#[template(path = "starred.html.j2")]
+struct StarredTemplate {
+    pub page: &'static str,
+    pub user_id: Option<UserId>,
+    pub flash_messages: IncomingFlashMessages,
+    pub csrf_token: String,
+    pub entries: Vec<FeedEntryForTemplate>,
+}
+
+#[derive(thiserror::Error)]
+pub enum StarredError {
+    #[error("Something went wrong")]
+    Unexpected(#[from] anyhow::Error),
+}
+
+debug_with_error_chain!(StarredError);
+
+/// This is the /starred handler.
+///
+/// It shows the starred entries of all the feeds, the most recently starred first.
+#[tracing::instrument(
+    name = "Starred",
+    skip(req, pool, session, flash_messages, csrf_token),
+    fields(
+        user_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_starred(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+    csrf_token: CsrfToken,
+) -> Result<HttpResponse, InternalError<StarredError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    let (original_feed_entries, preferences) = tokio::try_join!(
+        get_starred_entries(pool.as_ref(), user_id),
+        get_user_preferences(pool.as_ref(), user_id),
+    )
+    .map_err(StarredError::Unexpected)
+    .map_err(e500)?;
+
+    let feed_entries = original_feed_entries
+        .into_iter()
+        .map(|entry| FeedEntryForTemplate::new(entry, preferences.timezone))
+        .collect();
+
+    // Render
+
+    let tpl = StarredTemplate {
+        page: STARRED_PAGE,
+        user_id: Some(user_id),
+        flash_messages,
+        csrf_token: csrf_token.0,
+        entries: feed_entries,
+    };
+    let tpl_rendered = tpl
+        .render()
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(StarredError::Unexpected)
+        .map_err(e500)?;
+
+    let response = HttpResponse::Ok()
+        .content_type(http::header::ContentType::html())
+        .body(tpl_rendered);
+
+    Ok(response)
+}
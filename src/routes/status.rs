@@ -0,0 +1,142 @@
+use crate::debug_with_error_chain;
+use actix_web::web::Data as WebData;
+use actix_web::HttpResponse;
+use serde::Serialize;
+use sqlx::migrate::{Migrate, MigrateError};
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::time::Duration as StdDuration;
+use tracing::warn;
+
+/// How long a single dependency check can take before the application is considered degraded.
+const CHECK_TIMEOUT: StdDuration = StdDuration::from_secs(2);
+
+/// The body of a `/status` or `/readiness` response.
+#[derive(Debug, Serialize)]
+pub struct StatusResponse {
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl StatusResponse {
+    fn ok() -> Self {
+        Self {
+            status: "ok",
+            reason: None,
+        }
+    }
+
+    fn degraded(err: &CheckError) -> Self {
+        Self {
+            status: "degraded",
+            reason: Some(err.to_string()),
+        }
+    }
+}
+
+#[derive(thiserror::Error)]
+enum CheckError {
+    #[error("check timed out")]
+    Timeout,
+    #[error("database is unreachable")]
+    Database(#[source] sqlx::Error),
+    #[error("unable to read the migration status")]
+    Migrate(#[source] MigrateError),
+    #[error("migration {0} is only partially applied")]
+    DirtyMigration(i64),
+    #[error("migration {0} is not applied")]
+    PendingMigration(i64),
+}
+
+debug_with_error_chain!(CheckError);
+
+impl From<sqlx::Error> for CheckError {
+    fn from(err: sqlx::Error) -> Self {
+        CheckError::Database(err)
+    }
+}
+
+impl From<MigrateError> for CheckError {
+    fn from(err: MigrateError) -> Self {
+        CheckError::Migrate(err)
+    }
+}
+
+/// Runs `check` with a [`CHECK_TIMEOUT`] timeout.
+async fn with_timeout<F>(check: F) -> Result<(), CheckError>
+where
+    F: std::future::Future<Output = Result<(), CheckError>>,
+{
+    match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => Err(CheckError::Timeout),
+    }
+}
+
+/// Checks that the database accepts queries.
+async fn check_database(pool: &PgPool) -> Result<(), CheckError> {
+    sqlx::query("SELECT 1").execute(pool).await?;
+
+    Ok(())
+}
+
+/// Checks that every migration known to the application is applied and none is dirty.
+async fn check_migrations(pool: &PgPool) -> Result<(), CheckError> {
+    let mut conn = pool.acquire().await?;
+
+    if let Some(version) = conn.dirty_version().await? {
+        return Err(CheckError::DirtyMigration(version));
+    }
+
+    let applied = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect::<HashSet<_>>();
+
+    let migrator = sqlx::migrate!("./migrations");
+    for migration in migrator.iter() {
+        if !migration.migration_type.is_down_migration() && !applied.contains(&migration.version) {
+            return Err(CheckError::PendingMigration(migration.version));
+        }
+    }
+
+    Ok(())
+}
+
+fn build_response(result: Result<(), CheckError>) -> HttpResponse {
+    match result {
+        Ok(()) => HttpResponse::Ok().json(StatusResponse::ok()),
+        Err(err) => {
+            warn!(error = ?err, "application is degraded");
+
+            HttpResponse::ServiceUnavailable().json(StatusResponse::degraded(&err))
+        }
+    }
+}
+
+/// Liveness check.
+///
+/// Responds with a 200 OK if the database answers a trivial query in time, a 503 Service
+/// Unavailable otherwise. Doesn't require authentication.
+#[tracing::instrument(name = "Status", skip(pool))]
+pub async fn handle_status(pool: WebData<PgPool>) -> HttpResponse {
+    build_response(with_timeout(check_database(&pool)).await)
+}
+
+/// Readiness check.
+///
+/// Like [`handle_status`] but also checks that the database schema is up to date, that is every
+/// migration known to this build is applied. Doesn't require authentication.
+#[tracing::instrument(name = "Readiness", skip(pool))]
+pub async fn handle_readiness(pool: WebData<PgPool>) -> HttpResponse {
+    let result = async {
+        with_timeout(check_database(&pool)).await?;
+        with_timeout(check_migrations(&pool)).await
+    }
+    .await;
+
+    build_response(result)
+}
@@ -0,0 +1,105 @@
+use crate::debug_with_error_chain;
+use crate::domain::UserId;
+use crate::feed::{get_feeds_by_tag, get_unread_entries_by_tag, Feed};
+use crate::preferences::get_user_preferences;
+use crate::routes::unread::FeedEntryForTemplate;
+use crate::routes::{e500, error_redirect, get_user_id_or_redirect, FEEDS_PAGE};
+use crate::sessions::{CsrfToken, TypedSession};
+use actix_web::error::InternalError;
+use actix_web::http;
+use actix_web::web::{Data as WebData, Path as WebPath};
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use sqlx::PgPool;
+
+#[derive(askama::Template)]
+#[template(path = "tag_entries.html.j2")]
+struct TagEntriesTemplate {
+    pub page: &'static str,
+    pub user_id: Option<UserId>,
+    pub flash_messages: IncomingFlashMessages,
+    pub csrf_token: String,
+    pub tag: String,
+    pub feeds: Vec<Feed>,
+    pub entries: Vec<FeedEntryForTemplate>,
+}
+
+#[derive(thiserror::Error)]
+pub enum TagEntriesError {
+    #[error("Tag not found")]
+    NotFound,
+    #[error("Something went wrong")]
+    Unexpected(#[from] anyhow::Error),
+}
+
+debug_with_error_chain!(TagEntriesError);
+
+/// This is the /tags/:tag/entries handler.
+///
+/// It shows the unread entries of all the feeds tagged with the tag.
+#[tracing::instrument(
+    name = "Tag entries",
+    skip(req, pool, session, flash_messages, csrf_token, tag),
+    fields(
+        user_id = tracing::field::Empty,
+        tag = tracing::field::Empty,
+    )
+)]
+pub async fn handle_tag_entries(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+    csrf_token: CsrfToken,
+    tag: WebPath<String>,
+) -> Result<HttpResponse, InternalError<TagEntriesError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+    let tag = tag.into_inner();
+
+    tracing::Span::current()
+        .record("user_id", &tracing::field::display(&user_id))
+        .record("tag", &tracing::field::display(&tag));
+
+    // Get the feeds and their unread entries
+
+    let (feeds, original_feed_entries, preferences) = tokio::try_join!(
+        get_feeds_by_tag(pool.as_ref(), user_id, &tag),
+        get_unread_entries_by_tag(pool.as_ref(), user_id, &tag),
+        get_user_preferences(pool.as_ref(), user_id),
+    )
+    .map_err(TagEntriesError::Unexpected)
+    .map_err(e500)?;
+
+    if feeds.is_empty() {
+        return Err(error_redirect(TagEntriesError::NotFound, "/feeds"));
+    }
+
+    let feed_entries = original_feed_entries
+        .into_iter()
+        .map(|entry| FeedEntryForTemplate::new(entry, preferences.timezone))
+        .collect();
+
+    // Render
+
+    let tpl = TagEntriesTemplate {
+        page: FEEDS_PAGE,
+        user_id: Some(user_id),
+        flash_messages,
+        csrf_token: csrf_token.0,
+        tag,
+        feeds,
+        entries: feed_entries,
+    };
+    let tpl_rendered = tpl
+        .render()
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(TagEntriesError::Unexpected)
+        .map_err(e500)?;
+
+    let response = HttpResponse::Ok()
+        .content_type(http::header::ContentType::html())
+        .body(tpl_rendered);
+
+    Ok(response)
+}
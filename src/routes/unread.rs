@@ -1,41 +1,50 @@
 use crate::debug_with_error_chain;
 use crate::domain::UserId;
-use crate::feed::get_unread_entries;
-use crate::feed::FeedEntry;
-use crate::routes::{e500, get_user_id_or_redirect, UNREAD_PAGE};
-use crate::sessions::TypedSession;
+use crate::feed::{get_unread_entries, get_user_unread_since_days, mark_all_entries_as_read};
+use crate::feed::{FeedEntry, UnreadFilter};
+use crate::html::html_to_text;
+use crate::preferences::{get_user_preferences, Timezone};
+use crate::routes::{e500, error_redirect, get_user_id_or_redirect, see_other, UNREAD_PAGE};
+use crate::sessions::{CsrfToken, TypedSession};
 use actix_web::error::InternalError;
 use actix_web::http;
 use actix_web::web::Data as WebData;
-use actix_web::HttpResponse;
-use actix_web_flash_messages::IncomingFlashMessages;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
 use askama::Template;
 use sqlx::PgPool;
+use std::fmt::Write;
 
 // TODO(vincent): this is duplicated code, refactor it
 
-struct FeedEntryForTemplate {
-    original: FeedEntry,
-    created_at: String,
-    author: String,
+pub(super) struct FeedEntryForTemplate {
+    pub(super) original: FeedEntry,
+    pub(super) published_at: String,
+    pub(super) author: String,
+    pub(super) is_read: bool,
 }
 
 impl FeedEntryForTemplate {
-    fn new(original: FeedEntry) -> Self {
+    /// The publication date is shown in the `timezone` of the user.
+    pub(super) fn new(original: FeedEntry, timezone: Timezone) -> Self {
         // TODO(vincent): this is ugly, can we replace the unwrap() ?
-        let created_at = original
-            .created_at
+        let published_at = original
+            .published_at
+            .unwrap_or(original.created_at)
+            .to_offset(timezone.offset())
             .replace_nanosecond(0_000_000)
             .unwrap()
             .format(&time::format_description::well_known::Rfc3339)
             .unwrap_or_else(|_| "unknown".to_string()); // TODO(vincent): can this really fail ?
 
         let author = original.authors.first().cloned().unwrap_or_default();
+        let is_read = original.is_read();
 
         Self {
             original,
-            created_at,
+            published_at,
             author,
+            is_read,
         }
     }
 }
@@ -46,9 +55,14 @@ struct UnreadTemplate {
     pub page: &'static str,
     pub user_id: Option<UserId>,
     pub flash_messages: IncomingFlashMessages,
+    pub csrf_token: String,
     pub entries: Vec<FeedEntryForTemplate>,
+    pub since_days: Option<i32>,
 }
 
+/// The maximum number of entries shown in the unread page.
+const UNREAD_ENTRIES_LIMIT: usize = 500;
+
 #[derive(thiserror::Error)]
 pub enum UnreadError {
     #[error("Something went wrong")]
@@ -59,30 +73,40 @@ debug_with_error_chain!(UnreadError);
 
 #[tracing::instrument(
     name = "Unread",
-    skip(pool, session, flash_messages),
+    skip(req, pool, session, flash_messages, csrf_token),
     fields(
         user_id = tracing::field::Empty,
     )
 )]
 pub async fn handle_unread(
+    req: HttpRequest,
     pool: WebData<PgPool>,
     session: TypedSession,
     flash_messages: IncomingFlashMessages,
+    csrf_token: CsrfToken,
 ) -> Result<HttpResponse, InternalError<UnreadError>> {
-    let user_id = get_user_id_or_redirect(&session)?;
+    let user_id = get_user_id_or_redirect(&req, &session)?;
 
     tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
 
-    // Fetch the unread entries
+    // Fetch the unread entries, only from the last days if the user wants to
+
+    let (since_days, preferences) = tokio::try_join!(
+        get_user_unread_since_days(pool.as_ref(), user_id),
+        get_user_preferences(pool.as_ref(), user_id),
+    )
+    .map_err(UnreadError::Unexpected)
+    .map_err(e500)?;
+    let filter = UnreadFilter::last_days(since_days, UNREAD_ENTRIES_LIMIT);
 
-    let original_feed_entries = get_unread_entries(pool.as_ref(), user_id)
+    let original_feed_entries = get_unread_entries(pool.as_ref(), user_id, filter)
         .await
         .map_err(UnreadError::Unexpected)
         .map_err(e500)?;
 
     let feed_entries = original_feed_entries
         .into_iter()
-        .map(FeedEntryForTemplate::new)
+        .map(|entry| FeedEntryForTemplate::new(entry, preferences.timezone))
         .collect();
 
     // Render
@@ -91,7 +115,9 @@ pub async fn handle_unread(
         page: UNREAD_PAGE,
         user_id: Some(user_id),
         flash_messages,
+        csrf_token: csrf_token.0,
         entries: feed_entries,
+        since_days,
     };
     let tpl_rendered = tpl
         .render()
@@ -105,3 +131,100 @@ pub async fn handle_unread(
 
     Ok(response)
 }
+
+/// Render `entries` as a Markdown document, one section per entry.
+fn render_entries_as_markdown(entries: &[FeedEntryForTemplate]) -> String {
+    let mut result = String::new();
+
+    for entry in entries {
+        let _ = write!(
+            result,
+            "## {}\n*{} — {}*\n\n{}\n\n",
+            entry.original.title,
+            entry.author,
+            entry.published_at,
+            html_to_text(&entry.original.summary),
+        );
+
+        if let Some(ref url) = entry.original.url {
+            let _ = write!(result, "[Read original]({})\n\n", url);
+        }
+    }
+
+    result
+}
+
+/// This is the /feeds/export/unread.md handler.
+///
+/// It exports all unread entries of the user as a Markdown file.
+#[tracing::instrument(
+    name = "Export unread entries",
+    skip(req, pool, session),
+    fields(
+        user_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_unread_export_markdown(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    session: TypedSession,
+) -> Result<HttpResponse, InternalError<UnreadError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    // Fetch all the unread entries
+
+    let (original_feed_entries, preferences) = tokio::try_join!(
+        get_unread_entries(pool.as_ref(), user_id, UnreadFilter::default()),
+        get_user_preferences(pool.as_ref(), user_id),
+    )
+    .map_err(UnreadError::Unexpected)
+    .map_err(e500)?;
+
+    let feed_entries = original_feed_entries
+        .into_iter()
+        .map(|entry| FeedEntryForTemplate::new(entry, preferences.timezone))
+        .collect::<Vec<_>>();
+
+    // Render
+
+    let response = HttpResponse::Ok()
+        .content_type("text/markdown; charset=utf-8")
+        .insert_header((
+            http::header::CONTENT_DISPOSITION,
+            r#"attachment; filename="unread.md""#,
+        ))
+        .body(render_entries_as_markdown(&feed_entries));
+
+    Ok(response)
+}
+
+/// This is the /unread/mark-read handler.
+///
+/// Marks all unread entries of all feeds of the user as read.
+#[tracing::instrument(
+    name = "Unread mark read",
+    skip(req, pool, session),
+    fields(
+        user_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_unread_mark_read(
+    req: HttpRequest,
+    pool: WebData<PgPool>,
+    session: TypedSession,
+) -> Result<HttpResponse, InternalError<UnreadError>> {
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    let marked = mark_all_entries_as_read(pool.as_ref(), user_id)
+        .await
+        .map_err(UnreadError::Unexpected)
+        .map_err(|err| error_redirect(err, "/unread"))?;
+
+    FlashMessage::success(format!("Marked {} entries as read", marked)).send();
+
+    Ok(see_other("/unread"))
+}
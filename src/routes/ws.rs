@@ -0,0 +1,172 @@
+use crate::routes::get_user_id_or_redirect;
+use crate::sessions::TypedSession;
+use crate::startup::ApplicationBaseUrl;
+use crate::unread_updates::UnreadUpdates;
+use actix_web::error::InternalError;
+use actix_web::http::header;
+use actix_web::web::Data as WebData;
+use actix_web::web::Payload as WebPayload;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_ws::Message;
+use futures_util::StreamExt;
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{event, Level};
+use url::Url;
+
+/// Returns true if the `Origin` header of `req` matches the base URL of the application.
+///
+/// Browsers always send the origin of the page opening a WebSocket, this prevents any other site
+/// from opening a WebSocket with the cookies of the user. A request without an `Origin` header
+/// doesn't come from a browser and is allowed.
+///
+/// The port is only checked if the base URL has an explicit one.
+fn is_allowed_origin(req: &HttpRequest, base_url: &ApplicationBaseUrl) -> bool {
+    let origin = match req.headers().get(header::ORIGIN) {
+        Some(origin) => origin,
+        None => return true,
+    };
+
+    let (origin, base_url) = match (
+        origin.to_str().ok().and_then(|v| Url::parse(v).ok()),
+        Url::parse(&base_url.0).ok(),
+    ) {
+        (Some(origin), Some(base_url)) => (origin, base_url),
+        _ => return false,
+    };
+
+    origin.scheme() == base_url.scheme()
+        && origin.host() == base_url.host()
+        && (base_url.port().is_none()
+            || origin.port_or_known_default() == base_url.port_or_known_default())
+}
+
+/// This is the /ws handler.
+///
+/// It upgrades the connection to a WebSocket and pushes the changes in the number of unread
+/// entries of the user as JSON messages: `{"unread_delta": N}`.
+///
+/// Connections from another origin than the application are rejected, see [`is_allowed_origin`].
+#[tracing::instrument(
+    name = "WebSocket",
+    skip(req, body, session, base_url, unread_updates),
+    fields(
+        user_id = tracing::field::Empty,
+    )
+)]
+pub async fn handle_ws(
+    req: HttpRequest,
+    body: WebPayload,
+    session: TypedSession,
+    base_url: WebData<ApplicationBaseUrl>,
+    unread_updates: WebData<UnreadUpdates>,
+) -> Result<HttpResponse, InternalError<anyhow::Error>> {
+    if !is_allowed_origin(&req, &base_url) {
+        event!(Level::WARN, origin = ?req.headers().get(header::ORIGIN), "rejected a WebSocket from another origin");
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let user_id = get_user_id_or_redirect(&req, &session)?;
+
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    let (response, mut ws_session, mut messages) = actix_ws::handle(&req, body).map_err(|err| {
+        InternalError::from_response(anyhow::anyhow!("{}", err), err.error_response())
+    })?;
+
+    // Subscribe before returning the response so that no update is missed
+    let mut updates = unread_updates.subscribe();
+
+    actix_web::rt::spawn(async move {
+        loop {
+            tokio::select! {
+                update = updates.recv() => match update {
+                    Ok(update) if update.user_id == user_id => {
+                        let message = json!({ "unread_delta": update.delta }).to_string();
+                        if ws_session.text(message).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(skipped)) => {
+                        event!(Level::WARN, %user_id, skipped, "websocket lagged behind the unread updates");
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                message = messages.next() => match message {
+                    Some(Ok(Message::Ping(data))) => {
+                        if ws_session.pong(&data).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Ok(Message::Close(reason))) => {
+                        let _ = ws_session.close(reason).await;
+                        return;
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(_)) | None => break,
+                },
+            }
+        }
+
+        let _ = ws_session.close(None).await;
+    });
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn is_allowed(origin: Option<&str>, base_url: &str) -> bool {
+        let mut req = TestRequest::get();
+        if let Some(origin) = origin {
+            req = req.insert_header((header::ORIGIN, origin));
+        }
+
+        is_allowed_origin(
+            &req.to_http_request(),
+            &ApplicationBaseUrl(base_url.to_string()),
+        )
+    }
+
+    #[test]
+    fn origin_of_the_application_should_be_allowed() {
+        assert!(is_allowed(None, "https://servare.example"));
+        assert!(is_allowed(
+            Some("https://servare.example"),
+            "https://servare.example"
+        ));
+        assert!(is_allowed(
+            Some("https://servare.example:443"),
+            "https://servare.example/"
+        ));
+        assert!(is_allowed(
+            Some("http://127.0.0.1:4052"),
+            "http://127.0.0.1"
+        ));
+        assert!(is_allowed(
+            Some("http://127.0.0.1:4052"),
+            "http://127.0.0.1:4052"
+        ));
+    }
+
+    #[test]
+    fn other_origins_should_be_rejected() {
+        assert!(!is_allowed(
+            Some("https://evil.example"),
+            "https://servare.example"
+        ));
+        assert!(!is_allowed(
+            Some("http://servare.example"),
+            "https://servare.example"
+        ));
+        assert!(!is_allowed(
+            Some("http://127.0.0.1:8080"),
+            "http://127.0.0.1:4052"
+        ));
+        assert!(!is_allowed(Some("null"), "https://servare.example"));
+    }
+}
@@ -1,4 +1,6 @@
+use anyhow::anyhow;
 use std::future::Future;
+use std::time::Duration as StdDuration;
 use tokio::sync::broadcast::Receiver;
 use tracing::{debug, info, trace};
 
@@ -52,6 +54,7 @@ impl Shutdown {
 pub struct RunGroup {
     set: tokio::task::JoinSet<anyhow::Result<()>>,
     shutdown_sender: tokio::sync::broadcast::Sender<()>,
+    start_sender: tokio::sync::watch::Sender<bool>,
 }
 
 impl Default for RunGroup {
@@ -63,10 +66,12 @@ impl Default for RunGroup {
 impl RunGroup {
     pub fn new() -> Self {
         let (shutdown_sender, _) = tokio::sync::broadcast::channel(2);
+        let (start_sender, _) = tokio::sync::watch::channel(false);
 
         Self {
             set: tokio::task::JoinSet::new(),
             shutdown_sender,
+            start_sender,
         }
     }
 
@@ -85,6 +90,33 @@ impl RunGroup {
         self
     }
 
+    /// Creates a new task that will run the function `f`, which must complete within `timeout`.
+    ///
+    /// The timeout starts when [`RunGroup::start`] is called; if it elapses the task fails.
+    pub fn run_with_timeout<Func, F>(mut self, timeout: StdDuration, f: Func) -> Self
+    where
+        Func: FnOnce(Shutdown) -> F,
+        F: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let shutdown = Shutdown::new(self.shutdown_sender.subscribe());
+        let mut start_recv = self.start_sender.subscribe();
+
+        let future = f(shutdown);
+
+        self.set.spawn(async move {
+            // The run group was dropped without being started
+            if start_recv.changed().await.is_err() {
+                return Ok(());
+            }
+
+            tokio::time::timeout(timeout, future)
+                .await
+                .map_err(|_| anyhow!("task timed out after {:?}", timeout))?
+        });
+
+        self
+    }
+
     /// Start the run group
     pub async fn start(mut self) -> anyhow::Result<()> {
         // Add a final task that will notify all other tasks of a shutdown
@@ -100,6 +132,9 @@ impl RunGroup {
 
         info!("starting");
 
+        // Start the timeout of the tasks run with [`RunGroup::run_with_timeout`]
+        let _ = self.start_sender.send(true);
+
         // Wait for all tasks to be done
         while let Some(result) = self.set.join_next().await {
             // First ? operator for the future returned by spawn()
@@ -136,3 +171,43 @@ impl RunGroup {
         debug!("signal received, starting graceful shutdown");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn start_should_fail_when_a_task_times_out() {
+        let result = RunGroup::new()
+            .run_with_timeout(StdDuration::from_millis(10), |_shutdown| async {
+                tokio::time::sleep(StdDuration::from_secs(60)).await;
+                Ok(())
+            })
+            .start()
+            .await;
+
+        let err = result.expect_err("the run group should fail");
+        assert!(
+            err.to_string().contains("timed out"),
+            "unexpected error {}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn start_should_succeed_when_a_task_completes_within_its_timeout() {
+        // The run group only ends on a shutdown signal or on an error: stop it with an error once
+        // the timeout elapsed. If the timeout fired, its error would be returned instead.
+        let result = RunGroup::new()
+            .run_with_timeout(StdDuration::from_millis(100), |_shutdown| async { Ok(()) })
+            .run(|_shutdown| async {
+                tokio::time::sleep(StdDuration::from_millis(300)).await;
+                Err(anyhow!("stopping the run group"))
+            })
+            .start()
+            .await;
+
+        let err = result.expect_err("the run group should have been stopped");
+        assert_eq!("stopping the run group", err.to_string());
+    }
+}
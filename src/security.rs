@@ -0,0 +1,301 @@
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use url::{Host, Url};
+
+/// The address of the instance metadata service of most cloud providers.
+const METADATA_ADDR: Ipv4Addr = Ipv4Addr::new(169, 254, 169, 254);
+
+/// The maximum number of redirects followed by [`redirect_policy`].
+const MAX_REDIRECTS: usize = 10;
+
+/// Whether the feed URLs pointing to a private address can be fetched.
+///
+/// This is only useful in tests and development, where feeds are served locally.
+#[derive(Clone, Copy, Debug)]
+pub struct AllowPrivateUrls(pub bool);
+
+/// Returns true if `url` can be fetched without exposing the internal network of the server.
+///
+/// The host of `url` is resolved and the URL is rejected if any of its addresses is a loopback,
+/// private (RFC 1918), unique local, link-local or unspecified address, or the cloud metadata
+/// address. A host which can't be resolved is rejected too.
+///
+/// Note this resolves the host synchronously: call it from a blocking task.
+pub fn is_safe_url(url: &Url) -> bool {
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let addrs: Vec<IpAddr> = match url.host() {
+        Some(Host::Ipv4(ip)) => vec![IpAddr::V4(ip)],
+        Some(Host::Ipv6(ip)) => vec![IpAddr::V6(ip)],
+        Some(Host::Domain(domain)) => match (domain, port).to_socket_addrs() {
+            Ok(addrs) => addrs.map(|addr| addr.ip()).collect(),
+            Err(_) => return false,
+        },
+        None => return false,
+    };
+
+    !addrs.is_empty() && addrs.iter().all(|ip| !is_blocked_ip(ip))
+}
+
+/// Returns the redirect policy of the HTTP clients fetching user supplied URLs.
+///
+/// [`is_safe_url`] only vets the URL given by the user, so following a redirect must not lead to
+/// the internal network: a redirect to a blocked IP address is an error, unless
+/// `allow_private_urls` is set. At most 10 redirects are followed.
+///
+/// Only IP addresses are checked here: host names never reach the DNS resolver of the client, see
+/// [`dns_resolver`].
+pub fn redirect_policy(allow_private_urls: AllowPrivateUrls) -> redirect::Policy {
+    redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            return attempt.error("too many redirects");
+        }
+
+        let blocked = match attempt.url().host() {
+            Some(Host::Ipv4(ip)) => is_blocked_ip(&IpAddr::V4(ip)),
+            Some(Host::Ipv6(ip)) => is_blocked_ip(&IpAddr::V6(ip)),
+            _ => false,
+        };
+        if blocked && !allow_private_urls.0 {
+            let err = format!("redirect to {} points to a private address", attempt.url());
+            return attempt.error(err);
+        }
+
+        attempt.follow()
+    })
+}
+
+/// Returns the DNS resolver of the HTTP clients fetching user supplied URLs.
+///
+/// The client connects to a host name through its resolver, so this covers the URL given by the
+/// user, every redirect target, and a host name whose address changed since it was checked with
+/// [`is_safe_url`]. IP addresses aren't resolved, [`redirect_policy`] checks them.
+pub fn dns_resolver(allow_private_urls: AllowPrivateUrls) -> Arc<SafeResolver> {
+    Arc::new(SafeResolver { allow_private_urls })
+}
+
+/// A DNS resolver dropping the blocked addresses, unless `allow_private_urls` is set.
+///
+/// Resolving a host name with only blocked addresses is an error.
+pub struct SafeResolver {
+    allow_private_urls: AllowPrivateUrls,
+}
+
+impl Resolve for SafeResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(resolve_public_addrs(
+            name.as_str().to_string(),
+            self.allow_private_urls,
+        ))
+    }
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+async fn resolve_public_addrs(
+    host: String,
+    allow_private_urls: AllowPrivateUrls,
+) -> Result<Addrs, BoxError> {
+    // Like the default resolver of reqwest, getaddrinfo is blocking
+    let lookup_host = host.clone();
+    let addrs = tokio::task::spawn_blocking(move || {
+        (lookup_host.as_str(), 0)
+            .to_socket_addrs()
+            .map(|addrs| addrs.collect::<Vec<SocketAddr>>())
+    })
+    .await??;
+
+    let addrs: Vec<SocketAddr> = addrs
+        .into_iter()
+        .filter(|addr| allow_private_urls.0 || !is_blocked_ip(&addr.ip()))
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("{} doesn't resolve to a public address", host).into());
+    }
+
+    Ok(Box::new(addrs.into_iter()))
+}
+
+fn is_blocked_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_blocked_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_blocked_ipv4(&ip),
+            None => is_blocked_ipv6(ip),
+        },
+    }
+}
+
+fn is_blocked_ipv4(ip: &Ipv4Addr) -> bool {
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || *ip == METADATA_ADDR
+}
+
+fn is_blocked_ipv6(ip: &Ipv6Addr) -> bool {
+    let first_segment = ip.segments()[0];
+
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // Unique local, fc00::/7
+        || (first_segment & 0xfe00) == 0xfc00
+        // Link-local, fe80::/10
+        || (first_segment & 0xffc0) == 0xfe80
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::path;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn is_safe(url: &str) -> bool {
+        is_safe_url(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn loopback_addresses_should_be_blocked() {
+        assert!(!is_safe("http://127.0.0.1/feed"));
+        assert!(!is_safe("http://127.1.2.3:8080/feed"));
+        assert!(!is_safe("http://[::1]/feed"));
+        assert!(!is_safe("http://localhost/feed"));
+    }
+
+    #[test]
+    fn private_addresses_should_be_blocked() {
+        assert!(!is_safe("http://10.0.0.1/feed"));
+        assert!(!is_safe("http://172.16.0.1/feed"));
+        assert!(!is_safe("http://172.31.255.255/feed"));
+        assert!(!is_safe("http://192.168.1.1/feed"));
+        assert!(!is_safe("http://[fd00::1]/feed"));
+    }
+
+    #[test]
+    fn link_local_addresses_should_be_blocked() {
+        assert!(!is_safe("http://169.254.1.1/feed"));
+        assert!(!is_safe("http://[fe80::1]/feed"));
+    }
+
+    #[test]
+    fn the_metadata_address_should_be_blocked() {
+        assert!(!is_safe("http://169.254.169.254/latest/meta-data/"));
+        assert!(!is_safe(
+            "http://[::ffff:169.254.169.254]/latest/meta-data/"
+        ));
+    }
+
+    #[test]
+    fn unspecified_addresses_should_be_blocked() {
+        assert!(!is_safe("http://0.0.0.0/feed"));
+        assert!(!is_safe("http://[::]/feed"));
+    }
+
+    #[test]
+    fn public_addresses_should_be_allowed() {
+        assert!(is_safe("https://93.184.216.34/feed"));
+        assert!(is_safe("http://172.32.0.1/feed"));
+        assert!(is_safe("https://[2606:2800:220:1:248:1893:25c8:1946]/feed"));
+    }
+
+    fn client(allow_private_urls: AllowPrivateUrls) -> reqwest::Client {
+        reqwest::Client::builder()
+            .redirect(redirect_policy(allow_private_urls))
+            .dns_resolver(dns_resolver(allow_private_urls))
+            .build()
+            .unwrap()
+    }
+
+    async fn fetch_with_redirect(
+        allow_private_urls: AllowPrivateUrls,
+        location: &str,
+    ) -> reqwest::Result<String> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(path("/feed"))
+            .respond_with(ResponseTemplate::new(302).insert_header("Location", location))
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/redirected"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("feed"))
+            .mount(&mock_server)
+            .await;
+
+        client(allow_private_urls)
+            .get(format!("{}/feed", mock_server.uri()))
+            .send()
+            .await?
+            .text()
+            .await
+    }
+
+    #[tokio::test]
+    async fn redirects_to_a_private_address_should_be_blocked() {
+        let result = fetch_with_redirect(
+            AllowPrivateUrls(false),
+            "http://169.254.169.254/latest/meta-data/",
+        )
+        .await;
+
+        match result {
+            Err(err) => assert!(
+                err.is_redirect(),
+                "expected a redirect error, got {:?}",
+                err
+            ),
+            Ok(body) => panic!("the redirect should have been blocked, got {:?}", body),
+        }
+
+        // The mock server itself listens on a loopback address
+        let result = fetch_with_redirect(AllowPrivateUrls(false), "/redirected").await;
+        assert!(result.unwrap_err().is_redirect());
+    }
+
+    #[tokio::test]
+    async fn redirects_to_a_private_address_should_be_followed_if_allowed() {
+        let body = fetch_with_redirect(AllowPrivateUrls(true), "/redirected")
+            .await
+            .unwrap();
+        assert_eq!("feed", body);
+    }
+
+    #[tokio::test]
+    async fn redirects_to_a_host_name_resolving_to_a_private_address_should_be_blocked() {
+        let mock_server = MockServer::start().await;
+        let port = mock_server.address().port();
+
+        Mock::given(path("/feed"))
+            .respond_with(
+                ResponseTemplate::new(302)
+                    .insert_header("Location", format!("http://localhost:{}/", port).as_str()),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("feed"))
+            .mount(&mock_server)
+            .await;
+
+        let url = format!("{}/feed", mock_server.uri());
+
+        let result = client(AllowPrivateUrls(false)).get(&url).send().await;
+        match result {
+            Err(err) => assert!(err.is_connect(), "expected a connect error, got {:?}", err),
+            Ok(response) => panic!("the redirect should have been blocked, got {:?}", response),
+        }
+
+        let body = client(AllowPrivateUrls(true))
+            .get(&url)
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert_eq!("feed", body);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::authentication::ApiTokenAuthenticated;
+use crate::sessions::TypedSession;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header;
+use actix_web::web::Bytes;
+use actix_web::{FromRequest, HttpMessage, HttpResponse};
+use actix_web_lab::middleware::Next;
+use tracing::warn;
+
+/// The header containing the CSRF token of a request.
+///
+/// htmx sends it with every request, see the `hx-headers` attribute in `base.html.j2`.
+pub const CSRF_TOKEN_HEADER: &str = "X-CSRF-Token";
+
+/// The name of the form field containing the CSRF token of a request.
+const CSRF_TOKEN_FIELD: &str = "csrf_token";
+
+/// Rejects the state-changing requests of a logged in user without the CSRF token of its session.
+///
+/// The token is read from the [`CSRF_TOKEN_HEADER`] header or from the `csrf_token` field of a
+/// form. Safe requests, requests of anonymous visitors and requests authenticated with an API
+/// token are not checked.
+///
+/// This middleware must be registered _inside_ the session middleware and the API token middleware.
+pub async fn csrf_check(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if req.method().is_safe() || req.extensions().get::<ApiTokenAuthenticated>().is_some() {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    }
+
+    let session = TypedSession::extract(req.request()).await?;
+    let user_id = session
+        .get_user_id()
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let expected_token = session
+        .get_csrf_token()
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    drop(session);
+
+    if user_id.is_none() {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    }
+
+    let token = match req.headers().get(CSRF_TOKEN_HEADER) {
+        Some(value) => value.to_str().ok().map(ToString::to_string),
+        None => read_form_token(&mut req).await?,
+    };
+
+    match (token, expected_token) {
+        (Some(token), Some(expected_token)) if token == expected_token => next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body),
+        _ => {
+            warn!(path = %req.path(), "request without a valid CSRF token");
+
+            let response = HttpResponse::Forbidden().body("Invalid CSRF token");
+            Ok(req.into_response(response).map_into_right_body())
+        }
+    }
+}
+
+/// Returns the CSRF token in the form body of `req`, if any.
+///
+/// The body is put back in the request so that the handler can still read it.
+async fn read_form_token(req: &mut ServiceRequest) -> Result<Option<String>, actix_web::Error> {
+    let is_form = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.starts_with("application/x-www-form-urlencoded"))
+        .unwrap_or(false);
+    if !is_form {
+        return Ok(None);
+    }
+
+    let body = req.extract::<Bytes>().await?;
+
+    let token = url::form_urlencoded::parse(&body)
+        .find(|(key, _)| key == CSRF_TOKEN_FIELD)
+        .map(|(_, value)| value.into_owned());
+
+    req.set_payload(Payload::from(body));
+
+    Ok(token)
+}
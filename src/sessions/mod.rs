@@ -1,5 +1,9 @@
+mod csrf;
 mod state;
 mod store;
+mod ttl;
 
+pub use csrf::*;
 pub use state::*;
 pub use store::*;
+pub use ttl::*;
@@ -1,28 +1,130 @@
+use crate::authentication::ApiTokenAuthenticated;
 use crate::domain::UserId;
+use crate::sessions::SESSION_COOKIE_NAME;
 use actix_session::{Session, SessionExt};
+use actix_web::cookie::{Cookie, CookieJar, Key};
 use actix_web::dev::Payload;
-use actix_web::{FromRequest, HttpRequest};
+use actix_web::web::Data as WebData;
+use actix_web::{FromRequest, HttpMessage, HttpRequest};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::future;
+use std::time::Duration as StdDuration;
+use time::OffsetDateTime;
+use uuid::Uuid;
 
-pub struct TypedSession(Session);
+/// The key used to sign and encrypt the session cookie.
+///
+/// It is needed to read the session key back from the cookie, see [`TypedSession::session_id`].
+pub struct SessionCookieKey(pub Key);
+
+pub struct TypedSession {
+    session: Session,
+    req: HttpRequest,
+}
 
 impl TypedSession {
-    const USER_ID_KEY: &'static str = "user_id";
+    pub(crate) const USER_ID_KEY: &'static str = "user_id";
+    const CSRF_TOKEN_KEY: &'static str = "csrf_token";
+    pub(crate) const TTL_KEY: &'static str = "ttl_seconds";
+    pub(crate) const LOGGED_IN_AT_KEY: &'static str = "logged_in_at";
+
+    fn new(req: &HttpRequest) -> Self {
+        Self {
+            session: req.get_session(),
+            req: req.clone(),
+        }
+    }
 
     pub fn renew(&self) {
-        self.0.renew();
+        self.session.renew();
+    }
+
+    /// Returns the id of this session in the session store, if there's one.
+    ///
+    /// This is the session key read from the session cookie of the request: a session created or
+    /// renewed while handling the request keeps the previous id until the next request.
+    pub fn session_id(&self) -> Option<Uuid> {
+        let key = self.req.app_data::<WebData<SessionCookieKey>>()?;
+        let cookie = self.req.cookie(SESSION_COOKIE_NAME)?;
+
+        decrypt_session_id(&key.0, cookie)
+    }
+
+    /// Returns the value stored at `key` deserialized as a `T`, if any.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, serde_json::Error> {
+        self.session.get(key)
+    }
+
+    /// Stores `value` at `key`, replacing any existing value.
+    pub fn insert<T: Serialize>(&self, key: &str, value: T) -> Result<(), serde_json::Error> {
+        self.session.insert(key, value)
     }
 
     pub fn insert_user_id(&self, user_id: UserId) -> Result<(), serde_json::Error> {
-        self.0.insert(Self::USER_ID_KEY, user_id)
+        self.insert(Self::USER_ID_KEY, user_id)
     }
 
     pub fn get_user_id(&self) -> Result<Option<UserId>, serde_json::Error> {
-        self.0.get(Self::USER_ID_KEY)
+        self.get(Self::USER_ID_KEY)
+    }
+
+    /// Stores the time at which the user logged in, as a Unix timestamp.
+    pub fn insert_logged_in_at(
+        &self,
+        logged_in_at: OffsetDateTime,
+    ) -> Result<(), serde_json::Error> {
+        self.insert(Self::LOGGED_IN_AT_KEY, logged_in_at.unix_timestamp())
+    }
+
+    /// Returns the time at which the user logged in.
+    ///
+    /// A session created before the login time was recorded doesn't have one.
+    pub fn get_logged_in_at(&self) -> Result<Option<OffsetDateTime>, serde_json::Error> {
+        let timestamp = match self.get::<i64>(Self::LOGGED_IN_AT_KEY)? {
+            Some(timestamp) => timestamp,
+            None => return Ok(None),
+        };
+
+        OffsetDateTime::from_unix_timestamp(timestamp)
+            .map(Some)
+            .map_err(serde::de::Error::custom)
+    }
+
+    /// Sets a TTL specific to this session, overriding the default one.
+    pub fn set_ttl(&self, ttl: StdDuration) -> Result<(), serde_json::Error> {
+        self.insert(Self::TTL_KEY, ttl.as_secs())
+    }
+
+    pub fn get_ttl(&self) -> Result<Option<StdDuration>, serde_json::Error> {
+        let ttl_seconds = self.get::<u64>(Self::TTL_KEY)?;
+        Ok(ttl_seconds.map(StdDuration::from_secs))
+    }
+
+    /// Returns the CSRF token of this session, if any.
+    pub fn get_csrf_token(&self) -> Result<Option<String>, serde_json::Error> {
+        self.get(Self::CSRF_TOKEN_KEY)
+    }
+
+    /// Returns the CSRF token of this session, creating it first if necessary.
+    pub fn get_or_create_csrf_token(&self) -> Result<String, serde_json::Error> {
+        if let Some(token) = self.get_csrf_token()? {
+            return Ok(token);
+        }
+
+        self.renew_csrf_token()
+    }
+
+    /// Replaces the CSRF token of this session with a new one.
+    pub fn renew_csrf_token(&self) -> Result<String, serde_json::Error> {
+        let token = Uuid::new_v4().to_string();
+        self.insert(Self::CSRF_TOKEN_KEY, &token)?;
+
+        Ok(token)
     }
 
     pub fn logout(self) {
-        self.0.purge()
+        self.session.purge()
     }
 }
 
@@ -31,7 +133,134 @@ impl FromRequest for TypedSession {
     type Future = future::Ready<Result<TypedSession, Self::Error>>;
 
     fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
-        let typed_session = TypedSession(req.get_session());
+        let typed_session = TypedSession::new(req);
         future::ready(Ok(typed_session))
     }
 }
+
+/// Returns the session id stored in the session `cookie`, encrypted with `key`.
+fn decrypt_session_id(key: &Key, cookie: Cookie<'static>) -> Option<Uuid> {
+    let mut jar = CookieJar::new();
+    jar.add_original(cookie);
+    let cookie = jar.private(key).get(SESSION_COOKIE_NAME)?;
+
+    Uuid::try_parse(cookie.value()).ok()
+}
+
+/// The CSRF token of the current session, to be put in every form. See [`csrf_check`].
+///
+/// Extracting it creates the token if the session of a logged in user doesn't have one yet.
+///
+/// Anonymous visitors have nothing to protect, their token is empty: this avoids creating a
+/// session for every visitor. A request authenticated with an API token doesn't have a session,
+/// its token is empty too.
+///
+/// [`csrf_check`]: crate::sessions::csrf_check
+pub struct CsrfToken(pub String);
+
+impl FromRequest for CsrfToken {
+    type Error = actix_web::Error;
+    type Future = future::Ready<Result<CsrfToken, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        if req.extensions().get::<ApiTokenAuthenticated>().is_some() {
+            return future::ready(Ok(CsrfToken(String::new())));
+        }
+
+        let typed_session = TypedSession::new(req);
+
+        let result = match typed_session.get_user_id() {
+            Ok(Some(_)) => typed_session.get_or_create_csrf_token().map(CsrfToken),
+            Ok(None) => Ok(CsrfToken(String::new())),
+            Err(err) => Err(err),
+        };
+
+        future::ready(result.map_err(actix_web::error::ErrorInternalServerError))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::cookie::Cookie;
+    use actix_web::test::TestRequest;
+
+    fn typed_session() -> TypedSession {
+        let req = TestRequest::default().to_http_request();
+        TypedSession::new(&req)
+    }
+
+    #[test]
+    fn session_values_should_round_trip() {
+        let session = typed_session();
+
+        assert_eq!(None, session.get::<Vec<String>>("foo").unwrap());
+
+        let value = vec!["bar".to_string(), "baz".to_string()];
+        session.insert("foo", &value).unwrap();
+        assert_eq!(Some(value), session.get::<Vec<String>>("foo").unwrap());
+
+        session.insert("foo", 20).unwrap();
+        assert_eq!(Some(20), session.get::<u32>("foo").unwrap());
+    }
+
+    #[test]
+    fn getting_a_value_with_the_wrong_type_should_fail() {
+        let session = typed_session();
+
+        session.insert("foo", "bar").unwrap();
+        assert!(session.get::<u32>("foo").is_err());
+    }
+
+    #[test]
+    fn user_id_should_use_the_generic_methods() {
+        let session = typed_session();
+        let user_id = UserId::default();
+
+        session.insert_user_id(user_id).unwrap();
+        assert_eq!(Some(user_id), session.get_user_id().unwrap());
+        assert_eq!(
+            Some(user_id),
+            session.get::<UserId>(TypedSession::USER_ID_KEY).unwrap()
+        );
+    }
+
+    #[test]
+    fn logged_in_at_should_round_trip() {
+        let session = typed_session();
+
+        assert_eq!(None, session.get_logged_in_at().unwrap());
+
+        let logged_in_at = OffsetDateTime::from_unix_timestamp(1679653815).unwrap();
+        session.insert_logged_in_at(logged_in_at).unwrap();
+        assert_eq!(Some(logged_in_at), session.get_logged_in_at().unwrap());
+        assert_eq!(
+            Some(logged_in_at.unix_timestamp()),
+            session.get::<i64>(TypedSession::LOGGED_IN_AT_KEY).unwrap()
+        );
+    }
+
+    #[test]
+    fn session_id_should_be_read_from_the_private_session_cookie() {
+        let key = Key::generate();
+        let session_id = Uuid::new_v4();
+
+        let mut jar = CookieJar::new();
+        jar.private_mut(&key)
+            .add(Cookie::new(SESSION_COOKIE_NAME, session_id.to_string()));
+        let cookie = jar.get(SESSION_COOKIE_NAME).unwrap().clone();
+
+        // Without the key the cookie can't be decrypted
+
+        let req = TestRequest::default()
+            .cookie(cookie.clone())
+            .to_http_request();
+        assert_eq!(None, TypedSession::new(&req).session_id());
+
+        let req = TestRequest::default()
+            .app_data(WebData::new(SessionCookieKey(key)))
+            .cookie(cookie)
+            .to_http_request();
+        assert_eq!(Some(session_id), TypedSession::new(&req).session_id());
+    }
+}
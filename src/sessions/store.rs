@@ -1,8 +1,10 @@
+use crate::domain::UserId;
+use crate::sessions::TypedSession;
 use actix_session::storage::{LoadError, SaveError, UpdateError};
 use actix_session::storage::{SessionKey, SessionStore};
 use actix_web::cookie::time::Duration;
 use anyhow::anyhow;
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 use std::collections::HashMap;
 use uuid::Uuid;
 
@@ -43,6 +45,89 @@ impl PgSessionStore {
     }
 }
 
+/// Deletes all the sessions of the user `user_id`, logging it out everywhere.
+///
+/// Returns the number of deleted sessions.
+#[tracing::instrument(name = "Delete user sessions", skip(executor))]
+pub async fn delete_user_sessions<'e, E>(executor: E, user_id: UserId) -> Result<u64, anyhow::Error>
+where
+    E: PgExecutor<'e>,
+{
+    // The session state values are stored JSON encoded, see [`TypedSession::insert`]
+    let user_id_value = serde_json::to_string(&user_id)?;
+
+    let result = sqlx::query!(
+        "DELETE FROM sessions WHERE state->>$1 = $2",
+        TypedSession::USER_ID_KEY,
+        user_id_value,
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// An active session of a user, as listed in the settings.
+#[derive(Debug)]
+pub struct UserSession {
+    pub id: Uuid,
+    pub created_at: time::OffsetDateTime,
+    pub expires_at: time::OffsetDateTime,
+}
+
+/// Returns the active sessions of the user `user_id`, the most recently created first.
+#[tracing::instrument(name = "Get user sessions", skip(executor))]
+pub async fn get_user_sessions<'e, E>(
+    executor: E,
+    user_id: UserId,
+) -> Result<Vec<UserSession>, anyhow::Error>
+where
+    E: PgExecutor<'e>,
+{
+    let user_id_value = serde_json::to_string(&user_id)?;
+
+    let sessions = sqlx::query_as!(
+        UserSession,
+        r#"
+        SELECT id, created_at, expires_at FROM sessions
+        WHERE state->>$1 = $2 AND expires_at > now()
+        ORDER BY created_at DESC
+        "#,
+        TypedSession::USER_ID_KEY,
+        user_id_value,
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(sessions)
+}
+
+/// Deletes the session `session_id` of the user `user_id`, logging it out on that device.
+///
+/// Returns false if the user has no such session.
+#[tracing::instrument(name = "Delete user session", skip(executor))]
+pub async fn delete_user_session<'e, E>(
+    executor: E,
+    user_id: UserId,
+    session_id: Uuid,
+) -> Result<bool, anyhow::Error>
+where
+    E: PgExecutor<'e>,
+{
+    let user_id_value = serde_json::to_string(&user_id)?;
+
+    let result = sqlx::query!(
+        "DELETE FROM sessions WHERE id = $1 AND state->>$2 = $3",
+        session_id,
+        TypedSession::USER_ID_KEY,
+        user_id_value,
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
 async fn clean_sessions(pool: PgPool, clean_interval: time::Duration) {
     let mut interval = tokio::time::interval(clean_interval.unsigned_abs());
     loop {
@@ -69,6 +154,16 @@ async fn clean_sessions(pool: PgPool, clean_interval: time::Duration) {
 
 type SessionState = HashMap<String, String>;
 
+/// Returns the TTL specific to the session if there's one (see [`TypedSession::set_ttl`]),
+/// otherwise returns `default_ttl`.
+fn session_ttl(session_state: &SessionState, default_ttl: &Duration) -> Duration {
+    session_state
+        .get(TypedSession::TTL_KEY)
+        .and_then(|v| v.parse::<i64>().ok())
+        .map(Duration::seconds)
+        .unwrap_or(*default_ttl)
+}
+
 #[async_trait::async_trait(?Send)]
 impl SessionStore for PgSessionStore {
     async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
@@ -112,13 +207,14 @@ impl SessionStore for PgSessionStore {
         // Setup
 
         let session_id = Uuid::new_v4();
+        let ttl = session_ttl(&session_state, ttl);
         let state = serde_json::to_value(&session_state)
             .map_err(Into::into)
             .map_err(SaveError::Serialization)?;
 
         let created_at = time::OffsetDateTime::now_utc();
         let expires_at = created_at
-            .checked_add(*ttl)
+            .checked_add(ttl)
             .ok_or_else(|| SaveError::Other(anyhow!("unable to compute expiry timestamp")))?;
 
         // Save data
@@ -155,7 +251,7 @@ impl SessionStore for PgSessionStore {
             .map_err(Into::into)
             .map_err(UpdateError::Serialization)?;
         let expires_at = time::OffsetDateTime::now_utc()
-            .checked_add(*ttl)
+            .checked_add(session_ttl(&session_state, ttl))
             .ok_or_else(|| UpdateError::Other(anyhow!("unable to compute expiry timestamp")))?;
 
         // Check if the session exists
@@ -223,7 +319,10 @@ fn session_key_to_uuid(session_key: &SessionKey) -> Result<Uuid, anyhow::Error>
 
 #[cfg(test)]
 mod tests {
-    use super::{uuid_to_session_key, CleanupConfig, PgSessionStore};
+    use super::{delete_user_session, delete_user_sessions, get_user_sessions};
+    use super::{session_key_to_uuid, uuid_to_session_key, CleanupConfig, PgSessionStore};
+    use crate::domain::UserId;
+    use crate::sessions::TypedSession;
     use actix_session::storage::SessionStore;
     use actix_web::cookie::time::Duration;
     use sqlx::PgPool;
@@ -310,6 +409,25 @@ mod tests {
         assert!(loaded_state.is_none());
     }
 
+    #[sqlx::test]
+    async fn loading_a_session_saved_with_its_own_negative_ttl_returns_none(pool: PgPool) {
+        let store = PgSessionStore::new(pool, CleanupConfig::default());
+        let mut state = make_state();
+        state.insert(TypedSession::TTL_KEY.to_string(), "-10".to_string());
+
+        let session_key = store
+            .save(state.clone(), &Duration::seconds(10))
+            .await
+            .expect("Unable to save the session");
+
+        let loaded_state = store
+            .load(&session_key)
+            .await
+            .expect("Unable to load the session");
+
+        assert!(loaded_state.is_none());
+    }
+
     #[sqlx::test]
     async fn loading_a_deleted_session_returns_none(pool: PgPool) {
         let store = PgSessionStore::new(pool, CleanupConfig::default());
@@ -376,4 +494,99 @@ mod tests {
 
         assert!(loaded_state.is_some(), "found no state for {session_key:?}");
     }
+
+    #[sqlx::test]
+    async fn deleting_the_sessions_of_a_user_only_deletes_its_sessions(pool: PgPool) {
+        let store = PgSessionStore::new(pool.clone(), CleanupConfig::default());
+
+        let user_id = UserId::default();
+        let other_user_id = UserId::default();
+
+        let mut session_keys = Vec::new();
+        for id in [user_id, other_user_id] {
+            let mut state = make_state();
+            state.insert(
+                TypedSession::USER_ID_KEY.to_string(),
+                serde_json::to_string(&id).unwrap(),
+            );
+
+            let session_key = store
+                .save(state, &Duration::seconds(10))
+                .await
+                .expect("Unable to save the session");
+            session_keys.push(session_key);
+        }
+
+        let deleted = delete_user_sessions(&pool, user_id)
+            .await
+            .expect("Unable to delete the sessions");
+        assert_eq!(1, deleted);
+
+        let user_state = store
+            .load(&session_keys[0])
+            .await
+            .expect("Unable to load the session");
+        assert!(user_state.is_none());
+
+        let other_user_state = store
+            .load(&session_keys[1])
+            .await
+            .expect("Unable to load the session");
+        assert!(other_user_state.is_some());
+    }
+
+    #[sqlx::test]
+    async fn a_user_should_only_list_and_delete_its_own_sessions(pool: PgPool) {
+        let store = PgSessionStore::new(pool.clone(), CleanupConfig::default());
+
+        let user_id = UserId::default();
+        let other_user_id = UserId::default();
+
+        let mut session_ids = Vec::new();
+        for id in [user_id, other_user_id] {
+            let mut state = make_state();
+            state.insert(
+                TypedSession::USER_ID_KEY.to_string(),
+                serde_json::to_string(&id).unwrap(),
+            );
+
+            let session_key = store
+                .save(state, &Duration::seconds(10))
+                .await
+                .expect("Unable to save the session");
+            session_ids.push(session_key_to_uuid(&session_key).unwrap());
+        }
+
+        let sessions = get_user_sessions(&pool, user_id)
+            .await
+            .expect("Unable to get the sessions");
+        assert_eq!(
+            vec![session_ids[0]],
+            sessions
+                .iter()
+                .map(|session| session.id)
+                .collect::<Vec<_>>()
+        );
+
+        // The session of another user can't be deleted
+        let deleted = delete_user_session(&pool, user_id, session_ids[1])
+            .await
+            .expect("Unable to delete the session");
+        assert!(!deleted);
+
+        let deleted = delete_user_session(&pool, user_id, session_ids[0])
+            .await
+            .expect("Unable to delete the session");
+        assert!(deleted);
+
+        let sessions = get_user_sessions(&pool, user_id)
+            .await
+            .expect("Unable to get the sessions");
+        assert!(sessions.is_empty());
+
+        let other_sessions = get_user_sessions(&pool, other_user_id)
+            .await
+            .expect("Unable to get the sessions");
+        assert_eq!(1, other_sessions.len());
+    }
 }
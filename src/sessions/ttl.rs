@@ -0,0 +1,136 @@
+use crate::routes::see_other;
+use crate::sessions::TypedSession;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::web::Data as WebData;
+use actix_web::{FromRequest, HttpMessage};
+use actix_web_flash_messages::FlashMessage;
+use actix_web_lab::middleware::Next;
+use std::time::Duration as StdDuration;
+use time::OffsetDateTime;
+use tracing::info;
+
+/// The name of the session cookie.
+pub const SESSION_COOKIE_NAME: &str = "session_id";
+
+/// The TTL of a session when the user asked to be remembered when logging in.
+#[derive(Clone, Copy, Debug)]
+pub struct RememberMeTtl(pub StdDuration);
+
+/// How long a session can last after the login, whatever its TTL is.
+#[derive(Clone, Copy, Debug)]
+pub struct AbsoluteSessionTtl(pub StdDuration);
+
+/// Stored in the request extensions when the session has a specific TTL.
+#[derive(Clone, Copy, Debug)]
+struct SessionTtl(StdDuration);
+
+/// Records the TTL specific to the session (see [`TypedSession::set_ttl`]) in the request extensions.
+///
+/// This middleware must be registered _inside_ the session middleware, because the session
+/// state is no longer available once the session middleware has processed the response.
+pub async fn record_session_ttl(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let res = next.call(req).await?;
+
+    let session = TypedSession::extract(res.request()).await?;
+    if let Ok(Some(ttl)) = session.get_ttl() {
+        res.request().extensions_mut().insert(SessionTtl(ttl));
+    }
+
+    Ok(res)
+}
+
+/// Sets the `Max-Age` attribute of the session cookie to the TTL recorded by [`record_session_ttl`].
+///
+/// This middleware must be registered _outside_ the session middleware so that the session cookie
+/// is already set in the response.
+pub async fn apply_session_ttl(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let mut res = next.call(req).await?;
+
+    let ttl = res.request().extensions().get::<SessionTtl>().copied();
+    let ttl = match ttl {
+        Some(SessionTtl(ttl)) => ttl,
+        None => return Ok(res),
+    };
+
+    let cookie = res
+        .response()
+        .cookies()
+        .find(|cookie| cookie.name() == SESSION_COOKIE_NAME)
+        .map(|cookie| cookie.into_owned());
+
+    if let Some(mut cookie) = cookie {
+        let max_age = time::Duration::try_from(ttl)
+            .expect("StdDuration should always be convertible to time::Duration");
+        cookie.set_max_age(max_age);
+
+        let response = res.response_mut();
+        response.del_cookie(SESSION_COOKIE_NAME);
+        response.add_cookie(&cookie)?;
+    }
+
+    Ok(res)
+}
+
+/// Logs out the sessions older than the [`AbsoluteSessionTtl`] and redirects them to the login page.
+///
+/// The age of a session is computed from its login time (see [`TypedSession::get_logged_in_at`]),
+/// not from its cookie which is renewed as long as the session is used.
+/// A logged in session without a login time predates it and is considered expired.
+///
+/// This middleware must be registered _inside_ the session and flash messages middlewares.
+pub async fn session_max_age_check(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let absolute_ttl = match req.app_data::<WebData<AbsoluteSessionTtl>>() {
+        Some(absolute_ttl) => absolute_ttl.0,
+        None => {
+            return next
+                .call(req)
+                .await
+                .map(ServiceResponse::map_into_left_body)
+        }
+    };
+
+    let session = TypedSession::extract(req.request()).await?;
+    let user_id = session
+        .get_user_id()
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let logged_in_at = session
+        .get_logged_in_at()
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    let expired = match (user_id, logged_in_at) {
+        (_, Some(logged_in_at)) => OffsetDateTime::now_utc() - logged_in_at > absolute_ttl,
+        (Some(_), None) => true,
+        (None, None) => false,
+    };
+
+    if !expired {
+        // The session holds a clone of the request which must be gone before routing it
+        drop(session);
+
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    }
+
+    info!(
+        ?logged_in_at,
+        "session is older than the absolute TTL, logging out"
+    );
+
+    session.logout();
+    FlashMessage::error("Your session has expired, please log in again").send();
+
+    let response = see_other("/login");
+    Ok(req.into_response(response).map_into_right_body())
+}
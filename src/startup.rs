@@ -1,18 +1,32 @@
-use crate::configuration::{ApplicationConfig, DatabaseConfig, SessionConfig, TEMConfig};
+use crate::authentication::{reject_api_token_requests, ApiTokenMiddleware, PasswordHasherConfig};
+use crate::compression::skip_compressed_content;
+use crate::configuration::{
+    ApplicationConfig, Argon2Config, DatabaseConfig, SessionConfig, TEMConfig,
+};
+use crate::feed::FaviconCache;
 use crate::run_group::Shutdown;
+use crate::security::{dns_resolver, redirect_policy, AllowPrivateUrls};
+use crate::sessions::SESSION_COOKIE_NAME;
+use crate::sessions::{apply_session_ttl, csrf_check, record_session_ttl, session_max_age_check};
+use crate::sessions::{AbsoluteSessionTtl, RememberMeTtl, SessionCookieKey};
 use crate::sessions::{CleanupConfig as SessionStoreCleanupConfig, PgSessionStore};
-use crate::{routes::*, tem};
+use crate::unread_updates::UnreadUpdates;
+use crate::{debug_with_error_chain, routes::*, tem};
 use actix_session::SessionMiddleware;
+use actix_web::middleware::{Compress, Condition};
 use actix_web::{cookie, dev::Server};
 use actix_web::{web, App, HttpServer};
 use actix_web_flash_messages::storage::CookieMessageStore;
 use actix_web_flash_messages::FlashMessagesFramework;
+use actix_web_lab::middleware::from_fn;
+use anyhow::Context;
 use secrecy::{ExposeSecret, Secret};
+use sqlx::migrate::{Migrate, MigrateError};
 use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use sqlx::{ConnectOptions, PgPool};
-use std::net::TcpListener;
+use std::net::{SocketAddr, TcpListener};
 use std::time::Duration as StdDuration;
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
 use tracing_actix_web::TracingLogger;
 use tracing_log::log::LevelFilter;
 
@@ -20,33 +34,63 @@ use tracing_log::log::LevelFilter;
 pub enum Error {
     #[error("invalid cookie key")]
     InvalidCookieKey(#[source] anyhow::Error),
+    #[error("invalid TEM configuration")]
+    InvalidTEMConfig(#[source] anyhow::Error),
+    #[error("invalid Argon2 configuration")]
+    InvalidArgon2Config(#[source] argon2::Error),
     #[error("unable to bind tcp listener")]
     IO(#[from] std::io::Error),
     #[error(transparent)]
     Unexpected(#[from] anyhow::Error),
 }
 
+/// The secret used to sign data sent to users, for example email change confirmation tokens.
 #[derive(Clone)]
-pub struct HmacSecret<'a>(pub &'a Secret<String>);
+pub struct HmacSecret(pub Secret<String>);
+
+/// The public base URL of the application, used to build links sent to users.
+#[derive(Clone)]
+pub struct ApplicationBaseUrl(pub String);
 
 pub struct Application {
+    /// The port of the first listen address.
     pub port: u16,
+    pub addresses: Vec<SocketAddr>,
     server: Server,
 }
 
 impl Application {
-    /// Builds a new application using `config` and `pool`.
+    /// Builds a new application using `config`, `pool`, `favicon_cache` and `unread_updates`.
+    ///
+    /// If `tem_config` is set the application gets a [`tem::Client`] that route handlers
+    /// can use to send emails.
+    ///
+    /// Passwords are hashed with the parameters of `argon2_config`.
     ///
     /// The application will have started but not completed, you need to await
     /// on `run_until_stopped` to run the server to completion.
     pub fn build(
         config: &ApplicationConfig,
         session_config: &SessionConfig,
+        argon2_config: &Argon2Config,
+        tem_config: Option<&TEMConfig>,
         pool: PgPool,
+        favicon_cache: FaviconCache,
+        unread_updates: UnreadUpdates,
     ) -> Result<Application, Error> {
         let cookie_signing_key =
             cookie::Key::from(config.cookie_signing_key.expose_secret().as_bytes());
 
+        // Email client
+        let tem_client = tem_config
+            .map(get_tem_client)
+            .transpose()
+            .map_err(Error::InvalidTEMConfig)?;
+
+        // Password hashing
+        let password_hasher_config =
+            PasswordHasherConfig::try_from(argon2_config).map_err(Error::InvalidArgon2Config)?;
+
         // Flash messages
         let flash_messages_store = CookieMessageStore::builder(cookie_signing_key.clone()).build();
         let flash_messages_framework =
@@ -61,52 +105,139 @@ impl Application {
             ),
         );
 
-        // Build the TCP listener
-        let listener = std::net::TcpListener::bind(format!("{}:{}", config.host, config.port))
+        // Build the TCP listeners, one per listen address
+        let listeners = config
+            .listen_addresses()
+            .iter()
+            .map(TcpListener::bind)
+            .collect::<Result<Vec<_>, _>>()
             .map_err(Into::<Error>::into)?;
-        let port = listener.local_addr().unwrap().port();
+        let addresses = listeners
+            .iter()
+            .map(TcpListener::local_addr)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Into::<Error>::into)?;
+        let port = addresses[0].port();
+
+        info!(?addresses, "listening");
 
         // Finally create the HTTP server
         let server: Server = create_server(
-            listener,
+            listeners,
             pool,
+            favicon_cache,
+            unread_updates,
+            tem_client,
+            HmacSecret(config.cookie_signing_key.clone()),
+            ApplicationBaseUrl(config.base_url.clone()),
+            AllowPrivateUrls(config.allow_private_feed_urls),
+            password_hasher_config,
             cookie_signing_key,
             session_store,
             session_config.ttl(),
+            session_config.remember_me_ttl(),
+            session_config.absolute_ttl(),
             flash_messages_framework,
+            config.compression_enabled(),
+            config.is_test_mode(),
         )?;
 
-        Ok(Application { port, server })
+        Ok(Application {
+            port,
+            addresses,
+            server,
+        })
     }
 
-    pub async fn run(self, mut shutdown: Shutdown) -> anyhow::Result<()> {
+    /// Runs the server until it stops by itself or until `shutdown` is notified.
+    ///
+    /// On shutdown the server stops accepting new connections and waits for the in-flight
+    /// requests to complete, but no longer than `shutdown_timeout`.
+    pub async fn run(
+        self,
+        mut shutdown: Shutdown,
+        shutdown_timeout: StdDuration,
+    ) -> anyhow::Result<()> {
+        let server_handle = self.server.handle();
+        let mut server = tokio::spawn(self.server);
+
         tokio::select! {
             _ = shutdown.recv() => {
                     info!("application shutting down");
-                    Ok(())
             }
-            _ = self.server => {
+            result = &mut server => {
                     info!("server shut down");
-                    Ok(())
+                    result.context("server task failed")??;
+                    return Ok(());
             }
         }
+
+        let graceful_stop = async {
+            server_handle.stop(true).await;
+            let _ = (&mut server).await;
+        };
+
+        if tokio::time::timeout(shutdown_timeout, graceful_stop)
+            .await
+            .is_err()
+        {
+            warn!(
+                ?shutdown_timeout,
+                "in-flight requests did not complete in time, stopping the server anyway"
+            );
+            server.abort();
+        }
+
+        Ok(())
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn create_server(
-    listener: TcpListener,
+    listeners: Vec<TcpListener>,
     pool: PgPool,
+    favicon_cache: FaviconCache,
+    unread_updates: UnreadUpdates,
+    tem_client: Option<tem::Client>,
+    hmac_secret: HmacSecret,
+    base_url: ApplicationBaseUrl,
+    allow_private_urls: AllowPrivateUrls,
+    password_hasher_config: PasswordHasherConfig,
     cookie_signing_key: actix_web::cookie::Key,
     session_store: PgSessionStore,
     session_ttl: StdDuration,
+    remember_me_ttl: StdDuration,
+    absolute_session_ttl: StdDuration,
     flash_messages_framework: FlashMessagesFramework,
+    compression_enabled: bool,
+    test_mode: bool,
 ) -> Result<Server, anyhow::Error> {
     let pool = web::Data::new(pool);
+    let favicon_cache = web::Data::new(favicon_cache);
+    let unread_updates = web::Data::new(unread_updates);
+    let tem_client = tem_client.map(web::Data::new);
+    let hmac_secret = web::Data::new(hmac_secret);
+    let base_url = web::Data::new(base_url);
+    let redirect_policy = redirect_policy(allow_private_urls);
+    let dns_resolver = dns_resolver(allow_private_urls);
+    let allow_private_urls = web::Data::new(allow_private_urls);
+    let password_hasher_config = web::Data::new(password_hasher_config);
+    let session_cookie_key = web::Data::new(SessionCookieKey(cookie_signing_key.clone()));
+    let remember_me_ttl = web::Data::new(RememberMeTtl(remember_me_ttl));
+    let absolute_session_ttl = web::Data::new(AbsoluteSessionTtl(absolute_session_ttl));
+    let discovery_cache = web::Data::new(DiscoveryCache::new(
+        DISCOVERY_CACHE_CAPACITY,
+        DISCOVERY_CACHE_TTL,
+    ));
 
     let http_client = {
         let tmp = reqwest::Client::builder()
-            .redirect(reqwest::redirect::Policy::limited(10))
+            .redirect(redirect_policy)
+            .dns_resolver(dns_resolver)
             .cookie_store(true)
+            .gzip(true)
+            .brotli(true)
+            .deflate(true)
             .build()?;
 
         web::Data::new(tmp)
@@ -115,51 +246,141 @@ fn create_server(
     let session_ttl = time::Duration::try_from(session_ttl)
         .expect("StdDuration should always be convertible to time::Duration");
 
-    let server = HttpServer::new(move || {
+    let mut server = HttpServer::new(move || {
         let session_middleware =
             SessionMiddleware::builder(session_store.clone(), cookie_signing_key.clone())
                 .session_length(actix_session::SessionLength::BrowserSession {
                     state_ttl: Some(session_ttl),
                 })
-                .cookie_name("session_id".to_string())
+                .cookie_name(SESSION_COOKIE_NAME.to_string())
                 .build();
 
         App::new()
+            .wrap(from_fn(csrf_check))
+            .wrap(ApiTokenMiddleware)
+            .wrap(from_fn(session_max_age_check))
             .wrap(flash_messages_framework.clone())
+            .wrap(from_fn(record_session_ttl))
             .wrap(session_middleware)
+            .wrap(from_fn(apply_session_ttl))
+            .wrap(from_fn(skip_compressed_content))
+            .wrap(Condition::new(compression_enabled, Compress::default()))
             .wrap(TracingLogger::default())
             .service(actix_files::Files::new("/assets", "./assets").prefer_utf8(true))
             .route("/", web::get().to(handle_home))
             .route("/status", web::get().to(handle_status))
+            .route("/readiness", web::get().to(handle_readiness))
             .route("/login", web::get().to(handle_login_form))
             .route("/login", web::post().to(handle_login_submit))
             .route("/logout", web::to(handle_logout))
             .route("/settings", web::get().to(handle_settings))
+            .service(
+                web::scope("/settings")
+                    .wrap(from_fn(reject_api_token_requests))
+                    .route("/preferences", web::post().to(handle_settings_preferences))
+                    .route("/account", web::delete().to(handle_settings_account_delete))
+                    .route("/email", web::post().to(handle_settings_email))
+                    .route(
+                        "/email/confirm/{token}",
+                        web::get().to(handle_settings_email_confirm),
+                    )
+                    .route("/sessions", web::get().to(handle_settings_sessions))
+                    .route(
+                        "/sessions/{session_id}",
+                        web::delete().to(handle_settings_session_delete),
+                    )
+                    .route("/tokens", web::post().to(handle_settings_tokens_create))
+                    .route(
+                        "/tokens/{token_id}",
+                        web::delete().to(handle_settings_tokens_delete),
+                    ),
+            )
             .route("/feeds", web::get().to(handle_feeds))
             .service(
                 web::scope("/feeds")
                     .route("/add", web::post().to(handle_feeds_add))
                     .route("/add", web::get().to(handle_feeds_add_form))
+                    .route("/add/choose", web::get().to(handle_feeds_add_choose))
                     .route("/refresh", web::post().to(handle_feeds_refresh))
+                    .route(
+                        "/export/unread.md",
+                        web::get().to(handle_unread_export_markdown),
+                    )
                     .service(
                         web::scope("/{feed_id}")
+                            .route("", web::delete().to(handle_feed_delete))
                             .route("/", web::get().to(handle_feed_entries))
+                            .route("/category", web::post().to(handle_feed_category))
+                            .route("/tags", web::post().to(handle_feed_tags_add))
+                            .route("/tags/{tag_id}", web::delete().to(handle_feed_tags_remove))
+                            .route("/mark-read", web::post().to(handle_feed_mark_read))
                             .route("/favicon", web::get().to(handle_feed_favicon))
                             .route("/entries", web::get().to(handle_feed_entries))
-                            .route("/entries/{entry_id}", web::get().to(handle_feed_entry)),
+                            .route("/entries/{entry_id}", web::get().to(handle_feed_entry))
+                            .route(
+                                "/entries/{entry_id}/star",
+                                web::post().to(handle_feed_entry_star),
+                            )
+                            .route(
+                                "/entries/{entry_id}/unstar",
+                                web::post().to(handle_feed_entry_unstar),
+                            ),
                     ),
             )
             .route("/unread", web::get().to(handle_unread))
+            .route("/unread/mark-read", web::post().to(handle_unread_mark_read))
+            .route("/starred", web::get().to(handle_starred))
+            .route("/search", web::get().to(handle_search))
+            .route("/tags/{tag}/entries", web::get().to(handle_tag_entries))
+            .route("/ws", web::get().to(handle_ws))
+            .route(
+                "/admin/dead-letter",
+                web::get().to(handle_admin_dead_letter),
+            )
+            .route(
+                "/admin/dead-letter/{job_id}/requeue",
+                web::post().to(handle_admin_dead_letter_requeue),
+            )
+            .service(
+                web::scope("/api/v1")
+                    .app_data(api_json_config())
+                    .route("/feeds", web::get().to(handle_api_feeds))
+                    .route("/feeds", web::post().to(handle_api_feeds_add))
+                    .route(
+                        "/feeds/{feed_id}/entries",
+                        web::get().to(handle_api_feed_entries),
+                    ),
+            )
             .app_data(pool.clone())
             .app_data(http_client.clone())
-    })
-    .listen(listener)?
-    .run();
+            .app_data(remember_me_ttl.clone())
+            .app_data(absolute_session_ttl.clone())
+            .app_data(favicon_cache.clone())
+            .app_data(discovery_cache.clone())
+            .app_data(unread_updates.clone())
+            .app_data(hmac_secret.clone())
+            .app_data(base_url.clone())
+            .app_data(allow_private_urls.clone())
+            .app_data(password_hasher_config.clone())
+            .app_data(session_cookie_key.clone())
+            .configure(configure_tem_client(tem_client.clone()))
+            .configure(|cfg| {
+                if test_mode {
+                    cfg.route("/test-email", web::post().to(handle_test_email));
+                }
+            })
+    });
+    for listener in listeners {
+        server = server.listen(listener)?;
+    }
 
-    Ok(server)
+    Ok(server.run())
 }
 
-pub async fn get_connection_pool(config: &DatabaseConfig) -> Result<PgPool, sqlx::Error> {
+/// Connects to the database described by `config`.
+///
+/// The schema is not touched, use [`run_migrations`] to bring it up to date.
+pub async fn get_connection_pool(config: &DatabaseConfig) -> Result<PgPool, anyhow::Error> {
     let mut connect_options = PgConnectOptions::new()
         .username(&config.username)
         .password(config.password.expose_secret())
@@ -170,17 +391,117 @@ pub async fn get_connection_pool(config: &DatabaseConfig) -> Result<PgPool, sqlx
     connect_options.log_statements(LevelFilter::Trace);
 
     let pool = PgPoolOptions::new()
-        .max_connections(1024)
-        .acquire_timeout(StdDuration::from_secs(1))
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .acquire_timeout(config.acquire_timeout())
+        .idle_timeout(config.idle_timeout())
         .connect_with(connect_options)
         .await?;
 
-    // Run the migrations on first connection if necessary
-    sqlx::migrate!().run(&pool).await?;
-
     Ok(pool)
 }
 
+#[derive(thiserror::Error)]
+pub enum MigrationError {
+    #[error("migration {0} is dirty, it was only partially applied")]
+    Dirty(i64),
+    #[error(
+        "migration {0} was applied but is unknown, the database schema is ahead of the application"
+    )]
+    Missing(i64),
+    #[error(transparent)]
+    Migrate(MigrateError),
+    #[error(transparent)]
+    Sqlx(#[from] sqlx::Error),
+}
+
+debug_with_error_chain!(MigrationError);
+
+impl From<MigrateError> for MigrationError {
+    fn from(err: MigrateError) -> Self {
+        match err {
+            MigrateError::Dirty(version) => MigrationError::Dirty(version),
+            MigrateError::VersionMissing(version) => MigrationError::Missing(version),
+            err => MigrationError::Migrate(err),
+        }
+    }
+}
+
+/// Checks that the schema of the database can be migrated.
+///
+/// # Errors
+///
+/// This function will return an error if a migration is dirty, that is it was only partially
+/// applied.
+#[tracing::instrument(name = "Check migration status", skip(pool))]
+pub async fn check_migration_status(pool: &PgPool) -> Result<(), MigrationError> {
+    let mut conn = pool.acquire().await?;
+
+    conn.ensure_migrations_table().await?;
+    if let Some(version) = conn.dirty_version().await? {
+        return Err(MigrationError::Dirty(version));
+    }
+
+    Ok(())
+}
+
+/// Runs the pending migrations on the database, after checking its status with
+/// [`check_migration_status`].
+///
+/// This is idempotent: migrations already applied are skipped.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * a migration is dirty, that is it was only partially applied
+/// * a migration applied to the database is unknown to the application
+/// * running the migrations fails for any other reason
+#[tracing::instrument(name = "Run migrations", skip(pool))]
+pub async fn run_migrations(pool: &PgPool) -> Result<(), MigrationError> {
+    check_migration_status(pool).await?;
+
+    let mut conn = pool.acquire().await?;
+
+    let applied_before = conn.list_applied_migrations().await?.len();
+
+    // Run the pending migrations
+
+    sqlx::migrate!("./migrations").run(&mut conn).await?;
+
+    // Report the status
+
+    let applied_migrations = conn.list_applied_migrations().await?;
+
+    let versions = applied_migrations
+        .iter()
+        .map(|migration| migration.version)
+        .collect::<Vec<_>>();
+    debug!(?versions, "applied migrations");
+
+    let new_versions = &versions[applied_before.min(versions.len())..];
+    if new_versions.is_empty() {
+        info!("database schema is up to date");
+    } else {
+        info!(versions = ?new_versions, "applied {} migration(s)", new_versions.len());
+    }
+
+    Ok(())
+}
+
+/// Registers `tem_client` as app data if there is one.
+///
+/// Handlers which need to send emails extract a `web::Data<tem::Client>`, or an
+/// `Option<web::Data<tem::Client>>` if they can work without emails.
+fn configure_tem_client(
+    tem_client: Option<web::Data<tem::Client>>,
+) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |cfg| {
+        if let Some(tem_client) = tem_client {
+            cfg.app_data(tem_client);
+        }
+    }
+}
+
 pub fn get_tem_client(configuration: &TEMConfig) -> anyhow::Result<tem::Client> {
     let sender_email = configuration.sender()?;
 
@@ -190,5 +511,243 @@ pub fn get_tem_client(configuration: &TEMConfig) -> anyhow::Result<tem::Client>
         configuration.auth_key.clone(),
         sender_email,
         configuration.timeout(),
+        configuration.retry.clone(),
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::get_configuration;
+    use crate::domain::UserEmail;
+    use crate::tests::{create_user, get_pool, TestDatabase};
+    use actix_web::{test, HttpResponse};
+    use sqlx::Executor;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn check_migration_status_should_succeed_with_an_up_to_date_schema() {
+        let pool = get_pool().await;
+
+        let result = check_migration_status(&pool).await;
+        assert!(result.is_ok(), "result should be Ok, got {:?}", result);
+    }
+
+    #[tokio::test]
+    async fn check_migration_status_should_fail_with_a_dirty_migration() {
+        // Use a dedicated database to not break the other tests
+
+        let database = TestDatabase::new().await;
+        let db_pool = &database.pool;
+
+        // Mark the latest migration as dirty
+
+        let record = sqlx::query!(
+            r#"
+            UPDATE _sqlx_migrations SET success = false
+            WHERE version = (SELECT MAX(version) FROM _sqlx_migrations)
+            RETURNING version
+            "#
+        )
+        .fetch_one(db_pool)
+        .await
+        .unwrap();
+
+        let result = check_migration_status(db_pool).await;
+        match result {
+            Err(MigrationError::Dirty(version)) => assert_eq!(record.version, version),
+            result => panic!("expected a dirty migration error, got {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn connection_pool_should_wait_for_a_free_connection() {
+        let config = get_configuration().unwrap();
+        let db_config = DatabaseConfig {
+            max_connections: 1,
+            acquire_timeout_ms: 5000,
+            ..config.database
+        };
+
+        let pool = get_connection_pool(&db_config).await.unwrap();
+
+        // Hold the only connection of the pool
+
+        let tx = pool.begin().await.unwrap();
+
+        let query = tokio::spawn({
+            let pool = pool.clone();
+            async move { sqlx::query("SELECT 1").execute(&pool).await }
+        });
+
+        tokio::time::sleep(StdDuration::from_millis(200)).await;
+        assert!(
+            !query.is_finished(),
+            "the query should wait for the connection"
+        );
+
+        // Release it, the query can now run
+
+        tx.rollback().await.unwrap();
+
+        let result = query.await.unwrap();
+        assert!(result.is_ok(), "result should be Ok, got {:?}", result);
+    }
+
+    #[tokio::test]
+    async fn run_migrations_should_apply_the_schema_to_a_fresh_database() {
+        // Use a dedicated database to start from an empty schema
+
+        let database = TestDatabase::blank().await;
+        let db_pool = &database.pool;
+
+        // Running the migrations twice should work
+
+        let first_result = run_migrations(db_pool).await;
+        assert!(
+            first_result.is_ok(),
+            "result should be Ok, got {:?}",
+            first_result
+        );
+        let second_result = run_migrations(db_pool).await;
+        assert!(
+            second_result.is_ok(),
+            "result should be Ok, got {:?}",
+            second_result
+        );
+
+        let record = sqlx::query!(
+            r#"
+            SELECT COUNT(*) AS "count!" FROM information_schema.tables
+            WHERE table_schema = 'public' AND table_name IN ('users', 'feeds', 'feed_entries', 'jobs')
+            "#
+        )
+        .fetch_one(db_pool)
+        .await
+        .unwrap();
+        assert_eq!(4, record.count);
+    }
+
+    #[tokio::test]
+    async fn invalid_site_links_should_be_replaced_by_the_feed_url() {
+        // Use a dedicated database to not touch the feeds of other tests
+
+        let database = TestDatabase::new().await;
+        let db_pool = &database.pool;
+
+        // Insert feeds with a valid and an invalid site link, then run the migration again
+
+        let user_id = create_user(db_pool).await;
+
+        let mut feed_ids = Vec::new();
+        for (url, site_link) in [
+            ("https://example.com/feed1.xml", "not a link"),
+            ("https://example.com/feed2.xml", "https://example.com"),
+        ] {
+            let record = sqlx::query!(
+                r#"
+                INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+                VALUES ($1, $2, 'Foo', $3, 'Foo', now())
+                RETURNING id
+                "#,
+                &user_id.0,
+                url,
+                site_link,
+            )
+            .fetch_one(db_pool)
+            .await
+            .unwrap();
+
+            feed_ids.push(record.id);
+        }
+
+        let migration_result = db_pool
+            .execute(include_str!(
+                "../migrations/20230314190512_feeds_site_link_fallback.sql"
+            ))
+            .await;
+        assert!(
+            migration_result.is_ok(),
+            "result should be Ok, got {:?}",
+            migration_result
+        );
+
+        let site_links = sqlx::query!(
+            "SELECT id, site_link FROM feeds WHERE id = ANY($1) ORDER BY id",
+            &feed_ids[..],
+        )
+        .fetch_all(db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|record| record.site_link)
+        .collect::<Vec<_>>();
+        assert_eq!(
+            vec!["https://example.com/feed1.xml", "https://example.com"],
+            site_links
+        );
+    }
+
+    async fn send_test_email(tem_client: web::Data<tem::Client>) -> HttpResponse {
+        let recipient = UserEmail::parse("foo@example.com".to_string()).unwrap();
+
+        match tem_client
+            .send_email_single(&recipient, "Hello", "<p>Hello</p>", "Hello")
+            .await
+        {
+            Ok(()) => HttpResponse::Ok().finish(),
+            Err(_) => HttpResponse::InternalServerError().finish(),
+        }
+    }
+
+    async fn has_tem_client(tem_client: Option<web::Data<tem::Client>>) -> HttpResponse {
+        HttpResponse::Ok().body(tem_client.is_some().to_string())
+    }
+
+    #[actix_web::test]
+    async fn configure_tem_client_should_make_the_client_available_to_handlers() {
+        let email_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/emails"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&email_server)
+            .await;
+
+        let mut tem_config = get_configuration()
+            .unwrap()
+            .tem
+            .expect("The test configuration should have a TEM configuration");
+        tem_config.base_url = email_server.uri();
+        let tem_client = get_tem_client(&tem_config).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .configure(configure_tem_client(Some(web::Data::new(tem_client))))
+                .route("/", web::post().to(send_test_email)),
+        )
+        .await;
+
+        let req = test::TestRequest::post().uri("/").to_request();
+        let response = test::call_service(&app, req).await;
+
+        assert!(response.status().is_success());
+    }
+
+    #[actix_web::test]
+    async fn configure_tem_client_without_a_client_should_not_register_anything() {
+        let app = test::init_service(
+            App::new()
+                .configure(configure_tem_client(None))
+                .route("/", web::get().to(has_tem_client)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+
+        assert_eq!(&b"false"[..], &body[..]);
+    }
+}
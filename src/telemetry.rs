@@ -1,3 +1,8 @@
+use opentelemetry::sdk::export::trace::SpanData;
+use opentelemetry::sdk::trace::{Span, SpanProcessor, TracerProvider};
+use opentelemetry::trace::{TraceResult, TracerProvider as _};
+use opentelemetry::Context;
+use std::sync::{mpsc, Mutex};
 use tracing::subscriber::set_global_default;
 use tracing::Subscriber;
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
@@ -12,6 +17,7 @@ pub struct SubscriberBuilder {
     logging_targets: filter::Targets,
     jaeger_endpoint: Option<String>,
     jaeger_targets: filter::Targets,
+    span_sink: Option<mpsc::Sender<SpanData>>,
 }
 
 impl SubscriberBuilder {
@@ -24,6 +30,7 @@ impl SubscriberBuilder {
             jaeger_endpoint: None,
             logging_targets: filter::Targets::default(),
             jaeger_targets: filter::Targets::default(),
+            span_sink: None,
         }
     }
 
@@ -44,10 +51,21 @@ impl SubscriberBuilder {
         self
     }
 
+    /// Sends all completed spans to `sender`.
+    ///
+    /// This is intended for tests, to check that specific spans or fields were recorded.
+    /// The spans are filtered using the jaeger targets.
+    pub fn with_span_sink(mut self, sender: mpsc::Sender<SpanData>) -> Self {
+        self.span_sink = Some(sender);
+        self
+    }
+
     /// Creates a [`tracing::Subscriber`] configured to format logs with [`Bunyan`]
     ///
+    /// Nothing is installed globally, use [`init_global_default`] for that.
+    ///
     /// [`Bunyan`]: https://docs.rs/tracing-bunyan-formatter/latest/tracing_bunyan_formatter/
-    pub fn build<Sink>(self, sink: Sink) -> Box<dyn Subscriber + Sync + Send>
+    pub fn build<Sink>(self, sink: Sink) -> TracingSubscriber
     where
         Sink: for<'a> MakeWriter<'a> + Sync + Send + 'static,
     {
@@ -61,38 +79,104 @@ impl SubscriberBuilder {
             formatting_layer.with_filter(self.logging_targets)
         };
 
-        match self.jaeger_endpoint {
-            Some(endpoint) => {
-                let otel_tracer = opentelemetry_jaeger::new_agent_pipeline()
-                    .with_endpoint(endpoint)
-                    .with_service_name(self.name)
-                    .install_simple()
-                    .expect("unable to get otel jaeger agent pipeline");
-
-                let otel_layer = tracing_opentelemetry::layer()
-                    .with_tracer(otel_tracer)
-                    .with_filter(self.jaeger_targets);
-
-                Box::new(
+        if self.jaeger_endpoint.is_none() && self.span_sink.is_none() {
+            return TracingSubscriber {
+                subscriber: Box::new(
                     Registry::default()
                         .with(JsonStorageLayer)
-                        .with(logging_layer)
-                        .with(otel_layer),
-                )
-            }
-            None => Box::new(
+                        .with(logging_layer),
+                ),
+                tracer_provider: None,
+            };
+        }
+
+        // Both the jaeger exporter and the span sink are fed by the same tracer provider,
+        // there can only be one OpenTelemetry layer.
+
+        let mut provider_builder = TracerProvider::builder();
+
+        if let Some(endpoint) = self.jaeger_endpoint {
+            let exporter = opentelemetry_jaeger::new_agent_pipeline()
+                .with_endpoint(endpoint)
+                .with_service_name(self.name.clone())
+                .build_sync_agent_exporter()
+                .expect("unable to get otel jaeger agent exporter");
+
+            provider_builder = provider_builder.with_simple_exporter(exporter);
+        }
+        if let Some(sender) = self.span_sink {
+            provider_builder = provider_builder.with_span_processor(SpanSinkProcessor::new(sender));
+        }
+
+        let provider = provider_builder.build();
+        let otel_tracer = provider.tracer(self.name);
+
+        let otel_layer = tracing_opentelemetry::layer()
+            .with_tracer(otel_tracer)
+            .with_filter(self.jaeger_targets);
+
+        TracingSubscriber {
+            subscriber: Box::new(
                 Registry::default()
                     .with(JsonStorageLayer)
-                    .with(logging_layer),
+                    .with(logging_layer)
+                    .with(otel_layer),
             ),
+            tracer_provider: Some(provider),
+        }
+    }
+}
+
+/// A [`tracing::Subscriber`] built by [`SubscriberBuilder`].
+pub struct TracingSubscriber {
+    subscriber: Box<dyn Subscriber + Sync + Send>,
+    /// The OpenTelemetry tracer provider feeding the subscriber, if any.
+    ///
+    /// The tracer only keeps a weak reference to its provider, something must keep it alive.
+    tracer_provider: Option<TracerProvider>,
+}
+
+/// A [`SpanProcessor`] sending all completed spans to a channel.
+#[derive(Debug)]
+struct SpanSinkProcessor {
+    sender: Mutex<mpsc::Sender<SpanData>>,
+}
+
+impl SpanSinkProcessor {
+    fn new(sender: mpsc::Sender<SpanData>) -> Self {
+        Self {
+            sender: Mutex::new(sender),
         }
     }
 }
 
+impl SpanProcessor for SpanSinkProcessor {
+    fn on_start(&self, _span: &mut Span, _cx: &Context) {}
+
+    fn on_end(&self, span: SpanData) {
+        // The receiver is gone, nobody is interested in the spans anymore
+        let _ = self.sender.lock().unwrap().send(span);
+    }
+
+    fn force_flush(&self) -> TraceResult<()> {
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> TraceResult<()> {
+        Ok(())
+    }
+}
+
 /// Sets `subscriber` as the global default [`tracing::Subscriber`].
-pub fn init_global_default(subscriber: impl Subscriber + Sync + Send) {
+///
+/// Its OpenTelemetry tracer provider, if any, becomes the global tracer provider which keeps it alive.
+pub fn init_global_default(subscriber: TracingSubscriber) {
+    if let Some(tracer_provider) = subscriber.tracer_provider {
+        opentelemetry::global::set_tracer_provider(tracer_provider);
+    }
+
     LogTracer::init().expect("Failed to set logger");
-    set_global_default(subscriber).expect("Failed to set subscriber");
+    set_global_default(subscriber.subscriber).expect("Failed to set subscriber");
 }
 
 /// Spawns a blocking task in the scope of the current tracing span.
@@ -1,7 +1,7 @@
 use crate::domain::UserEmail;
 use secrecy::{ExposeSecret, Secret};
 use serde_json::json;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing::{event, Level};
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -13,6 +13,33 @@ impl ProjectId {
     }
 }
 
+/// Configures how failed requests to the TEM service are retried.
+///
+/// Only rate-limited (429) and server errors (5xx) are retried.
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct RetryConfig {
+    /// Maximum number of retries after the first attempt.
+    pub max_retries: u32,
+    /// Base delay of the exponential backoff, doubled after each retry.
+    pub base_delay_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay_ms: 200,
+        }
+    }
+}
+
+impl RetryConfig {
+    fn backoff_delay(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt);
+        Duration::from_millis(self.base_delay_ms.saturating_mul(factor))
+    }
+}
+
 #[derive(serde::Serialize)]
 struct SendEmailRequestRecipient<'a> {
     email: &'a str,
@@ -36,6 +63,8 @@ pub struct Client {
     project_id: ProjectId,
     auth_key: Secret<String>,
     sender: UserEmail,
+    timeout: Duration,
+    retry_config: RetryConfig,
 }
 
 impl Client {
@@ -45,6 +74,7 @@ impl Client {
         auth_key: Secret<String>,
         sender: UserEmail,
         timeout: Duration,
+        retry_config: RetryConfig,
     ) -> Self {
         let http_client = reqwest::Client::builder().timeout(timeout).build().unwrap();
 
@@ -54,13 +84,30 @@ impl Client {
             project_id,
             auth_key,
             sender,
+            timeout,
+            retry_config,
         }
     }
 
+    /// Sends an email to a single `recipient`.
+    ///
+    /// See [`Client::send_email`].
+    pub async fn send_email_single(
+        &self,
+        recipient: &UserEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<(), reqwest::Error> {
+        self.send_email(&[recipient], subject, html_content, text_content)
+            .await
+    }
+
+    /// Sends an email to all `recipients` in a single request.
     #[tracing::instrument(name = "Send an email", skip(self, html_content, text_content))]
     pub async fn send_email(
         &self,
-        recipient: &UserEmail,
+        recipients: &[&UserEmail],
         subject: &str,
         html_content: &str,
         text_content: &str,
@@ -72,10 +119,13 @@ impl Client {
                 email: self.sender.as_ref(),
                 name: Some("Vincent"),
             },
-            to: vec![SendEmailRequestRecipient {
-                email: recipient.as_ref(),
-                name: None,
-            }],
+            to: recipients
+                .iter()
+                .map(|recipient| SendEmailRequestRecipient {
+                    email: recipient.as_ref(),
+                    name: None,
+                })
+                .collect(),
             project_id: self.project_id.clone(),
             subject: subject.to_string(),
             text: text_content.to_string(),
@@ -88,14 +138,55 @@ impl Client {
             "sending email"
         );
 
-        let response = self
-            .http_client
-            .post(url)
-            .header("X-Auth-Token", self.auth_key.expose_secret())
-            .json(&body)
-            .send()
-            .await?
-            .error_for_status()?;
+        // Send the request, retrying on 429 and 5xx responses.
+        //
+        // The total time spent retrying is capped by the client timeout.
+
+        let started_at = Instant::now();
+        let mut attempt = 0;
+
+        let response = loop {
+            let response = self
+                .http_client
+                .post(&url)
+                .header("X-Auth-Token", self.auth_key.expose_secret())
+                .json(&body)
+                .send()
+                .await?;
+
+            let status = response.status();
+            let delay = if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                get_retry_after(&response)
+                    .unwrap_or_else(|| self.retry_config.backoff_delay(attempt))
+            } else {
+                self.retry_config.backoff_delay(attempt)
+            };
+
+            match response.error_for_status() {
+                Ok(response) => break response,
+                Err(err) => {
+                    let retryable = status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                        || status.is_server_error();
+                    let remaining = self.timeout.saturating_sub(started_at.elapsed());
+
+                    if !retryable || attempt >= self.retry_config.max_retries || delay >= remaining
+                    {
+                        return Err(err);
+                    }
+
+                    event!(
+                        Level::WARN,
+                        %status,
+                        attempt,
+                        delay_ms = delay.as_millis() as u64,
+                        "failed to send email, retrying"
+                    );
+
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        };
 
         let response_body = response.text().await?;
 
@@ -105,10 +196,20 @@ impl Client {
     }
 }
 
+/// Get the delay to wait for from the `Retry-After` header of `response`.
+///
+/// Only the delay-seconds form is supported.
+fn get_retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let value = response.headers().get(reqwest::header::RETRY_AFTER)?;
+    let seconds = value.to_str().ok()?.trim().parse::<u64>().ok()?;
+
+    Some(Duration::from_secs(seconds))
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Client;
     use super::ProjectId;
+    use super::{Client, RetryConfig};
     use crate::domain::UserEmail;
     use fake::faker::internet::en::SafeEmail;
     use fake::faker::lorem::en::{Paragraph, Sentence};
@@ -137,6 +238,23 @@ mod tests {
         }
     }
 
+    /// Matches a send email request with exactly `self.0` recipients.
+    struct RecipientsCountMatcher(usize);
+
+    impl wiremock::Match for RecipientsCountMatcher {
+        fn matches(&self, request: &wiremock::Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+            match result {
+                Ok(body) => body
+                    .get("to")
+                    .and_then(serde_json::Value::as_array)
+                    .map(|to| to.len() == self.0)
+                    .unwrap_or(false),
+                Err(_) => false,
+            }
+        }
+    }
+
     fn subject() -> String {
         Sentence(1..2).fake()
     }
@@ -150,6 +268,10 @@ mod tests {
     }
 
     fn email_client(base_url: String) -> Client {
+        email_client_with_retries(base_url, 0)
+    }
+
+    fn email_client_with_retries(base_url: String, max_retries: u32) -> Client {
         let project_id = ProjectId(Uuid::new_v4().to_string());
 
         Client::new(
@@ -158,6 +280,10 @@ mod tests {
             Secret::new(Faker.fake()),
             email(),
             Duration::from_millis(100),
+            RetryConfig {
+                max_retries,
+                base_delay_ms: 5,
+            },
         )
     }
 
@@ -173,7 +299,7 @@ mod tests {
             .await;
 
         let result = client
-            .send_email(&email(), &subject(), &content(), &content())
+            .send_email_single(&email(), &subject(), &content(), &content())
             .await;
 
         assert!(result.is_ok(), "send email result should be Ok, not Err");
@@ -191,7 +317,7 @@ mod tests {
             .await;
 
         let result = client
-            .send_email(&email(), &subject(), &content(), &content())
+            .send_email_single(&email(), &subject(), &content(), &content())
             .await;
 
         assert!(result.is_err(), "send email result should be Err, not Ok");
@@ -211,7 +337,7 @@ mod tests {
             .await;
 
         let result = client
-            .send_email(&email(), &subject(), &content(), &content())
+            .send_email_single(&email(), &subject(), &content(), &content())
             .await;
 
         assert!(result.is_err(), "send email result should be Err, not Ok");
@@ -233,7 +359,138 @@ mod tests {
             .await;
 
         let _ = client
-            .send_email(&email(), &subject(), &content(), &content())
+            .send_email_single(&email(), &subject(), &content(), &content())
             .await;
     }
+
+    #[tokio::test]
+    async fn send_email_succeeds_if_the_server_recovers_after_500s() {
+        let mock_server = MockServer::start().await;
+        let client = email_client_with_retries(mock_server.uri(), 3);
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let result = client
+            .send_email_single(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert!(result.is_ok(), "send email result should be Ok, not Err");
+    }
+
+    #[tokio::test]
+    async fn send_email_retries_if_the_server_returns_429() {
+        let mock_server = MockServer::start().await;
+        let client = email_client_with_retries(mock_server.uri(), 3);
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let result = client
+            .send_email_single(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert!(result.is_ok(), "send email result should be Ok, not Err");
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_the_retries_are_exhausted() {
+        let mock_server = MockServer::start().await;
+        let client = email_client_with_retries(mock_server.uri(), 2);
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let result = client
+            .send_email_single(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert!(result.is_err(), "send email result should be Err, not Ok");
+    }
+
+    #[tokio::test]
+    async fn send_email_does_not_retry_if_the_server_returns_400() {
+        let mock_server = MockServer::start().await;
+        let client = email_client_with_retries(mock_server.uri(), 3);
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(400))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let result = client
+            .send_email_single(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert!(result.is_err(), "send email result should be Err, not Ok");
+    }
+
+    #[tokio::test]
+    async fn send_email_sends_a_single_request_to_all_recipients() {
+        let mock_server = MockServer::start().await;
+        let client = email_client(mock_server.uri());
+
+        Mock::given(path("/emails"))
+            .and(RecipientsCountMatcher(3))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let (email1, email2, email3) = (email(), email(), email());
+
+        let result = client
+            .send_email(
+                &[&email1, &email2, &email3],
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        assert!(result.is_ok(), "send email result should be Ok, not Err");
+    }
+
+    #[tokio::test]
+    async fn send_email_single_sends_to_one_recipient() {
+        let mock_server = MockServer::start().await;
+        let client = email_client(mock_server.uri());
+
+        Mock::given(path("/emails"))
+            .and(RecipientsCountMatcher(1))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let result = client
+            .send_email_single(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert!(result.is_ok(), "send email result should be Ok, not Err");
+    }
 }
@@ -1,24 +1,112 @@
-use crate::configuration::get_configuration;
+use crate::authentication::PasswordHasherConfig;
+use crate::configuration::{get_configuration, DatabaseConfig};
 use crate::domain::{UserEmail, UserId};
-use crate::feed::{insert_feed, FeedId, ParsedFeed};
-use crate::startup::get_connection_pool;
+use crate::feed::{insert_feed, FeedEntryId, FeedId, ParsedFeed};
+use crate::startup::{get_connection_pool, run_migrations};
 use fake::faker::internet::en::{Password as FakerPassword, SafeEmail as FakerSafeEmail};
 use fake::faker::lorem::en::{Paragraph as FakerParagraph, Sentence as FakerSentence};
 use fake::Fake;
 use secrecy::Secret;
 use sqlx::PgPool;
 use url::Url;
+use uuid::Uuid;
 
-/// Get a connection pool suitable for tests
+/// Get a connection pool suitable for tests, with an up to date schema.
 ///
 /// # Panics
 ///
 /// Panics if:
 /// * the configuration is invalid somehow.
 /// * a connection pool can't be created.
+/// * the migrations can't be applied.
 pub async fn get_pool() -> PgPool {
     let config = get_configuration().unwrap();
-    get_connection_pool(&config.database).await.unwrap()
+    let pool = get_connection_pool(&config.database).await.unwrap();
+    run_migrations(&pool).await.unwrap();
+    pool
+}
+
+/// A database dedicated to a single test, for the tests which can't share the database of the
+/// other tests.
+///
+/// The database is dropped along with this struct, even if the test panics.
+pub struct TestDatabase {
+    pub pool: PgPool,
+    name: String,
+}
+
+impl TestDatabase {
+    /// Creates a new database with an up to date schema.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the database can't be created or the migrations can't be applied.
+    pub async fn new() -> Self {
+        let database = Self::blank().await;
+        run_migrations(&database.pool)
+            .await
+            .expect("unable to apply the migrations");
+        database
+    }
+
+    /// Creates a new database without any schema.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the database can't be created.
+    pub async fn blank() -> Self {
+        let config = get_configuration().unwrap();
+        let name = format!("servare_tests_{}", Uuid::new_v4().simple());
+
+        let pool = get_connection_pool(&config.database).await.unwrap();
+        sqlx::query(&format!(r#"CREATE DATABASE "{}""#, name))
+            .execute(&pool)
+            .await
+            .expect("unable to create the test database");
+        pool.close().await;
+
+        let db_config = DatabaseConfig {
+            name: name.clone(),
+            ..config.database
+        };
+        let pool = get_connection_pool(&db_config)
+            .await
+            .expect("unable to connect to the test database");
+
+        Self { pool, name }
+    }
+}
+
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        let name = self.name.clone();
+
+        // Drop can't be async: drop the database from a thread with its own runtime.
+        // FORCE closes the connections of the test still open.
+        let result = std::thread::spawn(move || -> anyhow::Result<()> {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+
+            runtime.block_on(async {
+                let config = get_configuration()?;
+                let pool = get_connection_pool(&config.database).await?;
+                sqlx::query(&format!(
+                    r#"DROP DATABASE IF EXISTS "{}" WITH (FORCE)"#,
+                    name
+                ))
+                .execute(&pool)
+                .await?;
+                pool.close().await;
+                Ok::<_, anyhow::Error>(())
+            })
+        })
+        .join();
+
+        if let Ok(Err(err)) = result {
+            eprintln!("unable to drop the test database {}: {:#}", self.name, err);
+        }
+    }
 }
 
 /// Creates a basic [`reqwest::Client`] suitable for tests.
@@ -50,12 +138,17 @@ pub async fn fetch(url: &Url) -> bytes::Bytes {
 /// Panics if any step in the user creation fail.
 pub async fn create_user(pool: &PgPool) -> UserId {
     let email = FakerSafeEmail().fake();
-    let password = FakerPassword(10..20).fake();
+    // Strong enough to pass the password validation
+    let password = format!("{}1", FakerPassword(12..20).fake::<String>());
 
-    let user_id =
-        crate::authentication::create_user(pool, &UserEmail(email), Secret::new(password))
-            .await
-            .expect("unable to create user");
+    let user_id = crate::authentication::create_user(
+        pool,
+        &PasswordHasherConfig::default(),
+        &UserEmail(email),
+        Secret::new(password),
+    )
+    .await
+    .expect("unable to create user");
 
     user_id
 }
@@ -74,9 +167,39 @@ pub async fn create_feed(pool: &PgPool, user_id: UserId, url: &Url, site_link: &
         title,
         site_link: Some(site_link.clone()),
         description,
+        language: None,
     };
 
     let feed_id = insert_feed(pool, user_id, &feed).await.unwrap();
 
     feed_id
 }
+
+/// Create a test feed entry for the feed [`feed_id`].
+///
+/// # Panics
+///
+/// Panics if the entry can't be inserted.
+pub async fn create_feed_entry(pool: &PgPool, feed_id: &FeedId) -> FeedEntryId {
+    let title: String = FakerSentence(4..15).fake();
+    let summary: String = FakerParagraph(1..40).fake();
+    let external_id = uuid::Uuid::new_v4().to_string();
+
+    let record = sqlx::query!(
+        r#"
+        INSERT INTO feed_entries(feed_id, external_id, title, created_at, summary)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+        &feed_id.0,
+        &external_id,
+        &title,
+        time::OffsetDateTime::now_utc(),
+        &summary,
+    )
+    .fetch_one(pool)
+    .await
+    .expect("unable to insert feed entry");
+
+    FeedEntryId(record.id)
+}
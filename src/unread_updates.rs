@@ -0,0 +1,60 @@
+use crate::domain::UserId;
+use tokio::sync::broadcast;
+
+/// A change in the number of unread entries of a user.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct UnreadUpdate {
+    pub user_id: UserId,
+    pub delta: i32,
+}
+
+/// Broadcasts [`UnreadUpdate`] to all subscribers.
+///
+/// This is shared between the job runner, which sends an update when it inserts new feed entries,
+/// and the application, which forwards the updates to the connected clients.
+#[derive(Clone)]
+pub struct UnreadUpdates {
+    sender: broadcast::Sender<UnreadUpdate>,
+}
+
+impl UnreadUpdates {
+    /// Creates a new broadcaster buffering at most `capacity` updates per subscriber.
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+
+        Self { sender }
+    }
+
+    /// Sends an update to all current subscribers.
+    ///
+    /// Nothing is sent if there are no subscribers.
+    pub fn send(&self, user_id: UserId, delta: i32) {
+        // An error only means there are no subscribers, which is fine
+        let _ = self.sender.send(UnreadUpdate { user_id, delta });
+    }
+
+    /// Subscribes to the updates sent after this call.
+    pub fn subscribe(&self) -> broadcast::Receiver<UnreadUpdate> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn unread_updates_should_be_received_by_subscribers() {
+        let updates = UnreadUpdates::new(10);
+        let user_id = UserId::default();
+
+        // Sending without subscribers must not fail
+        updates.send(user_id, 1);
+
+        let mut receiver = updates.subscribe();
+        updates.send(user_id, 3);
+
+        let update = receiver.recv().await.unwrap();
+        assert_eq!(UnreadUpdate { user_id, delta: 3 }, update);
+    }
+}
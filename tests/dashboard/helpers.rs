@@ -1,23 +1,36 @@
+use actix_web::cookie::{Cookie, CookieJar, Key};
 use anyhow::Context;
-use argon2::password_hash::{PasswordHasher, SaltString};
-use argon2::Argon2;
 use fake::faker::internet::en::{Password as FakerPassword, SafeEmail as FakerSafeEmail};
 use fake::Fake;
 use once_cell::sync::Lazy;
-use servare::configuration::get_configuration;
-use servare::domain::UserId;
-use servare::job::JobRunner;
+use opentelemetry::sdk::export::trace::SpanData;
+use opentelemetry::Key as AttributeKey;
+use reqwest::cookie::{CookieStore, Jar};
+use secrecy::{ExposeSecret, Secret};
+use servare::authentication::{create_user, PasswordHasherConfig};
+use servare::configuration::{get_configuration, DatabaseConfig};
+use servare::domain::{UserEmail, UserId};
+use servare::feed::FaviconCache;
+use servare::job::{DefaultJobHandler, JobRunner};
 use servare::run_group::RunGroup;
+use servare::security::AllowPrivateUrls;
 use servare::startup::Application;
-use servare::startup::{get_connection_pool, get_tem_client};
+use servare::startup::{get_connection_pool, get_tem_client, run_migrations};
+use servare::unread_updates::UnreadUpdates;
 use servare::{telemetry, tem};
 use sqlx::PgPool;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration as StdDuration;
 use tracing::Level;
 use tracing_subscriber::filter;
+use url::Url;
 use uuid::Uuid;
 use wiremock::MockServer;
 
-static TRACING: Lazy<()> = Lazy::new(|| {
+/// Initializes tracing for all tests.
+///
+/// The spans are always collected in a [`SpanCollector`] so that tests can check them.
+static TRACING: Lazy<SpanCollector> = Lazy::new(|| {
     std::env::set_var("RUST_LOG", "sqlx=error,info");
     let has_test_log = std::env::var("TEST_LOG").is_ok();
 
@@ -37,8 +50,14 @@ static TRACING: Lazy<()> = Lazy::new(|| {
         }
     };
 
-    let subscriber_builder =
-        telemetry::SubscriberBuilder::new("test").with_logging_targets(targets);
+    let (sender, receiver) = mpsc::channel();
+
+    let subscriber_builder = telemetry::SubscriberBuilder::new("test")
+        .with_logging_targets(targets)
+        .with_jaeger_targets(Some(
+            filter::Targets::new().with_target("servare", Level::TRACE),
+        ))
+        .with_span_sink(sender);
 
     let subscriber = if has_test_log {
         subscriber_builder.build(std::io::stdout)
@@ -46,8 +65,71 @@ static TRACING: Lazy<()> = Lazy::new(|| {
         subscriber_builder.build(std::io::sink)
     };
     telemetry::init_global_default(subscriber);
+
+    SpanCollector::new(receiver)
 });
 
+/// Collects the spans recorded by all tests.
+///
+/// Tests run concurrently in the same process so a test must look for spans containing data unique to it,
+/// for example the id of its test user.
+pub struct SpanCollector {
+    inner: Mutex<SpanCollectorInner>,
+}
+
+struct SpanCollectorInner {
+    receiver: mpsc::Receiver<SpanData>,
+    spans: Vec<SpanData>,
+}
+
+impl SpanCollector {
+    fn new(receiver: mpsc::Receiver<SpanData>) -> Self {
+        Self {
+            inner: Mutex::new(SpanCollectorInner {
+                receiver,
+                spans: Vec::new(),
+            }),
+        }
+    }
+
+    /// Waits for a span named `name` with the attribute `key` equal to `value`.
+    ///
+    /// Returns `None` if no such span was recorded after 5 seconds.
+    pub async fn wait_for_span(
+        &self,
+        name: &str,
+        key: &'static str,
+        value: &str,
+    ) -> Option<SpanData> {
+        let key = AttributeKey::new(key);
+
+        for _ in 0..50 {
+            {
+                let mut inner = self.inner.lock().unwrap();
+
+                let received = inner.receiver.try_iter().collect::<Vec<_>>();
+                inner.spans.extend(received);
+
+                let span = inner.spans.iter().find(|span| {
+                    span.name == name
+                        && span
+                            .attributes
+                            .get(&key)
+                            .map(|v| v.as_str() == value)
+                            .unwrap_or(false)
+                });
+                if let Some(span) = span {
+                    return Some(span.clone());
+                }
+            }
+
+            tokio::time::sleep(StdDuration::from_millis(100)).await;
+        }
+
+        None
+    }
+}
+
 pub struct TestUser {
     pub id: UserId,
     pub email: String,
@@ -57,40 +139,28 @@ pub struct TestUser {
 impl Default for TestUser {
     fn default() -> Self {
         Self {
-            id: UserId(Uuid::new_v4()),
+            // Replaced by the actual id when the user is stored
+            id: UserId::default(),
             email: FakerSafeEmail().fake(),
-            password: FakerPassword(10..20).fake(),
+            // Strong enough to pass the password validation
+            password: format!("{}1", FakerPassword(12..20).fake::<String>()),
         }
     }
 }
 
 impl TestUser {
-    async fn store(&self, pool: &PgPool) -> anyhow::Result<()> {
-        let salt = SaltString::generate(&mut rand::thread_rng());
-
-        let hasher = Argon2::new(
-            argon2::Algorithm::Argon2id,
-            argon2::Version::V0x13,
-            argon2::Params::new(15000, 2, 1, None).unwrap(),
-        );
-
-        let password_hash = hasher
-            .hash_password(self.password.as_bytes(), &salt)
-            .context("unable to compute password hash")?
-            .to_string();
-
-        sqlx::query!(
-            r#"
-            INSERT INTO users(id, email, password_hash)
-            VALUES ($1, $2, $3)
-            "#,
-            &self.id.0,
-            self.email,
-            password_hash,
+    /// Stores the user using the same code path as production, then records its [`UserId`].
+    async fn store(&mut self, pool: &PgPool) -> anyhow::Result<()> {
+        let user_id = create_user(
+            pool,
+            &PasswordHasherConfig::default(),
+            &UserEmail(self.email.clone()),
+            Secret::new(self.password.clone()),
         )
-        .execute(pool)
         .await
-        .context("unable to insert test user")?;
+        .context("unable to create test user")?;
+
+        self.id = user_id;
 
         Ok(())
     }
@@ -102,8 +172,14 @@ pub struct TestApp {
     pub port: u16,
     pub pool: PgPool,
     pub http_client: reqwest::Client,
+    /// The cookies of [`Self::http_client`].
+    pub cookie_jar: Arc<Jar>,
+    /// The key used by the application to encrypt the session cookie.
+    pub cookie_key: Key,
     pub email_server: MockServer,
     pub email_client: tem::Client,
+    pub job_runner: JobRunner,
+    pub span_collector: &'static SpanCollector,
 
     pub test_user: TestUser,
 }
@@ -128,13 +204,93 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    /// Returns the CSRF token of the session of [`Self::http_client`], if it has one.
+    pub async fn csrf_token(&self) -> Option<String> {
+        let url = Url::parse(&self.address).unwrap();
+        let cookies = self.cookie_jar.cookies(&url)?;
+
+        let session_id = cookies
+            .to_str()
+            .ok()?
+            .split("; ")
+            .filter_map(|cookie| Cookie::parse_encoded(cookie.to_string()).ok())
+            .find(|cookie| cookie.name() == "session_id")?;
+
+        // The session cookie is encrypted, see TypedSession::session_id
+        let mut jar = CookieJar::new();
+        jar.add_original(session_id);
+        let session_id = jar.private(&self.cookie_key).get("session_id")?;
+        let session_id = Uuid::try_parse(session_id.value()).ok()?;
+
+        let record = sqlx::query!(
+            r#"SELECT state->>'csrf_token' AS token FROM sessions WHERE id = $1"#,
+            session_id,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .expect("Failed to fetch the session")?;
+
+        // The values of the session state are JSON encoded
+        serde_json::from_str(&record.token?).ok()
+    }
+
+    /// Sets the CSRF token of the session on `request`, like htmx does.
+    async fn with_csrf_token(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.csrf_token().await {
+            Some(token) => request.header("X-CSRF-Token", token),
+            None => request,
+        }
+    }
+
+    pub async fn delete(&self, path: &str) -> reqwest::Response {
+        let request = self
+            .http_client
+            .delete(&format!("{}{}", self.address, path));
+
+        self.with_csrf_token(request)
+            .await
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Sends a DELETE request with a form body, like htmx does for a form with `hx-delete`.
+    pub async fn delete_form<T>(&self, path: &str, body: &T) -> reqwest::Response
+    where
+        T: serde::Serialize,
+    {
+        let request = self
+            .http_client
+            .delete(&format!("{}{}", self.address, path))
+            .form(body);
+
+        self.with_csrf_token(request)
+            .await
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Runs exactly one cycle of a temporary copy of the test [`JobRunner`].
+    ///
+    /// The cycle is run in the test task which makes it possible to test job outcomes
+    /// deterministically.
+    pub async fn run_single_job_cycle(&self) -> anyhow::Result<()> {
+        let mut job_runner = self.job_runner.clone();
+        job_runner.tick().await
+    }
+
     pub async fn post<T>(&self, path: &str, body: &T) -> reqwest::Response
     where
         T: serde::Serialize,
     {
-        self.http_client
+        let request = self
+            .http_client
             .post(&format!("{}{}", self.address, path))
-            .form(body)
+            .form(body);
+
+        self.with_csrf_token(request)
+            .await
             .send()
             .await
             .expect("Failed to execute request.")
@@ -155,6 +311,7 @@ pub async fn spawn_app() -> TestApp {
     let config = get_configuration().expect("Failed to get configuration");
 
     let pool = get_connection_pool(&config.database).await.unwrap();
+    run_migrations(&pool).await.unwrap();
 
     spawn_app_with_pool(pool).await
 }
@@ -164,7 +321,7 @@ pub async fn spawn_app() -> TestApp {
 /// The instance is ready to be used for testing.
 pub async fn spawn_app_with_pool(pool: PgPool) -> TestApp {
     // Enable tracing
-    Lazy::force(&TRACING);
+    let span_collector = Lazy::force(&TRACING);
 
     // Cleanup old data
     truncate_all(pool.clone()).await;
@@ -176,55 +333,95 @@ pub async fn spawn_app_with_pool(pool: PgPool) -> TestApp {
     // This means:
     // * set the port to 0 so that the OS is responsible for choosing a free port
     // * set the TEM base url to the URL of the mock email server
+    // * enable the test mode
+    // * allow private feed URLs since the feeds are served by a local mock server
     let mut configuration = get_configuration().expect("Failed to get configuration");
     configuration.application.port = 0;
-    configuration.tem.base_url = email_server.uri();
+    configuration.application.test_mode = true;
+    configuration.application.allow_private_feed_urls = true;
+    let mut tem_config = configuration
+        .tem
+        .clone()
+        .expect("The test configuration should have a TEM configuration");
+    tem_config.base_url = email_server.uri();
 
     //
     // Build the test email client and test HTTP client
     //
 
-    let email_client = get_tem_client(&configuration.tem).expect("Failed to get TEM client");
+    let email_client = get_tem_client(&tem_config).expect("Failed to get TEM client");
 
+    let cookie_jar = Arc::new(Jar::default());
     let http_client = reqwest::Client::builder()
         .redirect(reqwest::redirect::Policy::none())
-        .cookie_store(true)
+        .cookie_provider(cookie_jar.clone())
         .build()
         .expect("Failed to build HTTP client");
+    let cookie_key = Key::from(
+        configuration
+            .application
+            .cookie_signing_key
+            .expose_secret()
+            .as_bytes(),
+    );
 
     //
-    // Build the application and job runner
+    // Build the application
+    //
+    // Note the job runner doesn't run in the background: tests run jobs deterministically
+    // using [`TestApp::run_single_job_cycle`].
     //
 
     let app_pool = pool.clone();
-    let app = Application::build(&configuration.application, &configuration.session, app_pool)
-        .expect("Failed to build application");
+    let favicon_cache = FaviconCache::new(configuration.application.favicon_cache_size);
+    let unread_updates = UnreadUpdates::new(16);
+    let app = Application::build(
+        &configuration.application,
+        &configuration.session,
+        &configuration.argon2,
+        Some(&tem_config),
+        app_pool,
+        favicon_cache.clone(),
+        unread_updates.clone(),
+    )
+    .expect("Failed to build application");
     let app_port = app.port;
 
-    let job_pool = pool.clone();
-    let job_runner =
-        JobRunner::new(configuration.job, job_pool).expect("Failed to build job runner");
+    let job_runner = JobRunner::new(
+        configuration.job.clone(),
+        pool.clone(),
+        Box::new(DefaultJobHandler::new(favicon_cache, unread_updates)),
+    )
+    .and_then(|job_runner| {
+        job_runner.with_allow_private_urls(AllowPrivateUrls(
+            configuration.application.allow_private_feed_urls,
+        ))
+    })
+    .expect("Failed to build job runner");
 
     //
     // Run everything in a run group
     //
 
-    let run_group = RunGroup::new()
-        .run(|shutdown| app.run(shutdown))
-        .run(|shutdown| job_runner.run(shutdown));
+    let shutdown_timeout = configuration.application.shutdown_timeout();
+    let run_group = RunGroup::new().run(|shutdown| app.run(shutdown, shutdown_timeout));
 
     #[allow(clippy::let_underscore_future)]
     let _ = tokio::spawn(run_group.start());
 
     // Build the test harness
 
-    let test_app = TestApp {
+    let mut test_app = TestApp {
         address: format!("http://127.0.0.1:{}", app_port),
         port: app_port,
         pool,
         http_client,
+        cookie_jar,
+        cookie_key,
         email_server,
         email_client,
+        job_runner,
+        span_collector,
         test_user: TestUser::default(),
     };
 
@@ -238,6 +435,83 @@ pub async fn spawn_app_with_pool(pool: PgPool) -> TestApp {
     test_app
 }
 
+/// A database dedicated to a single test, for the tests which can't share the database of the
+/// other tests.
+///
+/// The database is dropped along with this struct, even if the test panics.
+pub struct TestDatabase {
+    pub pool: PgPool,
+    name: String,
+}
+
+impl TestDatabase {
+    /// Creates a new database with an up to date schema.
+    pub async fn new() -> Self {
+        let database = Self::blank().await;
+        run_migrations(&database.pool)
+            .await
+            .expect("Failed to run the migrations");
+        database
+    }
+
+    /// Creates a new database without any schema.
+    pub async fn blank() -> Self {
+        let config = get_configuration().expect("Failed to get configuration");
+        let name = format!("servare_tests_{}", Uuid::new_v4().simple());
+
+        let pool = get_connection_pool(&config.database)
+            .await
+            .expect("Failed to get a connection pool");
+        sqlx::query(&format!(r#"CREATE DATABASE "{}""#, name))
+            .execute(&pool)
+            .await
+            .expect("Failed to create the test database");
+        pool.close().await;
+
+        let db_config = DatabaseConfig {
+            name: name.clone(),
+            ..config.database
+        };
+        let pool = get_connection_pool(&db_config)
+            .await
+            .expect("Failed to get a connection pool on the test database");
+
+        Self { pool, name }
+    }
+}
+
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        let name = self.name.clone();
+
+        // Drop can't be async: drop the database from a thread with its own runtime.
+        // FORCE closes the connections of the test still open.
+        let result = std::thread::spawn(move || -> anyhow::Result<()> {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+
+            runtime.block_on(async {
+                let config = get_configuration()?;
+                let pool = get_connection_pool(&config.database).await?;
+                sqlx::query(&format!(
+                    r#"DROP DATABASE IF EXISTS "{}" WITH (FORCE)"#,
+                    name
+                ))
+                .execute(&pool)
+                .await?;
+                pool.close().await;
+                Ok::<_, anyhow::Error>(())
+            })
+        })
+        .join();
+
+        if let Ok(Err(err)) = result {
+            eprintln!("Failed to drop the test database {}: {:#}", self.name, err);
+        }
+    }
+}
+
 pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert_eq!(
         response.status().as_u16(),
@@ -255,6 +529,11 @@ async fn truncate_all(pool: PgPool) {
         .await
         .expect("Failed to truncate all jobs");
 
+    sqlx::query!("TRUNCATE dead_letter_jobs")
+        .execute(&pool)
+        .await
+        .expect("Failed to truncate all dead letter jobs");
+
     sqlx::query!("TRUNCATE feeds CASCADE")
         .execute(&pool)
         .await
@@ -1,2 +1,246 @@
+use crate::helpers::{spawn_app, LoginBody, TestDatabase};
+use secrecy::Secret;
+use select::document::Document;
+use select::predicate::Attr;
+use servare::authentication::{create_user, PasswordHasherConfig};
+use servare::configuration::get_configuration;
+use servare::domain::UserEmail;
+use servare::feed::{FaviconCache, FeedId};
+use servare::job::post_fetch_favicon_job;
+use servare::run_group::{RunGroup, Shutdown};
+use servare::startup::{get_connection_pool, run_migrations, Application};
+use servare::unread_updates::UnreadUpdates;
+use std::time::Duration as StdDuration;
+use url::Url;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
 mod helpers;
 mod routes;
+
+#[tokio::test]
+async fn application_should_serve_requests_on_all_hosts() {
+    let mut config = get_configuration().expect("Failed to get configuration");
+    let pool = get_connection_pool(&config.database).await.unwrap();
+    run_migrations(&pool).await.unwrap();
+
+    // Only listen on IPv6 if it's available
+    let mut hosts = vec!["127.0.0.1:0".to_string()];
+    if std::net::TcpListener::bind("[::1]:0").is_ok() {
+        hosts.push("[::1]:0".to_string());
+    }
+    config.application.hosts = Some(hosts.clone());
+
+    let app = Application::build(
+        &config.application,
+        &config.session,
+        &config.argon2,
+        config.tem.as_ref(),
+        pool,
+        FaviconCache::new(10),
+        UnreadUpdates::new(16),
+    )
+    .expect("Failed to build application");
+    let addresses = app.addresses.clone();
+    assert_eq!(hosts.len(), addresses.len());
+
+    let shutdown_timeout = config.application.shutdown_timeout();
+    let run_group = RunGroup::new().run(|shutdown| app.run(shutdown, shutdown_timeout));
+
+    #[allow(clippy::let_underscore_future)]
+    let _ = tokio::spawn(run_group.start());
+
+    for address in addresses {
+        let response = reqwest::get(format!("http://{}/status", address))
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(200, response.status().as_u16());
+    }
+}
+
+#[tokio::test]
+async fn running_a_job_should_record_a_span_with_its_id() {
+    let app = spawn_app().await;
+
+    // Post a job fetching the favicon from a mock server, so it doesn't hit the network
+
+    let mock_server = MockServer::start().await;
+    let site_link = Url::parse(&mock_server.uri()).unwrap();
+
+    let job_id = post_fetch_favicon_job(&app.pool, app.test_user.id, FeedId(0), site_link)
+        .await
+        .expect("Failed to post job")
+        .expect("The job should be added");
+
+    // Other tests share the job queue: run cycles until the job has run at least once
+
+    for _ in 0..20 {
+        app.run_single_job_cycle()
+            .await
+            .expect("Failed to run a job cycle");
+
+        let record = sqlx::query!("SELECT attempts FROM jobs WHERE id = $1", &job_id.0)
+            .fetch_optional(&app.pool)
+            .await
+            .expect("Failed to fetch the job");
+        if record.map(|record| record.attempts > 0).unwrap_or(true) {
+            break;
+        }
+    }
+
+    let span = app
+        .span_collector
+        .wait_for_span("Run job", "job_id", &job_id.to_string())
+        .await;
+    assert!(span.is_some(), "no span with the job id was recorded");
+}
+
+#[tokio::test]
+async fn application_should_boot_from_a_blank_database() {
+    let mut config = get_configuration().expect("Failed to get configuration");
+    config.application.port = 0;
+
+    // Use a dedicated database to start from a blank schema, and apply the migrations like
+    // run_serve does
+
+    let database = TestDatabase::blank().await;
+    run_migrations(&database.pool)
+        .await
+        .expect("Failed to run the migrations on a blank database");
+
+    let app = Application::build(
+        &config.application,
+        &config.session,
+        &config.argon2,
+        None,
+        database.pool.clone(),
+        FaviconCache::new(10),
+        UnreadUpdates::new(16),
+    )
+    .expect("Failed to build application");
+    let address = app.addresses[0];
+
+    let shutdown_timeout = config.application.shutdown_timeout();
+    let run_group = RunGroup::new().run(|shutdown| app.run(shutdown, shutdown_timeout));
+
+    #[allow(clippy::let_underscore_future)]
+    let _ = tokio::spawn(run_group.start());
+
+    // The login page uses the sessions table
+
+    let response = reqwest::get(format!("http://{}/login", address))
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn application_should_complete_in_flight_requests_on_shutdown() {
+    let mut config = get_configuration().expect("Failed to get configuration");
+    config.application.port = 0;
+    config.application.test_mode = true;
+
+    // Use a dedicated database: other tests truncate the users
+
+    let database = TestDatabase::new().await;
+
+    // The email server takes a while to respond, which makes POST /test-email slow
+
+    let email_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/emails"))
+        .respond_with(ResponseTemplate::new(200).set_delay(StdDuration::from_secs(1)))
+        .expect(1)
+        .mount(&email_server)
+        .await;
+
+    let mut tem_config = config
+        .tem
+        .clone()
+        .expect("The test configuration should have a TEM configuration");
+    tem_config.base_url = email_server.uri();
+
+    let app = Application::build(
+        &config.application,
+        &config.session,
+        &config.argon2,
+        Some(&tem_config),
+        database.pool.clone(),
+        FaviconCache::new(10),
+        UnreadUpdates::new(16),
+    )
+    .expect("Failed to build application");
+    let address = app.addresses[0];
+
+    let (shutdown_sender, shutdown_recv) = tokio::sync::broadcast::channel(1);
+    let app_task = tokio::spawn(app.run(Shutdown::new(shutdown_recv), StdDuration::from_secs(10)));
+
+    // Login
+
+    let login_body = LoginBody {
+        email: format!("{}@example.com", Uuid::new_v4()),
+        password: "foobarbaz-1234".to_string(),
+    };
+    create_user(
+        &database.pool,
+        &PasswordHasherConfig::try_from(&config.argon2).unwrap(),
+        &UserEmail(login_body.email.clone()),
+        Secret::new(login_body.password.clone()),
+    )
+    .await
+    .expect("Failed to create the user");
+
+    let http_client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .expect("Failed to build HTTP client");
+
+    let response = http_client
+        .post(format!("http://{}/login", address))
+        .form(&login_body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(303, response.status().as_u16());
+
+    let html = http_client
+        .get(format!("http://{}/settings", address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+    let csrf_token = Document::from(html.as_str())
+        .find(Attr("name", "csrf_token"))
+        .next()
+        .and_then(|node| node.attr("value"))
+        .expect("no CSRF token found")
+        .to_string();
+
+    // Shutdown while the slow request is in flight
+
+    let slow_request = tokio::spawn(
+        http_client
+            .post(format!("http://{}/test-email", address))
+            .header("X-CSRF-Token", csrf_token)
+            .send(),
+    );
+
+    tokio::time::sleep(StdDuration::from_millis(200)).await;
+    shutdown_sender.send(()).unwrap();
+
+    let response = slow_request.await.unwrap();
+    let app_result = app_task.await.unwrap();
+
+    // The server doesn't accept new connections anymore
+
+    let new_response = reqwest::get(format!("http://{}/status", address)).await;
+
+    let response = response.expect("The in-flight request should complete");
+    assert_eq!(200, response.status().as_u16());
+    app_result.expect("Failed to run the application");
+    assert!(new_response.is_err());
+}
@@ -0,0 +1,113 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, LoginBody};
+use reqwest::StatusCode;
+use servare::domain::set_user_is_admin;
+use servare::feed::FeedId;
+use servare::job::post_refresh_feed_job;
+use url::Url;
+use wiremock::matchers::path;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn a_job_failing_too_many_times_should_be_moved_to_the_dead_letter_queue() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // The feed can never be fetched
+
+    let mock_server = MockServer::start().await;
+    let feed_url = Url::parse(&format!("{}/feed.xml", mock_server.uri())).unwrap();
+
+    Mock::given(path("/feed.xml"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(5)
+        .mount(&mock_server)
+        .await;
+
+    // Create a feed with a refresh job; the feed has no favicon so no other job is added
+
+    let feed = sqlx::query!(
+        r#"
+        INSERT INTO feeds(user_id, url, title, site_link, description, added_at, has_favicon)
+        VALUES ($1, $2, 'Foo', 'https://example.com', 'Foo', now(), false)
+        RETURNING id
+        "#,
+        &app.test_user.id.0,
+        feed_url.to_string(),
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to insert feed");
+
+    let job_id = post_refresh_feed_job(&app.pool, app.test_user.id, FeedId(feed.id), feed_url)
+        .await
+        .expect("Failed to post the refresh feed job")
+        .expect("The job should be added");
+
+    // Run the job until it fails 5 times, skipping the back off each time
+
+    for _ in 0..5 {
+        sqlx::query!("UPDATE jobs SET next_run_at = now()")
+            .execute(&app.pool)
+            .await
+            .expect("Failed to reset the job back off");
+
+        app.run_single_job_cycle()
+            .await
+            .expect("Failed to run the job cycle");
+    }
+
+    mock_server.verify().await;
+
+    let jobs_count = sqlx::query!(r#"SELECT count(*) AS "count!" FROM jobs"#)
+        .fetch_one(&app.pool)
+        .await
+        .expect("Failed to count the jobs")
+        .count;
+    assert_eq!(0, jobs_count);
+
+    let record = sqlx::query!("SELECT id, attempts, error FROM dead_letter_jobs")
+        .fetch_one(&app.pool)
+        .await
+        .expect("Failed to fetch the dead letter job");
+    assert_eq!(job_id.0, record.id);
+    assert_eq!(5, record.attempts);
+    assert!(record.error.is_some());
+
+    // Only admins can see the dead letter queue
+
+    let response = app.get("/admin/dead-letter").await;
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    set_user_is_admin(&app.pool, app.test_user.id, true)
+        .await
+        .expect("Failed to make the user an admin");
+
+    let response = app.get_html("/admin/dead-letter").await;
+    assert!(response.contains(&job_id.to_string()));
+
+    // Requeue the job
+
+    let response = app
+        .post(&format!("/admin/dead-letter/{}/requeue", job_id), &())
+        .await;
+    assert_is_redirect_to(&response, "/admin/dead-letter");
+
+    let response = app.get_html("/admin/dead-letter").await;
+    assert!(response.contains("Job requeued"));
+    assert!(response.contains("No dead letter jobs"));
+
+    let record = sqlx::query!(r#"SELECT id, attempts, status as "status: String" FROM jobs"#)
+        .fetch_one(&app.pool)
+        .await
+        .expect("Failed to fetch the requeued job");
+    assert_eq!(job_id.0, record.id);
+    assert_eq!(0, record.attempts);
+    assert_eq!("pending", record.status);
+}
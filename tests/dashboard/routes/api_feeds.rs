@@ -0,0 +1,253 @@
+use crate::helpers::{spawn_app, TestApp, TestData};
+use secrecy::ExposeSecret;
+use servare::authentication::create_api_token;
+use wiremock::matchers::path;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Creates a feed with `entries` entries for the test user.
+///
+/// Returns the id of the feed.
+async fn create_feed_with_entries(app: &TestApp, entries: usize) -> i64 {
+    let feed = sqlx::query!(
+        r#"
+        INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+        VALUES ($1, 'https://example.com/feed.xml', 'Foo', 'https://example.com', 'Foo', now())
+        RETURNING id
+        "#,
+        &app.test_user.id.0,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to insert feed");
+
+    for i in 0..entries {
+        sqlx::query!(
+            r#"
+            INSERT INTO feed_entries(feed_id, external_id, title, url, created_at, summary, authors)
+            VALUES ($1, $2, $3, $4, now(), 'Hello world', '["Vincent"]')
+            "#,
+            feed.id,
+            format!("entry-{}", i),
+            format!("Entry {}", i),
+            format!("https://example.com/entry-{}", i),
+        )
+        .execute(&app.pool)
+        .await
+        .expect("Failed to insert feed entry");
+    }
+
+    feed.id
+}
+
+async fn get_json(app: &TestApp, path: &str) -> serde_json::Value {
+    let token = create_api_token(&app.pool, app.test_user.id, "test")
+        .await
+        .expect("Failed to create API token");
+
+    let response = app
+        .http_client
+        .get(&format!("{}{}", app.address, path))
+        .bearer_auth(token.expose_secret())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+
+    response.json().await.expect("Failed to parse JSON")
+}
+
+/// Sends a POST request with the JSON `body` to `path`, authenticated with a new API token.
+async fn post_json(app: &TestApp, path: &str, body: &serde_json::Value) -> reqwest::Response {
+    let token = create_api_token(&app.pool, app.test_user.id, "test")
+        .await
+        .expect("Failed to create API token");
+
+    app.http_client
+        .post(&format!("{}{}", app.address, path))
+        .bearer_auth(token.expose_secret())
+        .json(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+/// Asserts that `response` is an API error with the status `status` and the code `code`.
+async fn assert_is_api_error(response: reqwest::Response, status: u16, code: &str) {
+    assert_eq!(status, response.status().as_u16());
+
+    let body: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    assert_eq!(code, body["code"]);
+    assert!(body["error"].is_string());
+}
+
+fn entry_titles(body: &serde_json::Value) -> Vec<&str> {
+    body["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["title"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn api_feed_entries_should_return_the_first_page() {
+    let app = spawn_app().await;
+    let feed_id = create_feed_with_entries(&app, 3).await;
+
+    let body = get_json(&app, &format!("/api/v1/feeds/{}/entries?limit=2", feed_id)).await;
+
+    assert_eq!(vec!["Entry 2", "Entry 1"], entry_titles(&body));
+    assert!(body["next_cursor"].is_string());
+
+    let entry = &body["entries"][0];
+    assert_eq!("entry-2", entry["external_id"]);
+    assert_eq!("https://example.com/entry-2", entry["url"]);
+    assert_eq!("Hello world", entry["summary"]);
+    assert_eq!(serde_json::json!(["Vincent"]), entry["authors"]);
+    assert!(entry["published_at"].is_string());
+    assert!(entry["read_at"].is_null());
+}
+
+#[tokio::test]
+async fn api_feed_entries_should_return_the_next_page() {
+    let app = spawn_app().await;
+    let feed_id = create_feed_with_entries(&app, 3).await;
+
+    let body = get_json(&app, &format!("/api/v1/feeds/{}/entries?limit=2", feed_id)).await;
+    let cursor = body["next_cursor"].as_str().unwrap();
+
+    let body = get_json(
+        &app,
+        &format!(
+            "/api/v1/feeds/{}/entries?limit=2&cursor={}",
+            feed_id, cursor
+        ),
+    )
+    .await;
+
+    assert_eq!(vec!["Entry 0"], entry_titles(&body));
+    assert!(body["next_cursor"].is_null());
+}
+
+#[tokio::test]
+async fn api_feed_entries_should_work_with_an_empty_feed() {
+    let app = spawn_app().await;
+    let feed_id = create_feed_with_entries(&app, 0).await;
+
+    let body = get_json(&app, &format!("/api/v1/feeds/{}/entries", feed_id)).await;
+
+    assert!(entry_titles(&body).is_empty());
+    assert!(body["next_cursor"].is_null());
+}
+
+#[tokio::test]
+async fn api_feed_entries_should_require_an_api_token() {
+    let app = spawn_app().await;
+    let feed_id = create_feed_with_entries(&app, 1).await;
+
+    let response = app.get(&format!("/api/v1/feeds/{}/entries", feed_id)).await;
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn api_feed_entries_should_return_not_found_for_an_unknown_feed() {
+    let app = spawn_app().await;
+
+    let token = create_api_token(&app.pool, app.test_user.id, "test")
+        .await
+        .expect("Failed to create API token");
+
+    let response = app
+        .http_client
+        .get(&format!("{}/api/v1/feeds/{}/entries", app.address, 2000))
+        .bearer_auth(token.expose_secret())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_is_api_error(response, 404, "feed_not_found").await;
+}
+
+#[tokio::test]
+async fn api_feeds_should_return_the_feeds() {
+    let app = spawn_app().await;
+    let feed_id = create_feed_with_entries(&app, 1).await;
+
+    let body = get_json(&app, "/api/v1/feeds").await;
+
+    let feeds = body.as_array().unwrap();
+    assert_eq!(1, feeds.len());
+    assert_eq!(feed_id, feeds[0]["id"]);
+    assert_eq!("Foo", feeds[0]["title"]);
+    assert_eq!("https://example.com/feed.xml", feeds[0]["url"]);
+    assert_eq!("https://example.com/", feeds[0]["site_link"]);
+    assert!(feeds[0]["added_at"].is_string());
+}
+
+#[tokio::test]
+async fn api_feeds_should_require_an_api_token() {
+    let app = spawn_app().await;
+
+    let response = app.get("/api/v1/feeds").await;
+    assert_is_api_error(response, 401, "unauthorized").await;
+}
+
+#[tokio::test]
+async fn api_feeds_add_should_add_the_feed() {
+    let app = spawn_app().await;
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(path("/feed"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            TestData::get("tailscale_rss_feed.xml").unwrap().data,
+            "application/xml",
+        ))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let body = serde_json::json!({ "url": format!("{}/feed", mock_server.uri()) });
+
+    // Add the feed
+
+    let response = post_json(&app, "/api/v1/feeds", &body).await;
+    assert_eq!(201, response.status().as_u16());
+
+    let response_body: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    let feed_id = response_body["id"].as_i64().unwrap();
+
+    let feeds = get_json(&app, "/api/v1/feeds").await;
+    assert_eq!(feed_id, feeds[0]["id"]);
+    assert_eq!("Blog on Tailscale", feeds[0]["title"]);
+
+    // Adding it again is a conflict
+
+    let response = post_json(&app, "/api/v1/feeds", &body).await;
+    assert_is_api_error(response, 409, "feed_already_exists").await;
+
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn api_feeds_add_should_reject_an_invalid_body() {
+    let app = spawn_app().await;
+
+    let response = post_json(&app, "/api/v1/feeds", &serde_json::json!({ "foo": "bar" })).await;
+    assert_is_api_error(response, 400, "invalid_body").await;
+}
+
+#[tokio::test]
+async fn api_feeds_add_should_require_an_api_token() {
+    let app = spawn_app().await;
+
+    let response = app
+        .http_client
+        .post(&format!("{}/api/v1/feeds", app.address))
+        .json(&serde_json::json!({ "url": "https://example.com/feed.xml" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_is_api_error(response, 401, "unauthorized").await;
+}
@@ -0,0 +1,235 @@
+use crate::helpers::LoginBody;
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use secrecy::ExposeSecret;
+use select::document::Document;
+use select::predicate::Class;
+use servare::authentication::create_api_token;
+
+#[tokio::test]
+async fn settings_page_should_work_with_a_session() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Fetch the settings page
+    let response = app.get("/settings").await;
+    assert_eq!(200, response.status().as_u16());
+
+    let body = response.text().await.unwrap();
+    assert!(body.contains("Settings"));
+}
+
+#[tokio::test]
+async fn settings_page_should_work_with_an_api_token() {
+    // Setup, create a token
+    let app = spawn_app().await;
+
+    let token = create_api_token(&app.pool, app.test_user.id, "test")
+        .await
+        .expect("Failed to create API token");
+
+    // Fetch the settings page
+    let response = app
+        .http_client
+        .get(&format!("{}/settings", app.address))
+        .bearer_auth(token.expose_secret())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+
+    // Token authentication must not create a session
+    assert!(response
+        .cookies()
+        .all(|cookie| cookie.name() != "session_id"));
+
+    let body = response.text().await.unwrap();
+    assert!(body.contains("Settings"));
+}
+
+#[tokio::test]
+async fn settings_page_should_reject_an_invalid_api_token() {
+    // Setup
+    let app = spawn_app().await;
+
+    // Fetch the settings page
+    let response = app
+        .http_client
+        .get(&format!("{}/settings", app.address))
+        .bearer_auth("foobar")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[derive(serde::Serialize)]
+struct ApiTokenBody {
+    name: String,
+}
+
+#[tokio::test]
+async fn api_tokens_should_be_created_and_revoked_in_the_settings() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Create a token, it's shown only once
+
+    let body = ApiTokenBody {
+        name: "scripts".to_string(),
+    };
+    let response = app.post("/settings/tokens", &body).await;
+    assert_eq!(200, response.status().as_u16());
+
+    let html = response.text().await.unwrap();
+    let document = Document::from_read(html.as_bytes()).unwrap();
+    let token = document
+        .find(Class("api-token-value"))
+        .next()
+        .expect("The page should contain the API token")
+        .text();
+
+    let html = app.get_html("/settings").await;
+    assert!(html.contains("scripts"));
+    assert!(!html.contains(&token));
+
+    // Use it
+
+    let get_feeds = || {
+        app.http_client
+            .get(&format!("{}/api/v1/feeds", app.address))
+            .bearer_auth(&token)
+            .send()
+    };
+
+    let response = get_feeds().await.expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+
+    // Revoke it
+
+    let record = sqlx::query!(
+        "SELECT id FROM api_tokens WHERE user_id = $1 AND name = 'scripts'",
+        &app.test_user.id.0,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to fetch the API token");
+
+    let response = app.delete(&format!("/settings/tokens/{}", record.id)).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    let html = app.get_html("/settings").await;
+    assert!(html.contains("API token revoked"));
+
+    let response = get_feeds().await.expect("Failed to execute request.");
+    assert_eq!(401, response.status().as_u16());
+
+    // Revoking it again fails
+
+    let response = app.delete(&format!("/settings/tokens/{}", record.id)).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    let html = app.get_html("/settings").await;
+    assert!(html.contains("API token not found"));
+}
+
+#[tokio::test]
+async fn api_tokens_should_have_a_name() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    let body = ApiTokenBody {
+        name: "  ".to_string(),
+    };
+    let response = app.post("/settings/tokens", &body).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    let html = app.get_html("/settings").await;
+    assert!(html.contains("API token name is empty"));
+}
+
+#[tokio::test]
+async fn api_tokens_should_not_manage_credentials_or_the_account() {
+    // Setup, create a token
+    let app = spawn_app().await;
+
+    let token = create_api_token(&app.pool, app.test_user.id, "test")
+        .await
+        .expect("Failed to create API token");
+
+    // Try to create another token, change the email and delete the account
+
+    let response = app
+        .http_client
+        .post(&format!("{}/settings/tokens", app.address))
+        .bearer_auth(token.expose_secret())
+        .form(&ApiTokenBody {
+            name: "stolen".to_string(),
+        })
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(403, response.status().as_u16());
+
+    let response = app
+        .http_client
+        .post(&format!("{}/settings/email", app.address))
+        .bearer_auth(token.expose_secret())
+        .form(&[
+            ("new_email", "attacker@example.com"),
+            ("password", app.test_user.password.as_str()),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(403, response.status().as_u16());
+
+    let response = app
+        .http_client
+        .delete(&format!("{}/settings/account", app.address))
+        .bearer_auth(token.expose_secret())
+        .form(&[("password", app.test_user.password.as_str())])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(403, response.status().as_u16());
+
+    // Nothing changed
+
+    let record = sqlx::query!(
+        r#"
+        SELECT
+          (SELECT count(*) FROM api_tokens WHERE user_id = $1) AS "tokens!",
+          (SELECT count(*) FROM pending_email_changes WHERE user_id = $1) AS "email_changes!",
+          (SELECT count(*) FROM users WHERE id = $1) AS "users!"
+        "#,
+        &app.test_user.id.0,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to fetch the user data");
+
+    assert_eq!(1, record.tokens);
+    assert_eq!(0, record.email_changes);
+    assert_eq!(1, record.users);
+}
@@ -0,0 +1,54 @@
+use crate::helpers::LoginBody;
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn test_email_should_be_sent_to_the_user() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    Mock::given(method("POST"))
+        .and(path("/emails"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Send the test email
+
+    let response = app.post("/test-email", &()).await;
+    assert_eq!(200, response.status().as_u16());
+
+    // The email should be sent to the user
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(
+        Some(app.test_user.email.as_str()),
+        body["to"][0]["email"].as_str()
+    );
+}
+
+#[tokio::test]
+async fn test_email_should_redirect_if_not_logged_in() {
+    // Setup
+    let app = spawn_app().await;
+
+    Mock::given(method("POST"))
+        .and(path("/emails"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post("/test-email", &()).await;
+    assert_is_redirect_to(&response, "/login");
+}
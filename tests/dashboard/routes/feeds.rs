@@ -1,8 +1,11 @@
 use crate::helpers::{assert_is_redirect_to, spawn_app};
 use crate::helpers::{LoginBody, TestData};
+use futures_util::StreamExt;
 use select::document::Document;
-use select::predicate::Class;
+use select::predicate::{self, Class, Name, Predicate};
 use serde::Serialize;
+use servare::feed::FeedId;
+use servare::job::{cancel_job, post_refresh_feed_job};
 use url::Url;
 use wiremock::matchers::path;
 use wiremock::{Mock, MockServer, ResponseTemplate};
@@ -127,3 +130,1488 @@ async fn adding_a_feed_url_without_scheme_should_work() {
     let feed_cards = document.find(Class("feed-card")).count();
     assert_eq!(1, feed_cards);
 }
+
+#[tokio::test]
+async fn feed_entries_should_be_stored_after_running_the_jobs() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Setup a mock server that responds with a test XML feed on /feed.
+    // The site link of the feed points to the mock server itself so that the favicon job doesn't
+    // hit the network.
+
+    let mock_server = MockServer::start().await;
+    let mock_uri = mock_server.uri();
+
+    let feed_data = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<rss version="2.0">
+<channel>
+<title>Foo</title>
+<link>{mock_uri}/</link>
+<description>Foo</description>
+<item>
+<title>Hello</title>
+<link>{mock_uri}/hello</link>
+<guid>{mock_uri}/hello</guid>
+<description>Hello world</description>
+</item>
+</channel>
+</rss>"#
+    );
+
+    Mock::given(path("/feed"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(feed_data, "application/xml"))
+        .mount(&mock_server)
+        .await;
+
+    // Add the feed
+
+    let body = AddFeedBody {
+        url: format!("{}/feed", mock_uri),
+    };
+
+    let response = app.post("/feeds/add", &body).await;
+    assert_is_redirect_to(&response, "/feeds");
+
+    // Run the jobs: adding a feed posts both a fetch favicon job and a refresh feed job,
+    // and a single cycle runs only one job.
+
+    for _ in 0..2 {
+        app.run_single_job_cycle()
+            .await
+            .expect("Failed to run a job cycle");
+    }
+
+    // Check the entries were stored
+
+    let record = sqlx::query!(
+        r#"
+        SELECT COUNT(fe.id) AS "count!"
+        FROM feed_entries fe
+        INNER JOIN feeds f ON f.id = fe.feed_id
+        WHERE f.user_id = $1
+        "#,
+        &app.test_user.id.0,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to count the feed entries");
+
+    assert_eq!(1, record.count);
+}
+
+#[tokio::test]
+async fn adding_a_feed_linked_from_another_site_should_work() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Setup two mock servers:
+    // * the first one responds with a basic HTML page containing a link to a feed on the second one
+    // * the second one responds with a test XML feed on /feed
+
+    let site_server = MockServer::start().await;
+    let feed_server = MockServer::start().await;
+
+    Mock::given(path("/feed"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            TestData::get("tailscale_rss_feed.xml").unwrap().data,
+            "application/xml",
+        ))
+        .expect(1)
+        .mount(&feed_server)
+        .await;
+
+    let html = format!(
+        r#"<link type="application/rss+xml" href="{}/feed">"#,
+        feed_server.uri()
+    );
+
+    Mock::given(path("/"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(html, "text/html"))
+        .expect(1)
+        .mount(&site_server)
+        .await;
+
+    // Add the feed
+
+    let body = AddFeedBody {
+        url: site_server.uri(),
+    };
+
+    let response = app.post("/feeds/add", &body).await;
+    assert_is_redirect_to(&response, "/feeds");
+
+    // Fetch the feeds page and check the content
+
+    let response = app.get_html("/feeds").await;
+    assert!(response.contains("Found a feed"));
+
+    let document = Document::from_read(response.as_bytes()).unwrap();
+    let feed_cards = document.find(Class("feed-card")).count();
+    assert_eq!(1, feed_cards);
+}
+
+#[tokio::test]
+async fn adding_a_site_linking_to_multiple_feeds_should_let_the_user_choose() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Setup two mock servers:
+    // * the first one responds with a basic HTML page containing links to two feeds on the second one
+    // * the second one responds with a test XML feed on /rss and an Atom feed on /atom
+    //
+    // Both feeds are fetched once by the choose page, adding the feed uses the discovery cache
+
+    let site_server = MockServer::start().await;
+    let feed_server = MockServer::start().await;
+
+    Mock::given(path("/rss"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            TestData::get("tailscale_rss_feed.xml").unwrap().data,
+            "application/xml",
+        ))
+        .expect(1)
+        .mount(&feed_server)
+        .await;
+    Mock::given(path("/atom"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            r#"<?xml version="1.0" encoding="utf-8"?><feed xmlns="http://www.w3.org/2005/Atom"><title>Atom feed</title><id>urn:uuid:60a76c80-d399-11d9-b93C-0003939e0af6</id><updated>2023-03-15T10:00:00Z</updated></feed>"#,
+            "application/atom+xml",
+        ))
+        .expect(1)
+        .mount(&feed_server)
+        .await;
+
+    let html = format!(
+        r#"<link type="application/rss+xml" href="{0}/rss"><link type="application/atom+xml" href="{0}/atom">"#,
+        feed_server.uri()
+    );
+
+    // The site is fetched once, the choose page uses the discovery cache
+    Mock::given(path("/"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(html, "text/html"))
+        .expect(1)
+        .mount(&site_server)
+        .await;
+
+    // Adding the site redirects to the choose page
+
+    let body = AddFeedBody {
+        url: site_server.uri(),
+    };
+
+    let response = app.post("/feeds/add", &body).await;
+
+    let site_url = Url::parse(&site_server.uri()).unwrap();
+    let query = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("url", site_url.as_str())
+        .finish();
+    let choose_path = format!("/feeds/add/choose?{}", query);
+    assert_is_redirect_to(&response, &choose_path);
+
+    // The choose page lists both feeds with their titles
+
+    let response = app.get_html(&choose_path).await;
+    let document = Document::from_read(response.as_bytes()).unwrap();
+
+    let options: Vec<String> = document
+        .find(Name("input").and(predicate::Attr("type", "radio")))
+        .filter_map(|input| input.attr("value"))
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        vec![
+            format!("{}/rss", feed_server.uri()),
+            format!("{}/atom", feed_server.uri()),
+        ],
+        options
+    );
+
+    let titles: Vec<String> = document
+        .find(Class("feed-add-choose").descendant(Class("feed-title")))
+        .map(|node| node.text())
+        .collect();
+    assert_eq!(vec!["Blog on Tailscale", "Atom feed"], titles);
+
+    // Nothing was added yet
+
+    let response = app.get_html("/feeds").await;
+    let document = Document::from_read(response.as_bytes()).unwrap();
+    assert_eq!(0, document.find(Class("feed-card")).count());
+
+    // Choose the first feed
+
+    let body = AddFeedBody {
+        url: options[0].clone(),
+    };
+
+    let response = app.post("/feeds/add", &body).await;
+    assert_is_redirect_to(&response, "/feeds");
+
+    let response = app.get_html("/feeds").await;
+    assert!(response.contains("Found a feed"));
+
+    let document = Document::from_read(response.as_bytes()).unwrap();
+    let feed_cards = document.find(Class("feed-card")).count();
+    assert_eq!(1, feed_cards);
+}
+
+#[tokio::test]
+async fn unread_entries_should_be_exported_as_markdown() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Setup a mock server that responds with a test XML feed on /feed.
+
+    let mock_server = MockServer::start().await;
+    let mock_uri = mock_server.uri();
+
+    let feed_data = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<rss version="2.0">
+<channel>
+<title>Foo</title>
+<link>{mock_uri}/</link>
+<description>Foo</description>
+<item>
+<title>Hello</title>
+<link>{mock_uri}/hello</link>
+<guid>{mock_uri}/hello</guid>
+<description>&lt;p&gt;Hello &lt;b&gt;world&lt;/b&gt;&lt;/p&gt;</description>
+</item>
+<item>
+<title>Goodbye</title>
+<link>{mock_uri}/goodbye</link>
+<guid>{mock_uri}/goodbye</guid>
+<description>Goodbye world</description>
+</item>
+</channel>
+</rss>"#
+    );
+
+    Mock::given(path("/feed"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(feed_data, "application/xml"))
+        .mount(&mock_server)
+        .await;
+
+    // Add the feed and run the jobs to store the entries
+
+    let body = AddFeedBody {
+        url: format!("{}/feed", mock_uri),
+    };
+
+    let response = app.post("/feeds/add", &body).await;
+    assert_is_redirect_to(&response, "/feeds");
+
+    for _ in 0..2 {
+        app.run_single_job_cycle()
+            .await
+            .expect("Failed to run a job cycle");
+    }
+
+    // Export
+
+    let response = app.get("/feeds/export/unread.md").await;
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(
+        Some("attachment; filename=\"unread.md\""),
+        response
+            .headers()
+            .get("Content-Disposition")
+            .and_then(|v| v.to_str().ok())
+    );
+
+    let content = response.text().await.unwrap();
+    assert!(content.contains("## Hello"));
+    assert!(content.contains("## Goodbye"));
+    assert!(content.contains("Hello world"));
+    assert!(content.contains(&format!("[Read original]({}/hello)", mock_uri)));
+}
+
+#[tokio::test]
+async fn concurrent_feeds_refresh_should_add_one_job_per_feed() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Create some feeds
+
+    for i in 0..3 {
+        sqlx::query!(
+            r#"
+            INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+            VALUES ($1, $2, 'Foo', 'https://example.com', 'Foo', now())
+            "#,
+            &app.test_user.id.0,
+            format!("https://example.com/feed-{}.xml", i),
+        )
+        .execute(&app.pool)
+        .await
+        .expect("Failed to insert feed");
+    }
+
+    // Refresh concurrently
+
+    let (response1, response2, response3) = tokio::join!(
+        app.post("/feeds/refresh", &()),
+        app.post("/feeds/refresh", &()),
+        app.post("/feeds/refresh", &()),
+    );
+    assert_is_redirect_to(&response1, "/feeds");
+    assert_is_redirect_to(&response2, "/feeds");
+    assert_is_redirect_to(&response3, "/feeds");
+
+    // Check there's exactly one pending refresh job per feed
+
+    let records = sqlx::query!(
+        r#"
+        SELECT data->>'feed_id' AS "feed_id!", COUNT(*) AS "count!"
+        FROM jobs
+        WHERE status = 'pending'
+          AND data->>'type' = 'RefreshFeed'
+          AND data->>'user_id' = $1
+        GROUP BY data->>'feed_id'
+        "#,
+        app.test_user.id.0.to_string(),
+    )
+    .fetch_all(&app.pool)
+    .await
+    .expect("Failed to count the refresh jobs");
+
+    assert_eq!(3, records.len());
+    for record in records {
+        assert_eq!(
+            1, record.count,
+            "feed {} has duplicate jobs",
+            record.feed_id
+        );
+    }
+}
+
+// The WebSocket client needs to run on the actix runtime
+#[actix_web::test]
+async fn websocket_should_receive_unread_updates_after_running_the_jobs() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    let session_cookie = login_response
+        .cookies()
+        .find(|cookie| cookie.name() == "session_id")
+        .expect("No session cookie");
+
+    // Connect to the WebSocket endpoint using the session cookie
+
+    let (_, mut ws) = awc::Client::new()
+        .ws(format!("ws://127.0.0.1:{}/ws", app.port))
+        // The cookie value is already encoded, awc would encode it again
+        .set_header(
+            awc::http::header::COOKIE,
+            format!("{}={}", session_cookie.name(), session_cookie.value()),
+        )
+        .connect()
+        .await
+        .expect("Failed to connect to the WebSocket");
+
+    // Setup a mock server that responds with a test XML feed on /feed.
+
+    let mock_server = MockServer::start().await;
+    let mock_uri = mock_server.uri();
+
+    let feed_data = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<rss version="2.0">
+<channel>
+<title>Foo</title>
+<link>{mock_uri}/</link>
+<description>Foo</description>
+<item>
+<title>Hello</title>
+<link>{mock_uri}/hello</link>
+<guid>{mock_uri}/hello</guid>
+<description>Hello world</description>
+</item>
+</channel>
+</rss>"#
+    );
+
+    Mock::given(path("/feed"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(feed_data, "application/xml"))
+        .mount(&mock_server)
+        .await;
+
+    // Add the feed and run the jobs
+
+    let body = AddFeedBody {
+        url: format!("{}/feed", mock_uri),
+    };
+
+    let response = app.post("/feeds/add", &body).await;
+    assert_is_redirect_to(&response, "/feeds");
+
+    for _ in 0..2 {
+        app.run_single_job_cycle()
+            .await
+            .expect("Failed to run a job cycle");
+    }
+
+    // Check the update was received
+
+    let frame = tokio::time::timeout(std::time::Duration::from_secs(5), ws.next())
+        .await
+        .expect("Timed out waiting for a WebSocket message")
+        .expect("WebSocket closed")
+        .expect("Failed to read a WebSocket frame");
+
+    match frame {
+        awc::ws::Frame::Text(data) => {
+            let message: serde_json::Value = serde_json::from_slice(&data).unwrap();
+            assert_eq!(serde_json::json!({ "unread_delta": 1 }), message);
+        }
+        frame => panic!("expected a text frame, got {:?}", frame),
+    }
+}
+
+// The WebSocket client needs to run on the actix runtime
+#[actix_web::test]
+async fn websocket_from_another_origin_should_be_rejected() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    let session_cookie = login_response
+        .cookies()
+        .find(|cookie| cookie.name() == "session_id")
+        .expect("No session cookie");
+
+    // Connect to the WebSocket endpoint from another site
+
+    let result = awc::Client::new()
+        .ws(format!("ws://127.0.0.1:{}/ws", app.port))
+        .origin("https://evil.example")
+        // The cookie value is already encoded, awc would encode it again
+        .set_header(
+            awc::http::header::COOKIE,
+            format!("{}={}", session_cookie.name(), session_cookie.value()),
+        )
+        .connect()
+        .await;
+
+    match result {
+        Err(awc::error::WsClientError::InvalidResponseStatus(status)) => {
+            assert_eq!(awc::http::StatusCode::FORBIDDEN, status)
+        }
+        Err(err) => panic!("expected a forbidden response, got {:?}", err),
+        Ok(_) => panic!("the WebSocket should have been rejected"),
+    }
+}
+
+#[derive(Serialize)]
+struct PreferencesBody {
+    feed_order: String,
+    auto_mark_read_on_view: bool,
+}
+
+/// Views the entries of a new feed with 3 unread entries, with the auto mark read on view
+/// preference set to `auto_mark_read_on_view`.
+///
+/// Returns the number of unread entries after the view.
+async fn count_unread_entries_after_view(auto_mark_read_on_view: bool) -> i64 {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Save the preference
+
+    let body = PreferencesBody {
+        feed_order: "added_at:desc".to_string(),
+        auto_mark_read_on_view,
+    };
+    let response = app.post("/settings/preferences", &body).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    // Create a feed with some entries
+
+    let feed = sqlx::query!(
+        r#"
+        INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+        VALUES ($1, 'https://example.com/feed.xml', 'Foo', 'https://example.com', 'Foo', now())
+        RETURNING id
+        "#,
+        &app.test_user.id.0,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to insert feed");
+
+    for i in 0..3 {
+        sqlx::query!(
+            r#"
+            INSERT INTO feed_entries(feed_id, external_id, title, created_at, summary)
+            VALUES ($1, $2, $3, now(), 'Hello world')
+            "#,
+            feed.id,
+            format!("entry-{}", i),
+            format!("Entry {}", i),
+        )
+        .execute(&app.pool)
+        .await
+        .expect("Failed to insert feed entry");
+    }
+
+    // View the entries
+
+    let response = app.get(&format!("/feeds/{}/entries", feed.id)).await;
+    assert_eq!(200, response.status().as_u16());
+
+    let record = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!" FROM feed_entries
+        WHERE feed_id = $1 AND read_at IS NULL
+        "#,
+        feed.id,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to count the unread entries");
+
+    record.count
+}
+
+#[tokio::test]
+async fn viewing_feed_entries_should_mark_them_as_read_when_auto_read_is_enabled() {
+    let unread = count_unread_entries_after_view(true).await;
+    assert_eq!(0, unread);
+}
+
+#[tokio::test]
+async fn viewing_feed_entries_should_not_mark_them_as_read_when_auto_read_is_disabled() {
+    let unread = count_unread_entries_after_view(false).await;
+    assert_eq!(3, unread);
+}
+
+#[tokio::test]
+async fn feed_favicon_should_be_served_with_its_mime_type() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Create a feed with a SVG favicon
+
+    const SVG: &[u8] = br#"<svg xmlns="http://www.w3.org/2000/svg"></svg>"#;
+
+    let feed = sqlx::query!(
+        r#"
+        INSERT INTO feeds(user_id, url, title, site_link, description, added_at, site_favicon, site_favicon_mime, has_favicon)
+        VALUES ($1, 'https://example.com/feed.xml', 'Foo', 'https://example.com', 'Foo', now(), $2, 'image/svg+xml', true)
+        RETURNING id
+        "#,
+        &app.test_user.id.0,
+        SVG,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to insert feed");
+
+    // Fetch the favicon
+
+    let response = app.get(&format!("/feeds/{}/favicon", feed.id)).await;
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(
+        "image/svg+xml",
+        response.headers().get("Content-Type").unwrap()
+    );
+
+    // A SVG favicon can contain scripts, none must run
+    assert_eq!(
+        "default-src 'none'; style-src 'unsafe-inline'; sandbox",
+        response.headers().get("Content-Security-Policy").unwrap()
+    );
+    assert_eq!(
+        "nosniff",
+        response.headers().get("X-Content-Type-Options").unwrap()
+    );
+
+    assert_eq!(SVG, &response.bytes().await.unwrap()[..]);
+}
+
+#[tokio::test]
+async fn adding_a_feed_should_be_atomic() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Setup a mock server that responds with a test XML feed on /feed
+
+    let mock_server = MockServer::start().await;
+    let mock_uri = mock_server.uri();
+
+    Mock::given(path("/feed"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            TestData::get("tailscale_rss_feed.xml").unwrap().data,
+            "application/xml",
+        ))
+        .mount(&mock_server)
+        .await;
+
+    // Make posting the refresh feed job of the test user fail, after the feed is inserted.
+    // The trigger only affects the test user so that concurrent tests are not affected.
+
+    let suffix = app.test_user.id.0.simple().to_string();
+    let function_name = format!("fail_refresh_feed_job_{}", suffix);
+    let trigger_name = format!("fail_refresh_feed_job_trigger_{}", suffix);
+
+    sqlx::query(&format!(
+        r#"
+        CREATE FUNCTION {function_name}() RETURNS trigger AS $$
+        BEGIN
+            IF NEW.data->>'type' = 'RefreshFeed' AND NEW.data->>'user_id' = '{user_id}' THEN
+                RAISE EXCEPTION 'refresh feed job failure';
+            END IF;
+            RETURN NEW;
+        END;
+        $$ LANGUAGE plpgsql
+        "#,
+        function_name = function_name,
+        user_id = app.test_user.id,
+    ))
+    .execute(&app.pool)
+    .await
+    .expect("Failed to create the trigger function");
+
+    sqlx::query(&format!(
+        "CREATE TRIGGER {} BEFORE INSERT ON jobs FOR EACH ROW EXECUTE FUNCTION {}()",
+        trigger_name, function_name,
+    ))
+    .execute(&app.pool)
+    .await
+    .expect("Failed to create the trigger");
+
+    // Add the feed
+
+    let body = AddFeedBody {
+        url: format!("{}/feed", mock_uri),
+    };
+    let response = app.post("/feeds/add", &body).await;
+
+    sqlx::query(&format!("DROP TRIGGER {} ON jobs", trigger_name))
+        .execute(&app.pool)
+        .await
+        .expect("Failed to drop the trigger");
+    sqlx::query(&format!("DROP FUNCTION {}", function_name))
+        .execute(&app.pool)
+        .await
+        .expect("Failed to drop the trigger function");
+
+    assert_is_redirect_to(&response, "/feeds");
+
+    let response = app.get_html("/feeds").await;
+    assert!(response.contains("Something went wrong"));
+
+    // Check nothing was stored
+
+    let record = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM feeds WHERE user_id = $1) AS "feeds!",
+            (SELECT COUNT(*) FROM jobs WHERE data->>'user_id' = $2) AS "jobs!"
+        "#,
+        &app.test_user.id.0,
+        app.test_user.id.to_string(),
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to count the feeds and jobs");
+
+    assert_eq!(0, record.feeds);
+    assert_eq!(0, record.jobs);
+}
+
+#[tokio::test]
+async fn adding_the_same_feed_twice_should_fetch_it_once() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Setup a mock server that responds with a test XML feed on /feed, exactly once
+
+    let mock_server = MockServer::start().await;
+    let mock_uri = mock_server.uri();
+
+    Mock::given(path("/feed"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            TestData::get("tailscale_rss_feed.xml").unwrap().data,
+            "application/xml",
+        ))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    // Add the feed twice, the second time the data comes from the discovery cache
+
+    let body = AddFeedBody {
+        url: format!("{}/feed", mock_uri),
+    };
+
+    let response = app.post("/feeds/add", &body).await;
+    assert_is_redirect_to(&response, "/feeds");
+    let response = app.get_html("/feeds").await;
+    assert!(response.contains("Found a feed"));
+
+    let response = app.post("/feeds/add", &body).await;
+    assert_is_redirect_to(&response, "/feeds");
+    let response = app.get_html("/feeds").await;
+    assert!(response.contains("Feed already exists"));
+
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn adding_equivalent_feed_urls_should_be_detected_as_a_duplicate() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Setup a mock server that responds with a test XML feed at its root
+
+    let mock_server = MockServer::start().await;
+    let mock_uri = mock_server.uri();
+
+    Mock::given(path("/"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            TestData::get("tailscale_rss_feed.xml").unwrap().data,
+            "application/xml",
+        ))
+        .mount(&mock_server)
+        .await;
+
+    // Add the feed without a trailing slash
+
+    let body = AddFeedBody {
+        url: mock_uri.clone(),
+    };
+    let response = app.post("/feeds/add", &body).await;
+    assert_is_redirect_to(&response, "/feeds");
+    let response = app.get_html("/feeds").await;
+    assert!(response.contains("Found a feed"));
+
+    // Then with a trailing slash, and with a trailing slash and an empty query
+
+    for url in [format!("{}/", mock_uri), format!("{}/?", mock_uri)] {
+        let body = AddFeedBody { url };
+        let response = app.post("/feeds/add", &body).await;
+        assert_is_redirect_to(&response, "/feeds");
+        let response = app.get_html("/feeds").await;
+        assert!(response.contains("Feed already exists"));
+    }
+
+    let feeds = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM feeds WHERE user_id = $1"#,
+        &app.test_user.id.0,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to count the feeds")
+    .count;
+    assert_eq!(1, feeds);
+}
+
+#[derive(Serialize)]
+struct FeedCategoryBody {
+    pub category: String,
+}
+
+#[tokio::test]
+async fn feeds_should_be_grouped_by_their_category() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Create two feeds
+
+    let mut feed_ids = Vec::new();
+    for i in 0..2 {
+        let feed = sqlx::query!(
+            r#"
+            INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+            VALUES ($1, $2, 'Foo', 'https://example.com', 'Foo', now())
+            RETURNING id
+            "#,
+            &app.test_user.id.0,
+            format!("https://example.com/feed{}.xml", i),
+        )
+        .fetch_one(&app.pool)
+        .await
+        .expect("Failed to insert feed");
+
+        feed_ids.push(feed.id);
+    }
+
+    // Assign a category to the first feed
+
+    let body = FeedCategoryBody {
+        category: "Tech".to_string(),
+    };
+    let response = app
+        .post(&format!("/feeds/{}/category", feed_ids[0]), &body)
+        .await;
+    assert_is_redirect_to(&response, "/feeds");
+
+    let record = sqlx::query!(
+        r#"
+        SELECT c.name FROM feeds f
+        INNER JOIN categories c ON c.id = f.category_id
+        WHERE f.id = $1
+        "#,
+        feed_ids[0],
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to fetch the feed category");
+    assert_eq!("Tech", record.name);
+
+    // The feeds page should have one group per category
+
+    let response = app.get_html("/feeds").await;
+
+    let document = Document::from_read(response.as_bytes()).unwrap();
+    let category_names = document
+        .find(Class("category-name"))
+        .map(|node| node.text())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["Tech", "Uncategorized"], category_names);
+    assert_eq!(2, document.find(Class("feed-group")).count());
+
+    // Removing the category should remove the group
+
+    let body = FeedCategoryBody {
+        category: "".to_string(),
+    };
+    let response = app
+        .post(&format!("/feeds/{}/category", feed_ids[0]), &body)
+        .await;
+    assert_is_redirect_to(&response, "/feeds");
+
+    let response = app.get_html("/feeds").await;
+
+    let document = Document::from_read(response.as_bytes()).unwrap();
+    assert_eq!(1, document.find(Class("feed-group")).count());
+    assert_eq!(0, document.find(Class("category-name")).count());
+}
+
+#[tokio::test]
+async fn feeds_with_an_invalid_site_link_should_link_to_the_feed_url() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Create a feed with an invalid site link
+
+    sqlx::query!(
+        r#"
+        INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+        VALUES ($1, 'https://example.com/feed.xml', 'Foo', 'not a link', 'Foo', now())
+        "#,
+        &app.test_user.id.0,
+    )
+    .execute(&app.pool)
+    .await
+    .expect("Failed to insert feed");
+
+    // The site link should be the feed URL
+
+    let response = app.get("/feeds").await;
+    assert_eq!(200, response.status().as_u16());
+
+    let html = response.text().await.unwrap();
+    let document = Document::from_read(html.as_bytes()).unwrap();
+    let site_links = document
+        .find(Class("site-link").descendant(Name("a")))
+        .filter_map(|node| node.attr("href"))
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["https://example.com/feed.xml"], site_links);
+}
+
+#[tokio::test]
+async fn feed_entries_should_be_paginated() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Create a feed with more entries than fit in a page
+
+    let feed = sqlx::query!(
+        r#"
+        INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+        VALUES ($1, 'https://example.com/feed.xml', 'Foo', 'https://example.com', 'Foo', now())
+        RETURNING id
+        "#,
+        &app.test_user.id.0,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to insert feed");
+
+    sqlx::query!(
+        r#"
+        INSERT INTO feed_entries(feed_id, external_id, title, created_at, summary)
+        SELECT $1, 'entry-' || i, 'Entry ' || i, now(), 'Hello world'
+        FROM generate_series(1, 60) AS i
+        "#,
+        feed.id,
+    )
+    .execute(&app.pool)
+    .await
+    .expect("Failed to insert feed entries");
+
+    // The first page has a link to the next one
+
+    let html = app.get_html(&format!("/feeds/{}/entries", feed.id)).await;
+    let document = Document::from_read(html.as_bytes()).unwrap();
+
+    assert_eq!(50, document.find(Class("feed-entry-card")).count());
+    let next_page = document
+        .find(Class("load-more").descendant(Name("a")))
+        .next()
+        .and_then(|node| node.attr("href"))
+        .expect("no link to the next page")
+        .to_string();
+
+    // The second page has the remaining entries and no link
+
+    let html = app.get_html(&next_page).await;
+    let document = Document::from_read(html.as_bytes()).unwrap();
+
+    assert_eq!(10, document.find(Class("feed-entry-card")).count());
+    assert_eq!(0, document.find(Class("load-more")).count());
+}
+
+#[tokio::test]
+async fn deleting_a_feed_should_cancel_its_pending_jobs() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // The feed must never be fetched
+
+    let mock_server = MockServer::start().await;
+    let feed_url = Url::parse(&format!("{}/feed.xml", mock_server.uri())).unwrap();
+
+    Mock::given(path("/feed.xml"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    // Create a feed with a pending refresh job
+
+    let feed = sqlx::query!(
+        r#"
+        INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+        VALUES ($1, $2, 'Foo', 'https://example.com', 'Foo', now())
+        RETURNING id
+        "#,
+        &app.test_user.id.0,
+        feed_url.to_string(),
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to insert feed");
+    let feed_id = FeedId(feed.id);
+
+    let job_id = post_refresh_feed_job(&app.pool, app.test_user.id, feed_id, feed_url)
+        .await
+        .expect("Failed to post the refresh feed job")
+        .expect("The job should be added");
+
+    // Delete the feed
+
+    let response = app.delete(&format!("/feeds/{}", feed_id)).await;
+    assert_is_redirect_to(&response, "/feeds");
+
+    let response = app.get_html("/feeds").await;
+    assert!(response.contains("Feed deleted"));
+
+    let record = sqlx::query!(
+        "SELECT count(*) AS \"count!\" FROM feeds WHERE id = $1",
+        feed.id
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to count the feeds");
+    assert_eq!(0, record.count);
+
+    // The job was cancelled and is not run by the job runner
+
+    let cancelled = cancel_job(&app.pool, job_id)
+        .await
+        .expect("Failed to cancel the job");
+    assert!(!cancelled);
+
+    app.run_single_job_cycle()
+        .await
+        .expect("Failed to run the job cycle");
+
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn a_cancelled_job_should_not_be_run() {
+    let app = spawn_app().await;
+
+    // The feed must never be fetched
+
+    let mock_server = MockServer::start().await;
+    let feed_url = Url::parse(&format!("{}/feed.xml", mock_server.uri())).unwrap();
+
+    Mock::given(path("/feed.xml"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    // Create a feed with a pending refresh job; the feed is fresh and has no favicon so the job
+    // runner doesn't add any other job
+
+    let feed = sqlx::query!(
+        r#"
+        INSERT INTO feeds(user_id, url, title, site_link, description, added_at, has_favicon, last_refreshed_at)
+        VALUES ($1, $2, 'Foo', 'https://example.com', 'Foo', now(), false, now())
+        RETURNING id
+        "#,
+        &app.test_user.id.0,
+        feed_url.to_string(),
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to insert feed");
+
+    let job_id = post_refresh_feed_job(&app.pool, app.test_user.id, FeedId(feed.id), feed_url)
+        .await
+        .expect("Failed to post the refresh feed job")
+        .expect("The job should be added");
+
+    // Cancel it, then run the job runner
+
+    let cancelled = cancel_job(&app.pool, job_id)
+        .await
+        .expect("Failed to cancel the job");
+    assert!(cancelled);
+
+    app.run_single_job_cycle()
+        .await
+        .expect("Failed to run the job cycle");
+
+    mock_server.verify().await;
+}
+
+#[derive(Serialize)]
+struct FeedTagBody {
+    pub tag: String,
+}
+
+#[tokio::test]
+async fn feeds_should_be_tagged() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Create a feed with an entry
+
+    let feed = sqlx::query!(
+        r#"
+        INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+        VALUES ($1, 'https://example.com/feed.xml', 'Foo', 'https://example.com', 'Foo', now())
+        RETURNING id
+        "#,
+        &app.test_user.id.0,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to insert feed");
+
+    sqlx::query!(
+        r#"
+        INSERT INTO feed_entries(feed_id, external_id, title, created_at, summary)
+        VALUES ($1, 'entry-1', 'Tagged entry', now(), 'Foo')
+        "#,
+        feed.id,
+    )
+    .execute(&app.pool)
+    .await
+    .expect("Failed to insert feed entry");
+
+    // Tag the feed
+
+    let body = FeedTagBody {
+        tag: "Tech".to_string(),
+    };
+    let response = app.post(&format!("/feeds/{}/tags", feed.id), &body).await;
+    assert_is_redirect_to(&response, "/feeds");
+
+    let response = app.get_html("/feeds").await;
+
+    let document = Document::from_read(response.as_bytes()).unwrap();
+    let tag_names = document
+        .find(Class("tag").descendant(Name("a")))
+        .map(|node| node.text())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["Tech"], tag_names);
+
+    // The tag page should list the unread entries of the feed
+
+    let response = app.get_html("/tags/Tech/entries").await;
+    assert!(response.contains("Tagged entry"));
+
+    // Remove the tag
+
+    let record = sqlx::query!("SELECT id FROM tags WHERE name = 'Tech'")
+        .fetch_one(&app.pool)
+        .await
+        .expect("Failed to fetch the tag");
+
+    let response = app
+        .delete(&format!("/feeds/{}/tags/{}", feed.id, record.id))
+        .await;
+    assert_is_redirect_to(&response, "/feeds");
+
+    let response = app.get_html("/feeds").await;
+
+    let document = Document::from_read(response.as_bytes()).unwrap();
+    assert_eq!(0, document.find(Class("tag")).count());
+
+    // The tag page of an unused tag redirects to the feeds page
+
+    let response = app.get("/tags/Tech/entries").await;
+    assert_is_redirect_to(&response, "/feeds");
+}
+
+#[tokio::test]
+async fn feeds_should_display_their_unread_count() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Create a feed with two entries, one of them read
+
+    let feed = sqlx::query!(
+        r#"
+        INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+        VALUES ($1, 'https://example.com/feed.xml', 'Foo', 'https://example.com', 'Foo', now())
+        RETURNING id
+        "#,
+        &app.test_user.id.0,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to insert feed");
+
+    for i in 0..2 {
+        sqlx::query!(
+            r#"
+            INSERT INTO feed_entries(feed_id, external_id, title, created_at, summary)
+            VALUES ($1, $2, $3, now(), 'Hello world')
+            "#,
+            feed.id,
+            format!("entry-{}", i),
+            format!("Entry {}", i),
+        )
+        .execute(&app.pool)
+        .await
+        .expect("Failed to insert feed entry");
+    }
+
+    sqlx::query!(
+        "UPDATE feed_entries SET read_at = now() WHERE feed_id = $1 AND external_id = 'entry-0'",
+        feed.id,
+    )
+    .execute(&app.pool)
+    .await
+    .expect("Failed to mark the entry as read");
+
+    // The feed card should show a single unread entry
+
+    let response = app.get_html("/feeds").await;
+
+    let document = Document::from_read(response.as_bytes()).unwrap();
+    let unread_counts = document
+        .find(Class("feed-card").descendant(Class("unread-count")))
+        .map(|node| node.text())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["1".to_string()], unread_counts);
+}
+
+/// Returns the targets of the title links of the cards with the class `card_class` in `html`.
+fn card_title_links(html: &str, card_class: &'static str) -> Vec<String> {
+    let document = Document::from_read(html.as_bytes()).unwrap();
+
+    document
+        .find(Class(card_class).descendant(Class("title-link")))
+        .filter_map(|node| node.attr("href"))
+        .map(ToString::to_string)
+        .collect()
+}
+
+#[tokio::test]
+async fn a_user_should_navigate_from_the_feeds_to_an_entry() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Create a feed with an entry
+
+    let feed = sqlx::query!(
+        r#"
+        INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+        VALUES ($1, 'https://example.com/feed.xml', 'Foo', 'https://example.com', 'Foo', now())
+        RETURNING id
+        "#,
+        &app.test_user.id.0,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to insert feed");
+
+    let entry = sqlx::query!(
+        r#"
+        INSERT INTO feed_entries(feed_id, external_id, title, created_at, summary)
+        VALUES ($1, 'entry', 'Hello', now(), 'Hello world')
+        RETURNING id
+        "#,
+        feed.id,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to insert feed entry");
+
+    // The feed card links to the entries of the feed
+
+    let feeds_html = app.get_html("/feeds").await;
+
+    let entries_path = format!("/feeds/{}/entries", feed.id);
+    assert_eq!(
+        vec![entries_path.clone()],
+        card_title_links(&feeds_html, "feed-card")
+    );
+
+    // The entries page links to the entry
+
+    let entries_html = app.get_html(&entries_path).await;
+
+    let entry_path = format!("/feeds/{}/entries/{}", feed.id, entry.id);
+    assert_eq!(
+        vec![entry_path.clone()],
+        card_title_links(&entries_html, "feed-entry-card")
+    );
+
+    // The entry page shows the entry
+
+    let response = app.get(&entry_path).await;
+    assert_eq!(200, response.status().as_u16());
+
+    let entry_html = response.text().await.unwrap();
+    assert!(entry_html.contains("Hello world"));
+}
+
+#[tokio::test]
+async fn feed_entries_should_distinguish_read_entries() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Create a feed with two entries, the first one read
+
+    let feed = sqlx::query!(
+        r#"
+        INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+        VALUES ($1, 'https://example.com/feed.xml', 'Foo', 'https://example.com', 'Foo', now())
+        RETURNING id
+        "#,
+        &app.test_user.id.0,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to insert feed");
+
+    for i in 0..2 {
+        sqlx::query!(
+            r#"
+            INSERT INTO feed_entries(feed_id, external_id, title, created_at, summary)
+            VALUES ($1, $2, $3, now(), 'Hello world')
+            "#,
+            feed.id,
+            format!("entry-{}", i),
+            format!("Entry {}", i),
+        )
+        .execute(&app.pool)
+        .await
+        .expect("Failed to insert feed entry");
+    }
+
+    sqlx::query!(
+        "UPDATE feed_entries SET read_at = now() WHERE feed_id = $1 AND external_id = 'entry-0'",
+        feed.id,
+    )
+    .execute(&app.pool)
+    .await
+    .expect("Failed to mark the entry as read");
+
+    // Returns the title of each entry card along with whether it's marked as read
+    let entry_cards = |html: &str| {
+        let document = Document::from_read(html.as_bytes()).unwrap();
+        document
+            .find(Class("feed-entry-card"))
+            .map(|node| {
+                let title = node
+                    .find(Class("title-link"))
+                    .next()
+                    .map(|node| node.text())
+                    .unwrap_or_default();
+                let is_read = node.is(Class("read"));
+
+                (title, is_read)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // The feed entries show both entries, the most recent first, and only the first one is read
+
+    let html = app.get_html(&format!("/feeds/{}/entries", feed.id)).await;
+    assert_eq!(
+        vec![
+            ("Entry 1".to_string(), false),
+            ("Entry 0".to_string(), true)
+        ],
+        entry_cards(&html)
+    );
+
+    // The unread entries only show the unread one
+
+    let html = app.get_html("/unread").await;
+    assert_eq!(vec![("Entry 1".to_string(), false)], entry_cards(&html));
+}
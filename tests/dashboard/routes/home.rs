@@ -0,0 +1,115 @@
+use crate::helpers::LoginBody;
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use select::document::Document;
+use select::predicate::{Class, Name, Predicate};
+use sqlx::PgPool;
+
+/// Returns the unread count and the titles of the recently added feeds shown in the home page `html`.
+fn parse_home(html: &str) -> (Option<String>, Vec<String>) {
+    let document = Document::from_read(html.as_bytes()).unwrap();
+
+    let unread_count = document
+        .find(Class("home-unread-count"))
+        .next()
+        .map(|node| node.text());
+    let recent_feeds = document
+        .find(Class("home-recent-feeds").descendant(Name("a")))
+        .map(|node| node.text())
+        .collect();
+
+    (unread_count, recent_feeds)
+}
+
+async fn insert_entry(pool: &PgPool, feed_id: i64, external_id: &str) {
+    sqlx::query!(
+        r#"
+        INSERT INTO feed_entries(feed_id, external_id, title, created_at, summary)
+        VALUES ($1, $2, 'Entry', now(), 'Hello world')
+        "#,
+        feed_id,
+        external_id,
+    )
+    .execute(pool)
+    .await
+    .expect("Failed to insert feed entry");
+}
+
+#[tokio::test]
+async fn home_should_display_the_unread_count_and_the_recent_feeds() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Without feeds
+
+    let html = app.get_html("/").await;
+    assert_eq!(
+        (Some("0".to_string()), vec!["add one".to_string()]),
+        parse_home(&html)
+    );
+
+    // Create six feeds, added one hour apart: only the five most recent are shown
+
+    let mut feed_ids = Vec::new();
+    for i in 0..6 {
+        let feed = sqlx::query!(
+            r#"
+            INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+            VALUES ($1, $2, $3, 'https://example.com', 'Foo', now() - make_interval(hours => $4))
+            RETURNING id
+            "#,
+            &app.test_user.id.0,
+            format!("https://example.com/feed{}.xml", i),
+            format!("Feed {}", i),
+            i,
+        )
+        .fetch_one(&app.pool)
+        .await
+        .expect("Failed to insert feed");
+
+        feed_ids.push(feed.id);
+    }
+
+    insert_entry(&app.pool, feed_ids[0], "entry-0").await;
+    insert_entry(&app.pool, feed_ids[0], "entry-1").await;
+
+    let html = app.get_html("/").await;
+    let (unread_count, recent_feeds) = parse_home(&html);
+    assert_eq!(Some("2".to_string()), unread_count);
+    assert_eq!(
+        vec!["Feed 0", "Feed 1", "Feed 2", "Feed 3", "Feed 4"],
+        recent_feeds
+    );
+
+    // A new entry is counted
+
+    insert_entry(&app.pool, feed_ids[0], "entry-2").await;
+
+    let html = app.get_html("/").await;
+    assert_eq!(Some("3".to_string()), parse_home(&html).0);
+
+    // Entries marked as read are not counted anymore
+
+    let location = format!("/feeds/{}/entries", feed_ids[0]);
+    let response = app
+        .post(&format!("/feeds/{}/mark-read", feed_ids[0]), &())
+        .await;
+    assert_is_redirect_to(&response, &location);
+
+    let html = app.get_html("/").await;
+    assert_eq!(Some("0".to_string()), parse_home(&html).0);
+}
+
+#[tokio::test]
+async fn home_should_not_display_the_unread_count_when_logged_out() {
+    let app = spawn_app().await;
+
+    let html = app.get_html("/").await;
+    assert_eq!((None, Vec::<String>::new()), parse_home(&html));
+}
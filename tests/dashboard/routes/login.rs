@@ -1,5 +1,8 @@
 use crate::helpers::LoginBody;
 use crate::helpers::{assert_is_redirect_to, spawn_app};
+use select::document::Document;
+use select::predicate::{Attr, Class};
+use servare::configuration::get_configuration;
 
 #[tokio::test]
 async fn login_form_should_work() {
@@ -43,3 +46,281 @@ async fn login_with_bad_credentials_should_fail() {
     let home_response = app.get_html("/").await;
     assert!(home_response.contains("Authentication failed"));
 }
+
+#[tokio::test]
+async fn login_with_remember_me_should_set_a_long_lived_cookie() {
+    let app = spawn_app().await;
+    let config = get_configuration().expect("Failed to get configuration");
+
+    let login_body = [
+        ("email", app.test_user.email.as_str()),
+        ("password", app.test_user.password.as_str()),
+        ("remember_me", "true"),
+    ];
+
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    let session_cookie = login_response
+        .cookies()
+        .find(|cookie| cookie.name() == "session_id")
+        .expect("Failed to find the session cookie");
+
+    assert_eq!(
+        Some(config.session.remember_me_ttl()),
+        session_cookie.max_age()
+    );
+}
+
+#[tokio::test]
+async fn login_without_remember_me_should_set_a_session_cookie() {
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    let session_cookie = login_response
+        .cookies()
+        .find(|cookie| cookie.name() == "session_id")
+        .expect("Failed to find the session cookie");
+
+    assert!(session_cookie.max_age().is_none());
+}
+
+#[tokio::test]
+async fn login_should_record_the_user_id_in_a_span() {
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    let span = app
+        .span_collector
+        .wait_for_span("Login submit", "user_id", &app.test_user.id.to_string())
+        .await;
+    assert!(span.is_some(), "no span with the user id was recorded");
+}
+
+/// Returns the CSRF token of the first form in `html`.
+fn get_csrf_token(html: &str) -> String {
+    let document = Document::from(html);
+
+    document
+        .find(Attr("name", "csrf_token"))
+        .next()
+        .and_then(|node| node.attr("value"))
+        .expect("no CSRF token found")
+        .to_string()
+}
+
+#[tokio::test]
+async fn csrf_token_should_change_after_logout_and_login() {
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+
+    // Login and get the token
+
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    let token1 = get_csrf_token(&app.get_html("/settings").await);
+    let token2 = get_csrf_token(&app.get_html("/settings").await);
+    assert_eq!(
+        token1, token2,
+        "the token should be stable within a session"
+    );
+
+    // Logout, login again and get the new token
+
+    let logout_response = app.post("/logout", &()).await;
+    assert_is_redirect_to(&logout_response, "/");
+
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    let token3 = get_csrf_token(&app.get_html("/settings").await);
+    assert_ne!(token1, token3, "the token should change after a new login");
+}
+
+#[tokio::test]
+async fn state_changing_requests_without_the_csrf_token_should_be_rejected() {
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    let url = format!("{}/logout", app.address);
+
+    // Without a token
+
+    let response = app
+        .http_client
+        .post(&url)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(403, response.status().as_u16());
+
+    // With an invalid token, as a header or in the form
+
+    let response = app
+        .http_client
+        .post(&url)
+        .header("X-CSRF-Token", "foobar")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(403, response.status().as_u16());
+
+    let response = app
+        .http_client
+        .post(&url)
+        .form(&[("csrf_token", "foobar")])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(403, response.status().as_u16());
+
+    // With the token of the form
+
+    let token = get_csrf_token(&app.get_html("/settings").await);
+
+    let response = app
+        .http_client
+        .post(&url)
+        .form(&[("csrf_token", token)])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&response, "/");
+}
+
+#[tokio::test]
+async fn anonymous_visitors_should_not_get_a_session() {
+    let app = spawn_app().await;
+
+    let response = app.get("/login").await;
+    assert_eq!(200, response.status().as_u16());
+    assert!(response
+        .cookies()
+        .all(|cookie| cookie.name() != "session_id"));
+
+    let html = response.text().await.unwrap();
+    assert_eq!("", get_csrf_token(&html));
+}
+
+#[tokio::test]
+async fn settings_should_show_when_the_user_logged_in() {
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    let html = app.get_html("/settings").await;
+    let document = Document::from(html.as_str());
+
+    let logged_in_since = document
+        .find(Class("logged-in-since"))
+        .next()
+        .expect("no login time found")
+        .text();
+    assert!(logged_in_since.starts_with("Logged in since "));
+}
+
+#[tokio::test]
+async fn session_older_than_the_absolute_ttl_should_be_logged_out() {
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    let settings_response = app.get("/settings").await;
+    assert_eq!(200, settings_response.status().as_u16());
+
+    // Pretend the user logged in a long time ago, the session itself is still valid
+
+    sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET state = jsonb_set(state, '{logged_in_at}', to_jsonb('0'::text))
+        WHERE state->>'user_id' = $1
+        "#,
+        serde_json::to_string(&app.test_user.id).unwrap(),
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    let settings_response = app.get("/settings").await;
+    assert_is_redirect_to(&settings_response, "/login");
+
+    let login_html = app.get_html("/login").await;
+    assert!(login_html.contains("Your session has expired, please log in again"));
+
+    // The session is gone
+
+    let settings_response = app.get("/settings").await;
+    assert_is_redirect_to(&settings_response, "/login");
+}
+
+#[tokio::test]
+async fn session_without_a_login_time_should_be_logged_out() {
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Sessions created before the login time was recorded don't have one
+
+    sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET state = state - 'logged_in_at'
+        WHERE state->>'user_id' = $1
+        "#,
+        serde_json::to_string(&app.test_user.id).unwrap(),
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    let settings_response = app.get("/settings").await;
+    assert_is_redirect_to(&settings_response, "/login");
+
+    let settings_response = app.get("/settings").await;
+    assert_is_redirect_to(&settings_response, "/login");
+}
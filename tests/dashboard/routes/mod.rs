@@ -1,8 +1,18 @@
 use crate::helpers::spawn_app;
+use std::io::Read;
 
+mod admin;
+mod api_feeds;
+mod api_token;
+mod email;
 mod feeds;
+mod home;
 mod login;
+mod search;
 mod settings;
+mod starred;
+mod status;
+mod unread;
 
 #[tokio::test]
 async fn home_should_work() {
@@ -14,3 +24,55 @@ async fn home_should_work() {
         "home page doesn't contain the title 'Home'"
     );
 }
+
+#[tokio::test]
+async fn html_responses_should_be_compressed_if_accepted() {
+    let app = spawn_app().await;
+
+    let url = format!("{}/login", app.address);
+
+    // Use a client that doesn't decompress the responses automatically
+    let http_client = reqwest::Client::builder()
+        .no_gzip()
+        .no_brotli()
+        .no_deflate()
+        .build()
+        .expect("Failed to build HTTP client");
+
+    // Without an Accept-Encoding header the response is not compressed
+
+    let response = http_client
+        .get(&url)
+        .header("Accept-Encoding", "identity")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert!(response.headers().get("Content-Encoding").is_none());
+
+    let original = response.text().await.unwrap();
+
+    // With gzip
+
+    let response = http_client
+        .get(&url)
+        .header("Accept-Encoding", "gzip")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(
+        Some("gzip"),
+        response
+            .headers()
+            .get("Content-Encoding")
+            .and_then(|v| v.to_str().ok())
+    );
+
+    let compressed = response.bytes().await.unwrap();
+
+    let mut decompressed = String::new();
+    flate2::read::GzDecoder::new(&compressed[..])
+        .read_to_string(&mut decompressed)
+        .expect("Failed to decompress the response");
+
+    assert_eq!(original, decompressed);
+}
@@ -0,0 +1,75 @@
+use crate::helpers::LoginBody;
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use select::document::Document;
+use select::predicate::Class;
+
+#[tokio::test]
+async fn search_page_should_show_the_matching_entries() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Create a feed with some entries
+
+    let feed = sqlx::query!(
+        r#"
+        INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+        VALUES ($1, 'https://example.com/feed.xml', 'Foo', 'https://example.com', 'Foo', now())
+        RETURNING id
+        "#,
+        &app.test_user.id.0,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to insert feed");
+
+    let entries = [
+        ("Baking bread at home", "A simple sourdough recipe"),
+        ("Tuning Postgres", "Indexes and vacuum settings"),
+    ];
+    for (i, (title, summary)) in entries.iter().enumerate() {
+        sqlx::query!(
+            r#"
+            INSERT INTO feed_entries(feed_id, external_id, title, created_at, summary)
+            VALUES ($1, $2, $3, now(), $4)
+            "#,
+            feed.id,
+            format!("entry-{}", i),
+            title,
+            summary,
+        )
+        .execute(&app.pool)
+        .await
+        .expect("Failed to insert feed entry");
+    }
+
+    let count_entries = |html: &str| {
+        let document = Document::from_read(html.as_bytes()).unwrap();
+        document.find(Class("feed-entry-card")).count()
+    };
+
+    // Without a query nothing is shown
+
+    let html = app.get_html("/search").await;
+    assert_eq!(0, count_entries(&html));
+    assert!(!html.contains("No entries found"));
+
+    // Only the matching entry is shown
+
+    let html = app.get_html("/search?q=sourdough").await;
+    assert_eq!(1, count_entries(&html));
+    assert!(html.contains("Baking bread at home"));
+    assert!(!html.contains("Tuning Postgres"));
+
+    // No match
+
+    let html = app.get_html("/search?q=kubernetes").await;
+    assert_eq!(0, count_entries(&html));
+    assert!(html.contains("No entries found"));
+}
@@ -1,5 +1,12 @@
 use crate::helpers::LoginBody;
 use crate::helpers::{assert_is_redirect_to, spawn_app};
+use select::document::Document;
+use select::predicate::{Class, Name, Predicate};
+use servare::feed::FeedId;
+use servare::job::post_refresh_feed_job;
+use url::Url;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
 
 #[tokio::test]
 async fn settings_page_should_work_if_logged_in() {
@@ -21,6 +28,36 @@ async fn settings_page_should_work_if_logged_in() {
     assert!(response.contains("Settings"));
 }
 
+#[tokio::test]
+async fn settings_page_should_be_the_active_navigation_link() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // The link of the current page should be the only active one
+
+    for (path, expected) in [
+        ("/settings", "/settings"),
+        ("/unread", "/unread"),
+        ("/starred", "/starred"),
+    ] {
+        let response = app.get_html(path).await;
+
+        let document = Document::from_read(response.as_bytes()).unwrap();
+        let active_links = document
+            .find(Name("nav").descendant(Class("active")))
+            .filter_map(|node| node.attr("href"))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![expected], active_links, "path {}", path);
+    }
+}
+
 #[tokio::test]
 async fn settings_page_should_redirect_if_not_logged_in() {
     // Setup
@@ -30,3 +67,503 @@ async fn settings_page_should_redirect_if_not_logged_in() {
     let response = app.get("/settings").await;
     assert_is_redirect_to(&response, "/login");
 }
+
+#[derive(serde::Serialize)]
+struct PreferencesBody {
+    feed_order: String,
+}
+
+#[tokio::test]
+async fn feeds_page_should_honor_the_feed_order_preference() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Create some feeds, "Feed Bravo" being the oldest one
+
+    let titles = ["Feed Bravo", "Feed Charlie", "Feed Alpha"];
+    for (i, title) in titles.iter().enumerate() {
+        sqlx::query!(
+            r#"
+            INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+            VALUES ($1, $2, $3, 'https://example.com', 'Foo', now() + make_interval(secs => $4))
+            "#,
+            &app.test_user.id.0,
+            format!("https://example.com/feed-{}.xml", i),
+            title,
+            i as f64,
+        )
+        .execute(&app.pool)
+        .await
+        .expect("Failed to insert feed");
+    }
+
+    let cases = [
+        (
+            "added_at:desc",
+            ["Feed Alpha", "Feed Charlie", "Feed Bravo"],
+        ),
+        ("added_at:asc", ["Feed Bravo", "Feed Charlie", "Feed Alpha"]),
+        ("title:asc", ["Feed Alpha", "Feed Bravo", "Feed Charlie"]),
+        ("title:desc", ["Feed Charlie", "Feed Bravo", "Feed Alpha"]),
+    ];
+    for (feed_order, expected_titles) in cases {
+        // Save the preference
+        let body = PreferencesBody {
+            feed_order: feed_order.to_string(),
+        };
+        let response = app.post("/settings/preferences", &body).await;
+        assert_is_redirect_to(&response, "/settings");
+
+        let response = app.get_html("/settings").await;
+        assert!(response.contains("Preferences saved"));
+
+        // Check the order of the feeds
+        let response = app.get_html("/feeds").await;
+
+        let positions = expected_titles
+            .iter()
+            .map(|title| response.find(title).expect("feed title should be present"))
+            .collect::<Vec<_>>();
+        assert!(
+            positions.windows(2).all(|w| w[0] < w[1]),
+            "feeds should be ordered by {}",
+            feed_order
+        );
+    }
+}
+
+#[tokio::test]
+async fn settings_preferences_should_reject_an_invalid_feed_order() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Save an invalid preference
+    let body = PreferencesBody {
+        feed_order: "foobar:asc".to_string(),
+    };
+    let response = app.post("/settings/preferences", &body).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    let response = app.get_html("/settings").await;
+    assert!(response.contains("Invalid feed order"));
+}
+
+#[derive(serde::Serialize)]
+struct UserPreferencesBody {
+    feed_order: String,
+    entries_per_page: String,
+    timezone: String,
+    theme: String,
+}
+
+#[tokio::test]
+async fn user_preferences_should_apply_to_the_feed_entries() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Create a feed with three entries
+
+    let feed = sqlx::query!(
+        r#"
+        INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+        VALUES ($1, 'https://example.com/feed.xml', 'Foo', 'https://example.com', 'Foo', now())
+        RETURNING id
+        "#,
+        &app.test_user.id.0,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to insert feed");
+
+    for i in 0..3 {
+        sqlx::query!(
+            r#"
+            INSERT INTO feed_entries(feed_id, external_id, title, created_at, summary)
+            VALUES ($1, $2, 'Entry', '2023-03-20T10:00:00Z', 'Hello world')
+            "#,
+            feed.id,
+            format!("entry-{}", i),
+        )
+        .execute(&app.pool)
+        .await
+        .expect("Failed to insert feed entry");
+    }
+
+    let entries_path = format!("/feeds/{}/entries", feed.id);
+    let created_ats = |html: &str| {
+        let document = Document::from_read(html.as_bytes()).unwrap();
+        document
+            .find(Class("feed-entry-card").descendant(Class("created-at")))
+            .map(|node| node.text())
+            .collect::<Vec<_>>()
+    };
+
+    // With the default preferences the dates are in UTC
+
+    let html = app.get_html(&entries_path).await;
+    assert_eq!(vec!["2023-03-20T10:00:00Z"; 3], created_ats(&html));
+
+    // Save the preferences
+
+    let body = UserPreferencesBody {
+        feed_order: "added_at:desc".to_string(),
+        entries_per_page: "2".to_string(),
+        timezone: "+02:00".to_string(),
+        theme: "dark".to_string(),
+    };
+    let response = app.post("/settings/preferences", &body).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    let html = app.get_html("/settings").await;
+    assert!(html.contains("Preferences saved"));
+    assert!(html.contains(r#"value="+02:00""#));
+    assert!(html.contains(r#"<option value="dark" selected>"#));
+
+    // Only two entries are shown, in the timezone of the user
+
+    let html = app.get_html(&entries_path).await;
+    assert_eq!(vec!["2023-03-20T12:00:00+02:00"; 2], created_ats(&html));
+    assert!(html.contains("Showing 1–2 of 3 entries"));
+
+    // The next page shows the last entry
+
+    let next_page = Document::from_read(html.as_bytes())
+        .unwrap()
+        .find(Class("load-more").descendant(Name("a")))
+        .next()
+        .and_then(|node| node.attr("href").map(ToString::to_string))
+        .expect("There should be a next page");
+
+    let html = app.get_html(&next_page).await;
+    assert_eq!(vec!["2023-03-20T12:00:00+02:00"; 1], created_ats(&html));
+    assert!(html.contains("Showing 3–3 of 3 entries"));
+
+    // An invalid timezone is rejected
+
+    let body = UserPreferencesBody {
+        timezone: "Mars/Olympus_Mons".to_string(),
+        ..body
+    };
+    let response = app.post("/settings/preferences", &body).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    let html = app.get_html("/settings").await;
+    assert!(html.contains("Invalid timezone"));
+    assert!(html.contains(r#"value="+02:00""#));
+}
+
+#[derive(serde::Serialize)]
+struct AccountDeleteBody {
+    pub password: String,
+}
+
+#[tokio::test]
+async fn deleting_the_account_should_delete_all_the_user_data() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Create a feed with an entry and a pending job
+
+    let feed = sqlx::query!(
+        r#"
+        INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+        VALUES ($1, 'https://example.com/feed.xml', 'Foo', 'https://example.com', 'Foo', now())
+        RETURNING id
+        "#,
+        &app.test_user.id.0,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to insert feed");
+
+    sqlx::query!(
+        r#"
+        INSERT INTO feed_entries(feed_id, external_id, title, created_at, summary)
+        VALUES ($1, 'entry-1', 'Foo', now(), 'Foo')
+        "#,
+        feed.id,
+    )
+    .execute(&app.pool)
+    .await
+    .expect("Failed to insert feed entry");
+
+    post_refresh_feed_job(
+        &app.pool,
+        app.test_user.id,
+        FeedId(feed.id),
+        Url::parse("https://example.com/feed.xml").unwrap(),
+    )
+    .await
+    .expect("Failed to post the refresh feed job");
+
+    // The password must be confirmed
+
+    let body = AccountDeleteBody {
+        password: "foobar".to_string(),
+    };
+    let response = app.delete_form("/settings/account", &body).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    let response = app.get_html("/settings").await;
+    assert!(response.contains("Invalid password"));
+
+    // Delete the account
+
+    let body = AccountDeleteBody {
+        password: app.test_user.password.clone(),
+    };
+    let response = app.delete_form("/settings/account", &body).await;
+    assert_is_redirect_to(&response, "/");
+
+    let response = app.get_html("/").await;
+    assert!(response.contains("Your account has been deleted"));
+
+    // The user is logged out and can't login anymore
+
+    let response = app.get("/settings").await;
+    assert_is_redirect_to(&response, "/login");
+
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/login");
+
+    // Everything is gone
+
+    let user_id = app.test_user.id.0;
+    let record = sqlx::query!(
+        r#"
+        SELECT
+          (SELECT count(*) FROM users WHERE id = $1) AS "users!",
+          (SELECT count(*) FROM feeds WHERE user_id = $1) AS "feeds!",
+          (SELECT count(*) FROM feed_entries WHERE feed_id = $2) AS "feed_entries!",
+          (SELECT count(*) FROM jobs WHERE data->>'user_id' = $1::text) AS "jobs!",
+          (SELECT count(*) FROM sessions WHERE state->>'user_id' = '"' || $1::text || '"') AS "sessions!"
+        "#,
+        user_id,
+        feed.id,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to count the user rows");
+
+    assert_eq!(0, record.users);
+    assert_eq!(0, record.feeds);
+    assert_eq!(0, record.feed_entries);
+    assert_eq!(0, record.jobs);
+    assert_eq!(0, record.sessions);
+}
+
+#[derive(serde::Serialize)]
+struct EmailChangeBody {
+    pub new_email: String,
+    pub password: String,
+}
+
+#[tokio::test]
+async fn changing_the_email_should_require_a_confirmation() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    Mock::given(method("POST"))
+        .and(path("/emails"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let new_email = format!("new-{}", app.test_user.email);
+
+    // The password must be confirmed
+
+    let body = EmailChangeBody {
+        new_email: new_email.clone(),
+        password: "foobar".to_string(),
+    };
+    let response = app.post("/settings/email", &body).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    let response = app.get_html("/settings").await;
+    assert!(response.contains("Invalid password"));
+
+    // Request the change, a confirmation link is sent to the new email
+
+    let body = EmailChangeBody {
+        new_email: new_email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let response = app.post("/settings/email", &body).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    let response = app.get_html("/settings").await;
+    assert!(response.contains("A confirmation email has been sent"));
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(Some(new_email.as_str()), email["to"][0]["email"].as_str());
+
+    let text = email["text"].as_str().unwrap();
+    let link_start = text.find("/settings/email/confirm/").unwrap();
+    let link = text[link_start..].split_whitespace().next().unwrap();
+
+    // Nothing changes until the link is visited
+
+    let user = sqlx::query!("SELECT email FROM users WHERE id = $1", &app.test_user.id.0)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(app.test_user.email, user.email);
+
+    // A tampered link is rejected
+
+    let response = app.get(&format!("{}00", link)).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    let response = app.get_html("/settings").await;
+    assert!(response.contains("Invalid or expired confirmation link"));
+
+    // Confirm the change
+
+    let response = app.get(link).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    let response = app.get_html("/settings").await;
+    assert!(response.contains(&format!("Your email is now {}", new_email)));
+
+    let user = sqlx::query!("SELECT email FROM users WHERE id = $1", &app.test_user.id.0)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(new_email, user.email);
+
+    // The old email can't be used to login anymore, the new one can
+
+    let response = app.post("/logout", &()).await;
+    assert_is_redirect_to(&response, "/");
+
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/login");
+
+    let login_body = LoginBody {
+        email: new_email,
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+}
+
+#[tokio::test]
+async fn logging_out_a_session_should_prevent_it_from_accessing_protected_routes() {
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+
+    // Log in on two "devices": the test app client and another client with its own cookies
+
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    let other_client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let other_get_settings = || async {
+        other_client
+            .get(&format!("{}/settings", app.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    };
+
+    let login_response = other_client
+        .post(&format!("{}/login", app.address))
+        .form(&login_body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&login_response, "/");
+    assert_eq!(200, other_get_settings().await.status().as_u16());
+
+    // Both sessions are listed, only the first one is the current device
+
+    let response = app.get_html("/settings/sessions").await;
+
+    let document = Document::from_read(response.as_bytes()).unwrap();
+    let sessions = document
+        .find(Class("session"))
+        .map(|node| {
+            let is_current = node.find(Class("current")).next().is_some();
+            let delete_path = node
+                .find(Name("button"))
+                .next()
+                .and_then(|button| button.attr("hx-delete"))
+                .unwrap()
+                .to_string();
+
+            (is_current, delete_path)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(2, sessions.len());
+    assert_eq!(
+        1,
+        sessions
+            .iter()
+            .filter(|(is_current, _)| *is_current)
+            .count()
+    );
+
+    // Log out the other session
+
+    let (_, other_session_path) = sessions.iter().find(|(is_current, _)| !is_current).unwrap();
+
+    let response = app.delete(other_session_path).await;
+    assert_is_redirect_to(&response, "/settings/sessions");
+
+    let response = app.get_html("/settings/sessions").await;
+    assert!(response.contains("Session logged out"));
+
+    // The other client is logged out, the test app client isn't
+
+    let response = other_get_settings().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.get("/settings").await;
+    assert_eq!(200, response.status().as_u16());
+}
@@ -0,0 +1,93 @@
+use crate::helpers::LoginBody;
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use select::document::Document;
+use select::predicate::{Class, Name, Predicate};
+
+#[tokio::test]
+async fn starred_entries_should_be_listed_until_unstarred() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Create a feed with two entries
+
+    let feed = sqlx::query!(
+        r#"
+        INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+        VALUES ($1, 'https://example.com/feed.xml', 'Foo', 'https://example.com', 'Foo', now())
+        RETURNING id
+        "#,
+        &app.test_user.id.0,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to insert feed");
+
+    let mut entry_ids = Vec::new();
+    for title in ["Starred entry", "Other entry"] {
+        let entry = sqlx::query!(
+            r#"
+            INSERT INTO feed_entries(feed_id, external_id, title, created_at, summary)
+            VALUES ($1, $2, $2, now(), 'Foo')
+            RETURNING id
+            "#,
+            feed.id,
+            title,
+        )
+        .fetch_one(&app.pool)
+        .await
+        .expect("Failed to insert feed entry");
+
+        entry_ids.push(entry.id);
+    }
+
+    let entry_path = format!("/feeds/{}/entries/{}", feed.id, entry_ids[0]);
+
+    let response = app.get_html("/starred").await;
+    assert!(response.contains("No starred entries"));
+
+    // Star the entry, the entry page shows it's starred
+
+    let response = app.post(&format!("{}/star", entry_path), &()).await;
+    assert_is_redirect_to(&response, &entry_path);
+
+    let response = app.get_html(&entry_path).await;
+    let document = Document::from_read(response.as_bytes()).unwrap();
+    let unstar_path = format!("{}/unstar", entry_path);
+    let unstar_forms = document
+        .find(Name("form").and(Class("star")))
+        .filter(|form| form.attr("action") == Some(unstar_path.as_str()))
+        .count();
+    assert_eq!(1, unstar_forms);
+
+    // Only the starred entry is listed
+
+    let response = app.get_html("/starred").await;
+    let document = Document::from_read(response.as_bytes()).unwrap();
+    let titles: Vec<String> = document
+        .find(Class("feed-entry-card").descendant(Class("title-link")))
+        .map(|node| node.text())
+        .collect();
+    assert_eq!(vec!["Starred entry".to_string()], titles);
+
+    // Unstar it
+
+    let response = app.post(&format!("{}/unstar", entry_path), &()).await;
+    assert_is_redirect_to(&response, &entry_path);
+
+    let response = app.get_html("/starred").await;
+    assert!(response.contains("No starred entries"));
+
+    // An unknown entry can't be starred
+
+    let response = app
+        .post(&format!("/feeds/{}/entries/0/star", feed.id), &())
+        .await;
+    assert_is_redirect_to(&response, &format!("/feeds/{}/entries", feed.id));
+}
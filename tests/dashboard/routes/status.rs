@@ -0,0 +1,71 @@
+use crate::helpers::spawn_app;
+use servare::configuration::get_configuration;
+use servare::feed::FaviconCache;
+use servare::run_group::RunGroup;
+use servare::startup::Application;
+use servare::unread_updates::UnreadUpdates;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use std::time::Duration as StdDuration;
+
+#[tokio::test]
+async fn status_and_readiness_should_be_ok_with_a_healthy_database() {
+    let app = spawn_app().await;
+
+    for path in ["/status", "/readiness"] {
+        let response = app.get(path).await;
+        assert_eq!(
+            200,
+            response.status().as_u16(),
+            "unexpected status for {}",
+            path
+        );
+
+        let body: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+        assert_eq!(serde_json::json!({"status": "ok"}), body);
+    }
+}
+
+#[tokio::test]
+async fn status_and_readiness_should_be_degraded_with_an_unreachable_database() {
+    let mut config = get_configuration().expect("Failed to get configuration");
+    config.application.port = 0;
+
+    // Nothing listens on port 1, so every attempt to acquire a connection fails.
+    let pool = PgPoolOptions::new()
+        .acquire_timeout(StdDuration::from_millis(500))
+        .connect_lazy_with(PgConnectOptions::new().host("127.0.0.1").port(1));
+
+    let app = Application::build(
+        &config.application,
+        &config.session,
+        &config.argon2,
+        None,
+        pool,
+        FaviconCache::new(10),
+        UnreadUpdates::new(16),
+    )
+    .expect("Failed to build application");
+    let address = format!("http://127.0.0.1:{}", app.port);
+
+    let shutdown_timeout = config.application.shutdown_timeout();
+    let run_group = RunGroup::new().run(|shutdown| app.run(shutdown, shutdown_timeout));
+
+    #[allow(clippy::let_underscore_future)]
+    let _ = tokio::spawn(run_group.start());
+
+    for path in ["/status", "/readiness"] {
+        let response = reqwest::get(format!("{}{}", address, path))
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(
+            503,
+            response.status().as_u16(),
+            "unexpected status for {}",
+            path
+        );
+
+        let body: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+        assert_eq!(Some("degraded"), body["status"].as_str());
+        assert!(body["reason"].is_string());
+    }
+}
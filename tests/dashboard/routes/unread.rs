@@ -0,0 +1,168 @@
+use crate::helpers::LoginBody;
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use select::document::Document;
+use select::predicate::Class;
+
+#[derive(serde::Serialize)]
+struct PreferencesBody {
+    feed_order: String,
+    unread_since_days: String,
+}
+
+#[tokio::test]
+async fn unread_page_should_honor_the_unread_since_days_preference() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Create a feed with entries 1, 5 and 30 days old
+
+    let feed = sqlx::query!(
+        r#"
+        INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+        VALUES ($1, 'https://example.com/feed.xml', 'Foo', 'https://example.com', 'Foo', now())
+        RETURNING id
+        "#,
+        &app.test_user.id.0,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Failed to insert feed");
+
+    for days in [1, 5, 30] {
+        sqlx::query!(
+            r#"
+            INSERT INTO feed_entries(feed_id, external_id, title, created_at, summary)
+            VALUES ($1, $2, $3, now() - make_interval(days => $4), 'Hello world')
+            "#,
+            feed.id,
+            format!("entry-{}", days),
+            format!("Entry {} days old", days),
+            days,
+        )
+        .execute(&app.pool)
+        .await
+        .expect("Failed to insert feed entry");
+    }
+
+    let count_entries = |html: &str| {
+        let document = Document::from_read(html.as_bytes()).unwrap();
+        document.find(Class("feed-entry-card")).count()
+    };
+
+    // Without a preference all entries are shown
+
+    let html = app.get_html("/unread").await;
+    assert_eq!(3, count_entries(&html));
+    assert!(!html.contains("Showing entries from the last"));
+
+    // Only show the last 7 days
+
+    let body = PreferencesBody {
+        feed_order: "added_at:desc".to_string(),
+        unread_since_days: "7".to_string(),
+    };
+    let response = app.post("/settings/preferences", &body).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    let html = app.get_html("/unread").await;
+    assert_eq!(2, count_entries(&html));
+    assert!(html.contains("Showing entries from the last 7 days"));
+    assert!(!html.contains("Entry 30 days old"));
+
+    // An invalid number of days is rejected
+
+    let body = PreferencesBody {
+        feed_order: "added_at:desc".to_string(),
+        unread_since_days: "-1".to_string(),
+    };
+    let response = app.post("/settings/preferences", &body).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    let html = app.get_html("/settings").await;
+    assert!(html.contains("Invalid number of days"));
+}
+
+#[tokio::test]
+async fn entries_should_be_marked_as_read_in_one_operation() {
+    // Setup, login
+    let app = spawn_app().await;
+
+    let login_body = LoginBody {
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+    let login_response = app.post("/login", &login_body).await;
+    assert_is_redirect_to(&login_response, "/");
+
+    // Create two feeds with two entries each
+
+    let mut feed_ids = Vec::new();
+    for i in 0..2 {
+        let feed = sqlx::query!(
+            r#"
+            INSERT INTO feeds(user_id, url, title, site_link, description, added_at)
+            VALUES ($1, $2, 'Foo', 'https://example.com', 'Foo', now())
+            RETURNING id
+            "#,
+            &app.test_user.id.0,
+            format!("https://example.com/feed{}.xml", i),
+        )
+        .fetch_one(&app.pool)
+        .await
+        .expect("Failed to insert feed");
+
+        for j in 0..2 {
+            sqlx::query!(
+                r#"
+                INSERT INTO feed_entries(feed_id, external_id, title, created_at, summary)
+                VALUES ($1, $2, 'Entry', now(), 'Hello world')
+                "#,
+                feed.id,
+                format!("entry-{}-{}", i, j),
+            )
+            .execute(&app.pool)
+            .await
+            .expect("Failed to insert feed entry");
+        }
+
+        feed_ids.push(feed.id);
+    }
+
+    let count_entries = |html: &str| {
+        let document = Document::from_read(html.as_bytes()).unwrap();
+        document.find(Class("feed-entry-card")).count()
+    };
+
+    let html = app.get_html("/unread").await;
+    assert_eq!(4, count_entries(&html));
+
+    // Mark the entries of the first feed as read
+
+    let location = format!("/feeds/{}/entries", feed_ids[0]);
+    let response = app
+        .post(&format!("/feeds/{}/mark-read", feed_ids[0]), &())
+        .await;
+    assert_is_redirect_to(&response, &location);
+
+    let html = app.get_html(&location).await;
+    assert!(html.contains("Marked 2 entries as read"));
+
+    let html = app.get_html("/unread").await;
+    assert_eq!(2, count_entries(&html));
+
+    // Mark all the remaining entries as read
+
+    let response = app.post("/unread/mark-read", &()).await;
+    assert_is_redirect_to(&response, "/unread");
+
+    let html = app.get_html("/unread").await;
+    assert!(html.contains("Marked 2 entries as read"));
+    assert_eq!(0, count_entries(&html));
+}